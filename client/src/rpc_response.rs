@@ -157,6 +157,13 @@ pub enum SlotUpdate {
         slot: Slot,
         timestamp: u64,
     },
+    ForkAbandoned {
+        // Tip of the fork the node stopped building on
+        slot: Slot,
+        reset_slot: Slot,
+        reason: ForkAbandonedReason,
+        timestamp: u64,
+    },
 }
 
 impl SlotUpdate {
@@ -169,10 +176,22 @@ impl SlotUpdate {
             Self::Dead { slot, .. } => *slot,
             Self::OptimisticConfirmation { slot, .. } => *slot,
             Self::Root { slot, .. } => *slot,
+            Self::ForkAbandoned { slot, .. } => *slot,
         }
     }
 }
 
+/// Why a node reset its PoH away from the fork it was previously building on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ForkAbandonedReason {
+    /// The heaviest fork failed the switch threshold, so the node fell back to the
+    /// heaviest bank on its last voted fork
+    SwitchThresholdFailure,
+    /// Fork choice moved to a heavier fork
+    HeaviestForkChanged,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase", untagged)]
 pub enum RpcSignatureResult {
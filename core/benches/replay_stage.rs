@@ -0,0 +1,207 @@
+#![feature(test)]
+
+extern crate solana_core;
+extern crate test;
+
+use rayon::prelude::*;
+use solana_core::{
+    ancestry_cache::AncestryCache, consensus::Tower,
+    latest_validator_votes_for_frozen_banks::LatestValidatorVotesForFrozenBanks,
+    replay_stage::ReplayLoopContext,
+};
+use solana_rayon_threadlimit::get_thread_count;
+use solana_runtime::{bank::Bank, bank_forks::BankForks, vote_account::ArcVoteAccount};
+use solana_sdk::{
+    account::{Account, AccountSharedData, WritableAccount},
+    clock::Slot,
+    hash::Hash,
+    pubkey::Pubkey,
+};
+use solana_vote_program::vote_state::{VoteState, VoteStateVersions, MAX_LOCKOUT_HISTORY};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, JoinHandle},
+};
+use test::Bencher;
+
+const NUM_FORKS: u64 = 32;
+const FORK_LENGTH: u64 = 16;
+const NUM_VOTE_ACCOUNTS: usize = 2000;
+
+// A root with `NUM_FORKS` forks of `FORK_LENGTH` frozen banks each
+fn new_bank_forks() -> Arc<RwLock<BankForks>> {
+    let mut bank_forks = BankForks::new(Bank::default());
+    let root_bank = bank_forks.root_bank();
+    let mut slot = 0;
+    for _ in 0..NUM_FORKS {
+        let mut parent = root_bank.clone();
+        for _ in 0..FORK_LENGTH {
+            slot += 1;
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            bank.freeze();
+            parent = bank_forks.insert(bank);
+        }
+    }
+    Arc::new(RwLock::new(bank_forks))
+}
+
+// Keeps taking the write lock, like the other users of `BankForks` do under load
+fn spawn_writer(bank_forks: &Arc<RwLock<BankForks>>, exit: &Arc<AtomicBool>) -> JoinHandle<()> {
+    let bank_forks = bank_forks.clone();
+    let exit = exit.clone();
+    thread::spawn(move || {
+        while !exit.load(Ordering::Relaxed) {
+            let _bank_forks = bank_forks.write().unwrap();
+            thread::yield_now();
+        }
+    })
+}
+
+fn bench_with_writer<F: FnMut(&RwLock<BankForks>)>(bench: &mut Bencher, mut f: F) {
+    let bank_forks = new_bank_forks();
+    let exit = Arc::new(AtomicBool::new(false));
+    let writer = spawn_writer(&bank_forks, &exit);
+    bench.iter(|| f(&bank_forks));
+    exit.store(true, Ordering::Relaxed);
+    writer.join().unwrap();
+}
+
+#[bench]
+fn bench_replay_loop_separate_locks(bench: &mut Bencher) {
+    // The reads the replay loop made before `ReplayLoopContext`, each under its own lock
+    bench_with_writer(bench, |bank_forks| {
+        let ancestors = bank_forks.read().unwrap().ancestors();
+        let descendants = bank_forks.read().unwrap().descendants().clone();
+        let root = bank_forks.read().unwrap().root();
+        let frozen_banks: Vec<_> = bank_forks
+            .read()
+            .unwrap()
+            .frozen_banks()
+            .into_iter()
+            .filter(|(slot, _)| *slot >= root)
+            .map(|(_, bank)| bank)
+            .collect();
+        test::black_box((ancestors, descendants, frozen_banks));
+    });
+}
+
+#[bench]
+fn bench_replay_loop_context(bench: &mut Bencher) {
+    let mut ancestry_cache = None;
+    bench_with_writer(bench, |bank_forks| {
+        let ancestry_cache =
+            ancestry_cache.get_or_insert_with(|| AncestryCache::new(&bank_forks.read().unwrap()));
+        let context = ReplayLoopContext::new(bank_forks, ancestry_cache);
+        let frozen_banks = context.frozen_banks();
+        test::black_box((context.root, frozen_banks));
+    });
+}
+
+// `NUM_VOTE_ACCOUNTS` vote accounts with different stakes, each with a full tower of
+// votes on a single fork ending right before `vote_accounts_bank_slot()`
+fn new_vote_accounts() -> Vec<(Pubkey, (u64, ArcVoteAccount))> {
+    (0..NUM_VOTE_ACCOUNTS)
+        .map(|i| {
+            let mut vote_state = VoteState::default();
+            for slot in i % 8..i % 8 + MAX_LOCKOUT_HISTORY {
+                vote_state.process_slot_vote_unchecked(slot as Slot);
+            }
+            let mut account = AccountSharedData::from(Account {
+                data: vec![0; VoteState::size_of()],
+                lamports: 1 + i as u64,
+                ..Account::default()
+            });
+            VoteState::serialize(
+                &VoteStateVersions::new_current(vote_state),
+                &mut account.data_as_mut_slice(),
+            )
+            .unwrap();
+            (
+                Pubkey::new_unique(),
+                (1 + i as u64, ArcVoteAccount::from(account)),
+            )
+        })
+        .collect()
+}
+
+fn vote_accounts_bank_slot() -> Slot {
+    (MAX_LOCKOUT_HISTORY + 8) as Slot
+}
+
+fn bench_collect_vote_lockouts(bench: &mut Bencher, num_shards: usize) {
+    let vote_accounts = new_vote_accounts();
+    let bank_slot = vote_accounts_bank_slot();
+    let ancestors: HashMap<Slot, HashSet<Slot>> = (0..=bank_slot)
+        .map(|slot| (slot, (0..slot).collect()))
+        .collect();
+    bench.iter(|| {
+        let mut latest_validator_votes_for_frozen_banks =
+            LatestValidatorVotesForFrozenBanks::default();
+        test::black_box(Tower::collect_vote_lockouts_sharded(
+            &Pubkey::default(),
+            bank_slot,
+            vote_accounts.clone(),
+            &ancestors,
+            |_| Some(Hash::default()),
+            &mut latest_validator_votes_for_frozen_banks,
+            num_shards,
+        ));
+    });
+}
+
+#[bench]
+fn bench_collect_vote_lockouts_serial(bench: &mut Bencher) {
+    bench_collect_vote_lockouts(bench, 1);
+}
+
+#[bench]
+fn bench_collect_vote_lockouts_parallel(bench: &mut Bencher) {
+    bench_collect_vote_lockouts(bench, 4);
+}
+
+// Collects the vote lockouts of `NUM_FORKS` sibling banks frozen at once, like
+// `ReplayStage::compute_bank_stats` does for the newly frozen banks of concurrent forks
+fn bench_collect_fork_vote_lockouts(bench: &mut Bencher, num_threads: usize) {
+    let vote_accounts = new_vote_accounts();
+    let parent_slot = vote_accounts_bank_slot();
+    let fork_slots: Vec<Slot> = (1..=NUM_FORKS).map(|i| parent_slot + i).collect();
+    let mut ancestors: HashMap<Slot, HashSet<Slot>> = (0..=parent_slot)
+        .map(|slot| (slot, (0..slot).collect()))
+        .collect();
+    for slot in &fork_slots {
+        ancestors.insert(*slot, (0..=parent_slot).collect());
+    }
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+    let collect_vote_lockouts = |slot: &Slot| {
+        Tower::collect_vote_lockouts(
+            &Pubkey::default(),
+            *slot,
+            vote_accounts.clone(),
+            &ancestors,
+            |_| Some(Hash::default()),
+            &mut LatestValidatorVotesForFrozenBanks::default(),
+        )
+    };
+    bench.iter(|| {
+        let computed_bank_states: Vec<_> =
+            thread_pool.install(|| fork_slots.par_iter().map(collect_vote_lockouts).collect());
+        test::black_box(computed_bank_states);
+    });
+}
+
+#[bench]
+fn bench_collect_fork_vote_lockouts_serial(bench: &mut Bencher) {
+    bench_collect_fork_vote_lockouts(bench, 1);
+}
+
+#[bench]
+fn bench_collect_fork_vote_lockouts_parallel(bench: &mut Bencher) {
+    bench_collect_fork_vote_lockouts(bench, get_thread_count());
+}
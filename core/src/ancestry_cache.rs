@@ -0,0 +1,292 @@
+//! The `ancestry_cache` module keeps the ancestors and descendants maps of the banks in
+//! `BankForks` across replay loop iterations, so that only the banks inserted, pruned or
+//! purged since the last iteration have to be accounted for instead of rebuilding both
+//! maps from scratch every iteration.
+
+use crate::replay_stage::ReplayStage;
+use solana_runtime::{bank::Bank, bank_forks::BankForks};
+use solana_sdk::clock::{BankId, Slot};
+use std::collections::{HashMap, HashSet};
+
+pub struct AncestryCache {
+    /// Same as `BankForks::ancestors()`
+    pub ancestors: HashMap<Slot, HashSet<Slot>>,
+    /// Same as `BankForks::descendants()`
+    pub descendants: HashMap<Slot, HashSet<Slot>>,
+    root: Slot,
+    // Slots of the banks in `BankForks` as of the last update, with the id of the bank.
+    // A slot whose bank was purged and created again has a new bank id, even if both
+    // happened between two updates
+    slots: HashMap<Slot, BankId>,
+}
+
+impl AncestryCache {
+    pub fn new(bank_forks: &BankForks) -> Self {
+        Self {
+            ancestors: bank_forks.ancestors(),
+            descendants: bank_forks.descendants().clone(),
+            root: bank_forks.root(),
+            slots: bank_forks
+                .banks()
+                .iter()
+                .map(|(slot, bank)| (*slot, bank.bank_id()))
+                .collect(),
+        }
+    }
+
+    pub fn root(&self) -> Slot {
+        self.root
+    }
+
+    /// Brings the cache up to date with `bank_forks`: drops the banks pruned by
+    /// `handle_new_root` or purged as duplicates, moves the root, and adds the banks
+    /// inserted by `generate_new_bank_forks` and for the leader slot since the last update.
+    /// A bank replaced by another one for the same slot counts as both removed and added.
+    ///
+    /// Duplicate purging edits `ancestors` and `descendants` directly through
+    /// `ReplayStage::purge_ancestors_descendants`, which this picks up where it left off
+    pub fn update(&mut self, bank_forks: &BankForks) {
+        let banks = bank_forks.banks();
+        let removed_slots: HashSet<Slot> = self
+            .slots
+            .iter()
+            .filter(|(slot, bank_id)| {
+                banks
+                    .get(slot)
+                    .map_or(true, |bank| bank.bank_id() != **bank_id)
+            })
+            .map(|(slot, _)| *slot)
+            .collect();
+        if !removed_slots.is_empty() {
+            self.remove(&removed_slots);
+        }
+        if bank_forks.root() != self.root {
+            self.set_root(bank_forks.root());
+        }
+        for (slot, bank) in banks {
+            if !self.slots.contains_key(slot) {
+                self.insert(bank);
+            }
+        }
+    }
+
+    /// Removes `slot` and its descendants from the cache, ahead of their banks being
+    /// removed from `BankForks`, and returns the descendants. Unlike editing the maps
+    /// directly, the slot is then picked up again if a bank is created for it before the
+    /// next update
+    pub fn purge(&mut self, slot: Slot) -> HashSet<Slot> {
+        let slot_descendants = self.descendants.get(&slot).cloned().unwrap_or_default();
+        ReplayStage::purge_ancestors_descendants(
+            slot,
+            &slot_descendants,
+            &mut self.ancestors,
+            &mut self.descendants,
+        );
+        for purged_slot in slot_descendants.iter().chain(std::iter::once(&slot)) {
+            self.slots.remove(purged_slot);
+        }
+        slot_descendants
+    }
+
+    fn insert(&mut self, bank: &Bank) {
+        let slot = bank.slot();
+        let proper_ancestors: Vec<Slot> = bank
+            .ancestors
+            .keys()
+            .into_iter()
+            .filter(|ancestor| *ancestor != slot)
+            .collect();
+        self.descendants.entry(slot).or_default();
+        for ancestor in &proper_ancestors {
+            self.descendants.entry(*ancestor).or_default().insert(slot);
+        }
+        let root = self.root;
+        self.ancestors.insert(
+            slot,
+            proper_ancestors
+                .into_iter()
+                .filter(|ancestor| *ancestor >= root)
+                .collect(),
+        );
+        self.slots.insert(slot, bank.bank_id());
+    }
+
+    fn remove(&mut self, removed_slots: &HashSet<Slot>) {
+        for slot in removed_slots {
+            self.ancestors.remove(slot);
+            self.slots.remove(slot);
+        }
+        // Like `BankForks::remove()`, keep the entries of slots without a bank only for
+        // as long as they have descendants left
+        let slots = &self.slots;
+        self.descendants.retain(|slot, descendants| {
+            descendants.retain(|descendant| !removed_slots.contains(descendant));
+            slots.contains_key(slot) || !descendants.is_empty()
+        });
+    }
+
+    fn set_root(&mut self, root: Slot) {
+        self.root = root;
+        for ancestors in self.ancestors.values_mut() {
+            ancestors.retain(|ancestor| *ancestor >= root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+    use solana_runtime::{
+        accounts_background_service::AbsRequestSender,
+        genesis_utils::{create_genesis_config, GenesisConfigInfo},
+    };
+    use solana_sdk::pubkey::Pubkey;
+
+    fn check_cache(cache: &AncestryCache, bank_forks: &BankForks) {
+        assert_eq!(cache.root(), bank_forks.root());
+        assert_eq!(cache.ancestors, bank_forks.ancestors());
+        assert_eq!(&cache.descendants, bank_forks.descendants());
+    }
+
+    // Slots of the banks at or above the root, in ascending order
+    fn live_slots(bank_forks: &BankForks) -> Vec<Slot> {
+        let mut slots: Vec<Slot> = bank_forks
+            .banks()
+            .keys()
+            .copied()
+            .filter(|slot| *slot >= bank_forks.root())
+            .collect();
+        slots.sort_unstable();
+        slots
+    }
+
+    #[test]
+    fn test_ancestry_cache_random_updates() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut rng = thread_rng();
+        for _ in 0..10 {
+            let mut bank_forks = BankForks::new(Bank::new(&genesis_config));
+            let mut cache = AncestryCache::new(&bank_forks);
+            check_cache(&cache, &bank_forks);
+            let mut next_slot = 1;
+            for _ in 0..100 {
+                let slots = live_slots(&bank_forks);
+                match rng.gen_range(0, 10) {
+                    // Insert a child of a random bank
+                    0..=5 => {
+                        let parent = bank_forks[slots[rng.gen_range(0, slots.len())]].clone();
+                        bank_forks.insert(Bank::new_from_parent(
+                            &parent,
+                            &Pubkey::default(),
+                            next_slot,
+                        ));
+                        next_slot += 1;
+                    }
+                    // Root a random descendant of the root, sometimes keeping the banks
+                    // down to the old root as `handle_new_root` does for the highest
+                    // confirmed root
+                    6..=7 => {
+                        let old_root = bank_forks.root();
+                        let mut candidates: Vec<Slot> = bank_forks.descendants()[&old_root]
+                            .iter()
+                            .copied()
+                            .collect();
+                        candidates.sort_unstable();
+                        candidates.push(old_root);
+                        let new_root = candidates[rng.gen_range(0, candidates.len())];
+                        let highest_confirmed_root = if rng.gen() { Some(old_root) } else { None };
+                        bank_forks.set_root(
+                            new_root,
+                            &AbsRequestSender::default(),
+                            highest_confirmed_root,
+                        );
+                    }
+                    // Purge a random slot above the root and its descendants, the way
+                    // duplicate slots are
+                    _ => {
+                        if slots.len() < 2 {
+                            continue;
+                        }
+                        cache.update(&bank_forks);
+                        let slot = slots[rng.gen_range(1, slots.len())];
+                        let slot_descendants = cache.descendants[&slot].clone();
+                        ReplayStage::purge_ancestors_descendants(
+                            slot,
+                            &slot_descendants,
+                            &mut cache.ancestors,
+                            &mut cache.descendants,
+                        );
+                        for purged_slot in slot_descendants.iter().chain(std::iter::once(&slot)) {
+                            bank_forks.remove(*purged_slot).unwrap();
+                        }
+                    }
+                }
+                // Updates are batched over a few changes, like over a replay loop iteration
+                if rng.gen_range(0, 3) == 0 {
+                    cache.update(&bank_forks);
+                    check_cache(&cache, &bank_forks);
+                }
+            }
+            cache.update(&bank_forks);
+            check_cache(&cache, &bank_forks);
+        }
+    }
+
+    #[test]
+    fn test_ancestry_cache_slot_recreated() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut bank_forks = BankForks::new(Bank::new(&genesis_config));
+        let bank0 = bank_forks[0].clone();
+        let bank1 = bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        bank_forks.insert(Bank::new_from_parent(&bank1, &Pubkey::default(), 2));
+        bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 3));
+        let mut cache = AncestryCache::new(&bank_forks);
+
+        // Purge slot 1 and its descendant 2, as a duplicate, then create slot 1 again
+        // before the cache is updated
+        let slot_descendants = cache.descendants[&1].clone();
+        ReplayStage::purge_ancestors_descendants(
+            1,
+            &slot_descendants,
+            &mut cache.ancestors,
+            &mut cache.descendants,
+        );
+        bank_forks.remove(2).unwrap();
+        bank_forks.remove(1).unwrap();
+        let bank1 = bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        cache.update(&bank_forks);
+        check_cache(&cache, &bank_forks);
+        assert_eq!(cache.ancestors[&1], vec![0].into_iter().collect());
+        assert!(!cache.ancestors.contains_key(&2));
+
+        // Same without the maps having been purged first, slot 3 now builds on 1
+        bank_forks.remove(3).unwrap();
+        bank_forks.insert(Bank::new_from_parent(&bank1, &Pubkey::default(), 3));
+        cache.update(&bank_forks);
+        check_cache(&cache, &bank_forks);
+        assert_eq!(cache.ancestors[&3], vec![0, 1].into_iter().collect());
+    }
+
+    #[test]
+    fn test_ancestry_cache_highest_confirmed_root() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut bank_forks = BankForks::new(Bank::new(&genesis_config));
+        let mut cache = AncestryCache::new(&bank_forks);
+        let bank0 = bank_forks[0].clone();
+        let bank1 = bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let bank2 = bank_forks.insert(Bank::new_from_parent(&bank1, &Pubkey::default(), 2));
+        bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 3));
+        cache.update(&bank_forks);
+        check_cache(&cache, &bank_forks);
+
+        // Slot 1 is kept below the root, slots 0 and 3 are pruned
+        bank_forks.set_root(2, &AbsRequestSender::default(), Some(1));
+        bank_forks.insert(Bank::new_from_parent(&bank2, &Pubkey::default(), 4));
+        cache.update(&bank_forks);
+        check_cache(&cache, &bank_forks);
+        assert!(cache.ancestors[&1].is_empty());
+        assert_eq!(cache.descendants[&0], vec![1, 2].into_iter().collect());
+        assert!(!cache.ancestors.contains_key(&3));
+    }
+}
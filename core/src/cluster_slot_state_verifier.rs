@@ -3,12 +3,91 @@ use crate::{
     progress_map::ProgressMap,
 };
 use solana_sdk::{clock::Slot, hash::Hash};
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::FromIterator,
+};
+
+pub const DEFAULT_DUPLICATE_SLOTS_TRACKER_CAPACITY: usize = 10_000;
 
-pub(crate) type DuplicateSlotsTracker = BTreeSet<Slot>;
 pub(crate) type GossipDuplicateConfirmedSlots = BTreeMap<Slot, Hash>;
 type SlotStateHandler = fn(Slot, &Hash, Option<&Hash>, bool, bool) -> Vec<ResultingStateChange>;
 
+/// The slots that have been processed as duplicates, bounded so that bogus duplicate
+/// slots can't grow it without limit
+#[derive(Debug)]
+pub struct DuplicateSlotsTracker {
+    slots: BTreeSet<Slot>,
+    capacity: usize,
+}
+
+impl Default for DuplicateSlotsTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_DUPLICATE_SLOTS_TRACKER_CAPACITY)
+    }
+}
+
+impl FromIterator<Slot> for DuplicateSlotsTracker {
+    fn from_iter<I: IntoIterator<Item = Slot>>(slots: I) -> Self {
+        let mut duplicate_slots_tracker = Self::default();
+        for slot in slots {
+            duplicate_slots_tracker.insert(slot);
+        }
+        duplicate_slots_tracker
+    }
+}
+
+impl DuplicateSlotsTracker {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        Self {
+            slots: BTreeSet::new(),
+            capacity,
+        }
+    }
+
+    /// Returns false if `slot` is already tracked. Once more than `capacity` slots are
+    /// tracked, the smallest ones are evicted, as they're the least likely to still matter
+    pub fn insert(&mut self, slot: Slot) -> bool {
+        if !self.slots.insert(slot) {
+            return false;
+        }
+        let mut num_evicted = 0;
+        while self.slots.len() > self.capacity {
+            let smallest = *self.slots.iter().next().unwrap();
+            self.slots.remove(&smallest);
+            num_evicted += 1;
+        }
+        if num_evicted > 0 {
+            inc_new_counter_info!("duplicate_slots_tracker-evicted", num_evicted);
+        }
+        true
+    }
+
+    pub fn contains(&self, slot: &Slot) -> bool {
+        self.slots.contains(slot)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Slot> {
+        self.slots.iter()
+    }
+
+    /// Drops the slots below `new_root`
+    pub fn set_root(&mut self, new_root: Slot) {
+        let mut slots_ge_root = self.slots.split_off(&new_root);
+        // `self.slots` now only contains entries >= `new_root`
+        std::mem::swap(&mut self.slots, &mut slots_ge_root);
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub enum SlotStateUpdate {
     Frozen,
@@ -989,4 +1068,45 @@ mod test {
             (3, slot3_hash)
         );
     }
+
+    #[test]
+    fn test_duplicate_slots_tracker_eviction() {
+        let mut duplicate_slots_tracker = DuplicateSlotsTracker::new(3);
+        let tracked_slots = |duplicate_slots_tracker: &DuplicateSlotsTracker| {
+            duplicate_slots_tracker
+                .iter()
+                .copied()
+                .collect::<Vec<Slot>>()
+        };
+        for slot in 1..=3 {
+            assert!(duplicate_slots_tracker.insert(slot));
+        }
+        // Re-inserting a tracked slot is deduplicated and evicts nothing
+        assert!(!duplicate_slots_tracker.insert(2));
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![1, 2, 3]);
+
+        // Going over capacity evicts the smallest slot
+        assert!(duplicate_slots_tracker.insert(5));
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![2, 3, 5]);
+        assert!(!duplicate_slots_tracker.contains(&1));
+
+        // The remaining slots are still deduplicated
+        assert!(!duplicate_slots_tracker.insert(3));
+        assert!(!duplicate_slots_tracker.insert(5));
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![2, 3, 5]);
+
+        // A slot smaller than all tracked ones is evicted right away
+        assert!(duplicate_slots_tracker.insert(1));
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![2, 3, 5]);
+
+        // Whatever the insertion order, the largest slots are kept
+        for slot in (6..=10).rev() {
+            duplicate_slots_tracker.insert(slot);
+        }
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![8, 9, 10]);
+        assert_eq!(duplicate_slots_tracker.len(), 3);
+
+        duplicate_slots_tracker.set_root(9);
+        assert_eq!(tracked_slots(&duplicate_slots_tracker), vec![9, 10]);
+    }
 }
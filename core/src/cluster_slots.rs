@@ -99,6 +99,24 @@ impl ClusterSlots {
             .collect()
     }
 
+    /// Returns the highest slot a staked node has advertised in its epoch slots, i.e. the
+    /// furthest the cluster is known to have gotten
+    pub fn max_staked_slot(&self) -> Option<Slot> {
+        self.cluster_slots
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(_, slot_pubkeys)| {
+                slot_pubkeys
+                    .read()
+                    .unwrap()
+                    .values()
+                    .any(|stake| *stake > 0)
+            })
+            .map(|(slot, _)| *slot)
+    }
+
     #[cfg(test)]
     pub(crate) fn insert_node_id(&self, slot: Slot, node_id: Pubkey) {
         let balance = self
@@ -413,4 +431,28 @@ mod tests {
             .generate_repairs_for_missing_slots(&self_id, 0)
             .is_empty());
     }
+
+    #[test]
+    fn test_max_staked_slot() {
+        let cs = ClusterSlots::default();
+        assert_eq!(cs.max_staked_slot(), None);
+        let staked_node = solana_sdk::pubkey::new_rand();
+        *cs.validator_stakes.write().unwrap() = Arc::new(
+            vec![(
+                staked_node,
+                NodeVoteAccounts {
+                    total_stake: 1,
+                    vote_accounts: vec![Pubkey::default()],
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        cs.insert_node_id(5, staked_node);
+        // Slots only advertised by unstaked nodes are ignored
+        cs.insert_node_id(8, Pubkey::default());
+        assert_eq!(cs.max_staked_slot(), Some(5));
+        cs.insert_node_id(8, staked_node);
+        assert_eq!(cs.max_staked_slot(), Some(8));
+    }
 }
@@ -35,7 +35,7 @@ use std::{
 };
 use thiserror::Error;
 
-#[derive(PartialEq, Clone, Debug, AbiExample)]
+#[derive(PartialEq, Clone, Debug, Serialize, AbiExample)]
 pub enum SwitchForkDecision {
     SwitchProof(Hash),
     SameFork,
@@ -91,9 +91,15 @@ pub type Stake = u64;
 pub type VotedStakes = HashMap<Slot, Stake>;
 pub type PubkeyVotes = Vec<(Pubkey, Slot)>;
 
+// Number of shards `Tower::collect_vote_lockouts()` splits the vote accounts into, one per
+// stake quartile
+const NUM_VOTE_LOCKOUT_SHARDS: usize = 4;
+// Below this many vote accounts per shard, sharding costs more than it saves
+const MIN_VOTE_ACCOUNTS_PER_SHARD: usize = 128;
+
 // lint warning "bank_weight is never read"
 #[allow(dead_code)]
-pub(crate) struct ComputedBankState {
+pub struct ComputedBankState {
     pub voted_stakes: VotedStakes,
     pub total_stake: Stake,
     pub bank_weight: u128,
@@ -103,6 +109,40 @@ pub(crate) struct ComputedBankState {
     pub my_latest_landed_vote: Option<Slot>,
 }
 
+// What `Tower::collect_vote_lockouts()` gathers from a shard of the vote accounts, before
+// the shards are merged
+#[derive(Default)]
+struct VoteLockoutsShard {
+    voted_stakes: VotedStakes,
+    vote_slots: HashSet<Slot>,
+    total_stake: Stake,
+    bank_weight: u128,
+    lockout_intervals: LockoutIntervals,
+    my_latest_landed_vote: Option<Slot>,
+    // The last vote of each vote account, added to `LatestValidatorVotesForFrozenBanks`
+    // after merging
+    landed_votes: PubkeyVotes,
+}
+
+impl VoteLockoutsShard {
+    fn merge(&mut self, other: Self) {
+        for (slot, stake) in other.voted_stakes {
+            *self.voted_stakes.entry(slot).or_default() += stake;
+        }
+        self.vote_slots.extend(other.vote_slots);
+        self.total_stake += other.total_stake;
+        self.bank_weight += other.bank_weight;
+        for (interval_end, intervals) in other.lockout_intervals {
+            self.lockout_intervals
+                .entry(interval_end)
+                .or_insert_with(Vec::new)
+                .extend(intervals);
+        }
+        self.my_latest_landed_vote = self.my_latest_landed_vote.or(other.my_latest_landed_vote);
+        self.landed_votes.extend(other.landed_votes);
+    }
+}
+
 #[frozen_abi(digest = "Eay84NBbJqiMBfE7HHH2o6e51wcvoU79g8zCi5sw6uj3")]
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, AbiExample)]
 pub struct Tower {
@@ -198,6 +238,7 @@ impl Tower {
         ledger_path: &Path,
         my_pubkey: &Pubkey,
         vote_account: &Pubkey,
+        superminority_threshold: f64,
     ) -> Self {
         let root_bank = bank_forks.root_bank();
         let (_progress, heaviest_subtree_fork_choice) =
@@ -206,6 +247,7 @@ impl Tower {
                 bank_forks.frozen_banks().values().cloned().collect(),
                 my_pubkey,
                 vote_account,
+                superminority_threshold,
             );
         let root = root_bank.slot();
 
@@ -220,7 +262,7 @@ impl Tower {
         Self::new(my_pubkey, vote_account, root, &heaviest_bank, ledger_path)
     }
 
-    pub(crate) fn collect_vote_lockouts<F>(
+    pub fn collect_vote_lockouts<F>(
         vote_account_pubkey: &Pubkey,
         bank_slot: Slot,
         vote_accounts: F,
@@ -231,15 +273,116 @@ impl Tower {
     where
         F: IntoIterator<Item = (Pubkey, (u64, ArcVoteAccount))>,
     {
-        let mut vote_slots = HashSet::new();
-        let mut voted_stakes = HashMap::new();
-        let mut total_stake = 0;
-        let mut bank_weight = 0;
-        // Tree of intervals of lockouts of the form [slot, slot + slot.lockout],
-        // keyed by end of the range
-        let mut lockout_intervals = LockoutIntervals::new();
-        let mut my_latest_landed_vote = None;
+        let vote_accounts: Vec<_> = vote_accounts
+            .into_iter()
+            .filter(|(_, (voted_stake, _))| *voted_stake != 0)
+            .collect();
+        let num_shards = (vote_accounts.len() / MIN_VOTE_ACCOUNTS_PER_SHARD)
+            .max(1)
+            .min(NUM_VOTE_LOCKOUT_SHARDS);
+        Self::collect_vote_lockouts_sharded(
+            vote_account_pubkey,
+            bank_slot,
+            vote_accounts,
+            ancestors,
+            get_frozen_hash,
+            latest_validator_votes_for_frozen_banks,
+            num_shards,
+        )
+    }
+
+    /// Same as `collect_vote_lockouts()`, but with the vote accounts split into
+    /// `num_shards` shards by stake rank, processed in parallel. A single shard is
+    /// processed serially on the calling thread
+    pub fn collect_vote_lockouts_sharded(
+        vote_account_pubkey: &Pubkey,
+        bank_slot: Slot,
+        mut vote_accounts: Vec<(Pubkey, (u64, ArcVoteAccount))>,
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+        get_frozen_hash: impl Fn(Slot) -> Option<Hash>,
+        latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
+        num_shards: usize,
+    ) -> ComputedBankState {
+        assert!(num_shards > 0);
+        // Sorting by stake puts each stake quartile (for 4 shards) in its own shard
+        vote_accounts.sort_unstable_by(|(_, (a, _)), (_, (b, _))| b.cmp(a));
+        let shard_len = ((vote_accounts.len() + num_shards - 1) / num_shards).max(1);
+        let shards: Vec<_> = vote_accounts.chunks(shard_len).collect();
+        let shard =
+            Self::collect_shards_vote_lockouts(vote_account_pubkey, bank_slot, &shards, ancestors);
+
+        // Only done once all the shards are merged, as `get_frozen_hash` and
+        // `latest_validator_votes_for_frozen_banks` can't be shared across threads
+        for (key, last_landed_voted_slot) in shard.landed_votes {
+            latest_validator_votes_for_frozen_banks.check_add_vote(
+                key,
+                last_landed_voted_slot,
+                get_frozen_hash(last_landed_voted_slot),
+                true,
+            );
+        }
+
+        let mut voted_stakes = shard.voted_stakes;
+        // TODO: populate_ancestor_voted_stakes only adds zeros. Comment why
+        // that is necessary (if so).
+        Self::populate_ancestor_voted_stakes(&mut voted_stakes, shard.vote_slots, ancestors);
+        ComputedBankState {
+            voted_stakes,
+            total_stake: shard.total_stake,
+            bank_weight: shard.bank_weight,
+            lockout_intervals: shard.lockout_intervals,
+            my_latest_landed_vote: shard.my_latest_landed_vote,
+        }
+    }
+
+    // Splits `shards` in halves processed with `rayon::join()` until a single shard is
+    // left, then merges the results
+    fn collect_shards_vote_lockouts(
+        vote_account_pubkey: &Pubkey,
+        bank_slot: Slot,
+        shards: &[&[(Pubkey, (u64, ArcVoteAccount))]],
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+    ) -> VoteLockoutsShard {
+        match shards {
+            [] => VoteLockoutsShard::default(),
+            [shard] => {
+                Self::collect_shard_vote_lockouts(vote_account_pubkey, bank_slot, shard, ancestors)
+            }
+            _ => {
+                let (left, right) = shards.split_at(shards.len() / 2);
+                let (mut left, right) = rayon::join(
+                    || {
+                        Self::collect_shards_vote_lockouts(
+                            vote_account_pubkey,
+                            bank_slot,
+                            left,
+                            ancestors,
+                        )
+                    },
+                    || {
+                        Self::collect_shards_vote_lockouts(
+                            vote_account_pubkey,
+                            bank_slot,
+                            right,
+                            ancestors,
+                        )
+                    },
+                );
+                left.merge(right);
+                left
+            }
+        }
+    }
+
+    fn collect_shard_vote_lockouts(
+        vote_account_pubkey: &Pubkey,
+        bank_slot: Slot,
+        vote_accounts: &[(Pubkey, (u64, ArcVoteAccount))],
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+    ) -> VoteLockoutsShard {
+        let mut shard = VoteLockoutsShard::default();
         for (key, (voted_stake, account)) in vote_accounts {
+            let (key, voted_stake) = (*key, *voted_stake);
             if voted_stake == 0 {
                 continue;
             }
@@ -259,14 +402,15 @@ impl Tower {
                 Ok(vote_state) => vote_state.clone(),
             };
             for vote in &vote_state.votes {
-                lockout_intervals
+                shard
+                    .lockout_intervals
                     .entry(vote.last_locked_out_slot())
                     .or_insert_with(Vec::new)
                     .push((vote.slot, key));
             }
 
             if key == *vote_account_pubkey {
-                my_latest_landed_vote = vote_state.nth_recent_vote(0).map(|v| v.slot);
+                shard.my_latest_landed_vote = vote_state.nth_recent_vote(0).map(|v| v.slot);
                 debug!("vote state {:?}", vote_state);
                 debug!(
                     "observed slot {}",
@@ -287,19 +431,14 @@ impl Tower {
 
             // Add the last vote to update the `heaviest_subtree_fork_choice`
             if let Some(last_landed_voted_slot) = vote_state.last_voted_slot() {
-                latest_validator_votes_for_frozen_banks.check_add_vote(
-                    key,
-                    last_landed_voted_slot,
-                    get_frozen_hash(last_landed_voted_slot),
-                    true,
-                );
+                shard.landed_votes.push((key, last_landed_voted_slot));
             }
 
             vote_state.process_slot_vote_unchecked(bank_slot);
 
             for vote in &vote_state.votes {
-                bank_weight += vote.lockout() as u128 * voted_stake as u128;
-                vote_slots.insert(vote.slot);
+                shard.bank_weight += vote.lockout() as u128 * voted_stake as u128;
+                shard.vote_slots.insert(vote.slot);
             }
 
             if start_root != vote_state.root_slot {
@@ -309,8 +448,8 @@ impl Tower {
                         slot: root,
                     };
                     trace!("ROOT: {}", vote.slot);
-                    bank_weight += vote.lockout() as u128 * voted_stake as u128;
-                    vote_slots.insert(vote.slot);
+                    shard.bank_weight += vote.lockout() as u128 * voted_stake as u128;
+                    shard.vote_slots.insert(vote.slot);
                 }
             }
             if let Some(root) = vote_state.root_slot {
@@ -318,8 +457,8 @@ impl Tower {
                     confirmation_count: MAX_LOCKOUT_HISTORY as u32,
                     slot: root,
                 };
-                bank_weight += vote.lockout() as u128 * voted_stake as u128;
-                vote_slots.insert(vote.slot);
+                shard.bank_weight += vote.lockout() as u128 * voted_stake as u128;
+                shard.vote_slots.insert(vote.slot);
             }
 
             // The last vote in the vote stack is a simulated vote on bank_slot, which
@@ -338,25 +477,15 @@ impl Tower {
             if let Some(vote) = vote_state.nth_recent_vote(1) {
                 // Update all the parents of this last vote with the stake of this vote account
                 Self::update_ancestor_voted_stakes(
-                    &mut voted_stakes,
+                    &mut shard.voted_stakes,
                     vote.slot,
                     voted_stake,
                     ancestors,
                 );
             }
-            total_stake += voted_stake;
-        }
-
-        // TODO: populate_ancestor_voted_stakes only adds zeros. Comment why
-        // that is necessary (if so).
-        Self::populate_ancestor_voted_stakes(&mut voted_stakes, vote_slots, ancestors);
-        ComputedBankState {
-            voted_stakes,
-            total_stake,
-            bank_weight,
-            lockout_intervals,
-            my_latest_landed_vote,
+            shard.total_stake += voted_stake;
         }
+        shard
     }
 
     pub fn is_slot_confirmed(
@@ -1362,7 +1491,9 @@ pub mod test {
         fork_choice::{ForkChoice, SelectVoteAndResetForkResult},
         heaviest_subtree_fork_choice::SlotHashKey,
         progress_map::ForkProgress,
-        replay_stage::{HeaviestForkFailures, ReplayStage},
+        replay_stage::{
+            EpochStakesCache, HeaviestForkFailures, ReplayStage, SUPERMINORITY_THRESHOLD,
+        },
         unfrozen_gossip_verified_vote_hashes::UnfrozenGossipVerifiedVoteHashes,
     };
     use solana_ledger::{blockstore::make_slot_entries, get_tmp_ledger_path};
@@ -1387,7 +1518,7 @@ pub mod test {
         vote_transaction,
     };
     use std::{
-        collections::HashMap,
+        collections::{HashMap, VecDeque},
         fs::{remove_file, OpenOptions},
         io::{Read, Seek, SeekFrom, Write},
         sync::{Arc, RwLock},
@@ -1445,9 +1576,9 @@ pub mod test {
                 let parent = *walk.get_parent().unwrap().data();
                 let parent_bank = self.bank_forks.read().unwrap().get(parent).unwrap().clone();
                 let new_bank = Bank::new_from_parent(&parent_bank, &Pubkey::default(), slot);
-                self.progress
-                    .entry(slot)
-                    .or_insert_with(|| ForkProgress::new(Hash::default(), None, None, 0, 0));
+                self.progress.entry(slot).or_insert_with(|| {
+                    ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD)
+                });
                 for (pubkey, vote) in cluster_votes.iter() {
                     if vote.contains(&parent) {
                         let keypairs = self.validator_keypairs.get(pubkey).unwrap();
@@ -1495,6 +1626,10 @@ pub mod test {
                 .cloned()
                 .collect();
 
+            let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap();
             let _ = ReplayStage::compute_bank_stats(
                 my_pubkey,
                 &ancestors,
@@ -1506,6 +1641,8 @@ pub mod test {
                 &self.bank_forks,
                 &mut self.heaviest_subtree_fork_choice,
                 &mut self.latest_validator_votes_for_frozen_banks,
+                SUPERMINORITY_THRESHOLD,
+                &replay_thread_pool,
             );
 
             let vote_bank = self
@@ -1530,6 +1667,7 @@ pub mod test {
                 tower,
                 &self.latest_validator_votes_for_frozen_banks,
                 &self.heaviest_subtree_fork_choice,
+                &mut EpochStakesCache::default(),
             );
 
             // Make sure this slot isn't locked out or failing threshold
@@ -1558,7 +1696,7 @@ pub mod test {
                 &mut GossipDuplicateConfirmedSlots::default(),
                 &mut UnfrozenGossipVerifiedVoteHashes::default(),
                 &mut true,
-                &mut Vec::new(),
+                &mut VecDeque::new(),
             )
         }
 
@@ -1593,7 +1731,9 @@ pub mod test {
         ) {
             self.progress
                 .entry(slot)
-                .or_insert_with(|| ForkProgress::new(Hash::default(), None, None, 0, 0))
+                .or_insert_with(|| {
+                    ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD)
+                })
                 .fork_stats
                 .lockout_intervals
                 .entry(lockout_interval.1)
@@ -1707,6 +1847,7 @@ pub mod test {
                 None,
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
         let bank_forks = BankForks::new(bank0);
@@ -2291,10 +2432,23 @@ pub mod test {
             if slot == 48 {
                 assert!(results.get(&slot).unwrap().is_empty());
             } else {
-                assert_eq!(
-                    *results.get(&slot).unwrap(),
-                    vec![HeaviestForkFailures::FailedSwitchThreshold(slot)]
-                );
+                let failures = results.get(&slot).unwrap();
+                assert_eq!(failures.len(), 1);
+                match failures[0] {
+                    HeaviestForkFailures::FailedSwitchThreshold(
+                        failed_slot,
+                        switch_proof_stake,
+                        total_stake,
+                    ) => {
+                        assert_eq!(failed_slot, slot);
+                        assert!(total_stake > 0);
+                        assert!(
+                            (switch_proof_stake as f64 / total_stake as f64)
+                                <= SWITCH_FORK_THRESHOLD
+                        );
+                    }
+                    ref failure => panic!("unexpected failure {:?}", failure),
+                }
             }
         }
     }
@@ -2478,6 +2632,60 @@ pub mod test {
         assert_eq!(new_votes, account_latest_votes);
     }
 
+    #[test]
+    fn test_collect_vote_lockouts_sharded() {
+        // Accounts with different stakes, voting on different prefixes of 0..10
+        let votes: Vec<Vec<u64>> = (0..10).map(|i| (0..i).collect()).collect();
+        let stake_votes: Vec<(u64, &[u64])> = (0..40)
+            .map(|i| (i as u64 % 7, votes[i % 10].as_slice()))
+            .collect();
+        let accounts = gen_stakes(&stake_votes);
+        let ancestors = (0..=10).map(|i| (i, (0..i).collect())).collect();
+        let my_vote_pubkey = accounts[3].0;
+
+        let collect_vote_lockouts = |num_shards| {
+            let mut latest_validator_votes_for_frozen_banks =
+                LatestValidatorVotesForFrozenBanks::default();
+            let computed_bank_state = Tower::collect_vote_lockouts_sharded(
+                &my_vote_pubkey,
+                10,
+                accounts.clone(),
+                &ancestors,
+                |_| Some(Hash::default()),
+                &mut latest_validator_votes_for_frozen_banks,
+                num_shards,
+            );
+            let mut new_votes = latest_validator_votes_for_frozen_banks.take_votes_dirty_set(0);
+            new_votes.sort();
+            (computed_bank_state, new_votes)
+        };
+
+        let (serial, serial_votes) = collect_vote_lockouts(1);
+        assert_eq!(serial.total_stake, (0..40).map(|i| i % 7).sum::<u64>());
+        for num_shards in 2..=5 {
+            let (sharded, sharded_votes) = collect_vote_lockouts(num_shards);
+            assert_eq!(sharded.voted_stakes, serial.voted_stakes);
+            assert_eq!(sharded.total_stake, serial.total_stake);
+            assert_eq!(sharded.bank_weight, serial.bank_weight);
+            assert_eq!(sharded.my_latest_landed_vote, serial.my_latest_landed_vote);
+            assert_eq!(sharded_votes, serial_votes);
+            // Only the order of the intervals sharing an end may differ
+            let sorted_intervals = |lockout_intervals: LockoutIntervals| {
+                lockout_intervals
+                    .into_iter()
+                    .map(|(end, mut intervals)| {
+                        intervals.sort();
+                        (end, intervals)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                sorted_intervals(sharded.lockout_intervals),
+                sorted_intervals(serial.lockout_intervals.clone())
+            );
+        }
+    }
+
     #[test]
     fn test_check_vote_threshold_without_votes() {
         let tower = Tower::new_for_tests(1, 0.67);
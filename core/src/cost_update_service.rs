@@ -1,32 +1,86 @@
-//! this service receives instruction ExecuteTimings from replay_stage,
+//! this service receives instruction ExecuteTimings of each replayed slot from replay_stage,
 //! update cost_model which is shared with banking_stage to optimize
 //! packing transactions into block; it also triggers persisting cost
 //! table to blockstore.
 
 use crate::cost_model::CostModel;
+use crossbeam_channel::{Receiver, Sender};
 use solana_ledger::blockstore::Blockstore;
 use solana_measure::measure::Measure;
 use solana_runtime::bank::ExecuteTimings;
-use solana_sdk::timing::timestamp;
+use solana_sdk::{clock::Slot, pubkey::Pubkey, timing::timestamp};
 use std::{
+    collections::HashMap,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::Receiver,
         Arc, RwLock,
     },
     thread::{self, Builder, JoinHandle},
     time::Duration,
 };
 
+// Number of leaders with the most execute time reported per metrics window
+const MAX_LEADERS_REPORTED: usize = 10;
+
+#[derive(Debug, Default, PartialEq)]
+struct LeaderExecuteTimings {
+    num_slots: u64,
+    execute_us: u64,
+}
+
 #[derive(Default)]
 pub struct CostUpdateServiceTiming {
     last_print: u64,
     update_cost_model_count: u64,
     update_cost_model_elapsed: u64,
     persist_cost_table_elapsed: u64,
+    // Rolling aggregate of the execute timings of the slots received since the last report
+    num_slots: u64,
+    execute_timings: ExecuteTimings,
+    // Same aggregate per leader, only the top `MAX_LEADERS_REPORTED` are reported to keep
+    // the number of distinct leaders in the metrics bounded
+    leader_execute_timings: HashMap<Pubkey, LeaderExecuteTimings>,
 }
 
 impl CostUpdateServiceTiming {
+    fn record_slot(&mut self, cost_update: &SlotExecuteTimings) {
+        let execute_timings = &cost_update.execute_timings;
+        datapoint_info!(
+            "cost-update-service-slot-execute-timings",
+            ("slot", cost_update.slot as i64, i64),
+            ("check_us", execute_timings.check_us as i64, i64),
+            ("load_us", execute_timings.load_us as i64, i64),
+            ("execute_us", execute_timings.execute_us as i64, i64),
+            ("store_us", execute_timings.store_us as i64, i64),
+            (
+                "num_execute_batches",
+                execute_timings.num_execute_batches as i64,
+                i64
+            ),
+            (
+                "num_programs",
+                execute_timings.details.per_program_timings.len() as i64,
+                i64
+            ),
+        );
+        self.num_slots += 1;
+        self.execute_timings.accumulate(execute_timings);
+        let leader_execute_timings = self
+            .leader_execute_timings
+            .entry(cost_update.collector_id)
+            .or_default();
+        leader_execute_timings.num_slots += 1;
+        leader_execute_timings.execute_us += execute_timings.execute_us;
+    }
+
+    // Leaders with the most execute time since the last report, most expensive first
+    fn top_leaders(&self, max_leaders: usize) -> Vec<(&Pubkey, &LeaderExecuteTimings)> {
+        let mut leaders: Vec<_> = self.leader_execute_timings.iter().collect();
+        leaders.sort_by(|(_, a), (_, b)| b.execute_us.cmp(&a.execute_us));
+        leaders.truncate(max_leaders);
+        leaders
+    }
+
     fn update(
         &mut self,
         update_cost_model_count: u64,
@@ -58,7 +112,21 @@ impl CostUpdateServiceTiming {
                     self.persist_cost_table_elapsed as i64,
                     i64
                 ),
+                ("num_slots", self.num_slots as i64, i64),
+                ("check_us", self.execute_timings.check_us as i64, i64),
+                ("load_us", self.execute_timings.load_us as i64, i64),
+                ("execute_us", self.execute_timings.execute_us as i64, i64),
+                ("store_us", self.execute_timings.store_us as i64, i64),
+                ("num_leaders", self.leader_execute_timings.len() as i64, i64),
             );
+            for (leader, leader_execute_timings) in self.top_leaders(MAX_LEADERS_REPORTED) {
+                datapoint_info!(
+                    "cost-update-service-leader-execute-timings",
+                    ("leader", leader.to_string(), String),
+                    ("num_slots", leader_execute_timings.num_slots as i64, i64),
+                    ("execute_us", leader_execute_timings.execute_us as i64, i64),
+                );
+            }
 
             *self = CostUpdateServiceTiming::default();
             self.last_print = now;
@@ -66,7 +134,16 @@ impl CostUpdateServiceTiming {
     }
 }
 
-pub type CostUpdateReceiver = Receiver<ExecuteTimings>;
+/// The execute timings of a bank completed by replay
+pub struct SlotExecuteTimings {
+    pub slot: Slot,
+    /// Leader of the slot
+    pub collector_id: Pubkey,
+    pub execute_timings: ExecuteTimings,
+}
+
+pub type CostUpdateSender = Sender<SlotExecuteTimings>;
+pub type CostUpdateReceiver = Receiver<SlotExecuteTimings>;
 
 pub struct CostUpdateService {
     thread_hdl: JoinHandle<()>,
@@ -112,7 +189,8 @@ impl CostUpdateService {
             let mut update_count = 0_u64;
             let mut update_cost_model_time = Measure::start("update_cost_model_time");
             for cost_update in cost_update_receiver.try_iter() {
-                dirty |= Self::update_cost_model(&cost_model, &cost_update);
+                cost_update_service_timing.record_slot(&cost_update);
+                dirty |= Self::update_cost_model(&cost_model, &cost_update.execute_timings);
                 update_count += 1;
             }
             update_cost_model_time.stop();
@@ -189,6 +267,57 @@ mod tests {
     use super::*;
     use solana_sdk::pubkey::Pubkey;
 
+    #[test]
+    fn test_cost_update_service_timing_aggregate() {
+        let leader1 = Pubkey::new_unique();
+        let leader2 = Pubkey::new_unique();
+        let leader3 = Pubkey::new_unique();
+        let mut timing = CostUpdateServiceTiming::default();
+        for (slot, leader, execute_us) in &[
+            (1, leader1, 100),
+            (2, leader2, 50),
+            (3, leader3, 10),
+            (4, leader2, 80),
+        ] {
+            let mut execute_timings = ExecuteTimings::default();
+            execute_timings.execute_us = *execute_us;
+            timing.record_slot(&SlotExecuteTimings {
+                slot: *slot,
+                collector_id: *leader,
+                execute_timings,
+            });
+        }
+        assert_eq!(timing.num_slots, 4);
+        assert_eq!(timing.execute_timings.execute_us, 240);
+
+        // Only the leaders with the most execute time are reported
+        assert_eq!(
+            timing.top_leaders(2),
+            vec![
+                (
+                    &leader2,
+                    &LeaderExecuteTimings {
+                        num_slots: 2,
+                        execute_us: 130,
+                    }
+                ),
+                (
+                    &leader1,
+                    &LeaderExecuteTimings {
+                        num_slots: 1,
+                        execute_us: 100,
+                    }
+                ),
+            ]
+        );
+
+        // Reporting starts a new aggregate
+        timing.update(0, 0, 0);
+        assert_eq!(timing.num_slots, 0);
+        assert_eq!(timing.execute_timings.execute_us, 0);
+        assert!(timing.leader_execute_timings.is_empty());
+    }
+
     #[test]
     fn test_update_cost_model_with_empty_execute_timings() {
         let cost_model = Arc::new(RwLock::new(CostModel::default()));
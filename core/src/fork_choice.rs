@@ -10,7 +10,8 @@ use std::{
     sync::{Arc, RwLock},
 };
 
-pub(crate) struct SelectVoteAndResetForkResult {
+/// The banks selected by the replay loop to vote on and to reset PoH to
+pub struct SelectVoteAndResetForkResult {
     pub vote_bank: Option<(Arc<Bank>, SwitchForkDecision)>,
     pub reset_bank: Option<Arc<Bank>>,
     pub heaviest_fork_failures: Vec<HeaviestForkFailures>,
@@ -304,6 +304,43 @@ impl HeaviestSubtreeForkChoice {
         self.last_root_time = Instant::now();
     }
 
+    /// Removes `slot_hash_key` and all of its descendants from the tree, returning the
+    /// removed keys. Ancestors are re-aggregated so that stake voted on the removed
+    /// subtree no longer counts towards them. Latest votes that still point at a
+    /// removed key are left as is, subtracting their stake later is a no-op.
+    pub fn purge_slot_and_descendants(&mut self, slot_hash_key: &SlotHashKey) -> Vec<SlotHashKey> {
+        assert_ne!(*slot_hash_key, self.root, "Can't purge the root");
+        let parent = match self.fork_infos.get(slot_hash_key) {
+            Some(fork_info) => fork_info
+                .parent
+                .expect("Only the root doesn't have a parent"),
+            None => return vec![],
+        };
+
+        let mut purged = vec![];
+        let mut to_visit = vec![*slot_hash_key];
+        while let Some(node_key) = to_visit.pop() {
+            let fork_info = self
+                .fork_infos
+                .remove(&node_key)
+                .expect("Descendants of a node must exist in tree");
+            to_visit.extend(fork_info.children);
+            purged.push(node_key);
+        }
+
+        self.fork_infos
+            .get_mut(&parent)
+            .expect("Parent must exist in tree")
+            .children
+            .retain(|child| child != slot_hash_key);
+
+        let mut update_operations = UpdateOperations::default();
+        self.do_insert_aggregate_operation(&mut update_operations, &None, parent);
+        self.insert_aggregate_operations(&mut update_operations, parent);
+        self.process_update_operations(update_operations);
+        purged
+    }
+
     pub fn add_root_parent(&mut self, root_parent: SlotHashKey) {
         assert!(root_parent.0 < self.root.0);
         assert!(self.fork_infos.get(&root_parent).is_none());
@@ -476,6 +513,130 @@ impl HeaviestSubtreeForkChoice {
             .map(|fork_info| fork_info.is_candidate())
     }
 
+    /// Reverts `mark_fork_invalid_candidate()` of `slot_hash_key` without marking it
+    /// duplicate confirmed, as `mark_fork_valid_candidate()` would. Nodes of the fork that
+    /// were invalid because of it inherit the latest invalid ancestor of its parent again
+    pub fn restore_fork_candidate(&mut self, slot_hash_key: &SlotHashKey) {
+        if !self.fork_infos.contains_key(slot_hash_key) {
+            return;
+        }
+        info!(
+            "restoring fork starting at: {:?} as candidate",
+            slot_hash_key
+        );
+        let parent_latest_invalid_ancestor = self
+            .parent(slot_hash_key)
+            .and_then(|parent| self.latest_invalid_ancestor(&parent));
+        let mut update_operations = UpdateOperations::default();
+        for child_hash_key in self.subtree_diff(*slot_hash_key, SlotHashKey::default()) {
+            let fork_info = self.fork_infos.get_mut(&child_hash_key).unwrap();
+            if fork_info.latest_invalid_ancestor == Some(slot_hash_key.0) {
+                fork_info.latest_invalid_ancestor = parent_latest_invalid_ancestor;
+            }
+            self.do_insert_aggregate_operation(&mut update_operations, &None, child_hash_key);
+        }
+
+        // Aggregate across all ancestors to find the new best slots including this fork
+        self.insert_aggregate_operations(&mut update_operations, *slot_hash_key);
+        self.process_update_operations(update_operations);
+    }
+
+    /// Returns a human readable explanation of how fork choice decided on `(slot, hash)`:
+    /// for every fork point between the root and it, each candidate fork with its stake,
+    /// best slot and candidate status, and which of them was picked
+    pub fn explain_selection(&self, slot: Slot, hash: Hash) -> String {
+        self.explain_selection_with(slot, hash, |_| None)
+    }
+
+    /// Same as `explain_selection()`, with `describe_best_slot` adding details about the
+    /// best slot of each candidate fork that fork choice itself doesn't track, like
+    /// whether the tower is locked out of it
+    pub fn explain_selection_with(
+        &self,
+        slot: Slot,
+        hash: Hash,
+        describe_best_slot: impl Fn(&SlotHashKey) -> Option<String>,
+    ) -> String {
+        let slot_hash_key = (slot, hash);
+        if !self.contains_slot(&slot_hash_key) {
+            return format!("{} ({}) is not in fork choice", slot, hash);
+        }
+        let (root_slot, root_hash) = self.root;
+        let (best_slot, best_hash) = self.best_overall_slot();
+        let mut lines = vec![
+            format!("fork choice for {} ({})", slot, hash),
+            format!("root: {} ({})", root_slot, root_hash),
+            format!("best overall slot: {} ({})", best_slot, best_hash),
+        ];
+
+        let mut path: Vec<_> = self.ancestor_iterator(slot_hash_key).collect();
+        path.reverse();
+        path.push(slot_hash_key);
+        for (parent, on_path) in path.iter().zip(path.iter().skip(1)) {
+            let children = self.children(parent).unwrap();
+            if children.len() < 2 {
+                continue;
+            }
+            let parent_best_slot = self.best_slot(parent).unwrap();
+            lines.push(format!(
+                "fork at {} ({}), {} candidates:",
+                parent.0,
+                parent.1,
+                children.len()
+            ));
+            let mut children = children.to_vec();
+            children.sort_by(|a, b| self.max_by_weight(*b, *a));
+            for child in children {
+                let fork_info = &self.fork_infos[&child];
+                let status = match fork_info.latest_invalid_ancestor {
+                    None if fork_info.is_duplicate_confirmed() => {
+                        "candidate, duplicate confirmed".to_string()
+                    }
+                    None => "candidate".to_string(),
+                    Some(invalid_slot) => format!("not a candidate, duplicate {}", invalid_slot),
+                };
+                let mut line = format!(
+                    "  {} {} ({}): stake voted subtree {}, stake voted at {}, best slot {} ({}), {}",
+                    if fork_info.best_slot == parent_best_slot && fork_info.is_candidate() {
+                        "picked"
+                    } else {
+                        "      "
+                    },
+                    child.0,
+                    child.1,
+                    fork_info.stake_voted_subtree,
+                    fork_info.stake_voted_at,
+                    fork_info.best_slot.0,
+                    fork_info.best_slot.1,
+                    status,
+                );
+                if let Some(description) = describe_best_slot(&fork_info.best_slot) {
+                    line.push_str(&format!(", {}", description));
+                }
+                if child == *on_path {
+                    line.push_str(&format!(", leads to {}", slot));
+                }
+                lines.push(line);
+            }
+        }
+
+        let best_overall_slot = self.best_overall_slot();
+        lines.push(if slot_hash_key == best_overall_slot {
+            format!("{} is the best overall slot", slot)
+        } else if self
+            .ancestor_iterator(best_overall_slot)
+            .any(|ancestor| ancestor == slot_hash_key)
+        {
+            format!(
+                "{} is on the heaviest fork, an ancestor of the best overall slot",
+                slot
+            )
+        } else {
+            format!("{} is not on the heaviest fork", slot)
+        });
+        lines.join("\n")
+    }
+
     fn propagate_new_leaf(
         &mut self,
         slot_hash_key: &SlotHashKey,
@@ -1314,6 +1475,71 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_purge_slot_and_descendants() {
+        let mut heaviest_subtree_fork_choice = setup_forks();
+        let stake = 100;
+        let (bank, vote_pubkeys) = bank_utils::setup_bank_and_vote_pubkeys(2, stake);
+
+        // Vote for slots 6 and 4, the tie is broken in favor of the fork with slot 2
+        heaviest_subtree_fork_choice.add_votes(
+            [
+                (vote_pubkeys[0], (6, Hash::default())),
+                (vote_pubkeys[1], (4, Hash::default())),
+            ]
+            .iter(),
+            bank.epoch_stakes_map(),
+            bank.epoch_schedule(),
+        );
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+
+        // Purging slot 2 removes the fork 2 -> 4 along with the stake voted on it
+        let mut purged =
+            heaviest_subtree_fork_choice.purge_slot_and_descendants(&(2, Hash::default()));
+        purged.sort();
+        assert_eq!(purged, vec![(2, Hash::default()), (4, Hash::default())]);
+        for i in 0..=6 {
+            let exists = i != 2 && i != 4;
+            assert_eq!(
+                heaviest_subtree_fork_choice.contains_block(&(i, Hash::default())),
+                exists
+            );
+        }
+        assert_eq!(
+            heaviest_subtree_fork_choice
+                .children(&(1, Hash::default()))
+                .unwrap(),
+            &[(3, Hash::default())]
+        );
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 6);
+        for i in &[0, 1] {
+            assert_eq!(
+                heaviest_subtree_fork_choice
+                    .stake_voted_subtree(&(*i, Hash::default()))
+                    .unwrap(),
+                stake
+            );
+        }
+
+        // Purging a slot that no longer exists is a no-op
+        assert!(heaviest_subtree_fork_choice
+            .purge_slot_and_descendants(&(4, Hash::default()))
+            .is_empty());
+
+        // The validator whose latest vote was purged can vote again
+        heaviest_subtree_fork_choice.add_votes(
+            [(vote_pubkeys[1], (5, Hash::default()))].iter(),
+            bank.epoch_stakes_map(),
+            bank.epoch_schedule(),
+        );
+        assert_eq!(
+            heaviest_subtree_fork_choice
+                .stake_voted_subtree(&(1, Hash::default()))
+                .unwrap(),
+            2 * stake
+        );
+    }
+
     #[test]
     fn test_set_root_and_add_votes() {
         let mut heaviest_subtree_fork_choice = setup_forks();
@@ -2071,6 +2297,75 @@ mod test {
         assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4)
     }
 
+    #[test]
+    fn test_explain_selection() {
+        /*
+            Build fork structure:
+                     slot 0
+                       |
+                     slot 1
+                   /   |   \
+              slot 2 slot 3 slot 4
+        */
+        let forks = tr(0) / (tr(1) / tr(2) / tr(3) / tr(4));
+        let mut heaviest_subtree_fork_choice = HeaviestSubtreeForkChoice::new_from_tree(forks);
+        let stake = 100;
+        let (bank, vote_pubkeys) = bank_utils::setup_bank_and_vote_pubkeys(3, stake);
+        let pubkey_votes: Vec<(Pubkey, SlotHashKey)> = vec![
+            (vote_pubkeys[0], (3, Hash::default())),
+            (vote_pubkeys[1], (3, Hash::default())),
+            (vote_pubkeys[2], (2, Hash::default())),
+        ];
+        heaviest_subtree_fork_choice.add_votes(
+            pubkey_votes.iter(),
+            bank.epoch_stakes_map(),
+            bank.epoch_schedule(),
+        );
+        let hash = Hash::default();
+
+        let explanation = heaviest_subtree_fork_choice.explain_selection(2, hash);
+        let lines: Vec<_> = explanation.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                format!("fork choice for 2 ({})", hash),
+                format!("root: 0 ({})", hash),
+                format!("best overall slot: 3 ({})", hash),
+                format!("fork at 1 ({}), 3 candidates:", hash),
+                format!(
+                    "  picked 3 ({}): stake voted subtree 200, stake voted at 200, best slot 3 ({}), candidate",
+                    hash, hash
+                ),
+                format!(
+                    "         2 ({}): stake voted subtree 100, stake voted at 100, best slot 2 ({}), candidate, leads to 2",
+                    hash, hash
+                ),
+                format!(
+                    "         4 ({}): stake voted subtree 0, stake voted at 0, best slot 4 ({}), candidate",
+                    hash, hash
+                ),
+                "2 is not on the heaviest fork".to_string(),
+            ]
+        );
+
+        // The winner is identified, with the details added by the caller
+        let explanation =
+            heaviest_subtree_fork_choice
+                .explain_selection_with(3, hash, |(slot, _)| Some(format!("described {}", slot)));
+        assert!(explanation.contains(&format!(
+            "  picked 3 ({}): stake voted subtree 200, stake voted at 200, best slot 3 ({}), candidate, described 3, leads to 3",
+            hash, hash
+        )));
+        assert!(explanation.ends_with("3 is the best overall slot"));
+        assert!(heaviest_subtree_fork_choice
+            .explain_selection(1, hash)
+            .ends_with("1 is on the heaviest fork, an ancestor of the best overall slot"));
+        assert_eq!(
+            heaviest_subtree_fork_choice.explain_selection(5, hash),
+            format!("5 ({}) is not in fork choice", hash)
+        );
+    }
+
     #[test]
     fn test_add_votes_duplicate_tie() {
         let (mut heaviest_subtree_fork_choice, duplicate_leaves_descended_from_4, _) =
@@ -3245,6 +3540,44 @@ mod test {
         heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&3.slot_hash());
     }
 
+    #[test]
+    fn test_restore_fork_candidate() {
+        let mut heaviest_subtree_fork_choice = setup_forks();
+        let invalid_candidate = (3, Hash::default());
+        heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&invalid_candidate);
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+
+        // Restoring the fork makes it a candidate again without duplicate confirming it
+        heaviest_subtree_fork_choice.restore_fork_candidate(&invalid_candidate);
+        for slot in &[3, 5, 6] {
+            assert!(heaviest_subtree_fork_choice
+                .is_candidate(&(*slot, Hash::default()))
+                .unwrap());
+            assert!(!heaviest_subtree_fork_choice
+                .is_duplicate_confirmed(&(*slot, Hash::default()))
+                .unwrap());
+        }
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+        heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&(2, Hash::default()));
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 6);
+
+        // A fork invalid because of an ancestor stays invalid because of it, while the
+        // descendants invalid for themselves keep their own mark
+        heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&(1, Hash::default()));
+        heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&(6, Hash::default()));
+        heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&invalid_candidate);
+        heaviest_subtree_fork_choice.restore_fork_candidate(&invalid_candidate);
+        for (slot, latest_invalid_ancestor) in &[(3, 1), (5, 1), (6, 6)] {
+            assert_eq!(
+                heaviest_subtree_fork_choice
+                    .latest_invalid_ancestor(&(*slot, Hash::default()))
+                    .unwrap(),
+                *latest_invalid_ancestor
+            );
+        }
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 0);
+    }
+
     fn setup_set_unconfirmed_and_confirmed_duplicate_slot_tests(
         smaller_duplicate_slot: Slot,
         larger_duplicate_slot: Slot,
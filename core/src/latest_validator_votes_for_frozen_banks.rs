@@ -3,7 +3,7 @@ use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey};
 use std::collections::{hash_map::Entry, HashMap};
 
 #[derive(Default)]
-pub(crate) struct LatestValidatorVotesForFrozenBanks {
+pub struct LatestValidatorVotesForFrozenBanks {
     // TODO: Clean outdated/unstaked pubkeys from this list.
     max_gossip_frozen_votes: HashMap<Pubkey, (Slot, Vec<Hash>)>,
     max_replay_frozen_votes: HashMap<Pubkey, (Slot, Vec<Hash>)>,
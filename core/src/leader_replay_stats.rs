@@ -0,0 +1,196 @@
+//! The `leader_replay_stats` module aggregates how the blocks of each leader fared in
+//! replay, to identify leaders producing consistently slow or invalid blocks.
+use solana_sdk::{
+    clock::{Epoch, Slot},
+    pubkey::Pubkey,
+    timing::timestamp,
+};
+use std::collections::{HashMap, HashSet};
+
+// How often the stats of leaders with new blocks are reported
+const LEADER_REPLAY_STATS_REPORT_INTERVAL_MS: u64 = 60_000;
+
+/// Replay outcomes of the blocks of a single leader in the current epoch
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LeaderReplayStat {
+    // Blocks replayed until frozen
+    pub blocks_replayed: u64,
+    // Leader slots skipped on the fork of a frozen block, counted once however many
+    // forks skipped them
+    pub blocks_dropped: u64,
+    // Blocks that failed to replay and were marked dead
+    pub blocks_dead: u64,
+    // Time from the start of replay until the block froze, summed over all replayed
+    // blocks
+    pub total_replay_ms: u64,
+    pub num_entries: u64,
+    pub num_transactions: u64,
+}
+
+impl LeaderReplayStat {
+    /// Average time it took to replay a block of this leader
+    pub fn average_replay_ms(&self) -> u64 {
+        if self.blocks_replayed == 0 {
+            0
+        } else {
+            self.total_replay_ms / self.blocks_replayed
+        }
+    }
+}
+
+/// Per leader replay stats of the current epoch, reset once a block of a later epoch
+/// is replayed
+#[derive(Debug, Default)]
+pub struct LeaderReplayStats {
+    epoch: Epoch,
+    stats: HashMap<Pubkey, LeaderReplayStat>,
+    // Slots of the current epoch already counted as dropped
+    dropped_slots: HashSet<Slot>,
+    // Leaders whose stats changed since the last report
+    updated_leaders: HashSet<Pubkey>,
+    last_report: u64,
+}
+
+impl LeaderReplayStats {
+    /// Records that `leader`'s block in `epoch` froze after `replay_ms` of replay
+    pub fn record_replayed(
+        &mut self,
+        leader: &Pubkey,
+        epoch: Epoch,
+        replay_ms: u64,
+        num_entries: u64,
+        num_transactions: u64,
+    ) {
+        if let Some(stat) = self.stat_mut(leader, epoch) {
+            stat.blocks_replayed += 1;
+            stat.total_replay_ms += replay_ms;
+            stat.num_entries += num_entries;
+            stat.num_transactions += num_transactions;
+        }
+    }
+
+    /// Records that `slot`, a leader slot of `leader` in `epoch`, was skipped on the
+    /// fork of a frozen block. Slots skipped on several forks are only counted once
+    pub fn record_dropped(&mut self, leader: &Pubkey, epoch: Epoch, slot: Slot) {
+        if epoch == self.epoch && self.dropped_slots.contains(&slot) {
+            return;
+        }
+        if let Some(stat) = self.stat_mut(leader, epoch) {
+            stat.blocks_dropped += 1;
+            self.dropped_slots.insert(slot);
+        }
+    }
+
+    /// Records that `leader`'s block in `epoch` was marked dead
+    pub fn record_dead(&mut self, leader: &Pubkey, epoch: Epoch) {
+        if let Some(stat) = self.stat_mut(leader, epoch) {
+            stat.blocks_dead += 1;
+        }
+    }
+
+    /// Epoch the stats are for
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// Returns the stats of every leader with a block replayed, dropped or dead in
+    /// the current epoch
+    pub fn snapshot(&self) -> HashMap<Pubkey, LeaderReplayStat> {
+        self.stats.clone()
+    }
+
+    /// Reports the stats of the leaders updated since the last report, at most once
+    /// per `LEADER_REPLAY_STATS_REPORT_INTERVAL_MS`
+    pub fn maybe_report(&mut self) {
+        let now = timestamp();
+        if now.saturating_sub(self.last_report) < LEADER_REPLAY_STATS_REPORT_INTERVAL_MS {
+            return;
+        }
+        self.last_report = now;
+        self.report();
+    }
+
+    fn report(&mut self) {
+        for leader in self.updated_leaders.drain() {
+            let stat = &self.stats[&leader];
+            datapoint_info!(
+                "replay_stage-leader_replay_stats",
+                ("leader", leader.to_string(), String),
+                ("epoch", self.epoch, i64),
+                ("blocks_replayed", stat.blocks_replayed, i64),
+                ("blocks_dropped", stat.blocks_dropped, i64),
+                ("blocks_dead", stat.blocks_dead, i64),
+                ("average_replay_ms", stat.average_replay_ms(), i64),
+                ("num_entries", stat.num_entries, i64),
+                ("num_transactions", stat.num_transactions, i64),
+            );
+        }
+    }
+
+    // Blocks of an earlier epoch than the current one, replayed late on another fork,
+    // are ignored. A block of a later epoch reports and prunes the stats of the
+    // current one
+    fn stat_mut(&mut self, leader: &Pubkey, epoch: Epoch) -> Option<&mut LeaderReplayStat> {
+        if epoch < self.epoch {
+            return None;
+        }
+        if epoch > self.epoch {
+            self.report();
+            self.stats.clear();
+            self.dropped_slots.clear();
+            self.epoch = epoch;
+        }
+        self.updated_leaders.insert(*leader);
+        Some(self.stats.entry(*leader).or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leader_replay_stats() {
+        let leader1 = Pubkey::new_unique();
+        let leader2 = Pubkey::new_unique();
+        let mut stats = LeaderReplayStats::default();
+        stats.record_replayed(&leader1, 0, 100, 10, 5);
+        stats.record_replayed(&leader1, 0, 300, 20, 15);
+        stats.record_dead(&leader1, 0);
+        stats.record_dropped(&leader2, 0, 3);
+        // Skipped again on a sibling fork
+        stats.record_dropped(&leader2, 0, 3);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(
+            snapshot[&leader1],
+            LeaderReplayStat {
+                blocks_replayed: 2,
+                blocks_dropped: 0,
+                blocks_dead: 1,
+                total_replay_ms: 400,
+                num_entries: 30,
+                num_transactions: 20,
+            }
+        );
+        assert_eq!(snapshot[&leader1].average_replay_ms(), 200);
+        assert_eq!(snapshot[&leader2].blocks_dropped, 1);
+        assert_eq!(snapshot[&leader2].average_replay_ms(), 0);
+
+        // A block of the next epoch starts over, late blocks of the previous one are
+        // ignored
+        stats.record_dead(&leader2, 1);
+        stats.record_replayed(&leader1, 0, 100, 10, 5);
+        assert_eq!(stats.epoch(), 1);
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(
+            snapshot[&leader2],
+            LeaderReplayStat {
+                blocks_dead: 1,
+                ..LeaderReplayStat::default()
+            }
+        );
+    }
+}
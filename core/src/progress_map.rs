@@ -1,7 +1,6 @@
 use crate::{
     cluster_info_vote_listener::SlotVoteTracker,
     cluster_slots::SlotPubkeys,
-    replay_stage::SUPERMINORITY_THRESHOLD,
     {consensus::Stake, consensus::VotedStakes},
 };
 use solana_ledger::blockstore_processor::{ConfirmationProgress, ConfirmationTiming};
@@ -14,7 +13,7 @@ use std::{
 
 type VotedSlot = Slot;
 type ExpirationSlot = Slot;
-pub(crate) type LockoutIntervals = BTreeMap<ExpirationSlot, Vec<(VotedSlot, Pubkey)>>;
+pub type LockoutIntervals = BTreeMap<ExpirationSlot, Vec<(VotedSlot, Pubkey)>>;
 
 #[derive(Default)]
 pub(crate) struct ReplaySlotStats(ConfirmationTiming);
@@ -73,6 +72,11 @@ impl ReplaySlotStats {
                 self.execute_timings.num_execute_batches,
                 i64
             ),
+            (
+                "num_fee_payer_failures",
+                self.execute_timings.num_fee_payer_failures,
+                i64
+            ),
             (
                 "serialize_us",
                 self.execute_timings.details.serialize_us,
@@ -186,6 +190,7 @@ impl ForkProgress {
         validator_stake_info: Option<ValidatorStakeInfo>,
         num_blocks_on_fork: u64,
         num_dropped_blocks_on_fork: u64,
+        superminority_threshold: f64,
     ) -> Self {
         let (
             is_leader_slot,
@@ -204,7 +209,7 @@ impl ForkProgress {
                             true
                         } else {
                             info.stake as f64 / info.total_epoch_stake as f64
-                                > SUPERMINORITY_THRESHOLD
+                                > superminority_threshold
                         }
                     },
                     info.total_epoch_stake,
@@ -237,6 +242,7 @@ impl ForkProgress {
         prev_leader_slot: Option<Slot>,
         num_blocks_on_fork: u64,
         num_dropped_blocks_on_fork: u64,
+        superminority_threshold: f64,
     ) -> Self {
         let validator_stake_info = {
             if bank.collector_id() == validator_identity {
@@ -256,6 +262,7 @@ impl ForkProgress {
             validator_stake_info,
             num_blocks_on_fork,
             num_dropped_blocks_on_fork,
+            superminority_threshold,
         )
     }
 }
@@ -333,6 +340,26 @@ impl PropagatedStats {
     }
 }
 
+/// Stake of the vote accounts that are still locked out on the fork of a bank as of its
+/// slot, by how long they remain locked out
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LockoutSummary {
+    /// Stake keyed by the last slot it is locked out until, each vote account counted
+    /// once at its latest lockout
+    pub locked_out_stakes: BTreeMap<Slot, Stake>,
+    pub total_stake: Stake,
+}
+
+impl LockoutSummary {
+    /// Stake that is still locked out at `slot`
+    pub fn locked_out_stake_at(&self, slot: Slot) -> Stake {
+        self.locked_out_stakes
+            .range(slot..)
+            .map(|(_, stake)| stake)
+            .sum()
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct ProgressMap {
     progress_map: HashMap<Slot, ForkProgress>,
@@ -453,6 +480,59 @@ impl ProgressMap {
             .unwrap_or(None)
     }
 
+    /// Counts the entries by how far past `root` their slot is, keyed by the lower bound
+    /// of each of the `SLOT_AGE_BUCKETS` ranges. Every bucket is present, even if empty
+    pub fn slot_age_histogram(&self, root: Slot) -> BTreeMap<u64, usize> {
+        let mut histogram: BTreeMap<u64, usize> =
+            SLOT_AGE_BUCKETS.iter().map(|bucket| (*bucket, 0)).collect();
+        for slot in self.progress_map.keys() {
+            let age = slot.saturating_sub(root);
+            let bucket = SLOT_AGE_BUCKETS
+                .iter()
+                .rev()
+                .find(|bucket| age >= **bucket)
+                .unwrap();
+            *histogram.get_mut(bucket).unwrap() += 1;
+        }
+        histogram
+    }
+
+    /// Summarizes the lockout intervals collected for `slot` by `compute_bank_stats`,
+    /// weighing each vote account by its stake in `vote_accounts`. Returns None until the
+    /// stats of the slot have been computed
+    pub fn lockout_summary(
+        &self,
+        slot: Slot,
+        vote_accounts: &HashMap<Pubkey, (Stake, ArcVoteAccount)>,
+    ) -> Option<LockoutSummary> {
+        let fork_stats = self.get_fork_stats(slot)?;
+        if !fork_stats.computed {
+            return None;
+        }
+        // Intervals are keyed by the end of the lockout in ascending order, so the last
+        // one seen for a vote account is its latest lockout
+        let mut latest_lockouts: HashMap<&Pubkey, Slot> = HashMap::new();
+        for (lockout_interval_end, intervals) in fork_stats.lockout_intervals.range(slot..) {
+            for (_voted_slot, vote_account_pubkey) in intervals {
+                latest_lockouts.insert(vote_account_pubkey, *lockout_interval_end);
+            }
+        }
+        let mut locked_out_stakes = BTreeMap::new();
+        for (vote_account_pubkey, lockout_interval_end) in latest_lockouts {
+            let stake = vote_accounts
+                .get(vote_account_pubkey)
+                .map(|(stake, _)| *stake)
+                .unwrap_or(0);
+            if stake > 0 {
+                *locked_out_stakes.entry(lockout_interval_end).or_insert(0) += stake;
+            }
+        }
+        Some(LockoutSummary {
+            locked_out_stakes,
+            total_stake: fork_stats.total_stake,
+        })
+    }
+
     pub fn handle_new_root(&mut self, bank_forks: &BankForks) {
         self.progress_map
             .retain(|k, _| bank_forks.get(*k).is_some());
@@ -479,9 +559,17 @@ impl ProgressMap {
     }
 }
 
+/// Lower bounds of the slot age ranges of `ProgressMap::slot_age_histogram()`
+pub const SLOT_AGE_BUCKETS: [u64; 4] = [0, 10, 100, 1000];
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{
+        consensus::{test::VoteSimulator, Tower},
+        replay_stage::SUPERMINORITY_THRESHOLD,
+    };
+    use trees::tr;
 
     #[test]
     fn test_add_vote_pubkey() {
@@ -572,7 +660,14 @@ mod test {
     fn test_is_propagated_status_on_construction() {
         // If the given ValidatorStakeInfo == None, then this is not
         // a leader slot and is_propagated == false
-        let progress = ForkProgress::new(Hash::default(), Some(9), None, 0, 0);
+        let progress = ForkProgress::new(
+            Hash::default(),
+            Some(9),
+            None,
+            0,
+            0,
+            SUPERMINORITY_THRESHOLD,
+        );
         assert!(!progress.propagated_stats.is_propagated);
 
         // If the stake is zero, then threshold is always achieved
@@ -585,6 +680,7 @@ mod test {
             }),
             0,
             0,
+            SUPERMINORITY_THRESHOLD,
         );
         assert!(progress.propagated_stats.is_propagated);
 
@@ -599,6 +695,7 @@ mod test {
             }),
             0,
             0,
+            SUPERMINORITY_THRESHOLD,
         );
         assert!(!progress.propagated_stats.is_propagated);
 
@@ -613,9 +710,29 @@ mod test {
             }),
             0,
             0,
+            SUPERMINORITY_THRESHOLD,
         );
         assert!(progress.propagated_stats.is_propagated);
 
+        // A validator with 1/4 of the stake doesn't pass the default threshold by itself,
+        // but does pass a lower configured threshold
+        let stake_info = || ValidatorStakeInfo {
+            stake: 1,
+            total_epoch_stake: 4,
+            ..ValidatorStakeInfo::default()
+        };
+        let progress = ForkProgress::new(
+            Hash::default(),
+            Some(9),
+            Some(stake_info()),
+            0,
+            0,
+            SUPERMINORITY_THRESHOLD,
+        );
+        assert!(!progress.propagated_stats.is_propagated);
+        let progress = ForkProgress::new(Hash::default(), Some(9), Some(stake_info()), 0, 0, 0.2);
+        assert!(progress.propagated_stats.is_propagated);
+
         // Check that the default ValidatorStakeInfo::default() constructs a ForkProgress
         // with is_propagated == false, otherwise propagation tests will fail to run
         // the proper checks (most will auto-pass without checking anything)
@@ -625,6 +742,7 @@ mod test {
             Some(ValidatorStakeInfo::default()),
             0,
             0,
+            SUPERMINORITY_THRESHOLD,
         );
         assert!(!progress.propagated_stats.is_propagated);
     }
@@ -635,7 +753,17 @@ mod test {
 
         // Insert new ForkProgress for slot 10 (not a leader slot) and its
         // previous leader slot 9 (leader slot)
-        progress_map.insert(10, ForkProgress::new(Hash::default(), Some(9), None, 0, 0));
+        progress_map.insert(
+            10,
+            ForkProgress::new(
+                Hash::default(),
+                Some(9),
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
+        );
         progress_map.insert(
             9,
             ForkProgress::new(
@@ -644,6 +772,7 @@ mod test {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
 
@@ -655,7 +784,17 @@ mod test {
         // The previous leader before 8, slot 7, does not exist in
         // progress map, so is_propagated(8) should return true as
         // this implies the parent is rooted
-        progress_map.insert(8, ForkProgress::new(Hash::default(), Some(7), None, 0, 0));
+        progress_map.insert(
+            8,
+            ForkProgress::new(
+                Hash::default(),
+                Some(7),
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
+        );
         assert!(progress_map.is_propagated(8));
 
         // If we set the is_propagated = true, is_propagated should return true
@@ -678,4 +817,99 @@ mod test {
             .is_leader_slot = true;
         assert!(!progress_map.is_propagated(10));
     }
+
+    #[test]
+    fn test_slot_age_histogram() {
+        let root = 100;
+        let mut progress_map = ProgressMap::default();
+        assert_eq!(
+            progress_map.slot_age_histogram(root),
+            vec![(0, 0), (10, 0), (100, 0), (1000, 0)]
+                .into_iter()
+                .collect()
+        );
+
+        for slot in &[100, 105, 109, 110, 199, 200, 1099, 1100, 5000] {
+            progress_map.insert(
+                *slot,
+                ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD),
+            );
+        }
+        assert_eq!(
+            progress_map.slot_age_histogram(root),
+            vec![(0, 3), (10, 2), (100, 2), (1000, 2)]
+                .into_iter()
+                .collect()
+        );
+
+        // Entries below the root count as having no age
+        assert_eq!(
+            progress_map.slot_age_histogram(5000),
+            vec![(0, 9), (10, 0), (100, 0), (1000, 0)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_lockout_summary() {
+        // Validator 0 votes on 1 and 2, validator 1 on 3 and 5
+        let mut vote_simulator = VoteSimulator::new(2);
+        let validator0 = vote_simulator.node_pubkeys[0];
+        let validator1 = vote_simulator.node_pubkeys[1];
+        let forks = tr(0) / (tr(1) / (tr(2) / tr(4)) / (tr(3) / (tr(5) / tr(6))));
+        let mut cluster_votes = HashMap::new();
+        cluster_votes.insert(validator0, vec![1, 2]);
+        cluster_votes.insert(validator1, vec![3, 5]);
+        vote_simulator.fill_bank_forks(forks, &cluster_votes);
+
+        let vote_accounts = |slot: Slot| -> HashMap<Pubkey, (Stake, ArcVoteAccount)> {
+            vote_simulator.bank_forks.read().unwrap()[slot]
+                .vote_accounts()
+                .into_iter()
+                .collect()
+        };
+        let vote_accounts4 = vote_accounts(4);
+        let vote_accounts6 = vote_accounts(6);
+        let stake0 = vote_accounts4[&vote_simulator.vote_pubkeys[0]].0;
+        let stake1 = vote_accounts4[&vote_simulator.vote_pubkeys[1]].0;
+
+        // Nothing to summarize until the bank stats are computed
+        assert!(vote_simulator
+            .progress
+            .lockout_summary(4, &vote_accounts4)
+            .is_none());
+        let mut tower = Tower::new_with_key(&validator0);
+        vote_simulator.simulate_vote(4, &validator0, &mut tower);
+        assert!(vote_simulator
+            .progress
+            .lockout_summary(7, &vote_accounts4)
+            .is_none());
+
+        // On the fork of slot 4, validator 0 is locked out until slot 5 by its vote on
+        // slot 1, whose lockout doubled with the vote on slot 2
+        let summary4 = vote_simulator
+            .progress
+            .lockout_summary(4, &vote_accounts4)
+            .unwrap();
+        assert_eq!(
+            summary4.locked_out_stakes,
+            vec![(5, stake0)].into_iter().collect()
+        );
+        assert_eq!(summary4.total_stake, stake0 + stake1);
+        assert_eq!(summary4.locked_out_stake_at(5), stake0);
+        assert_eq!(summary4.locked_out_stake_at(6), 0);
+
+        // On the fork of slot 6, the lockout of validator 0's vote on slot 1 has expired,
+        // validator 1 is locked out until slot 7
+        let summary6 = vote_simulator
+            .progress
+            .lockout_summary(6, &vote_accounts6)
+            .unwrap();
+        assert_eq!(
+            summary6.locked_out_stakes,
+            vec![(7, stake1)].into_iter().collect()
+        );
+        assert_eq!(summary6.locked_out_stake_at(6), stake1);
+    }
 }
@@ -1,6 +1,7 @@
 //! The `replay_stage` replays transactions broadcast by the leader.
 
 use crate::{
+    ancestry_cache::AncestryCache,
     broadcast_stage::RetransmitSlotsSender,
     cache_block_meta_service::CacheBlockMetaSender,
     cluster_info_vote_listener::{
@@ -8,42 +9,52 @@ use crate::{
     },
     cluster_slot_state_verifier::*,
     cluster_slots::ClusterSlots,
-    cluster_slots_service::ClusterSlotsUpdateSender,
     commitment_service::{AggregateCommitmentService, CommitmentAggregationData},
     consensus::{
         ComputedBankState, Stake, SwitchForkDecision, Tower, VotedStakes, SWITCH_FORK_THRESHOLD,
     },
+    cost_update_service::SlotExecuteTimings,
     fork_choice::{ForkChoice, SelectVoteAndResetForkResult},
-    heaviest_subtree_fork_choice::HeaviestSubtreeForkChoice,
+    heaviest_subtree_fork_choice::{HeaviestSubtreeForkChoice, SlotHashKey},
     latest_validator_votes_for_frozen_banks::LatestValidatorVotesForFrozenBanks,
-    progress_map::{ForkProgress, ProgressMap, PropagatedStats},
+    leader_replay_stats::{LeaderReplayStat, LeaderReplayStats},
+    progress_map::{ForkProgress, LockoutSummary, ProgressMap, PropagatedStats},
     repair_service::DuplicateSlotsResetReceiver,
     rewards_recorder_service::RewardsRecorderSender,
+    sender_with_backpressure::SenderWithBackpressure,
     unfrozen_gossip_verified_vote_hashes::UnfrozenGossipVerifiedVoteHashes,
     window_service::DuplicateSlotReceiver,
 };
-use solana_client::rpc_response::SlotUpdate;
+use crossbeam_channel::{Receiver as CrossbeamReceiver, Sender as CrossbeamSender};
+use rayon::{prelude::*, ThreadPool};
+use solana_client::rpc_response::{ForkAbandonedReason, SlotUpdate};
 use solana_gossip::cluster_info::ClusterInfo;
 use solana_ledger::{
     block_error::BlockError,
     blockstore::Blockstore,
-    blockstore_processor::{self, BlockstoreProcessorError, TransactionStatusSender},
+    blockstore_processor::{
+        self, BlockstoreProcessorError, ConfirmationProgress, ConfirmationTiming,
+        TransactionStatusSender,
+    },
     entry::VerifyRecyclers,
     leader_schedule_cache::LeaderScheduleCache,
 };
 use solana_measure::measure::Measure;
-use solana_metrics::inc_new_counter_info;
+use solana_metrics::{inc_new_counter_info, inc_new_counter_warn};
 use solana_poh::poh_recorder::{PohRecorder, GRACE_TICKS_FACTOR, MAX_GRACE_SLOTS};
+use solana_rayon_threadlimit::get_thread_count;
 use solana_rpc::{
     optimistically_confirmed_bank_tracker::{BankNotification, BankNotificationSender},
+    rpc_health,
     rpc_subscriptions::RpcSubscriptions,
 };
 use solana_runtime::{
     accounts_background_service::AbsRequestSender, bank::Bank, bank::ExecuteTimings,
-    bank_forks::BankForks, commitment::BlockCommitmentCache, vote_sender_types::ReplayVoteSender,
+    bank_forks::BankForks, commitment::BlockCommitmentCache, epoch_stakes::EpochStakes,
+    stakes::Stakes, vote_sender_types::ReplayVoteSender,
 };
 use solana_sdk::{
-    clock::{Slot, MAX_PROCESSING_AGE, NUM_CONSECUTIVE_LEADER_SLOTS},
+    clock::{Epoch, Slot, MAX_PROCESSING_AGE, NUM_CONSECUTIVE_LEADER_SLOTS},
     genesis_config::ClusterType,
     hash::Hash,
     pubkey::Pubkey,
@@ -54,10 +65,12 @@ use solana_sdk::{
 };
 use solana_vote_program::vote_state::Vote;
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    net::SocketAddr,
     result,
+    str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::{Receiver, RecvTimeoutError, Sender},
         Arc, Mutex, RwLock,
     },
@@ -65,22 +78,342 @@ use std::{
     time::{Duration, Instant},
 };
 
-pub const MAX_ENTRY_RECV_PER_ITER: usize = 512;
 pub const SUPERMINORITY_THRESHOLD: f64 = 1f64 / 3f64;
 pub const MAX_UNCONFIRMED_SLOTS: usize = 5;
 pub const DUPLICATE_LIVENESS_THRESHOLD: f64 = 0.1;
 pub const DUPLICATE_THRESHOLD: f64 = 1.0 - SWITCH_FORK_THRESHOLD - DUPLICATE_LIVENESS_THRESHOLD;
-const MAX_VOTE_SIGNATURES: usize = 200;
-const MAX_VOTE_REFRESH_INTERVAL_MILLIS: usize = 5000;
+// Default number of signatures of our most recent votes kept for detecting whether one
+// of them has been rooted
+pub const MAX_VOTE_SIGNATURES: usize = 200;
+// Default number of consecutive reset iterations a partition must be detected for before
+// it's reported
+pub const DEFAULT_PARTITION_DETECTION_THRESHOLD: usize = 1;
+// Default minimum time between refreshes of a vote that hasn't landed
+pub const MAX_VOTE_REFRESH_INTERVAL_MILLIS: u64 = 5000;
+// Lower bound on the configurable vote refresh interval, to avoid spamming gossip
+pub const MIN_VOTE_REFRESH_INTERVAL_MILLIS: u64 = 500;
+// Upper bound on the configurable vote refresh interval, past which a vote that
+// hasn't landed would stay stuck for too long
+pub const MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS: u64 = 30_000;
+// Wait for new shreds right after replay made progress, and lower bound on the
+// configurable maximum wait
+pub const MIN_REPLAY_WAIT_MS: u64 = 10;
+// Default longest wait for new shreds once replay has been idle for a while
+pub const DEFAULT_MAX_REPLAY_WAIT_MS: u64 = 200;
+// Wait for new shreds while catching up, just long enough not to busy-spin
+const CATCHUP_REPLAY_WAIT_MS: u64 = 2;
+// Number of banks left replaying by an iteration of the replay loop from which the node
+// is considered to be catching up. A single one is the norm once caught up, as the
+// shreds of the current slot trickle in
+const MIN_CATCHUP_ACTIVE_BANKS: usize = 2;
+const MAX_SLOT_TIMINGS_IN_SNAPSHOT: usize = 32;
+// How long voting stays deferred after a restart while no cluster tip is known to
+// compare the heaviest bank against
+const MAX_VOTING_DEFERRED_WITHOUT_CLUSTER_TIP_MS: u64 = 120_000;
+// Number of banks whose replay timings are reported per metrics window
+const MAX_BANK_TIMINGS_PER_WINDOW: usize = 16;
+// Upper bound of the first bucket of the replay duration histogram, every following
+// bucket doubles it. The last bucket holds everything longer than that
+const REPLAY_DURATION_HISTOGRAM_BASE_MS: u64 = 10;
+const REPLAY_DURATION_HISTOGRAM_NUM_BUCKETS: usize = 12;
+// How long the requests of `VoteSimulationHandle` wait for the replay loop to answer
+const SIMULATE_VOTE_TIMEOUT_MS: u64 = 1000;
+// How often the total number of skipped leader slots is reported
+const SKIPPED_SLOTS_REPORT_INTERVAL_MS: u64 = 10_000;
+// How far behind the parent of a leader slot a vote may be for its stake to still
+// count as online, about a minute at the default slot time
+const MAX_ONLINE_VOTE_AGE_SLOTS: Slot = 150;
+// Number of heaviest bank evaluations that failed to produce a vote kept for
+// `ReplayStage::recent_vote_failures()`
+const MAX_RECENT_VOTE_FAILURES: usize = 64;
+
+/// Callback invoked with `(last_voted_slot, heaviest_bank_slot)` on partition transitions
+pub type PartitionCallback = Arc<dyn Fn(Slot, Slot) + Sync + Send>;
+
+/// Callback invoked with `(old_pubkey, new_pubkey)` when the identity keypair is rotated
+pub type IdentityHotswapCallback = Arc<dyn Fn(Pubkey, Pubkey) + Sync + Send>;
+
+// A slot to summarize the lockouts of, and where to send the summary
+type LockoutSummaryRequest = (Slot, CrossbeamSender<Option<LockoutSummary>>);
+// A slot to simulate voting with as the heaviest bank, and where to send the result
+type SimulateVoteRequest = (Slot, CrossbeamSender<Option<SelectVoteAndResetForkResult>>);
+// A slot to explain the fork choice of, and where to send the explanation
+type ExplainForkChoiceRequest = (Slot, CrossbeamSender<Option<String>>);
+
+/// Asks the replay loop what it would vote on and why, without voting, and how much stake
+/// is locked out on a fork. Can be cloned and used from any thread, e.g. by the admin RPC
+/// service
+#[derive(Clone)]
+pub struct VoteSimulationHandle {
+    simulate_vote_request_sender: CrossbeamSender<SimulateVoteRequest>,
+    explain_fork_choice_request_sender: CrossbeamSender<ExplainForkChoiceRequest>,
+    lockout_summary_request_sender: CrossbeamSender<LockoutSummaryRequest>,
+}
+
+impl VoteSimulationHandle {
+    /// Returns the vote and reset banks the replay loop would select if the bank of
+    /// `slot` were the heaviest, given its current tower and fork state. The simulation
+    /// runs on the next iteration of the replay loop, so this blocks for up to
+    /// `SIMULATE_VOTE_TIMEOUT_MS` and returns None if the loop doesn't answer in time or
+    /// hasn't computed the stats of the bank
+    pub fn simulate_vote(&self, slot: Slot) -> Option<SelectVoteAndResetForkResult> {
+        let (response_sender, response_receiver) = crossbeam_channel::bounded(1);
+        self.simulate_vote_request_sender
+            .send((slot, response_sender))
+            .ok()?;
+        response_receiver
+            .recv_timeout(Duration::from_millis(SIMULATE_VOTE_TIMEOUT_MS))
+            .ok()
+            .flatten()
+    }
+
+    /// Returns a human readable explanation of how fork choice decided on the frozen bank
+    /// of `slot`, see `HeaviestSubtreeForkChoice::explain_selection()`, with the lockout,
+    /// vote threshold, switch threshold and propagation status of each candidate fork.
+    /// Blocks for up to `SIMULATE_VOTE_TIMEOUT_MS`, returns None if the loop doesn't answer
+    /// in time or there's no frozen bank for `slot`
+    pub fn explain_fork_choice(&self, slot: Slot) -> Option<String> {
+        let (response_sender, response_receiver) = crossbeam_channel::bounded(1);
+        self.explain_fork_choice_request_sender
+            .send((slot, response_sender))
+            .ok()?;
+        response_receiver
+            .recv_timeout(Duration::from_millis(SIMULATE_VOTE_TIMEOUT_MS))
+            .ok()
+            .flatten()
+    }
+
+    /// Returns the stake still locked out on the fork of `slot`, as computed by the
+    /// replay loop for the bank of `slot`. The summary is only built on request, by the
+    /// next iteration of the replay loop, so this blocks for up to
+    /// `SIMULATE_VOTE_TIMEOUT_MS` and returns None if the loop doesn't answer in time or
+    /// hasn't computed the stats of the bank
+    pub fn lockout_summary(&self, slot: Slot) -> Option<LockoutSummary> {
+        let (response_sender, response_receiver) = crossbeam_channel::bounded(1);
+        self.lockout_summary_request_sender
+            .send((slot, response_sender))
+            .ok()?;
+        response_receiver
+            .recv_timeout(Duration::from_millis(SIMULATE_VOTE_TIMEOUT_MS))
+            .ok()
+            .flatten()
+    }
+}
+
+/// Reads the state the replay loop publishes and pauses or resumes the replay of slots.
+/// Can be cloned and used from any thread, e.g. by the admin RPC service
+#[derive(Clone)]
+pub struct ReplayStageHandle {
+    replay_timing: Arc<RwLock<ReplayTimingSnapshot>>,
+    paused_slots: Arc<RwLock<HashSet<Slot>>>,
+    replay_paused: Arc<AtomicBool>,
+    replay_duration_histogram: Arc<ReplayDurationHistogram>,
+    leader_replay_stats: Arc<RwLock<LeaderReplayStats>>,
+    bank_forks: Arc<RwLock<BankForks>>,
+    last_iteration_timestamp: Arc<AtomicU64>,
+    replay_status: Arc<RwLock<ReplayStatus>>,
+    last_voted_slot_and_hash: Arc<RwLock<Option<(Slot, Hash)>>>,
+    recent_vote_failures: Arc<RwLock<VecDeque<VoteFailure>>>,
+}
+
+impl ReplayStageHandle {
+    /// Returns the replay loop's view of the fork tree as of its last iteration
+    pub fn replay_status(&self) -> ReplayStatus {
+        self.replay_status.read().unwrap().clone()
+    }
+
+    /// Returns the slot and bank hash of this validator's last vote, as recorded in
+    /// the tower. Updated by the replay loop each time it votes on a bank
+    pub fn last_voted_slot_and_hash(&self) -> Option<(Slot, Hash)> {
+        *self.last_voted_slot_and_hash.read().unwrap()
+    }
+
+    /// Returns up to `limit` of the most recent heaviest banks this validator couldn't
+    /// vote on, newest first, along with the reasons it couldn't
+    pub fn recent_vote_failures(&self, limit: usize) -> Vec<VoteFailure> {
+        ReplayStage::most_recent_vote_failures(&self.recent_vote_failures, limit)
+    }
+
+    /// Returns whether the replay loop completed an iteration within the last
+    /// `max_stall_ms` milliseconds, by the same rule as the RPC health check
+    pub fn is_healthy(&self, max_stall_ms: u64) -> bool {
+        rpc_health::replay_stall_ms(&self.last_iteration_timestamp, max_stall_ms).is_none()
+    }
+
+    /// Returns `(slot, parent_slot, bank_hash)` of every bank currently in the fork
+    /// tree, see `fork_tree_snapshot()`
+    pub fn fork_tree_snapshot(&self) -> Vec<(Slot, Slot, Hash)> {
+        fork_tree_snapshot(&self.bank_forks)
+    }
+
+    /// Returns the `(upper bound in milliseconds, count)` buckets of the time taken to
+    /// replay the slots completed since this stage was started or the histogram was reset
+    pub fn replay_duration_histogram(&self) -> Vec<(u64, u64)> {
+        self.replay_duration_histogram.buckets()
+    }
+
+    pub fn reset_replay_duration_histogram(&self) {
+        self.replay_duration_histogram.reset()
+    }
+
+    /// Returns how the blocks of each leader fared in replay so far this epoch. This
+    /// validator's own blocks aren't replayed, they only show up when dropped
+    pub fn leader_replay_stats(&self) -> HashMap<Pubkey, LeaderReplayStat> {
+        self.leader_replay_stats.read().unwrap().snapshot()
+    }
+
+    /// Returns whether the replay loop is currently paused by a `ReplayControl::Pause`
+    pub fn is_replay_paused(&self) -> bool {
+        self.replay_paused.load(Ordering::Relaxed)
+    }
+
+    /// Stops replaying `slot` until it is resumed. Returns false if the slot
+    /// was already paused
+    pub fn pause_slot(&self, slot: Slot) -> bool {
+        self.paused_slots.write().unwrap().insert(slot)
+    }
+
+    /// Resumes replay of a previously paused `slot`. Returns false if the slot
+    /// was not paused
+    pub fn resume_slot(&self, slot: Slot) -> bool {
+        self.paused_slots.write().unwrap().remove(&slot)
+    }
+
+    /// Returns a handle to the set of paused slots, for services that outlive or
+    /// don't own the `ReplayStage`
+    pub fn paused_slots(&self) -> Arc<RwLock<HashSet<Slot>>> {
+        self.paused_slots.clone()
+    }
+
+    /// Returns the cumulative replay loop timings since this stage was started, as of
+    /// the last iteration of the replay loop
+    pub fn latest_timing_snapshot(&self) -> ReplayTimingSnapshot {
+        self.replay_timing.read().unwrap().clone()
+    }
+}
 
-#[derive(PartialEq, Debug)]
-pub(crate) enum HeaviestForkFailures {
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub enum HeaviestForkFailures {
     LockedOut(u64),
     FailedThreshold(u64),
-    FailedSwitchThreshold(u64),
+    // (slot, switch proof stake, total stake)
+    FailedSwitchThreshold(u64, u64, u64),
     NoPropagatedConfirmation(u64),
 }
 
+/// Control messages for temporarily stopping the replay loop, e.g. for maintenance
+/// of the ledger disk
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ReplayControl {
+    /// Stop creating, replaying and voting on banks until resumed
+    Pause,
+    /// Resume replay after a `Pause`
+    Resume,
+    /// Run a single iteration of the replay loop while paused
+    StepOneIteration,
+}
+
+/// How the replay loop reacts to a slot being signaled as duplicate
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DuplicateSlotRecoveryMode {
+    /// Purge the duplicate slot and its descendants as soon as the duplicate is signaled,
+    /// so that repair can fetch another version right away
+    Aggressive,
+    /// Only stop building on the duplicate slot, and wait for the cluster to confirm a
+    /// version of the slot over gossip before purging it
+    Conservative,
+}
+
+impl Default for DuplicateSlotRecoveryMode {
+    fn default() -> Self {
+        DuplicateSlotRecoveryMode::Conservative
+    }
+}
+
+impl DuplicateSlotRecoveryMode {
+    pub const STRINGS: [&'static str; 2] = ["aggressive", "conservative"];
+}
+
+impl FromStr for DuplicateSlotRecoveryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "aggressive" => Ok(DuplicateSlotRecoveryMode::Aggressive),
+            "conservative" => Ok(DuplicateSlotRecoveryMode::Conservative),
+            _ => Err(format!(
+                "{} is unrecognized for duplicate slot recovery mode",
+                s
+            )),
+        }
+    }
+}
+
+/// A heaviest bank the replay loop evaluated but couldn't vote on, and why
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct VoteFailure {
+    pub slot: Slot,
+    pub failures: Vec<HeaviestForkFailures>,
+    // Timestamp in milliseconds of the evaluation
+    pub timestamp: u64,
+}
+
+/// Fork choice decisions made by the replay loop, for debugging why a validator
+/// did or did not vote on a slot
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub enum ForkChoiceEvent {
+    /// Emitted once per iteration after the vote and reset banks are selected
+    SelectedForks {
+        heaviest_slot: Slot,
+        heaviest_hash: Hash,
+        heaviest_on_same_voted_fork_slot: Option<Slot>,
+        // Only known when a bank to vote on was selected
+        switch_fork_decision: Option<SwitchForkDecision>,
+        heaviest_fork_failures: Vec<HeaviestForkFailures>,
+        vote_slot: Option<Slot>,
+        reset_slot: Option<Slot>,
+    },
+}
+
+/// Partition transitions detected by the replay loop, for alerting or automated actions
+#[derive(PartialEq, Clone, Debug)]
+pub enum PartitionEvent {
+    /// The heaviest bank is no longer a descendant of the last vote, and has stayed so for
+    /// `partition_detection_threshold` iterations
+    Detected {
+        heaviest_slot: Slot,
+        last_voted_slot: Slot,
+        reset_slot: Slot,
+    },
+    /// The heaviest bank is a descendant of the last vote again
+    Resolved {
+        heaviest_slot: Slot,
+        last_voted_slot: Slot,
+        reset_slot: Slot,
+    },
+}
+
+impl ForkChoiceEvent {
+    fn new(
+        heaviest_bank: &Bank,
+        heaviest_bank_on_same_voted_fork: Option<&Arc<Bank>>,
+        result: &SelectVoteAndResetForkResult,
+    ) -> Self {
+        ForkChoiceEvent::SelectedForks {
+            heaviest_slot: heaviest_bank.slot(),
+            heaviest_hash: heaviest_bank.hash(),
+            heaviest_on_same_voted_fork_slot: heaviest_bank_on_same_voted_fork.map(|b| b.slot()),
+            switch_fork_decision: result
+                .vote_bank
+                .as_ref()
+                .map(|(_, switch_fork_decision)| switch_fork_decision.clone()),
+            heaviest_fork_failures: result.heaviest_fork_failures.clone(),
+            vote_slot: result.vote_bank.as_ref().map(|(b, _)| b.slot()),
+            reset_slot: result.reset_bank.as_ref().map(|b| b.slot()),
+        }
+    }
+}
+
 // Implement a destructor for the ReplayStage thread to signal it exited
 // even on panics
 struct Finalizer {
@@ -109,6 +442,71 @@ struct LastVoteRefreshTime {
 struct SkippedSlotsInfo {
     last_retransmit_slot: u64,
     last_skipped_slot: u64,
+    // Leader slots skipped because the previous leader slots hadn't propagated
+    skipped_count: u64,
+    last_report: u64,
+}
+
+impl SkippedSlotsInfo {
+    // Records that the leader slot `poh_slot` is being skipped, returning whether it's the
+    // first time, as the skip is re-checked until PoH moves on to the next slot
+    fn record_skipped_slot(&mut self, poh_slot: Slot) -> bool {
+        if poh_slot == self.last_skipped_slot {
+            return false;
+        }
+        self.last_skipped_slot = poh_slot;
+        self.skipped_count += 1;
+        true
+    }
+
+    fn maybe_report(&mut self) {
+        let now = timestamp();
+        if now.saturating_sub(self.last_report) > SKIPPED_SLOTS_REPORT_INTERVAL_MS {
+            datapoint_info!(
+                "replay_stage-skipped_leader_slots_total",
+                ("count", self.skipped_count, i64),
+            );
+            self.last_report = now;
+        }
+    }
+}
+
+// The epoch stakes the switch threshold is checked against, kept across replay loop
+// iterations instead of being looked up in the heaviest bank every time. The stakes of an
+// epoch are fixed by the bank at its boundary, so they're only cached once the root has
+// reached the epoch: from then on every fork shares that boundary bank, and the cache only
+// needs invalidating when the epoch changes. The stakes of epochs past the root's can
+// still differ between forks, so they're always looked up
+#[derive(Default)]
+pub(crate) struct EpochStakesCache {
+    cached: Option<(Epoch, EpochStakes)>,
+    // Number of times the stakes were looked up in a bank rather than reused
+    num_fetches: usize,
+}
+
+impl EpochStakesCache {
+    fn epoch_stakes(&mut self, bank: &Bank, root: Slot) -> EpochStakes {
+        let epoch = bank.epoch();
+        if let Some((cached_epoch, epoch_stakes)) = &self.cached {
+            if *cached_epoch == epoch {
+                return epoch_stakes.clone();
+            }
+        }
+        let epoch_stakes = bank
+            .epoch_stakes(epoch)
+            .expect("Bank epoch stakes must contain entry for the bank's own epoch")
+            .clone();
+        self.num_fetches += 1;
+        trace!(
+            "looked up the stakes of epoch {}, {} lookups so far",
+            epoch,
+            self.num_fetches
+        );
+        if bank.epoch_schedule().get_epoch(root) == epoch {
+            self.cached = Some((epoch, epoch_stakes.clone()));
+        }
+        epoch_stakes
+    }
 }
 
 pub struct ReplayStageConfig {
@@ -125,31 +523,277 @@ pub struct ReplayStageConfig {
     pub cache_block_meta_sender: Option<CacheBlockMetaSender>,
     pub bank_notification_sender: Option<BankNotificationSender>,
     pub wait_for_vote_to_start_leader: bool,
+    // Upper bound on the number of entries replayed into a single bank per
+    // iteration of the replay loop, `None` replays everything available
+    pub max_entry_recv_per_iter: Option<usize>,
+    // Report transactions whose fee payer can't pay for them apart from other
+    // transaction errors, see `ProcessOptions::report_fee_payer_failures`
+    pub report_fee_payer_failures: bool,
+    // Replay and track forks without ever constructing or sending vote transactions.
+    // The tower is still updated so that fork choice and rooting work as usual
+    pub voting_disabled: bool,
+    // Minimum time between refreshes of a vote that hasn't landed, must be within
+    // `MIN_VOTE_REFRESH_INTERVAL_MILLIS..=MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS`.
+    // `Duration::ZERO` never refreshes
+    pub vote_refresh_interval: Duration,
+    // Invoked with `(last_voted_slot, heaviest_bank_slot)` when a partition is detected
+    pub partition_callback: Option<PartitionCallback>,
+    // Invoked with `(last_voted_slot, heaviest_bank_slot)` when a detected partition resolves
+    pub partition_resolved_callback: Option<PartitionCallback>,
+    // Receives a `PartitionEvent` whenever a partition is detected or resolves
+    pub partition_event_sender: Option<Sender<PartitionEvent>>,
+    // Invoked with `(old_pubkey, new_pubkey)` from the replay loop, before PoH is reset
+    // under the new identity, when it picks up an identity keypair swapped into
+    // `ClusterInfo`
+    pub identity_hotswap_callback: Option<IdentityHotswapCallback>,
+    // Slots that are not replayed until they are removed from the set again, for
+    // freezing replay at a particular slot while debugging a bad fork
+    pub paused_slots: Arc<RwLock<HashSet<Slot>>>,
+    // Receives a `ForkChoiceEvent` for every fork selection made by the replay loop
+    pub fork_choice_event_sender: Option<Sender<ForkChoiceEvent>>,
+    // Fraction of the epoch stake that must have seen a leader block for it to be
+    // considered propagated, must be in `(0.0, 0.5]`
+    pub superminority_threshold: f64,
+    // Receives `ReplayControl` messages for pausing and resuming the replay loop
+    pub replay_control_receiver: Option<Receiver<ReplayControl>>,
+    // Receives `(slot, reason)` requests to abandon a slot that replay considers fine but
+    // the cluster has rejected. Requests for rooted slots are ignored
+    pub external_dead_slot_receiver: Option<Receiver<(Slot, String)>>,
+    // Upper bound on the number of new banks created per iteration of the replay loop,
+    // children of the heaviest bank are always created. `None` creates all of them
+    pub max_concurrent_forks: Option<usize>,
+    // Pays the fees of vote transactions instead of the identity keypair
+    pub vote_fee_payer_keypair: Option<Arc<Keypair>>,
+    // Timestamp in milliseconds at which the replay loop last completed an iteration,
+    // shared with the health checks
+    pub last_iteration_timestamp: Arc<AtomicU64>,
+    pub duplicate_slot_recovery_mode: DuplicateSlotRecoveryMode,
+    // Purge dead slots once the cluster duplicate confirms them, so that the confirmed
+    // version gets repaired and replayed rather than leaving the fork unreachable
+    pub recover_dead_duplicate_confirmed_slots: bool,
+    // Number of signatures of our most recent votes kept until one of them is rooted,
+    // must be at least 1. A larger window detects a landed vote further back
+    pub max_vote_signatures: usize,
+    // Number of consecutive reset iterations a partition must be detected for before it's
+    // reported, must be at least 1. Partitions resolved sooner, like brief reorgs, are
+    // never reported
+    pub partition_detection_threshold: usize,
+    // After a restart, don't vote (or record votes in the tower) until the heaviest bank
+    // is within this many slots of the highest slot the cluster is known to have reached,
+    // or no such slot has been known for `MAX_VOTING_DEFERRED_WITHOUT_CLUSTER_TIP_MS`.
+    // None votes right away
+    pub defer_voting_until_within_slots: Option<u64>,
+    // Fraction of the stake that must be online, by having voted within
+    // `MAX_ONLINE_VOTE_AGE_SLOTS` of the parent bank, for a leader slot to be started.
+    // None starts leader slots regardless
+    pub min_online_stake_to_lead: Option<f64>,
+    // Longest the replay loop waits for new shreds once it has been idle for a while,
+    // waits start at `MIN_REPLAY_WAIT_MS` and double with each idle iteration
+    pub max_replay_wait: Duration,
 }
 
-#[derive(Default)]
+/// A view of `BankForks` taken under a single read lock once per replay loop iteration,
+/// instead of locking again for each piece of state the iteration needs
+pub struct ReplayLoopContext<'a> {
+    pub root: Slot,
+    /// Brought up to date with `BankForks` when the context is taken
+    pub ancestry: &'a mut AncestryCache,
+    // Every bank at or above `root` when the context was taken
+    banks: Vec<Arc<Bank>>,
+}
+
+impl<'a> ReplayLoopContext<'a> {
+    pub fn new(bank_forks: &RwLock<BankForks>, ancestry: &'a mut AncestryCache) -> Self {
+        let bank_forks = bank_forks.read().unwrap();
+        let root = bank_forks.root();
+        ancestry.update(&bank_forks);
+        Self {
+            root,
+            ancestry,
+            banks: bank_forks
+                .banks()
+                .iter()
+                .filter(|(slot, _)| **slot >= root)
+                .map(|(_, bank)| bank.clone())
+                .collect(),
+        }
+    }
+
+    /// Banks of the context that are frozen by now. Replay only ever freezes banks, so
+    /// this picks up the banks completed since the context was taken without locking
+    /// again. Banks purged from `ancestors` since, as duplicates, are left out
+    pub fn frozen_banks(&self) -> Vec<Arc<Bank>> {
+        self.banks
+            .iter()
+            .filter(|bank| bank.is_frozen() && self.ancestry.ancestors.contains_key(&bank.slot()))
+            .cloned()
+            .collect()
+    }
+
+    /// Slots of the banks of the context that aren't frozen by now, either still being
+    /// replayed or dead. Banks purged from `ancestors` are left out
+    pub fn active_slots(&self) -> impl Iterator<Item = Slot> + '_ {
+        self.banks
+            .iter()
+            .filter(move |bank| {
+                !bank.is_frozen() && self.ancestry.ancestors.contains_key(&bank.slot())
+            })
+            .map(|bank| bank.slot())
+    }
+}
+
+/// The replay loop's view of the fork tree, published once per iteration so that it can
+/// be inspected without locking `BankForks` or the tower
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayStatus {
+    /// Slots of the banks that are still being replayed, in ascending order
+    pub active_slots: Vec<Slot>,
+    pub heaviest_slot: Slot,
+    pub last_voted_slot: Option<Slot>,
+    pub partition_exists: bool,
+}
+
+/// Replay timings of a single completed slot, in microseconds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlotReplayTiming {
+    pub slot: Slot,
+    pub fetch_elapsed: u64,
+    pub fetch_fail_elapsed: u64,
+    pub poh_verify_elapsed: u64,
+    pub transaction_verify_elapsed: u64,
+    pub replay_elapsed: u64,
+    pub num_entries: usize,
+    pub num_shreds: u64,
+}
+
+/// Replay time, in microseconds, and number of replayed transactions of a single
+/// bank within the current metrics window
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BankReplayTiming {
+    pub slot: Slot,
+    pub replay_elapsed: u64,
+    pub num_txs: usize,
+}
+
+/// Cumulative replay loop timings, in microseconds, since the replay stage
+/// was started
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayTimingSnapshot {
+    // Timestamp in milliseconds at which the one second metrics window was last reset
+    pub last_reset_timestamp: u64,
+    // Timings of the most recently completed slots, oldest first
+    pub recent_slots: VecDeque<SlotReplayTiming>,
+    pub collect_frozen_banks_elapsed: u64,
+    pub compute_bank_stats_elapsed: u64,
+    pub select_vote_and_reset_forks_elapsed: u64,
+    pub start_leader_elapsed: u64,
+    pub reset_bank_elapsed: u64,
+    pub voting_elapsed: u64,
+    pub vote_push_us: u64,
+    pub vote_send_us: u64,
+    pub generate_vote_us: u64,
+    pub update_commitment_cache_us: u64,
+    pub select_forks_elapsed: u64,
+    pub compute_slot_stats_elapsed: u64,
+    pub generate_new_bank_forks_elapsed: u64,
+    pub replay_active_banks_elapsed: u64,
+    pub wait_receive_elapsed: u64,
+    pub heaviest_fork_failures_elapsed: u64,
+    pub bank_count: u64,
+    pub process_gossip_duplicate_confirmed_slots_elapsed: u64,
+    pub process_duplicate_slots_elapsed: u64,
+    pub process_unfrozen_gossip_verified_vote_hashes_elapsed: u64,
+}
+
+// What an iteration of `replay_active_banks()` got done
+#[derive(Debug, Default, PartialEq)]
+struct ReplayActiveBanksOutcome {
+    did_complete_bank: bool,
+    // Whether any bank replayed new entries, completed or not
+    did_replay_entries: bool,
+}
+
+// How long the replay loop waits for a blockstore signal when it didn't complete a
+// bank. Waits are short while replay is making progress, so newly arrived shreds are
+// picked up quickly, and back off exponentially while the node is idle
+struct ReplayWaitBackoff {
+    wait: Duration,
+    min_wait: Duration,
+    max_wait: Duration,
+}
+
+impl ReplayWaitBackoff {
+    fn new(max_wait: Duration) -> Self {
+        let min_wait = Duration::from_millis(MIN_REPLAY_WAIT_MS).min(max_wait);
+        Self {
+            wait: min_wait,
+            min_wait,
+            max_wait,
+        }
+    }
+
+    fn wait(&self) -> Duration {
+        self.wait
+    }
+
+    // Wait for the next iteration given the number of banks the last one left replaying,
+    // and whether it replayed any entries. While catching up the shreds of those banks
+    // keep arriving, so waiting any longer than `CATCHUP_REPLAY_WAIT_MS` only delays their
+    // replay. Banks that made no progress, e.g. a stalled slot on an abandoned fork, don't
+    // count as catching up
+    fn next_wait(&self, num_active_banks: usize, did_replay_entries: bool) -> Duration {
+        if did_replay_entries && num_active_banks >= MIN_CATCHUP_ACTIVE_BANKS {
+            self.wait.min(Duration::from_millis(CATCHUP_REPLAY_WAIT_MS))
+        } else {
+            self.wait
+        }
+    }
+
+    // A bank completed or a signal arrived, more work is likely to follow soon
+    fn reset(&mut self) {
+        self.wait = self.min_wait;
+    }
+
+    // Waited for the whole timeout without anything happening
+    fn back_off(&mut self) {
+        self.wait = (self.wait * 2).min(self.max_wait);
+    }
+}
+
+/// Replay loop timings, in microseconds, accumulated over the current one second
+/// metrics window
+#[derive(Clone, Debug, Default)]
 pub struct ReplayTiming {
     last_print: u64,
-    collect_frozen_banks_elapsed: u64,
-    compute_bank_stats_elapsed: u64,
-    select_vote_and_reset_forks_elapsed: u64,
-    start_leader_elapsed: u64,
-    reset_bank_elapsed: u64,
-    voting_elapsed: u64,
-    vote_push_us: u64,
-    vote_send_us: u64,
-    generate_vote_us: u64,
-    update_commitment_cache_us: u64,
-    select_forks_elapsed: u64,
-    compute_slot_stats_elapsed: u64,
-    generate_new_bank_forks_elapsed: u64,
-    replay_active_banks_elapsed: u64,
-    wait_receive_elapsed: u64,
-    heaviest_fork_failures_elapsed: u64,
-    bank_count: u64,
-    process_gossip_duplicate_confirmed_slots_elapsed: u64,
-    process_duplicate_slots_elapsed: u64,
-    process_unfrozen_gossip_verified_vote_hashes_elapsed: u64,
+    // Not reset with the window, the slot age histogram is reported on its own schedule
+    last_slot_age_histogram_report: u64,
+    // Never reset, unlike the per-window fields below
+    cumulative: ReplayTimingSnapshot,
+    pub collect_frozen_banks_elapsed: u64,
+    pub compute_bank_stats_elapsed: u64,
+    pub select_vote_and_reset_forks_elapsed: u64,
+    pub start_leader_elapsed: u64,
+    pub reset_bank_elapsed: u64,
+    pub voting_elapsed: u64,
+    pub vote_push_us: u64,
+    pub vote_send_us: u64,
+    pub generate_vote_us: u64,
+    pub update_commitment_cache_us: u64,
+    pub select_forks_elapsed: u64,
+    pub compute_slot_stats_elapsed: u64,
+    pub generate_new_bank_forks_elapsed: u64,
+    pub replay_active_banks_elapsed: u64,
+    pub wait_receive_elapsed: u64,
+    pub heaviest_fork_failures_elapsed: u64,
+    pub bank_count: u64,
+    pub process_gossip_duplicate_confirmed_slots_elapsed: u64,
+    pub process_duplicate_slots_elapsed: u64,
+    pub process_unfrozen_gossip_verified_vote_hashes_elapsed: u64,
+    // How long, in milliseconds, the loop currently waits for new shreds when idle.
+    // Not accumulated, the latest value is reported
+    pub replay_wait_ms: u64,
+    // The most recently replayed banks of the current window, oldest first
+    pub bank_timings: VecDeque<BankReplayTiming>,
 }
 impl ReplayTiming {
     #[allow(clippy::too_many_arguments)]
@@ -190,9 +834,47 @@ impl ReplayTiming {
         self.process_unfrozen_gossip_verified_vote_hashes_elapsed +=
             process_unfrozen_gossip_verified_vote_hashes_elapsed;
         self.process_duplicate_slots_elapsed += process_duplicate_slots_elapsed;
+
+        let cumulative = &mut self.cumulative;
+        cumulative.collect_frozen_banks_elapsed += collect_frozen_banks_elapsed;
+        cumulative.compute_bank_stats_elapsed += compute_bank_stats_elapsed;
+        cumulative.select_vote_and_reset_forks_elapsed += select_vote_and_reset_forks_elapsed;
+        cumulative.start_leader_elapsed += start_leader_elapsed;
+        cumulative.reset_bank_elapsed += reset_bank_elapsed;
+        cumulative.voting_elapsed += voting_elapsed;
+        cumulative.select_forks_elapsed += select_forks_elapsed;
+        cumulative.compute_slot_stats_elapsed += compute_slot_stats_elapsed;
+        cumulative.generate_new_bank_forks_elapsed += generate_new_bank_forks_elapsed;
+        cumulative.replay_active_banks_elapsed += replay_active_banks_elapsed;
+        cumulative.wait_receive_elapsed += wait_receive_elapsed;
+        cumulative.heaviest_fork_failures_elapsed += heaviest_fork_failures_elapsed;
+        cumulative.bank_count += bank_count;
+        cumulative.process_gossip_duplicate_confirmed_slots_elapsed +=
+            process_gossip_duplicate_confirmed_slots_elapsed;
+        cumulative.process_unfrozen_gossip_verified_vote_hashes_elapsed +=
+            process_unfrozen_gossip_verified_vote_hashes_elapsed;
+        cumulative.process_duplicate_slots_elapsed += process_duplicate_slots_elapsed;
+
         let now = timestamp();
         let elapsed_ms = now - self.last_print;
         if elapsed_ms > 1000 {
+            let slowest_bank = self
+                .bank_timings
+                .iter()
+                .max_by_key(|bank_timing| bank_timing.replay_elapsed)
+                .cloned()
+                .unwrap_or_default();
+            let bank_timings = self
+                .bank_timings
+                .iter()
+                .map(|bank_timing| {
+                    format!(
+                        "{}:{}:{}",
+                        bank_timing.slot, bank_timing.replay_elapsed, bank_timing.num_txs
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
             datapoint_info!(
                 "replay-loop-voting-stats",
                 ("vote_push_us", self.vote_push_us, i64),
@@ -270,22 +952,172 @@ impl ReplayTiming {
                     i64
                 ),
                 ("bank_count", self.bank_count as i64, i64),
+                ("replay_wait_ms", self.replay_wait_ms as i64, i64),
                 (
                     "process_duplicate_slots_elapsed",
                     self.process_duplicate_slots_elapsed as i64,
                     i64
                 ),
+                ("slowest_bank_slot", slowest_bank.slot as i64, i64),
+                (
+                    "slowest_bank_replay_elapsed",
+                    slowest_bank.replay_elapsed as i64,
+                    i64
+                ),
+                ("slowest_bank_num_txs", slowest_bank.num_txs as i64, i64),
+                ("bank_timings", bank_timings, String),
             );
 
-            *self = ReplayTiming::default();
-            self.last_print = now;
+            self.reset_and_return();
+        }
+    }
+
+    /// Starts a new metrics window and returns the timings of the one that just ended,
+    /// so they can still be looked at after being reported. The cumulative timings carry
+    /// over to the new window
+    pub fn reset_and_return(&mut self) -> ReplayTiming {
+        // The voting timings are accumulated outside of `update()`, so fold
+        // them into the cumulative totals of the new window
+        let mut cumulative = self.cumulative.clone();
+        cumulative.vote_push_us += self.vote_push_us;
+        cumulative.vote_send_us += self.vote_send_us;
+        cumulative.generate_vote_us += self.generate_vote_us;
+        cumulative.update_commitment_cache_us += self.update_commitment_cache_us;
+        let new_window = ReplayTiming {
+            last_print: timestamp(),
+            last_slot_age_histogram_report: self.last_slot_age_histogram_report,
+            cumulative,
+            ..ReplayTiming::default()
+        };
+        std::mem::replace(self, new_window)
+    }
+
+    /// Adds the replay time and transaction count of one replay of `slot` to the
+    /// current window, evicting the oldest bank once the window is full
+    fn record_bank_replay(&mut self, slot: Slot, replay_elapsed: u64, num_txs: usize) {
+        if let Some(bank_timing) = self
+            .bank_timings
+            .iter_mut()
+            .find(|bank_timing| bank_timing.slot == slot)
+        {
+            bank_timing.replay_elapsed += replay_elapsed;
+            bank_timing.num_txs += num_txs;
+            return;
+        }
+        if self.bank_timings.len() == MAX_BANK_TIMINGS_PER_WINDOW {
+            self.bank_timings.pop_front();
+        }
+        self.bank_timings.push_back(BankReplayTiming {
+            slot,
+            replay_elapsed,
+            num_txs,
+        });
+    }
+
+    fn record_completed_slot(
+        &mut self,
+        slot: Slot,
+        timing: &ConfirmationTiming,
+        num_entries: usize,
+        num_shreds: u64,
+    ) {
+        let recent_slots = &mut self.cumulative.recent_slots;
+        if recent_slots.len() == MAX_SLOT_TIMINGS_IN_SNAPSHOT {
+            recent_slots.pop_front();
+        }
+        recent_slots.push_back(SlotReplayTiming {
+            slot,
+            fetch_elapsed: timing.fetch_elapsed,
+            fetch_fail_elapsed: timing.fetch_fail_elapsed,
+            poh_verify_elapsed: timing.poh_verify_elapsed,
+            transaction_verify_elapsed: timing.transaction_verify_elapsed,
+            replay_elapsed: timing.replay_elapsed,
+            num_entries,
+            num_shreds,
+        });
+    }
+
+    /// Returns the cumulative timings since this `ReplayTiming` was created
+    pub fn snapshot(&self) -> ReplayTimingSnapshot {
+        let mut snapshot = self.cumulative.clone();
+        snapshot.last_reset_timestamp = self.last_print;
+        snapshot.vote_push_us += self.vote_push_us;
+        snapshot.vote_send_us += self.vote_send_us;
+        snapshot.generate_vote_us += self.generate_vote_us;
+        snapshot.update_commitment_cache_us += self.update_commitment_cache_us;
+        snapshot
+    }
+}
+
+/// Distribution of the time from the first replay of a slot until the slot is
+/// completed, in exponentially growing buckets
+#[derive(Debug, Default)]
+pub struct ReplayDurationHistogram {
+    buckets: [AtomicU64; REPLAY_DURATION_HISTOGRAM_NUM_BUCKETS],
+}
+
+impl ReplayDurationHistogram {
+    fn bucket_upper_bound_ms(bucket: usize) -> u64 {
+        if bucket + 1 == REPLAY_DURATION_HISTOGRAM_NUM_BUCKETS {
+            u64::MAX
+        } else {
+            REPLAY_DURATION_HISTOGRAM_BASE_MS << bucket
+        }
+    }
+
+    pub fn record(&self, duration_ms: u64) {
+        let bucket = (0..REPLAY_DURATION_HISTOGRAM_NUM_BUCKETS)
+            .find(|bucket| duration_ms <= Self::bucket_upper_bound_ms(*bucket))
+            .unwrap();
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the `(upper bound in milliseconds, count)` of every bucket, the upper
+    /// bound of the last bucket is `u64::MAX`
+    pub fn buckets(&self) -> Vec<(u64, u64)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                (
+                    Self::bucket_upper_bound_ms(bucket),
+                    count.load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        for count in &self.buckets {
+            count.store(0, Ordering::Relaxed);
         }
     }
 }
 
+/// Returns `(slot, parent_slot, bank_hash)` of every bank in `bank_forks`, ordered by
+/// slot so that parents always come before their children. Banks that are not frozen
+/// yet report the default hash
+pub fn fork_tree_snapshot(bank_forks: &RwLock<BankForks>) -> Vec<(Slot, Slot, Hash)> {
+    let banks: Vec<_> = bank_forks
+        .read()
+        .unwrap()
+        .banks()
+        .values()
+        .cloned()
+        .collect();
+    let mut fork_tree: Vec<_> = banks
+        .iter()
+        .map(|bank| (bank.slot(), bank.parent_slot(), bank.hash()))
+        .collect();
+    fork_tree.sort_unstable_by_key(|(slot, _, _)| *slot);
+    fork_tree
+}
+
 pub struct ReplayStage {
     t_replay: JoinHandle<()>,
     commitment_service: AggregateCommitmentService,
+    vote_simulation_handle: VoteSimulationHandle,
+    replay_stage_handle: ReplayStageHandle,
 }
 
 impl ReplayStage {
@@ -302,12 +1134,12 @@ impl ReplayStage {
         vote_tracker: Arc<VoteTracker>,
         cluster_slots: Arc<ClusterSlots>,
         retransmit_slots_sender: RetransmitSlotsSender,
-        _duplicate_slots_reset_receiver: DuplicateSlotsResetReceiver,
+        duplicate_slots_reset_receiver: DuplicateSlotsResetReceiver,
         replay_vote_sender: ReplayVoteSender,
         gossip_duplicate_confirmed_slots_receiver: GossipDuplicateConfirmedSlotsReceiver,
         gossip_verified_vote_hash_receiver: GossipVerifiedVoteHashReceiver,
-        cluster_slots_update_sender: ClusterSlotsUpdateSender,
-        cost_update_sender: Sender<ExecuteTimings>,
+        cluster_slots_update_sender: SenderWithBackpressure<Vec<Slot>>,
+        cost_update_sender: SenderWithBackpressure<SlotExecuteTimings>,
     ) -> Self {
         let ReplayStageConfig {
             vote_account,
@@ -323,7 +1155,61 @@ impl ReplayStage {
             cache_block_meta_sender,
             bank_notification_sender,
             wait_for_vote_to_start_leader,
+            max_entry_recv_per_iter,
+            report_fee_payer_failures,
+            voting_disabled,
+            vote_refresh_interval,
+            partition_callback,
+            partition_resolved_callback,
+            partition_event_sender,
+            identity_hotswap_callback,
+            paused_slots,
+            fork_choice_event_sender,
+            superminority_threshold,
+            replay_control_receiver,
+            external_dead_slot_receiver,
+            max_concurrent_forks,
+            vote_fee_payer_keypair,
+            last_iteration_timestamp,
+            duplicate_slot_recovery_mode,
+            recover_dead_duplicate_confirmed_slots,
+            max_vote_signatures,
+            partition_detection_threshold,
+            defer_voting_until_within_slots,
+            min_online_stake_to_lead,
+            max_replay_wait,
         } = config;
+        assert!(
+            vote_refresh_interval == Duration::ZERO
+                || (Duration::from_millis(MIN_VOTE_REFRESH_INTERVAL_MILLIS)
+                    ..=Duration::from_millis(MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS))
+                    .contains(&vote_refresh_interval),
+            "vote_refresh_interval must be zero or within [{}, {}]ms, got {:?}",
+            MIN_VOTE_REFRESH_INTERVAL_MILLIS,
+            MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS,
+            vote_refresh_interval
+        );
+        assert!(
+            max_vote_signatures > 0,
+            "max_vote_signatures must be at least 1"
+        );
+        assert!(
+            partition_detection_threshold > 0,
+            "partition_detection_threshold must be at least 1"
+        );
+        assert!(
+            superminority_threshold > 0.0 && superminority_threshold <= 0.5,
+            "superminority_threshold must be in (0.0, 0.5], got {}",
+            superminority_threshold
+        );
+        datapoint_info!(
+            "refresh_vote",
+            (
+                "vote_refresh_interval_ms",
+                vote_refresh_interval.as_millis() as i64,
+                i64
+            ),
+        );
 
         trace!("replay stage");
         // Start the replay stage loop
@@ -333,6 +1219,31 @@ impl ReplayStage {
             rpc_subscriptions.clone(),
         );
 
+        let shared_replay_timing = Arc::new(RwLock::new(ReplayTimingSnapshot::default()));
+        let shared_replay_timing_ = shared_replay_timing.clone();
+        let paused_slots_ = paused_slots.clone();
+        let replay_paused = Arc::new(AtomicBool::new(false));
+        let replay_paused_ = replay_paused.clone();
+        let replay_duration_histogram = Arc::new(ReplayDurationHistogram::default());
+        let replay_duration_histogram_ = replay_duration_histogram.clone();
+        let leader_replay_stats = Arc::new(RwLock::new(LeaderReplayStats::default()));
+        let leader_replay_stats_ = leader_replay_stats.clone();
+        let shared_bank_forks = bank_forks.clone();
+        last_iteration_timestamp.store(timestamp(), Ordering::Relaxed);
+        let last_iteration_timestamp_ = last_iteration_timestamp.clone();
+        let replay_status = Arc::new(RwLock::new(ReplayStatus::default()));
+        let replay_status_ = replay_status.clone();
+        let last_voted_slot_and_hash = Arc::new(RwLock::new(tower.last_voted_slot_hash()));
+        let last_voted_slot_and_hash_ = last_voted_slot_and_hash.clone();
+        let recent_vote_failures = Arc::new(RwLock::new(VecDeque::new()));
+        let recent_vote_failures_ = recent_vote_failures.clone();
+        let (lockout_summary_request_sender, lockout_summary_request_receiver) =
+            crossbeam_channel::unbounded();
+        let (simulate_vote_request_sender, simulate_vote_request_receiver) =
+            crossbeam_channel::unbounded();
+        let (explain_fork_choice_request_sender, explain_fork_choice_request_receiver) =
+            crossbeam_channel::unbounded();
+
         #[allow(clippy::cognitive_complexity)]
         let t_replay = Builder::new()
             .name("solana-replay-stage".to_string())
@@ -348,18 +1259,38 @@ impl ReplayStage {
                     &bank_forks,
                     &my_pubkey,
                     &vote_account,
+                    superminority_threshold,
                 );
                 let mut current_leader = None;
                 let mut last_reset = Hash::default();
                 let mut partition_exists = false;
+                let mut partition_detected_iterations = 0;
+                let mut epoch_stakes_cache = EpochStakesCache::default();
+                let mut caught_up_to_cluster = false;
+                let voting_deferred_start = Instant::now();
+                let mut last_voting_deferred_report = Instant::now();
                 let mut skipped_slots_info = SkippedSlotsInfo::default();
                 let mut replay_timing = ReplayTiming::default();
+                let mut replay_wait = ReplayWaitBackoff::new(max_replay_wait);
                 let mut duplicate_slots_tracker = DuplicateSlotsTracker::default();
                 let mut gossip_duplicate_confirmed_slots = GossipDuplicateConfirmedSlots::default();
                 let mut unfrozen_gossip_verified_vote_hashes = UnfrozenGossipVerifiedVoteHashes::default();
                 let mut latest_validator_votes_for_frozen_banks = LatestValidatorVotesForFrozenBanks::default();
-                let mut voted_signatures = Vec::new();
+                let mut ancestry_cache = AncestryCache::new(&bank_forks.read().unwrap());
+                let mut voted_signatures = VecDeque::new();
                 let mut has_new_vote_been_rooted = !wait_for_vote_to_start_leader;
+                let mut logged_paused_slots = HashSet::new();
+                let mut logged_dropped_new_fork_slots = HashSet::new();
+                let mut paused_forks = HashSet::new();
+                // Reused across iterations to collect newly confirmed slots without allocating
+                let mut confirmed_forks = vec![];
+                // Shared by the forks replayed in parallel and the entry verification and
+                // transaction execution nested in their replay
+                let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(get_thread_count())
+                    .thread_name(|ix| format!("replay_{}", ix))
+                    .build()
+                    .unwrap();
                 let mut last_vote_refresh_time = LastVoteRefreshTime {
                     last_refresh_time: Instant::now(),
                     last_print_time: Instant::now(),
@@ -370,6 +1301,39 @@ impl ReplayStage {
                         break;
                     }
 
+                    Self::answer_lockout_summary_requests(
+                        &lockout_summary_request_receiver,
+                        &progress,
+                        &bank_forks,
+                    );
+
+                    if let Some(replay_control_receiver) = replay_control_receiver.as_ref() {
+                        if !Self::process_replay_control(replay_control_receiver, &replay_paused_) {
+                            // Keep ingesting from gossip while paused so those channels
+                            // don't back up
+                            Self::process_gossip_duplicate_confirmed_slots(
+                                &gossip_duplicate_confirmed_slots_receiver,
+                                &mut duplicate_slots_tracker,
+                                &mut gossip_duplicate_confirmed_slots,
+                                &bank_forks,
+                                &mut progress,
+                                &mut heaviest_subtree_fork_choice,
+                            );
+                            Self::process_gossip_verified_vote_hashes(
+                                &gossip_verified_vote_hash_receiver,
+                                &mut unfrozen_gossip_verified_vote_hashes,
+                                &heaviest_subtree_fork_choice,
+                                &mut latest_validator_votes_for_frozen_banks,
+                            );
+                            // A paused loop is still alive, don't report it as stalled
+                            last_iteration_timestamp_.store(timestamp(), Ordering::Relaxed);
+                            match ledger_signal_receiver.recv_timeout(Duration::from_millis(100)) {
+                                Err(RecvTimeoutError::Timeout) | Ok(_) => continue,
+                                Err(_) => break,
+                            }
+                        }
+                    }
+
                     let mut generate_new_bank_forks_time =
                         Measure::start("generate_new_bank_forks_time");
                     Self::generate_new_bank_forks(
@@ -378,15 +1342,23 @@ impl ReplayStage {
                         &leader_schedule_cache,
                         &rpc_subscriptions,
                         &mut progress,
+                        &heaviest_subtree_fork_choice,
+                        superminority_threshold,
+                        max_concurrent_forks,
+                        &mut logged_dropped_new_fork_slots,
                     );
                     generate_new_bank_forks_time.stop();
 
                     let mut tpu_has_bank = poh_recorder.lock().unwrap().has_bank();
 
+                    // A copy, so that pausing or resuming a slot doesn't wait for the replay
+                    let paused_slots = paused_slots_.read().unwrap().clone();
                     let mut replay_active_banks_time = Measure::start("replay_active_banks_time");
-                    let ancestors = bank_forks.read().unwrap().ancestors();
-                    let descendants = bank_forks.read().unwrap().descendants().clone();
-                    let did_complete_bank = Self::replay_active_banks(
+                    let mut context = ReplayLoopContext::new(&bank_forks, &mut ancestry_cache);
+                    let ReplayActiveBanksOutcome {
+                        did_complete_bank,
+                        did_replay_entries,
+                    } = Self::replay_active_banks(
                         &blockstore,
                         &bank_forks,
                         &my_pubkey,
@@ -406,22 +1378,40 @@ impl ReplayStage {
                         &mut latest_validator_votes_for_frozen_banks,
                         &cluster_slots_update_sender,
                         &cost_update_sender,
+                        max_entry_recv_per_iter,
+                        report_fee_payer_failures,
+                        &mut replay_timing,
+                        &paused_slots,
+                        &mut logged_paused_slots,
+                        superminority_threshold,
+                        &replay_thread_pool,
+                        &replay_duration_histogram_,
+                        &leader_replay_stats_,
+                        &leader_schedule_cache,
+                        context.ancestry,
                     );
                     replay_active_banks_time.stop();
+                    let num_active_banks = context
+                        .active_slots()
+                        .filter(|slot| progress.is_dead(*slot) != Some(true))
+                        .count();
 
-                    let forks_root = bank_forks.read().unwrap().root();
                     // Reset any duplicate slots that have been confirmed
                     // by the network in anticipation of the confirmed version of
                     // the slot
-                    /*let mut reset_duplicate_slots_time = Measure::start("reset_duplicate_slots");
                     Self::reset_duplicate_slots(
                         &duplicate_slots_reset_receiver,
-                        &mut ancestors,
-                        &mut descendants,
+                        &mut context.ancestry.ancestors,
+                        &mut context.ancestry.descendants,
                         &mut progress,
                         &bank_forks,
+                        &blockstore,
+                        &mut heaviest_subtree_fork_choice,
+                        &poh_recorder,
+                        &my_pubkey,
+                        &leader_schedule_cache,
+                        &mut last_reset,
                     );
-                    reset_duplicate_slots_time.stop();*/
 
                     // Check for any newly confirmed slots detected from gossip.
                     let mut process_gossip_duplicate_confirmed_slots_time = Measure::start("process_gossip_duplicate_confirmed_slots");
@@ -433,6 +1423,16 @@ impl ReplayStage {
                         &mut progress,
                         &mut heaviest_subtree_fork_choice,
                     );
+                    if recover_dead_duplicate_confirmed_slots {
+                        Self::recover_dead_duplicate_confirmed_slots(
+                            &gossip_duplicate_confirmed_slots,
+                            context.ancestry,
+                            &mut progress,
+                            &bank_forks,
+                            &blockstore,
+                            &mut heaviest_subtree_fork_choice,
+                        );
+                    }
                     process_gossip_duplicate_confirmed_slots_time.stop();
 
 
@@ -457,28 +1457,46 @@ impl ReplayStage {
                             &duplicate_slots_receiver,
                             &mut duplicate_slots_tracker,
                             &gossip_duplicate_confirmed_slots,
+                            &mut context.ancestry.ancestors,
+                            &mut context.ancestry.descendants,
                             &bank_forks,
+                            &blockstore,
                             &mut progress,
                             &mut heaviest_subtree_fork_choice,
+                            duplicate_slot_recovery_mode,
                         );
                     }
                     process_duplicate_slots_time.stop();
 
+                    if let Some(external_dead_slot_receiver) = external_dead_slot_receiver.as_ref() {
+                        Self::process_external_dead_slots(
+                            external_dead_slot_receiver,
+                            &blockstore,
+                            &bank_forks,
+                            context.ancestry,
+                            &rpc_subscriptions,
+                            transaction_status_sender.as_ref(),
+                            &mut duplicate_slots_tracker,
+                            &gossip_duplicate_confirmed_slots,
+                            &mut progress,
+                            &mut heaviest_subtree_fork_choice,
+                        );
+                    }
+
                     let mut collect_frozen_banks_time = Measure::start("frozen_banks");
-                    let mut frozen_banks: Vec<_> = bank_forks
-                        .read()
-                        .unwrap()
+                    let mut frozen_banks: Vec<_> = context
                         .frozen_banks()
                         .into_iter()
-                        .filter(|(slot, _)| *slot >= forks_root)
-                        .map(|(_, bank)| bank)
+                        // Banks can be marked dead after they froze, for a zero hash or
+                        // by an external request
+                        .filter(|bank| !progress.is_dead(bank.slot()).unwrap_or(false))
                         .collect();
                     collect_frozen_banks_time.stop();
 
                     let mut compute_bank_stats_time = Measure::start("compute_bank_stats");
                     let newly_computed_slot_stats = Self::compute_bank_stats(
                         &vote_account,
-                        &ancestors,
+                        &context.ancestry.ancestors,
                         &mut frozen_banks,
                         &tower,
                         &mut progress,
@@ -487,62 +1505,149 @@ impl ReplayStage {
                         &bank_forks,
                         &mut heaviest_subtree_fork_choice,
                         &mut latest_validator_votes_for_frozen_banks,
+                        superminority_threshold,
+                        &replay_thread_pool,
                     );
                     compute_bank_stats_time.stop();
 
                     let mut compute_slot_stats_time = Measure::start("compute_slot_stats_time");
                     for slot in newly_computed_slot_stats {
                         let fork_stats = progress.get_fork_stats(slot).unwrap();
-                        let confirmed_forks = Self::confirm_forks(
-                            &tower,
-                            &fork_stats.voted_stakes,
-                            fork_stats.total_stake,
-                            &progress,
-                            &bank_forks,
-                        );
+                        let root_slot = {
+                            let r_bank_forks = bank_forks.read().unwrap();
+                            Self::for_each_confirmed_fork(
+                                &tower,
+                                &fork_stats.voted_stakes,
+                                fork_stats.total_stake,
+                                &progress,
+                                &r_bank_forks,
+                                |slot, bank_hash| confirmed_forks.push((slot, bank_hash)),
+                            );
+                            r_bank_forks.root()
+                        };
 
-                        Self::mark_slots_confirmed(&confirmed_forks, &bank_forks, &mut progress, &mut duplicate_slots_tracker, &mut heaviest_subtree_fork_choice);
+                        Self::mark_slots_confirmed(confirmed_forks.drain(..), root_slot, &mut progress, &mut duplicate_slots_tracker, &mut heaviest_subtree_fork_choice);
                     }
                     compute_slot_stats_time.stop();
 
+                    Self::update_paused_forks(
+                        &paused_slots,
+                        &mut paused_forks,
+                        &bank_forks,
+                        &duplicate_slots_tracker,
+                        &mut heaviest_subtree_fork_choice,
+                    );
+
                     let mut select_forks_time = Measure::start("select_forks_time");
                     let (heaviest_bank, heaviest_bank_on_same_voted_fork) = heaviest_subtree_fork_choice
-                        .select_forks(&frozen_banks, &tower, &progress, &ancestors, &bank_forks);
+                        .select_forks(&frozen_banks, &tower, &progress, &context.ancestry.ancestors, &bank_forks);
                     select_forks_time.stop();
 
-                    if let Some(heaviest_bank_on_same_voted_fork) = heaviest_bank_on_same_voted_fork.as_ref() {
-                        if let Some(my_latest_landed_vote) = progress.my_latest_landed_vote(heaviest_bank_on_same_voted_fork.slot()) {
-                            Self::refresh_last_vote(&mut tower, &cluster_info,
-                                                    heaviest_bank_on_same_voted_fork,
-                                                    &poh_recorder, my_latest_landed_vote,
-                                                    &vote_account,
-                                                    &identity_keypair,
-                                                    &authorized_voter_keypairs.read().unwrap(),
-                                                    &mut voted_signatures,
-                                                    has_new_vote_been_rooted, &mut
-                                                    last_vote_refresh_time);
-                        }
+                    let cluster_tip_slot = Self::cluster_tip_slot(&cluster_slots, &gossip_duplicate_confirmed_slots);
+                    let voting_deferred = Self::check_voting_deferred(
+                        defer_voting_until_within_slots,
+                        &mut caught_up_to_cluster,
+                        heaviest_bank.slot(),
+                        cluster_tip_slot,
+                        voting_deferred_start.elapsed(),
+                    );
+                    if voting_deferred && last_voting_deferred_report.elapsed() >= Duration::from_secs(1) {
+                        datapoint_info!(
+                            "replay_stage-voting_deferred",
+                            ("heaviest_slot", heaviest_bank.slot(), i64),
+                            ("cluster_tip_known", cluster_tip_slot.is_some(), bool),
+                            ("cluster_tip_slot", cluster_tip_slot.unwrap_or_default(), i64),
+                            (
+                                "gap",
+                                cluster_tip_slot.unwrap_or_default().saturating_sub(heaviest_bank.slot()),
+                                i64
+                            ),
+                        );
+                        last_voting_deferred_report = Instant::now();
+                    }
+
+                    // A vote from a saved tower hasn't landed while catching up, don't keep
+                    // resending it
+                    if !voting_disabled && !voting_deferred {
+                        if let Some(heaviest_bank_on_same_voted_fork) = heaviest_bank_on_same_voted_fork.as_ref() {
+                            if let Some(my_latest_landed_vote) = progress.my_latest_landed_vote(heaviest_bank_on_same_voted_fork.slot()) {
+                                Self::refresh_last_vote(
+                                    &mut tower,
+                                    &cluster_info,
+                                    heaviest_bank_on_same_voted_fork,
+                                    &poh_recorder,
+                                    my_latest_landed_vote,
+                                    &vote_account,
+                                    vote_fee_payer_keypair.as_deref().unwrap_or(&identity_keypair),
+                                    &authorized_voter_keypairs.read().unwrap(),
+                                    &mut voted_signatures,
+                                    max_vote_signatures,
+                                    has_new_vote_been_rooted,
+                                    &mut last_vote_refresh_time,
+                                    vote_refresh_interval,
+                                    Instant::now(),
+                                );
+                            }
+                        }
                     }
 
                     let mut select_vote_and_reset_forks_time =
                         Measure::start("select_vote_and_reset_forks");
-                    let SelectVoteAndResetForkResult {
-                        vote_bank,
-                        reset_bank,
-                        heaviest_fork_failures,
-                    } = Self::select_vote_and_reset_forks(
+                    let select_vote_and_reset_forks_result = Self::select_vote_and_reset_forks(
                         &heaviest_bank,
                         heaviest_bank_on_same_voted_fork.as_ref(),
-                        &ancestors,
-                        &descendants,
+                        &context.ancestry.ancestors,
+                        &context.ancestry.descendants,
                         &progress,
                         &mut tower,
                         &latest_validator_votes_for_frozen_banks,
                         &heaviest_subtree_fork_choice,
+                        &mut epoch_stakes_cache,
                     );
                     select_vote_and_reset_forks_time.stop();
+                    Self::answer_simulate_vote_requests(
+                        &simulate_vote_request_receiver,
+                        &bank_forks,
+                        heaviest_bank_on_same_voted_fork.as_ref(),
+                        &context.ancestry.ancestors,
+                        &context.ancestry.descendants,
+                        &progress,
+                        &tower,
+                        &latest_validator_votes_for_frozen_banks,
+                        &heaviest_subtree_fork_choice,
+                    );
+                    Self::answer_explain_fork_choice_requests(
+                        &explain_fork_choice_request_receiver,
+                        &bank_forks,
+                        &context.ancestry.ancestors,
+                        &context.ancestry.descendants,
+                        &progress,
+                        &tower,
+                        &latest_validator_votes_for_frozen_banks,
+                        &heaviest_subtree_fork_choice,
+                    );
+                    if let Some(fork_choice_event_sender) = fork_choice_event_sender.as_ref() {
+                        let _ = fork_choice_event_sender.send(ForkChoiceEvent::new(
+                            &heaviest_bank,
+                            heaviest_bank_on_same_voted_fork.as_ref(),
+                            &select_vote_and_reset_forks_result,
+                        ));
+                    }
+                    let SelectVoteAndResetForkResult {
+                        vote_bank,
+                        reset_bank,
+                        heaviest_fork_failures,
+                    } = select_vote_and_reset_forks_result;
+
+                    // Neither vote nor record the vote in the tower while catching up
+                    let vote_bank = if voting_deferred { None } else { vote_bank };
 
                     let mut heaviest_fork_failures_time = Measure::start("heaviest_fork_failures_time");
+                    Self::record_vote_failures(
+                        &recent_vote_failures_,
+                        heaviest_bank.slot(),
+                        &heaviest_fork_failures,
+                    );
                     if tower.is_recent(heaviest_bank.slot()) && !heaviest_fork_failures.is_empty() {
                         info!(
                             "Couldn't vote on heaviest fork: {:?}, heaviest_fork_failures: {:?}",
@@ -550,20 +1655,37 @@ impl ReplayStage {
                             heaviest_fork_failures
                         );
 
-                        for r in heaviest_fork_failures {
-                            if let HeaviestForkFailures::NoPropagatedConfirmation(slot) = r {
-                                if let Some(latest_leader_slot) =
-                                    progress.get_latest_leader_slot(slot)
-                                {
-                                    progress.log_propagated_stats(latest_leader_slot, &bank_forks);
+                        for r in &heaviest_fork_failures {
+                            match r {
+                                HeaviestForkFailures::NoPropagatedConfirmation(slot) => {
+                                    if let Some(latest_leader_slot) =
+                                        progress.get_latest_leader_slot(*slot)
+                                    {
+                                        progress.log_propagated_stats(latest_leader_slot, &bank_forks);
+                                    }
+                                }
+                                HeaviestForkFailures::FailedSwitchThreshold(
+                                    slot,
+                                    switch_proof_stake,
+                                    total_stake,
+                                ) => {
+                                    info!(
+                                        "Failed switch threshold for slot {}: switch proof stake: {}, total stake: {}, threshold: {}",
+                                        slot,
+                                        switch_proof_stake,
+                                        total_stake,
+                                        SWITCH_FORK_THRESHOLD,
+                                    );
                                 }
+                                _ => (),
                             }
                         }
                     }
                     heaviest_fork_failures_time.stop();
 
                     let mut voting_time = Measure::start("voting_time");
-                    // Vote on a fork
+                    // Vote on a fork. This locks `bank_forks` again rather than using the
+                    // context, as voting may set a new root and prune the banks off of it
                     if let Some((ref vote_bank, ref switch_fork_decision)) = vote_bank {
                         if let Some(votable_leader) =
                             leader_schedule_cache.slot_leader_at(vote_bank.slot(), Some(vote_bank))
@@ -585,6 +1707,7 @@ impl ReplayStage {
                             &mut progress,
                             &vote_account,
                             &identity_keypair,
+                            vote_fee_payer_keypair.as_deref().unwrap_or(&identity_keypair),
                             &authorized_voter_keypairs.read().unwrap(),
                             &cluster_info,
                             &blockstore,
@@ -600,8 +1723,11 @@ impl ReplayStage {
                             &mut gossip_duplicate_confirmed_slots,
                             &mut unfrozen_gossip_verified_vote_hashes,
                             &mut voted_signatures,
+                            max_vote_signatures,
                             &mut has_new_vote_been_rooted,
                             &mut replay_timing,
+                            &last_voted_slot_and_hash_,
+                            voting_disabled,
                         );
                     };
                     voting_time.stop();
@@ -609,6 +1735,15 @@ impl ReplayStage {
                     let mut reset_bank_time = Measure::start("reset_bank");
                     // Reset onto a fork
                     if let Some(reset_bank) = reset_bank {
+                        Self::maybe_notify_fork_abandoned(
+                            &last_reset,
+                            &reset_bank,
+                            &poh_recorder,
+                            &bank_forks,
+                            &context.ancestry.ancestors,
+                            &heaviest_fork_failures,
+                            &rpc_subscriptions,
+                        );
                         if last_reset != reset_bank.last_blockhash() {
                             info!(
                                 "vote bank: {:?} reset bank: {:?}",
@@ -631,12 +1766,12 @@ impl ReplayStage {
                                 ),
                             );
 
-                            if my_pubkey != cluster_info.id() {
-                                identity_keypair = cluster_info.keypair().clone();
-                                let my_old_pubkey = my_pubkey;
-                                my_pubkey = identity_keypair.pubkey();
-                                warn!("Identity changed from {} to {}", my_old_pubkey, my_pubkey);
-                            }
+                            Self::maybe_update_identity(
+                                &mut my_pubkey,
+                                &mut identity_keypair,
+                                &cluster_info,
+                                identity_hotswap_callback.as_ref(),
+                            );
 
                             Self::reset_poh_recorder(
                                 &my_pubkey,
@@ -651,34 +1786,21 @@ impl ReplayStage {
                             if let Some(last_voted_slot) = tower.last_voted_slot() {
                                 // If the current heaviest bank is not a descendant of the last voted slot,
                                 // there must be a partition
-                                let partition_detected = Self::is_partition_detected(&ancestors, last_voted_slot, heaviest_bank.slot());
-
-                                if !partition_exists && partition_detected
-                                {
-                                    warn!(
-                                        "PARTITION DETECTED waiting to join heaviest fork: {} last vote: {:?}, reset slot: {}",
-                                        heaviest_bank.slot(),
-                                        last_voted_slot,
-                                        reset_bank.slot(),
-                                    );
-                                    inc_new_counter_info!("replay_stage-partition_detected", 1);
-                                    datapoint_info!(
-                                        "replay_stage-partition",
-                                        ("slot", reset_bank.slot() as i64, i64)
-                                    );
-                                    partition_exists = true;
-                                } else if partition_exists
-                                    && !partition_detected
-                                {
-                                    warn!(
-                                        "PARTITION resolved heaviest fork: {} last vote: {:?}, reset slot: {}",
-                                        heaviest_bank.slot(),
-                                        last_voted_slot,
-                                        reset_bank.slot()
-                                    );
-                                    partition_exists = false;
-                                    inc_new_counter_info!("replay_stage-partition_resolved", 1);
-                                }
+                                let partition_detected = Self::debounce_partition_detection(
+                                    &mut partition_detected_iterations,
+                                    Self::is_partition_detected(&context.ancestry.ancestors, last_voted_slot, heaviest_bank.slot()),
+                                    partition_detection_threshold,
+                                );
+                                Self::update_partition_state(
+                                    &mut partition_exists,
+                                    partition_detected,
+                                    last_voted_slot,
+                                    heaviest_bank.slot(),
+                                    reset_bank.slot(),
+                                    partition_callback.as_ref(),
+                                    partition_resolved_callback.as_ref(),
+                                    partition_event_sender.as_ref(),
+                                );
                             }
                         }
                     }
@@ -686,6 +1808,8 @@ impl ReplayStage {
 
                     let mut start_leader_time = Measure::start("start_leader_time");
                     if !tpu_has_bank {
+                        // Needs a fresh view of `bank_forks`, which may have a new root
+                        // by now, and inserts the new leader bank
                         Self::maybe_start_leader(
                             &my_pubkey,
                             &bank_forks,
@@ -696,7 +1820,9 @@ impl ReplayStage {
                             &retransmit_slots_sender,
                             &mut skipped_slots_info,
                             has_new_vote_been_rooted,
+                            min_online_stake_to_lead,
                         );
+                        skipped_slots_info.maybe_report();
 
                         let poh_bank = poh_recorder.lock().unwrap().bank();
                         if let Some(bank) = poh_bank {
@@ -711,18 +1837,24 @@ impl ReplayStage {
                     start_leader_time.stop();
 
                     let mut wait_receive_time = Measure::start("wait_receive_time");
-                    if !did_complete_bank {
+                    if did_complete_bank {
+                        replay_wait.reset();
+                    } else {
                         // only wait for the signal if we did not just process a bank; maybe there are more slots available
-
-                        let timer = Duration::from_millis(100);
-                        let result = ledger_signal_receiver.recv_timeout(timer);
+                        let result = ledger_signal_receiver
+                            .recv_timeout(replay_wait.next_wait(num_active_banks, did_replay_entries));
                         match result {
-                            Err(RecvTimeoutError::Timeout) => (),
+                            Err(RecvTimeoutError::Timeout) => replay_wait.back_off(),
                             Err(_) => break,
-                            Ok(_) => trace!("blockstore signal"),
+                            Ok(_) => {
+                                trace!("blockstore signal");
+                                replay_wait.reset();
+                            }
                         };
                     }
                     wait_receive_time.stop();
+                    replay_timing.replay_wait_ms =
+                        replay_wait.next_wait(num_active_banks, did_replay_entries).as_millis() as u64;
 
                     replay_timing.update(
                         collect_frozen_banks_time.as_us(),
@@ -742,6 +1874,18 @@ impl ReplayStage {
                         process_unfrozen_gossip_verified_vote_hashes_time.as_us(),
                         process_duplicate_slots_time.as_us(),
                     );
+                    // Only the cumulative timings are published, so readers never observe
+                    // a partially reset metrics window and the lock is held just for the swap
+                    let timing_snapshot = replay_timing.snapshot();
+                    *shared_replay_timing_.write().unwrap() = timing_snapshot;
+                    Self::publish_replay_status(
+                        &replay_status_,
+                        &bank_forks,
+                        heaviest_bank.slot(),
+                        &tower,
+                        partition_exists,
+                    );
+                    last_iteration_timestamp_.store(timestamp(), Ordering::Relaxed);
                 }
             })
             .unwrap();
@@ -749,7 +1893,452 @@ impl ReplayStage {
         Self {
             t_replay,
             commitment_service,
+            vote_simulation_handle: VoteSimulationHandle {
+                simulate_vote_request_sender,
+                explain_fork_choice_request_sender,
+                lockout_summary_request_sender,
+            },
+            replay_stage_handle: ReplayStageHandle {
+                replay_timing: shared_replay_timing,
+                paused_slots,
+                replay_paused,
+                replay_duration_histogram,
+                leader_replay_stats,
+                bank_forks: shared_bank_forks,
+                last_iteration_timestamp,
+                replay_status,
+                last_voted_slot_and_hash,
+                recent_vote_failures,
+            },
+        }
+    }
+
+    fn answer_lockout_summary_requests(
+        lockout_summary_request_receiver: &CrossbeamReceiver<LockoutSummaryRequest>,
+        progress: &ProgressMap,
+        bank_forks: &RwLock<BankForks>,
+    ) {
+        for (slot, response_sender) in lockout_summary_request_receiver.try_iter() {
+            let bank = bank_forks.read().unwrap().get(slot).cloned();
+            let summary = bank.and_then(|bank| {
+                let vote_accounts: HashMap<_, _> = bank.vote_accounts().into_iter().collect();
+                progress.lockout_summary(slot, &vote_accounts)
+            });
+            // The requester may have timed out already
+            let _ = response_sender.send(summary);
+        }
+    }
+
+    /// Returns what the replay loop would vote on if the bank of `slot` were the heaviest,
+    /// see `VoteSimulationHandle::simulate_vote()`
+    pub fn simulate_vote(&self, slot: Slot) -> Option<SelectVoteAndResetForkResult> {
+        self.vote_simulation_handle.simulate_vote(slot)
+    }
+
+    pub fn vote_simulation_handle(&self) -> VoteSimulationHandle {
+        self.vote_simulation_handle.clone()
+    }
+
+    pub fn replay_stage_handle(&self) -> ReplayStageHandle {
+        self.replay_stage_handle.clone()
+    }
+
+    // Runs the vote selection for each requested slot on a copy of the tower, so that
+    // the simulation has no effect on what the replay loop actually votes on
+    #[allow(clippy::too_many_arguments)]
+    fn answer_simulate_vote_requests(
+        simulate_vote_request_receiver: &CrossbeamReceiver<SimulateVoteRequest>,
+        bank_forks: &RwLock<BankForks>,
+        heaviest_bank_on_same_voted_fork: Option<&Arc<Bank>>,
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+        descendants: &HashMap<Slot, HashSet<Slot>>,
+        progress: &ProgressMap,
+        tower: &Tower,
+        latest_validator_votes_for_frozen_banks: &LatestValidatorVotesForFrozenBanks,
+        fork_choice: &HeaviestSubtreeForkChoice,
+    ) {
+        for (slot, response_sender) in simulate_vote_request_receiver.try_iter() {
+            let bank = bank_forks.read().unwrap().get(slot).cloned();
+            let result = bank
+                .filter(|_| {
+                    progress
+                        .get_fork_stats(slot)
+                        .map(|fork_stats| fork_stats.computed)
+                        .unwrap_or(false)
+                })
+                .map(|bank| {
+                    Self::select_vote_and_reset_forks(
+                        &bank,
+                        heaviest_bank_on_same_voted_fork,
+                        ancestors,
+                        descendants,
+                        progress,
+                        &mut tower.clone(),
+                        latest_validator_votes_for_frozen_banks,
+                        fork_choice,
+                        &mut EpochStakesCache::default(),
+                    )
+                });
+            // The requester may have timed out already
+            let _ = response_sender.send(result);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn answer_explain_fork_choice_requests(
+        explain_fork_choice_request_receiver: &CrossbeamReceiver<ExplainForkChoiceRequest>,
+        bank_forks: &RwLock<BankForks>,
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+        descendants: &HashMap<Slot, HashSet<Slot>>,
+        progress: &ProgressMap,
+        tower: &Tower,
+        latest_validator_votes_for_frozen_banks: &LatestValidatorVotesForFrozenBanks,
+        fork_choice: &HeaviestSubtreeForkChoice,
+    ) {
+        for (slot, response_sender) in explain_fork_choice_request_receiver.try_iter() {
+            let bank = bank_forks.read().unwrap().get(slot).cloned();
+            let explanation = bank.filter(|bank| bank.is_frozen()).map(|bank| {
+                fork_choice.explain_selection_with(slot, bank.hash(), |(best_slot, _)| {
+                    Self::describe_vote_checks(
+                        *best_slot,
+                        bank_forks,
+                        ancestors,
+                        descendants,
+                        progress,
+                        tower,
+                        latest_validator_votes_for_frozen_banks,
+                        fork_choice,
+                    )
+                })
+            });
+            // The requester may have timed out already
+            let _ = response_sender.send(explanation);
+        }
+    }
+
+    // Describes the checks `select_vote_and_reset_forks()` would run before voting on the
+    // bank of `slot` if it were the heaviest, using a copy of the tower. None if the bank's
+    // stats haven't been computed
+    #[allow(clippy::too_many_arguments)]
+    fn describe_vote_checks(
+        slot: Slot,
+        bank_forks: &RwLock<BankForks>,
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+        descendants: &HashMap<Slot, HashSet<Slot>>,
+        progress: &ProgressMap,
+        tower: &Tower,
+        latest_validator_votes_for_frozen_banks: &LatestValidatorVotesForFrozenBanks,
+        fork_choice: &HeaviestSubtreeForkChoice,
+    ) -> Option<String> {
+        let bank = bank_forks.read().unwrap().get(slot).cloned()?;
+        let fork_stats = progress
+            .get_fork_stats(slot)
+            .filter(|fork_stats| fork_stats.computed)?;
+        let is_leader_slot = progress.get_propagated_stats(slot)?.is_leader_slot;
+        let switch_fork_decision = tower.clone().check_switch_threshold(
+            slot,
+            ancestors,
+            descendants,
+            progress,
+            bank.total_epoch_stake(),
+            bank.epoch_vote_accounts(bank.epoch())
+                .expect("Bank epoch vote accounts must contain entry for the bank's own epoch"),
+            latest_validator_votes_for_frozen_banks,
+            fork_choice,
+        );
+        let switch_threshold = match switch_fork_decision {
+            SwitchForkDecision::SameFork => "same fork".to_string(),
+            SwitchForkDecision::SwitchProof(_) => "passed".to_string(),
+            SwitchForkDecision::FailedSwitchThreshold(switch_proof_stake, total_stake) => {
+                format!("failed with {}/{} stake", switch_proof_stake, total_stake)
+            }
+            SwitchForkDecision::FailedSwitchDuplicateRollback(latest_duplicate_ancestor) => {
+                format!(
+                    "failed, rolling back from duplicate {}",
+                    latest_duplicate_ancestor
+                )
+            }
+        };
+        Some(format!(
+            "locked out: {}, vote threshold: {}, switch threshold: {}, propagated: {}",
+            fork_stats.is_locked_out,
+            if fork_stats.vote_threshold {
+                "passed"
+            } else {
+                "failed"
+            },
+            switch_threshold,
+            is_leader_slot || progress.is_propagated(slot),
+        ))
+    }
+
+    fn most_recent_vote_failures(
+        recent_vote_failures: &RwLock<VecDeque<VoteFailure>>,
+        limit: usize,
+    ) -> Vec<VoteFailure> {
+        recent_vote_failures
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Records why the heaviest bank couldn't be voted on, if it couldn't. The same
+    // bank usually fails for the same reasons over many iterations of the replay
+    // loop, those repeats are only recorded once
+    fn record_vote_failures(
+        recent_vote_failures: &RwLock<VecDeque<VoteFailure>>,
+        slot: Slot,
+        failures: &[HeaviestForkFailures],
+    ) {
+        if failures.is_empty() {
+            return;
+        }
+        let mut recent_vote_failures = recent_vote_failures.write().unwrap();
+        if let Some(last) = recent_vote_failures.back() {
+            if last.slot == slot && last.failures == failures {
+                return;
+            }
+        }
+        if recent_vote_failures.len() == MAX_RECENT_VOTE_FAILURES {
+            recent_vote_failures.pop_front();
+        }
+        recent_vote_failures.push_back(VoteFailure {
+            slot,
+            failures: failures.to_vec(),
+            timestamp: timestamp(),
+        });
+    }
+
+    // Builds the new status before taking the write lock, so that readers of
+    // `replay_status()` are only ever blocked for the swap. Reads the active banks from
+    // `bank_forks` rather than the loop's context, as a new root may have pruned some
+    fn publish_replay_status(
+        replay_status: &RwLock<ReplayStatus>,
+        bank_forks: &RwLock<BankForks>,
+        heaviest_slot: Slot,
+        tower: &Tower,
+        partition_exists: bool,
+    ) {
+        let mut active_slots = bank_forks.read().unwrap().active_banks();
+        active_slots.sort_unstable();
+        let status = ReplayStatus {
+            active_slots,
+            heaviest_slot,
+            last_voted_slot: tower.last_voted_slot(),
+            partition_exists,
+        };
+        *replay_status.write().unwrap() = status;
+    }
+
+    // Applies the `ReplayControl` messages received since the last iteration of the
+    // replay loop. Returns whether the next iteration should run
+    fn process_replay_control(
+        replay_control_receiver: &Receiver<ReplayControl>,
+        replay_paused: &AtomicBool,
+    ) -> bool {
+        let was_paused = replay_paused.load(Ordering::Relaxed);
+        let mut paused = was_paused;
+        let mut step_one_iteration = false;
+        for control in replay_control_receiver.try_iter() {
+            match control {
+                ReplayControl::Pause => paused = true,
+                ReplayControl::Resume => paused = false,
+                ReplayControl::StepOneIteration => step_one_iteration = true,
+            }
+        }
+        if paused != was_paused {
+            info!("replay {}", if paused { "paused" } else { "resumed" });
+            datapoint_info!("replay_stage-paused", ("paused", paused, bool));
+            replay_paused.store(paused, Ordering::Relaxed);
+        }
+        !paused || step_one_iteration
+    }
+
+    // Picks up an identity keypair swapped into `cluster_info`, returning whether the
+    // identity changed
+    fn maybe_update_identity(
+        my_pubkey: &mut Pubkey,
+        identity_keypair: &mut Arc<Keypair>,
+        cluster_info: &ClusterInfo,
+        identity_hotswap_callback: Option<&IdentityHotswapCallback>,
+    ) -> bool {
+        if *my_pubkey == cluster_info.id() {
+            return false;
+        }
+        *identity_keypair = cluster_info.keypair().clone();
+        let my_old_pubkey = *my_pubkey;
+        *my_pubkey = identity_keypair.pubkey();
+        warn!("Identity changed from {} to {}", my_old_pubkey, my_pubkey);
+        if let Some(identity_hotswap_callback) = identity_hotswap_callback {
+            identity_hotswap_callback(my_old_pubkey, *my_pubkey);
+        }
+        true
+    }
+
+    // Tracks whether this validator's last vote is on a different fork than the
+    // heaviest bank, only logging, invoking the callbacks and sending events on transitions
+    #[allow(clippy::too_many_arguments)]
+    fn update_partition_state(
+        partition_exists: &mut bool,
+        partition_detected: bool,
+        last_voted_slot: Slot,
+        heaviest_slot: Slot,
+        reset_slot: Slot,
+        partition_callback: Option<&PartitionCallback>,
+        partition_resolved_callback: Option<&PartitionCallback>,
+        partition_event_sender: Option<&Sender<PartitionEvent>>,
+    ) {
+        if !*partition_exists && partition_detected {
+            warn!(
+                "PARTITION DETECTED waiting to join heaviest fork: {} last vote: {:?}, reset slot: {}",
+                heaviest_slot, last_voted_slot, reset_slot,
+            );
+            inc_new_counter_info!("replay_stage-partition_detected", 1);
+            datapoint_info!("replay_stage-partition", ("slot", reset_slot as i64, i64));
+            *partition_exists = true;
+            if let Some(partition_callback) = partition_callback {
+                partition_callback(last_voted_slot, heaviest_slot);
+            }
+            if let Some(partition_event_sender) = partition_event_sender {
+                let _ = partition_event_sender.send(PartitionEvent::Detected {
+                    heaviest_slot,
+                    last_voted_slot,
+                    reset_slot,
+                });
+            }
+        } else if *partition_exists && !partition_detected {
+            warn!(
+                "PARTITION resolved heaviest fork: {} last vote: {:?}, reset slot: {}",
+                heaviest_slot, last_voted_slot, reset_slot
+            );
+            *partition_exists = false;
+            inc_new_counter_info!("replay_stage-partition_resolved", 1);
+            if let Some(partition_resolved_callback) = partition_resolved_callback {
+                partition_resolved_callback(last_voted_slot, heaviest_slot);
+            }
+            if let Some(partition_event_sender) = partition_event_sender {
+                let _ = partition_event_sender.send(PartitionEvent::Resolved {
+                    heaviest_slot,
+                    last_voted_slot,
+                    reset_slot,
+                });
+            }
+        }
+    }
+
+    // The fork of a paused slot that was already frozen must not be selected by fork
+    // choice, otherwise the heaviest fork could run through a slot the operator is trying
+    // to hold replay at. Such forks are marked invalid candidates like unconfirmed
+    // duplicates, tracked in `paused_forks`, and restored once the slot is resumed.
+    // Forks the cluster duplicate confirmed can't be excluded, and forks marked invalid
+    // as duplicates are left to the duplicate handling
+    fn update_paused_forks(
+        paused_slots: &HashSet<Slot>,
+        paused_forks: &mut HashSet<SlotHashKey>,
+        bank_forks: &RwLock<BankForks>,
+        duplicate_slots_tracker: &DuplicateSlotsTracker,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) {
+        paused_forks.retain(|slot_hash_key| {
+            if paused_slots.contains(&slot_hash_key.0) {
+                return true;
+            }
+            if !duplicate_slots_tracker.contains(&slot_hash_key.0) {
+                heaviest_subtree_fork_choice.restore_fork_candidate(slot_hash_key);
+            }
+            false
+        });
+
+        let r_bank_forks = bank_forks.read().unwrap();
+        for slot in paused_slots {
+            let slot_hash_key = match r_bank_forks.get(*slot) {
+                Some(bank) if bank.is_frozen() => (*slot, bank.hash()),
+                _ => continue,
+            };
+            if paused_forks.contains(&slot_hash_key)
+                || heaviest_subtree_fork_choice.is_candidate(&slot_hash_key) != Some(true)
+                || heaviest_subtree_fork_choice.is_duplicate_confirmed(&slot_hash_key) == Some(true)
+            {
+                continue;
+            }
+            heaviest_subtree_fork_choice.mark_fork_invalid_candidate(&slot_hash_key);
+            paused_forks.insert(slot_hash_key);
+        }
+    }
+
+    // Counts the consecutive reset iterations a partition has been detected for, returns
+    // whether it has persisted for at least `partition_detection_threshold` of them
+    fn debounce_partition_detection(
+        partition_detected_iterations: &mut usize,
+        partition_detected: bool,
+        partition_detection_threshold: usize,
+    ) -> bool {
+        if partition_detected {
+            *partition_detected_iterations = partition_detected_iterations.saturating_add(1);
+        } else {
+            *partition_detected_iterations = 0;
+        }
+        *partition_detected_iterations >= partition_detection_threshold
+    }
+
+    // The highest slot the cluster is known to have reached, from the epoch slots of
+    // staked nodes and the slots duplicate confirmed by gossip votes
+    fn cluster_tip_slot(
+        cluster_slots: &ClusterSlots,
+        gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
+    ) -> Option<Slot> {
+        cluster_slots
+            .max_staked_slot()
+            .max(gossip_duplicate_confirmed_slots.keys().next_back().copied())
+    }
+
+    // Returns whether voting is deferred. Catching up ends the first time the heaviest
+    // bank gets within `defer_voting_until_within_slots` of the tip, voting isn't deferred
+    // again after that. Without any known tip the heaviest bank can't be shown to have
+    // caught up, so voting stays deferred until `deferred_for`, how long it has been
+    // deferred, reaches `MAX_VOTING_DEFERRED_WITHOUT_CLUSTER_TIP_MS`
+    fn check_voting_deferred(
+        defer_voting_until_within_slots: Option<u64>,
+        caught_up_to_cluster: &mut bool,
+        heaviest_slot: Slot,
+        cluster_tip_slot: Option<Slot>,
+        deferred_for: Duration,
+    ) -> bool {
+        let max_gap = match defer_voting_until_within_slots {
+            Some(max_gap) => max_gap,
+            None => return false,
+        };
+        if *caught_up_to_cluster {
+            return false;
+        }
+        match cluster_tip_slot {
+            Some(cluster_tip_slot) => {
+                let gap = cluster_tip_slot.saturating_sub(heaviest_slot);
+                if gap > max_gap {
+                    return true;
+                }
+                info!(
+                    "heaviest slot {} caught up to within {} slots of the cluster, voting",
+                    heaviest_slot, gap
+                );
+            }
+            None => {
+                if deferred_for < Duration::from_millis(MAX_VOTING_DEFERRED_WITHOUT_CLUSTER_TIP_MS)
+                {
+                    return true;
+                }
+                warn!(
+                    "no cluster tip known after {:?}, voting from heaviest slot {}",
+                    deferred_for, heaviest_slot
+                );
+            }
         }
+        *caught_up_to_cluster = true;
+        false
     }
 
     fn is_partition_detected(
@@ -768,6 +2357,7 @@ impl ReplayStage {
         bank_forks: &RwLock<BankForks>,
         my_pubkey: &Pubkey,
         vote_account: &Pubkey,
+        superminority_threshold: f64,
     ) -> (ProgressMap, HeaviestSubtreeForkChoice) {
         let (root_bank, frozen_banks) = {
             let bank_forks = bank_forks.read().unwrap();
@@ -777,7 +2367,13 @@ impl ReplayStage {
             )
         };
 
-        Self::initialize_progress_and_fork_choice(&root_bank, frozen_banks, my_pubkey, vote_account)
+        Self::initialize_progress_and_fork_choice(
+            &root_bank,
+            frozen_banks,
+            my_pubkey,
+            vote_account,
+            superminority_threshold,
+        )
     }
 
     pub(crate) fn initialize_progress_and_fork_choice(
@@ -785,6 +2381,7 @@ impl ReplayStage {
         mut frozen_banks: Vec<Arc<Bank>>,
         my_pubkey: &Pubkey,
         vote_account: &Pubkey,
+        superminority_threshold: f64,
     ) -> (ProgressMap, HeaviestSubtreeForkChoice) {
         let mut progress = ProgressMap::default();
 
@@ -795,7 +2392,15 @@ impl ReplayStage {
             let prev_leader_slot = progress.get_bank_prev_leader_slot(bank);
             progress.insert(
                 bank.slot(),
-                ForkProgress::new_from_bank(bank, my_pubkey, vote_account, prev_leader_slot, 0, 0),
+                ForkProgress::new_from_bank(
+                    bank,
+                    my_pubkey,
+                    vote_account,
+                    prev_leader_slot,
+                    0,
+                    0,
+                    superminority_threshold,
+                ),
             );
         }
         let root = root_bank.slot();
@@ -807,39 +2412,78 @@ impl ReplayStage {
         (progress, heaviest_subtree_fork_choice)
     }
 
-    #[allow(dead_code)]
+    #[allow(clippy::too_many_arguments)]
     fn reset_duplicate_slots(
         duplicate_slots_reset_receiver: &DuplicateSlotsResetReceiver,
         ancestors: &mut HashMap<Slot, HashSet<Slot>>,
         descendants: &mut HashMap<Slot, HashSet<Slot>>,
         progress: &mut ProgressMap,
         bank_forks: &RwLock<BankForks>,
+        blockstore: &Blockstore,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+        poh_recorder: &Mutex<PohRecorder>,
+        my_pubkey: &Pubkey,
+        leader_schedule_cache: &LeaderScheduleCache,
+        last_reset: &mut Hash,
     ) {
         for duplicate_slot in duplicate_slots_reset_receiver.try_iter() {
-            Self::purge_unconfirmed_duplicate_slot(
+            let last_reset_slot = poh_recorder.lock().unwrap().last_reset_slot();
+            let is_poh_on_purged_fork = last_reset_slot == duplicate_slot
+                || descendants
+                    .get(&duplicate_slot)
+                    .map(|slot_descendants| slot_descendants.contains(&last_reset_slot))
+                    .unwrap_or(false);
+            let surviving_parent = Self::purge_unconfirmed_duplicate_slot(
                 duplicate_slot,
                 ancestors,
                 descendants,
                 progress,
                 bank_forks,
+                blockstore,
+                heaviest_subtree_fork_choice,
             );
+            if let Some(surviving_parent) = surviving_parent.filter(|_| is_poh_on_purged_fork) {
+                // PoH was reset onto, or is building a leader block on top of, a bank that
+                // no longer exists, so move it back onto the closest surviving ancestor
+                Self::reset_poh_recorder(
+                    my_pubkey,
+                    blockstore,
+                    &surviving_parent,
+                    poh_recorder,
+                    leader_schedule_cache,
+                );
+                *last_reset = surviving_parent.last_blockhash();
+            }
         }
     }
 
-    #[allow(dead_code)]
+    // Purges `duplicate_slot` and all of its descendants from `BankForks`, the progress
+    // map, fork choice and Blockstore so that the repaired version of the slot can be
+    // ingested and replayed from scratch. Returns the parent of `duplicate_slot` if
+    // anything was purged.
     fn purge_unconfirmed_duplicate_slot(
         duplicate_slot: Slot,
         ancestors: &mut HashMap<Slot, HashSet<Slot>>,
         descendants: &mut HashMap<Slot, HashSet<Slot>>,
         progress: &mut ProgressMap,
         bank_forks: &RwLock<BankForks>,
-    ) {
+        blockstore: &Blockstore,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) -> Option<Arc<Bank>> {
         warn!("purging slot {}", duplicate_slot);
+        let (root_bank, duplicate_bank) = {
+            let r_bank_forks = bank_forks.read().unwrap();
+            (
+                r_bank_forks.root_bank(),
+                r_bank_forks.get(duplicate_slot).cloned(),
+            )
+        };
         let slot_descendants = descendants.get(&duplicate_slot).cloned();
-        if slot_descendants.is_none() {
+        if slot_descendants.is_none() || duplicate_slot <= root_bank.slot() {
             // Root has already moved past this slot, no need to purge it
-            return;
+            return None;
         }
+        let duplicate_bank = duplicate_bank?;
 
         // Clear the ancestors/descendants map to keep them
         // consistent
@@ -851,6 +2495,13 @@ impl ReplayStage {
             descendants,
         );
 
+        // Unfrozen banks were never added to fork choice. Removing the frozen version
+        // also removes all of its descendants
+        if duplicate_bank.is_frozen() {
+            heaviest_subtree_fork_choice
+                .purge_slot_and_descendants(&(duplicate_slot, duplicate_bank.hash()));
+        }
+
         for d in slot_descendants
             .iter()
             .chain(std::iter::once(&duplicate_slot))
@@ -859,42 +2510,53 @@ impl ReplayStage {
             let _ = progress.remove(d);
 
             // Clear the duplicate banks from BankForks
-            {
-                let mut w_bank_forks = bank_forks.write().unwrap();
-                w_bank_forks.remove(*d);
+            let removed_bank = bank_forks.write().unwrap().remove(*d);
+
+            // Clear the signatures and accounts written by the purged bank, so they
+            // don't conflict with the replay of another version of the slot
+            if let Some(removed_bank) = removed_bank {
+                root_bank.clear_slot_signatures(*d);
+                root_bank.remove_unrooted_slots(&[(*d, removed_bank.bank_id())]);
             }
+
+            // Clear the shreds and the dead flag of the slot so that repair can
+            // insert another version, and replay will pick it up again
+            blockstore.clear_unconfirmed_slot(*d);
         }
+
+        duplicate_bank.parent()
     }
 
     // Purge given slot and all its descendants from the `ancestors` and
     // `descendants` structures so that they're consistent with `BankForks`
     // and the `progress` map.
-    fn purge_ancestors_descendants(
+    pub(crate) fn purge_ancestors_descendants(
         slot: Slot,
         slot_descendants: &HashSet<Slot>,
         ancestors: &mut HashMap<Slot, HashSet<Slot>>,
         descendants: &mut HashMap<Slot, HashSet<Slot>>,
     ) {
-        if !ancestors.contains_key(&slot) {
+        let slot_ancestors = match ancestors.remove(&slot) {
+            Some(slot_ancestors) => slot_ancestors,
             // Slot has already been purged
-            return;
-        }
+            None => return,
+        };
 
-        // Purge this slot from each of its ancestors' `descendants` maps
-        for a in ancestors
-            .get(&slot)
-            .expect("must exist based on earlier check")
-        {
-            descendants
+        // Purge this slot and its descendants from each of its ancestors' `descendants`
+        // sets. Only the purged slots are looked up, so the rest of each set, including
+        // the subtrees forking off elsewhere, is left untouched
+        for a in &slot_ancestors {
+            let a_descendants = descendants
                 .get_mut(a)
-                .expect("If exists in ancestor map must exist in descendants map")
-                .retain(|d| *d != slot && !slot_descendants.contains(d));
+                .expect("If exists in ancestor map must exist in descendants map");
+            a_descendants.remove(&slot);
+            for d in slot_descendants {
+                a_descendants.remove(d);
+            }
         }
-        ancestors
-            .remove(&slot)
-            .expect("must exist based on earlier check");
 
-        // Purge all the descendants of this slot from both maps
+        // Purge all the descendants of this slot from both maps. They all descend
+        // from this slot, so their `descendants` sets are dropped along with them
         for descendant in slot_descendants {
             ancestors.remove(descendant).expect("must exist");
             descendants
@@ -919,6 +2581,8 @@ impl ReplayStage {
         fork_choice: &mut HeaviestSubtreeForkChoice,
     ) {
         let root = bank_forks.read().unwrap().root();
+        let mut num_new_confirmations = 0;
+        let mut num_repeated_confirmations = 0;
         for new_confirmed_slots in gossip_duplicate_confirmed_slots_receiver.try_iter() {
             for (confirmed_slot, confirmed_hash) in new_confirmed_slots {
                 if confirmed_slot <= root {
@@ -927,9 +2591,12 @@ impl ReplayStage {
                     gossip_duplicate_confirmed_slots.insert(confirmed_slot, confirmed_hash)
                 {
                     assert_eq!(prev_hash, confirmed_hash);
-                    // Already processed this signal
-                    return;
+                    // Already processed this signal, the rest of the batch and the batches
+                    // after it may still hold new ones
+                    num_repeated_confirmations += 1;
+                    continue;
                 }
+                num_new_confirmations += 1;
 
                 check_slot_agrees_with_cluster(
                     confirmed_slot,
@@ -947,6 +2614,92 @@ impl ReplayStage {
                 );
             }
         }
+        if num_new_confirmations > 0 || num_repeated_confirmations > 0 {
+            datapoint_info!(
+                "replay_stage-gossip_duplicate_confirmed_slots",
+                ("new", num_new_confirmations, i64),
+                ("repeated", num_repeated_confirmations, i64),
+            );
+        }
+    }
+
+    // A dead slot is never replayed again, which leaves its descendants unreachable. Once
+    // the cluster duplicate confirms a version of such a slot, clear the dead version so
+    // that the confirmed one can be repaired and replayed from scratch
+    fn recover_dead_duplicate_confirmed_slots(
+        gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
+        ancestry: &mut AncestryCache,
+        progress: &mut ProgressMap,
+        bank_forks: &RwLock<BankForks>,
+        blockstore: &Blockstore,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) {
+        let dead_confirmed_slots: Vec<(Slot, Hash)> = gossip_duplicate_confirmed_slots
+            .iter()
+            .filter(|(slot, _)| progress.is_dead(**slot).unwrap_or(false))
+            .map(|(slot, hash)| (*slot, *hash))
+            .collect();
+        for (slot, confirmed_hash) in dead_confirmed_slots {
+            if Self::undead_slot(
+                slot,
+                ancestry,
+                progress,
+                bank_forks,
+                blockstore,
+                heaviest_subtree_fork_choice,
+            ) {
+                warn!(
+                    "Dead slot {} was duplicate confirmed by the cluster with hash {}, purging it",
+                    slot, confirmed_hash
+                );
+                datapoint_info!(
+                    "replay_stage-recover_dead_duplicate_confirmed_slot",
+                    ("slot", slot as i64, i64),
+                );
+            }
+        }
+    }
+
+    /// Clears the dead status of `slot` so that another version of it can be repaired
+    /// and replayed: its shreds and dead flag in the blockstore, its bank and those of any
+    /// descendants, and their replay progress. The bank is then rebuilt like any new
+    /// slot. Returns false without changing anything if the slot isn't dead or isn't
+    /// newer than the root
+    fn undead_slot(
+        slot: Slot,
+        ancestry: &mut AncestryCache,
+        progress: &mut ProgressMap,
+        bank_forks: &RwLock<BankForks>,
+        blockstore: &Blockstore,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) -> bool {
+        let (root_bank, dead_bank) = {
+            let r_bank_forks = bank_forks.read().unwrap();
+            (r_bank_forks.root_bank(), r_bank_forks.get(slot).cloned())
+        };
+        if slot <= root_bank.slot() || !progress.is_dead(slot).unwrap_or(false) {
+            return false;
+        }
+
+        let slot_descendants = ancestry.purge(slot);
+        // A bank frozen before it was marked dead is in fork choice, along with its
+        // descendants
+        if let Some(dead_bank) = dead_bank.filter(|dead_bank| dead_bank.is_frozen()) {
+            heaviest_subtree_fork_choice.purge_slot_and_descendants(&(slot, dead_bank.hash()));
+        }
+        for s in slot_descendants.iter().chain(std::iter::once(&slot)) {
+            let _ = progress.remove(s);
+            // Clear the signatures and accounts written by the removed bank, so they
+            // don't conflict with the replay of another version of the slot
+            if let Some(removed_bank) = bank_forks.write().unwrap().remove(*s) {
+                root_bank.clear_slot_signatures(*s);
+                root_bank.remove_unrooted_slots(&[(*s, removed_bank.bank_id())]);
+            }
+        }
+        // Replaying the same shreds would most likely fail again, so clear them along with
+        // the dead flag to let repair insert the confirmed version
+        blockstore.clear_unconfirmed_slot(slot);
+        true
     }
 
     fn process_gossip_verified_vote_hashes(
@@ -968,14 +2721,22 @@ impl ReplayStage {
         }
     }
 
-    // Checks for and handle forks with duplicate slots.
+    // Checks for and handle forks with duplicate slots. In `Aggressive` mode, duplicate
+    // slots that the cluster hasn't confirmed our version of are purged right away.
+    // Must not be called while the TPU is building a bank, which may descend from a
+    // purged slot.
+    #[allow(clippy::too_many_arguments)]
     fn process_duplicate_slots(
         duplicate_slots_receiver: &DuplicateSlotReceiver,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
+        ancestors: &mut HashMap<Slot, HashSet<Slot>>,
+        descendants: &mut HashMap<Slot, HashSet<Slot>>,
         bank_forks: &RwLock<BankForks>,
+        blockstore: &Blockstore,
         progress: &mut ProgressMap,
         fork_choice: &mut HeaviestSubtreeForkChoice,
+        duplicate_slot_recovery_mode: DuplicateSlotRecoveryMode,
     ) {
         let new_duplicate_slots: Vec<Slot> = duplicate_slots_receiver.try_iter().collect();
         let (root_slot, bank_hashes) = {
@@ -1001,6 +2762,31 @@ impl ReplayStage {
                 fork_choice,
                 SlotStateUpdate::Duplicate,
             );
+
+            if duplicate_slot_recovery_mode == DuplicateSlotRecoveryMode::Aggressive
+                && duplicate_slot > root_slot
+            {
+                if let Some(bank_hash) = bank_hash {
+                    let is_duplicate_confirmed = gossip_duplicate_confirmed_slots
+                        .get(&duplicate_slot)
+                        .map(|confirmed_hash| *confirmed_hash == bank_hash)
+                        .unwrap_or(false)
+                        || fork_choice
+                            .is_duplicate_confirmed(&(duplicate_slot, bank_hash))
+                            .unwrap_or(false);
+                    if !is_duplicate_confirmed {
+                        Self::purge_unconfirmed_duplicate_slot(
+                            duplicate_slot,
+                            ancestors,
+                            descendants,
+                            progress,
+                            bank_forks,
+                            blockstore,
+                            fork_choice,
+                        );
+                    }
+                }
+            }
         }
     }
 
@@ -1064,6 +2850,33 @@ impl ReplayStage {
         progress_map.is_propagated(parent_slot)
     }
 
+    // Returns the fraction of the stake in `bank` whose vote account voted on one of the
+    // last `MAX_ONLINE_VOTE_AGE_SLOTS` slots up to `bank`
+    fn online_stake_fraction(bank: &Bank) -> f64 {
+        let min_vote_slot = bank.slot().saturating_sub(MAX_ONLINE_VOTE_AGE_SLOTS);
+        let (online_stake, total_stake) = bank.vote_accounts().into_iter().fold(
+            (0, 0),
+            |(online_stake, total_stake), (_, (stake, vote_account))| {
+                let is_online = vote_account
+                    .vote_state()
+                    .as_ref()
+                    .ok()
+                    .and_then(|vote_state| vote_state.last_voted_slot())
+                    .map(|last_voted_slot| last_voted_slot >= min_vote_slot)
+                    .unwrap_or(false);
+                (
+                    online_stake + if is_online { stake } else { 0 },
+                    total_stake + stake,
+                )
+            },
+        );
+        if total_stake == 0 {
+            0.0
+        } else {
+            online_stake as f64 / total_stake as f64
+        }
+    }
+
     fn should_retransmit(poh_slot: Slot, last_retransmit_slot: &mut Slot) -> bool {
         if poh_slot < *last_retransmit_slot
             || poh_slot >= *last_retransmit_slot + NUM_CONSECUTIVE_LEADER_SLOTS
@@ -1085,6 +2898,7 @@ impl ReplayStage {
         retransmit_slots_sender: &RetransmitSlotsSender,
         skipped_slots_info: &mut SkippedSlotsInfo,
         has_new_vote_been_rooted: bool,
+        min_online_stake_to_lead: Option<f64>,
     ) {
         // all the individual calls to poh_recorder.lock() are designed to
         // increase granularity, decrease contention
@@ -1147,7 +2961,7 @@ impl ReplayStage {
             if !Self::check_propagation_for_start_leader(poh_slot, parent_slot, progress_map) {
                 let latest_unconfirmed_leader_slot = progress_map.get_latest_leader_slot(parent_slot)
                     .expect("In order for propagated check to fail, latest leader must exist in progress map");
-                if poh_slot != skipped_slots_info.last_skipped_slot {
+                if skipped_slots_info.record_skipped_slot(poh_slot) {
                     datapoint_info!(
                         "replay_stage-skip_leader_slot",
                         ("slot", poh_slot, i64),
@@ -1159,7 +2973,6 @@ impl ReplayStage {
                         )
                     );
                     progress_map.log_propagated_stats(latest_unconfirmed_leader_slot, bank_forks);
-                    skipped_slots_info.last_skipped_slot = poh_slot;
                 }
                 let bank = bank_forks
                     .read()
@@ -1180,6 +2993,22 @@ impl ReplayStage {
                 return;
             }
 
+            if let Some(min_online_stake_to_lead) = min_online_stake_to_lead {
+                let online_stake = Self::online_stake_fraction(&parent);
+                if online_stake < min_online_stake_to_lead {
+                    if skipped_slots_info.record_skipped_slot(poh_slot) {
+                        datapoint_info!(
+                            "replay_stage-skip_leader_slot_low_online_stake",
+                            ("slot", poh_slot, i64),
+                            ("parent_slot", parent_slot, i64),
+                            ("online_stake", online_stake, f64),
+                            ("min_online_stake", min_online_stake_to_lead, f64),
+                        );
+                    }
+                    return;
+                }
+            }
+
             let root_slot = bank_forks.read().unwrap().root();
             datapoint_info!("replay_stage-my_leader_slot", ("slot", poh_slot, i64),);
             info!(
@@ -1209,6 +3038,8 @@ impl ReplayStage {
         transaction_status_sender: Option<&TransactionStatusSender>,
         replay_vote_sender: &ReplayVoteSender,
         verify_recyclers: &VerifyRecyclers,
+        max_entry_recv_per_iter: Option<usize>,
+        report_fee_payer_failures: bool,
     ) -> result::Result<usize, BlockstoreProcessorError> {
         let tx_count_before = bank_progress.replay_progress.num_txs;
         let confirm_result = blockstore_processor::confirm_slot(
@@ -1217,11 +3048,17 @@ impl ReplayStage {
             &mut bank_progress.replay_stats,
             &mut bank_progress.replay_progress,
             false,
+            None,
+            false,
             transaction_status_sender,
             Some(replay_vote_sender),
             None,
+            None,
+            report_fee_payer_failures,
+            None,
             verify_recyclers,
             false,
+            max_entry_recv_per_iter,
         );
         let tx_count_after = bank_progress.replay_progress.num_txs;
         let tx_count = tx_count_after - tx_count_before;
@@ -1242,6 +3079,7 @@ impl ReplayStage {
         root: Slot,
         err: &BlockstoreProcessorError,
         rpc_subscriptions: &Arc<RpcSubscriptions>,
+        transaction_status_sender: Option<&TransactionStatusSender>,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
         progress: &mut ProgressMap,
@@ -1271,19 +3109,28 @@ impl ReplayStage {
                 ("slot", slot, i64)
             );
         }
-        progress.get_mut(&slot).unwrap().is_dead = true;
+        let slot_progress = progress.get_mut(&slot).unwrap();
+        slot_progress.is_dead = true;
         blockstore
-            .set_dead_slot(slot)
+            .set_dead_slot_with_reason(slot, err.to_string())
             .expect("Failed to mark slot as dead in blockstore");
+        if let Some(transaction_status_sender) = transaction_status_sender {
+            transaction_status_sender.send_dead_slot(
+                slot,
+                err.to_string(),
+                slot_progress.replay_progress.last_entry,
+            );
+        }
         rpc_subscriptions.notify_slot_update(SlotUpdate::Dead {
             slot,
             err: format!("error: {:?}", err),
             timestamp: timestamp(),
         });
+        // Dead slots count as never frozen, even one that froze with a zero hash
         check_slot_agrees_with_cluster(
             slot,
             root,
-            Some(bank.hash()),
+            Some(Hash::default()),
             duplicate_slots_tracker,
             gossip_duplicate_confirmed_slots,
             progress,
@@ -1302,6 +3149,7 @@ impl ReplayStage {
         progress: &mut ProgressMap,
         vote_account_pubkey: &Pubkey,
         identity_keypair: &Keypair,
+        vote_fee_payer_keypair: &Keypair,
         authorized_voter_keypairs: &[Arc<Keypair>],
         cluster_info: &Arc<ClusterInfo>,
         blockstore: &Arc<Blockstore>,
@@ -1316,15 +3164,19 @@ impl ReplayStage {
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         gossip_duplicate_confirmed_slots: &mut GossipDuplicateConfirmedSlots,
         unfrozen_gossip_verified_vote_hashes: &mut UnfrozenGossipVerifiedVoteHashes,
-        vote_signatures: &mut Vec<Signature>,
+        vote_signatures: &mut VecDeque<Signature>,
+        max_vote_signatures: usize,
         has_new_vote_been_rooted: &mut bool,
         replay_timing: &mut ReplayTiming,
+        last_voted_slot_and_hash: &RwLock<Option<(Slot, Hash)>>,
+        voting_disabled: bool,
     ) {
         if bank.is_empty() {
             inc_new_counter_info!("replay_stage-voted_empty_bank", 1);
         }
         trace!("handle votable bank {}", bank.slot());
         let new_root = tower.record_bank_vote(bank, vote_account_pubkey);
+        *last_voted_slot_and_hash.write().unwrap() = tower.last_voted_slot_hash();
 
         if let Err(err) = tower.save(identity_keypair) {
             error!("Unable to save tower: {:?}", err);
@@ -1393,29 +3245,34 @@ impl ReplayStage {
         update_commitment_cache_time.stop();
         replay_timing.update_commitment_cache_us += update_commitment_cache_time.as_us();
 
+        if voting_disabled {
+            return;
+        }
         Self::push_vote(
             cluster_info,
             bank,
             poh_recorder,
             vote_account_pubkey,
-            identity_keypair,
+            vote_fee_payer_keypair,
             authorized_voter_keypairs,
             tower,
             switch_fork_decision,
             vote_signatures,
+            max_vote_signatures,
             *has_new_vote_been_rooted,
             replay_timing,
         );
     }
 
     fn generate_vote_tx(
-        node_keypair: &Keypair,
+        vote_fee_payer_keypair: &Keypair,
         bank: &Bank,
         vote_account_pubkey: &Pubkey,
         authorized_voter_keypairs: &[Arc<Keypair>],
         vote: Vote,
         switch_fork_decision: &SwitchForkDecision,
-        vote_signatures: &mut Vec<Signature>,
+        vote_signatures: &mut VecDeque<Signature>,
+        max_vote_signatures: usize,
         has_new_vote_been_rooted: bool,
     ) -> Option<Transaction> {
         if authorized_voter_keypairs.is_empty() {
@@ -1459,6 +3316,19 @@ impl ReplayStage {
             .find(|keypair| keypair.pubkey() == authorized_voter_pubkey)
         {
             None => {
+                datapoint_warn!(
+                    "replay_stage-authorized_voter_missing",
+                    ("vote_account", vote_account_pubkey.to_string(), String),
+                    (
+                        "authorized_voter",
+                        authorized_voter_pubkey.to_string(),
+                        String
+                    ),
+                    ("epoch", bank.epoch() as i64, i64),
+                );
+                // The vote program only accepts the authorized voter of the epoch of the
+                // bank the vote lands in, so voting with the keypair of the next epoch's
+                // voter ahead of a rotation would only waste fees
                 warn!("The authorized keypair {} for vote account {} is not available.  Unable to vote",
                       authorized_voter_pubkey, vote_account_pubkey);
                 return None;
@@ -1475,16 +3345,17 @@ impl ReplayStage {
             )
             .expect("Switch threshold failure should not lead to voting");
 
-        let mut vote_tx = Transaction::new_with_payer(&[vote_ix], Some(&node_keypair.pubkey()));
+        let mut vote_tx =
+            Transaction::new_with_payer(&[vote_ix], Some(&vote_fee_payer_keypair.pubkey()));
 
         let blockhash = bank.last_blockhash();
-        vote_tx.partial_sign(&[node_keypair], blockhash);
+        vote_tx.partial_sign(&[vote_fee_payer_keypair], blockhash);
         vote_tx.partial_sign(&[authorized_voter_keypair.as_ref()], blockhash);
 
         if !has_new_vote_been_rooted {
-            vote_signatures.push(vote_tx.signatures[0]);
-            if vote_signatures.len() > MAX_VOTE_SIGNATURES {
-                vote_signatures.remove(0);
+            vote_signatures.push_back(vote_tx.signatures[0]);
+            if vote_signatures.len() > max_vote_signatures {
+                vote_signatures.pop_front();
             }
         } else {
             vote_signatures.clear();
@@ -1501,14 +3372,18 @@ impl ReplayStage {
         poh_recorder: &Mutex<PohRecorder>,
         my_latest_landed_vote: Slot,
         vote_account_pubkey: &Pubkey,
-        identity_keypair: &Keypair,
+        vote_fee_payer_keypair: &Keypair,
         authorized_voter_keypairs: &[Arc<Keypair>],
-        vote_signatures: &mut Vec<Signature>,
+        vote_signatures: &mut VecDeque<Signature>,
+        max_vote_signatures: usize,
         has_new_vote_been_rooted: bool,
         last_vote_refresh_time: &mut LastVoteRefreshTime,
+        vote_refresh_interval: Duration,
+        // Taken by the caller rather than read here, so that tests control the clock
+        now: Instant,
     ) {
         let last_voted_slot = tower.last_voted_slot();
-        if last_voted_slot.is_none() {
+        if last_voted_slot.is_none() || vote_refresh_interval == Duration::ZERO {
             return;
         }
 
@@ -1516,9 +3391,12 @@ impl ReplayStage {
         // last attempt at a vote transaction has expired
         let last_voted_slot = last_voted_slot.unwrap();
         if my_latest_landed_vote > last_voted_slot
-            && last_vote_refresh_time.last_print_time.elapsed().as_secs() >= 1
+            && now
+                .saturating_duration_since(last_vote_refresh_time.last_print_time)
+                .as_secs()
+                >= 1
         {
-            last_vote_refresh_time.last_print_time = Instant::now();
+            last_vote_refresh_time.last_print_time = now;
             info!(
                 "Last landed vote for slot {} in bank {} is greater than the current last vote for slot: {} tracked by Tower",
                 my_latest_landed_vote,
@@ -1532,7 +3410,8 @@ impl ReplayStage {
                 .unwrap_or(false)
             // In order to avoid voting on multiple forks all past MAX_PROCESSING_AGE that don't
             // include the last voted blockhash
-            || last_vote_refresh_time.last_refresh_time.elapsed().as_millis() < MAX_VOTE_REFRESH_INTERVAL_MILLIS as u128
+            || now.saturating_duration_since(last_vote_refresh_time.last_refresh_time)
+                < vote_refresh_interval
         {
             return;
         }
@@ -1540,13 +3419,14 @@ impl ReplayStage {
         // TODO: check the timestamp in this vote is correct, i.e. it shouldn't
         // have changed from the original timestamp of the vote.
         let vote_tx = Self::generate_vote_tx(
-            identity_keypair,
+            vote_fee_payer_keypair,
             heaviest_bank_on_same_fork,
             vote_account_pubkey,
             authorized_voter_keypairs,
             tower.last_vote(),
             &SwitchForkDecision::SameFork,
             vote_signatures,
+            max_vote_signatures,
             has_new_vote_been_rooted,
         );
 
@@ -1562,12 +3442,13 @@ impl ReplayStage {
                 ("target_bank_slot", heaviest_bank_on_same_fork.slot(), i64),
                 ("target_bank_hash", hash_string, String),
             );
-            let _ = cluster_info.send_vote(
+            Self::send_vote_to_tpu(
+                cluster_info,
                 &vote_tx,
                 crate::banking_stage::next_leader_tpu(cluster_info, poh_recorder),
             );
             cluster_info.refresh_vote(vote_tx, last_voted_slot);
-            last_vote_refresh_time.last_refresh_time = Instant::now();
+            last_vote_refresh_time.last_refresh_time = now;
         }
     }
 
@@ -1577,23 +3458,25 @@ impl ReplayStage {
         bank: &Bank,
         poh_recorder: &Mutex<PohRecorder>,
         vote_account_pubkey: &Pubkey,
-        identity_keypair: &Keypair,
+        vote_fee_payer_keypair: &Keypair,
         authorized_voter_keypairs: &[Arc<Keypair>],
         tower: &mut Tower,
         switch_fork_decision: &SwitchForkDecision,
-        vote_signatures: &mut Vec<Signature>,
+        vote_signatures: &mut VecDeque<Signature>,
+        max_vote_signatures: usize,
         has_new_vote_been_rooted: bool,
         replay_timing: &mut ReplayTiming,
     ) {
         let mut generate_time = Measure::start("generate_vote");
         let vote_tx = Self::generate_vote_tx(
-            identity_keypair,
+            vote_fee_payer_keypair,
             bank,
             vote_account_pubkey,
             authorized_voter_keypairs,
             tower.last_vote(),
             switch_fork_decision,
             vote_signatures,
+            max_vote_signatures,
             has_new_vote_been_rooted,
         );
         generate_time.stop();
@@ -1601,7 +3484,8 @@ impl ReplayStage {
         if let Some(vote_tx) = vote_tx {
             tower.refresh_last_vote_tx_blockhash(vote_tx.message.recent_blockhash);
             let mut send_time = Measure::start("send_vote");
-            let _ = cluster_info.send_vote(
+            Self::send_vote_to_tpu(
+                cluster_info,
                 &vote_tx,
                 crate::banking_stage::next_leader_tpu(cluster_info, poh_recorder),
             );
@@ -1613,6 +3497,26 @@ impl ReplayStage {
         }
     }
 
+    // Sends `vote_tx` directly to `tpu`, or to our own TPU if the next leader is
+    // unknown. Failures are counted and logged, but are otherwise not fatal
+    // since the vote is still propagated through gossip. Returns whether the
+    // send succeeded.
+    fn send_vote_to_tpu(
+        cluster_info: &ClusterInfo,
+        vote_tx: &Transaction,
+        tpu: Option<SocketAddr>,
+    ) -> bool {
+        let tpu = tpu.unwrap_or_else(|| cluster_info.my_contact_info().tpu);
+        match cluster_info.send_vote(vote_tx, Some(tpu)) {
+            Ok(()) => true,
+            Err(err) => {
+                inc_new_counter_warn!("replay_stage-vote_send_failure", 1);
+                warn!("failed to send vote to tpu {}: {:?}", tpu, err);
+                false
+            }
+        }
+    }
+
     fn update_commitment_cache(
         bank: Arc<Bank>,
         root: Slot,
@@ -1626,6 +3530,46 @@ impl ReplayStage {
         }
     }
 
+    // Notifies slot update subscribers when resetting PoH onto `reset_bank` leaves the
+    // fork PoH was last reset on, returning why the fork was abandoned. Nothing is sent
+    // unless `last_reset` changed, nor when `reset_bank` merely extends the previous fork
+    fn maybe_notify_fork_abandoned(
+        last_reset: &Hash,
+        reset_bank: &Bank,
+        poh_recorder: &Mutex<PohRecorder>,
+        bank_forks: &RwLock<BankForks>,
+        ancestors: &HashMap<Slot, HashSet<Slot>>,
+        heaviest_fork_failures: &[HeaviestForkFailures],
+        rpc_subscriptions: &RpcSubscriptions,
+    ) -> Option<ForkAbandonedReason> {
+        if *last_reset == reset_bank.last_blockhash() {
+            return None;
+        }
+        let abandoned_slot = poh_recorder.lock().unwrap().last_reset_slot();
+        let reset_slot = reset_bank.slot();
+        // Slots at or below the root have either been rooted or pruned, root
+        // notifications already cover those
+        if abandoned_slot <= bank_forks.read().unwrap().root()
+            || abandoned_slot == reset_slot
+            || ancestors
+                .get(&reset_slot)
+                .map(|ancestors| ancestors.contains(&abandoned_slot))
+                .unwrap_or(true)
+        {
+            return None;
+        }
+        let reason = if heaviest_fork_failures
+            .iter()
+            .any(|failure| matches!(failure, HeaviestForkFailures::FailedSwitchThreshold(..)))
+        {
+            ForkAbandonedReason::SwitchThresholdFailure
+        } else {
+            ForkAbandonedReason::HeaviestForkChanged
+        };
+        rpc_subscriptions.notify_fork_abandoned(abandoned_slot, reset_slot, reason);
+        Some(reason)
+    }
+
     fn reset_poh_recorder(
         my_pubkey: &Pubkey,
         blockstore: &Blockstore,
@@ -1660,6 +3604,246 @@ impl ReplayStage {
         );
     }
 
+    /// Replays the given banks from the blockstore. Banks with distinct parents are
+    /// independent of one another and are replayed in parallel on `replay_thread_pool`,
+    /// while banks sharing a parent are replayed one after the other. The replay always
+    /// runs inside `replay_thread_pool`, so the parallel work nested in it runs on that
+    /// same pool rather than on pools of its own. The progress entries of the banks are
+    /// taken out of `progress` for the duration of the replay and put back before
+    /// returning.
+    #[allow(clippy::too_many_arguments)]
+    fn replay_banks_in_parallel<'a>(
+        banks: impl Iterator<Item = &'a Arc<Bank>>,
+        blockstore: &Blockstore,
+        progress: &mut ProgressMap,
+        transaction_status_sender: Option<&TransactionStatusSender>,
+        replay_vote_sender: &ReplayVoteSender,
+        verify_recyclers: &VerifyRecyclers,
+        max_entry_recv_per_iter: Option<usize>,
+        report_fee_payer_failures: bool,
+        replay_thread_pool: &ThreadPool,
+    ) -> HashMap<Slot, result::Result<usize, BlockstoreProcessorError>> {
+        // Group the banks by parent, keeping the order the banks were given in so that
+        // higher priority forks are handed to the thread pool first
+        let mut banks_by_parent: Vec<Vec<(Arc<Bank>, ForkProgress)>> = vec![];
+        let mut parent_indexes: HashMap<Slot, usize> = HashMap::new();
+        for bank in banks {
+            let bank_progress = progress
+                .remove(&bank.slot())
+                .expect("active bank must exist in progress map");
+            let index = *parent_indexes.entry(bank.parent_slot()).or_insert_with(|| {
+                banks_by_parent.push(vec![]);
+                banks_by_parent.len() - 1
+            });
+            banks_by_parent[index].push((bank.clone(), bank_progress));
+        }
+
+        let replay_banks = |banks: Vec<(Arc<Bank>, ForkProgress)>| {
+            banks
+                .into_iter()
+                .map(|(bank, mut bank_progress)| {
+                    let replay_result = Self::replay_blockstore_into_bank(
+                        &bank,
+                        blockstore,
+                        &mut bank_progress,
+                        transaction_status_sender,
+                        replay_vote_sender,
+                        verify_recyclers,
+                        max_entry_recv_per_iter,
+                        report_fee_payer_failures,
+                    );
+                    (bank.slot(), bank_progress, replay_result)
+                })
+                .collect::<Vec<_>>()
+        };
+        let replayed: Vec<_> = replay_thread_pool.install(|| {
+            banks_by_parent
+                .into_par_iter()
+                .flat_map(replay_banks)
+                .collect()
+        });
+
+        replayed
+            .into_iter()
+            .map(|(slot, bank_progress, replay_result)| {
+                progress.insert(slot, bank_progress);
+                (slot, replay_result)
+            })
+            .collect()
+    }
+
+    // Orders the active banks so that the forks with the most stake voted on their parent's
+    // subtree are replayed first, letting voting resume sooner while catching up. Banks
+    // whose parent isn't in fork choice yet come last, and ties are broken by slot. Since
+    // a parent's subtree stake is at least that of any of its children, and an unfrozen
+    // parent isn't in fork choice, a bank never comes before its parent
+    fn sort_banks_by_replay_priority(
+        banks: &mut Vec<Arc<Bank>>,
+        heaviest_subtree_fork_choice: &HeaviestSubtreeForkChoice,
+    ) {
+        banks.sort_by_cached_key(|bank| {
+            let parent_stake = heaviest_subtree_fork_choice
+                .stake_voted_subtree(&(bank.parent_slot(), bank.parent_hash()));
+            (std::cmp::Reverse(parent_stake), bank.slot())
+        });
+    }
+
+    fn report_slot_age_histogram(progress: &ProgressMap, root: Slot) {
+        let histogram = progress.slot_age_histogram(root);
+        datapoint_info!(
+            "replay-slot-age-histogram",
+            ("root", root as i64, i64),
+            ("age_0_10", histogram[&0] as i64, i64),
+            ("age_10_100", histogram[&10] as i64, i64),
+            ("age_100_1000", histogram[&100] as i64, i64),
+            ("age_1000_plus", histogram[&1000] as i64, i64),
+        );
+    }
+
+    // Marks the slots requested through `external_dead_slot_receiver` dead, as if they had
+    // failed to replay. A frozen bank is also purged along with its descendants, see
+    // `purge_externally_dead_bank()`
+    #[allow(clippy::too_many_arguments)]
+    fn process_external_dead_slots(
+        external_dead_slot_receiver: &Receiver<(Slot, String)>,
+        blockstore: &Blockstore,
+        bank_forks: &RwLock<BankForks>,
+        ancestry: &mut AncestryCache,
+        rpc_subscriptions: &Arc<RpcSubscriptions>,
+        transaction_status_sender: Option<&TransactionStatusSender>,
+        duplicate_slots_tracker: &mut DuplicateSlotsTracker,
+        gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
+        progress: &mut ProgressMap,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) {
+        let root = bank_forks.read().unwrap().root();
+        for (slot, reason) in external_dead_slot_receiver.try_iter() {
+            if slot <= root || blockstore.is_root(slot) {
+                warn!(
+                    "ignoring request to mark rooted slot {} dead: {}",
+                    slot, reason
+                );
+                continue;
+            }
+            let bank = bank_forks.read().unwrap().get(slot).cloned();
+            let bank = match bank {
+                Some(bank) if progress.get(&slot).is_some() => bank,
+                _ => {
+                    warn!(
+                        "ignoring request to mark slot {} dead, it hasn't been replayed: {}",
+                        slot, reason
+                    );
+                    continue;
+                }
+            };
+            if progress.is_dead(slot).unwrap_or(false) {
+                continue;
+            }
+            info!("marking slot {} dead on request: {}", slot, reason);
+            Self::mark_dead_slot(
+                blockstore,
+                &bank,
+                root,
+                &BlockstoreProcessorError::ExternallyMarkedDead(reason),
+                rpc_subscriptions,
+                transaction_status_sender,
+                duplicate_slots_tracker,
+                gossip_duplicate_confirmed_slots,
+                progress,
+                heaviest_subtree_fork_choice,
+            );
+            if bank.is_frozen() {
+                Self::purge_externally_dead_bank(
+                    &bank,
+                    ancestry,
+                    progress,
+                    bank_forks,
+                    heaviest_subtree_fork_choice,
+                );
+            }
+        }
+    }
+
+    // Drops the frozen bank of a slot marked dead, along with its descendants, so that no
+    // new children are built on it. The dead slot keeps its progress entry, its bank is
+    // then recreated unfrozen by `generate_new_bank_forks()` and skipped as dead
+    fn purge_externally_dead_bank(
+        bank: &Bank,
+        ancestry: &mut AncestryCache,
+        progress: &mut ProgressMap,
+        bank_forks: &RwLock<BankForks>,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+    ) {
+        let slot = bank.slot();
+        let root_bank = bank_forks.read().unwrap().root_bank();
+        let slot_descendants = ancestry.purge(slot);
+        heaviest_subtree_fork_choice.purge_slot_and_descendants(&(slot, bank.hash()));
+        for s in slot_descendants.iter().chain(std::iter::once(&slot)) {
+            if *s != slot {
+                let _ = progress.remove(s);
+            }
+            // Clear the signatures and accounts written by the removed bank, so they
+            // don't conflict with another version of the slot
+            if let Some(removed_bank) = bank_forks.write().unwrap().remove(*s) {
+                root_bank.clear_slot_signatures(*s);
+                root_bank.remove_unrooted_slots(&[(*s, removed_bank.bank_id())]);
+            }
+        }
+    }
+
+    // A bug somewhere can produce a bank frozen with a default `bank_hash`. Rather than
+    // panic the whole validator, mark its slot dead like any other one that failed to
+    // replay, and drop the frozen bank so that no children are built on it. Returns
+    // whether the slot was marked dead
+    #[allow(clippy::too_many_arguments)]
+    fn mark_dead_zero_bank_hash(
+        bank_hash: Hash,
+        bank: &Bank,
+        my_pubkey: &Pubkey,
+        root: Slot,
+        blockstore: &Blockstore,
+        rpc_subscriptions: &Arc<RpcSubscriptions>,
+        transaction_status_sender: Option<&TransactionStatusSender>,
+        duplicate_slots_tracker: &mut DuplicateSlotsTracker,
+        gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
+        progress: &mut ProgressMap,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+        leader_replay_stats: &RwLock<LeaderReplayStats>,
+        ancestry: &mut AncestryCache,
+        bank_forks: &RwLock<BankForks>,
+    ) -> bool {
+        if bank_hash != Hash::default() {
+            return false;
+        }
+        if bank.collector_id() != my_pubkey {
+            leader_replay_stats
+                .write()
+                .unwrap()
+                .record_dead(bank.collector_id(), bank.epoch());
+        }
+        Self::mark_dead_slot(
+            blockstore,
+            bank,
+            root,
+            &BlockstoreProcessorError::InvalidBlock(BlockError::ZeroBankHash),
+            rpc_subscriptions,
+            transaction_status_sender,
+            duplicate_slots_tracker,
+            gossip_duplicate_confirmed_slots,
+            progress,
+            heaviest_subtree_fork_choice,
+        );
+        // Dead slots are otherwise never frozen
+        Self::purge_externally_dead_bank(
+            bank,
+            ancestry,
+            progress,
+            bank_forks,
+            heaviest_subtree_fork_choice,
+        );
+        true
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn replay_active_banks(
         blockstore: &Blockstore,
@@ -1679,16 +3863,46 @@ impl ReplayStage {
         gossip_duplicate_confirmed_slots: &GossipDuplicateConfirmedSlots,
         unfrozen_gossip_verified_vote_hashes: &mut UnfrozenGossipVerifiedVoteHashes,
         latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
-        cluster_slots_update_sender: &ClusterSlotsUpdateSender,
-        cost_update_sender: &Sender<ExecuteTimings>,
-    ) -> bool {
-        let mut did_complete_bank = false;
+        cluster_slots_update_sender: &SenderWithBackpressure<Vec<Slot>>,
+        cost_update_sender: &SenderWithBackpressure<SlotExecuteTimings>,
+        max_entry_recv_per_iter: Option<usize>,
+        report_fee_payer_failures: bool,
+        replay_timing: &mut ReplayTiming,
+        paused_slots: &HashSet<Slot>,
+        logged_paused_slots: &mut HashSet<Slot>,
+        superminority_threshold: f64,
+        replay_thread_pool: &ThreadPool,
+        replay_duration_histogram: &ReplayDurationHistogram,
+        leader_replay_stats: &RwLock<LeaderReplayStats>,
+        leader_schedule_cache: &LeaderScheduleCache,
+        ancestry: &mut AncestryCache,
+    ) -> ReplayActiveBanksOutcome {
+        let mut outcome = ReplayActiveBanksOutcome::default();
         let mut tx_count = 0;
-        let mut execute_timings = ExecuteTimings::default();
-        let active_banks = bank_forks.read().unwrap().active_banks();
+        let now = timestamp();
+        if now.saturating_sub(replay_timing.last_slot_age_histogram_report) >= 1000 {
+            replay_timing.last_slot_age_histogram_report = now;
+            Self::report_slot_age_histogram(progress, bank_forks.read().unwrap().root());
+        }
+        let mut active_banks = bank_forks.read().unwrap().active_banks();
+        active_banks.sort_unstable();
         trace!("active banks {:?}", active_banks);
 
+        // Forget resumed slots so that pausing them again is logged again
+        logged_paused_slots.retain(|slot| paused_slots.contains(slot));
+        let mut banks_to_process = Vec::with_capacity(active_banks.len());
+        let mut replay_elapsed_before = HashMap::with_capacity(active_banks.len());
+        let mut num_entries_before = HashMap::with_capacity(active_banks.len());
         for bank_slot in &active_banks {
+            // If the slot was paused by the operator, leave it unreplayed and unfrozen
+            // without marking it dead, so that replay can pick up where it left off
+            if paused_slots.contains(bank_slot) {
+                if logged_paused_slots.insert(*bank_slot) {
+                    info!("bank_slot {} is paused, skipping replay", bank_slot);
+                }
+                continue;
+            }
+
             // If the fork was marked as dead, don't replay it
             if progress.get(bank_slot).map(|p| p.is_dead).unwrap_or(false) {
                 debug!("bank_slot {:?} is marked dead", *bank_slot);
@@ -1712,7 +3926,7 @@ impl ReplayStage {
             // Insert a progress entry even for slots this node is the leader for, so that
             // 1) confirm_forks can report confirmation, 2) we can cache computations about
             // this bank in `select_forks()`
-            let bank_progress = &mut progress.entry(bank.slot()).or_insert_with(|| {
+            let bank_progress = progress.entry(bank.slot()).or_insert_with(|| {
                 ForkProgress::new_from_bank(
                     &bank,
                     my_pubkey,
@@ -1720,22 +3934,57 @@ impl ReplayStage {
                     prev_leader_slot,
                     num_blocks_on_fork,
                     num_dropped_blocks_on_fork,
+                    superminority_threshold,
                 )
             });
-            if bank.collector_id() != my_pubkey {
-                let root_slot = bank_forks.read().unwrap().root();
-                let replay_result = Self::replay_blockstore_into_bank(
-                    &bank,
-                    blockstore,
-                    bank_progress,
-                    transaction_status_sender,
-                    replay_vote_sender,
-                    verify_recyclers,
-                );
-                execute_timings.accumulate(&bank_progress.replay_stats.execute_timings);
+            replay_elapsed_before.insert(bank.slot(), bank_progress.replay_stats.replay_elapsed);
+            num_entries_before.insert(bank.slot(), bank_progress.replay_progress.num_entries);
+            banks_to_process.push(bank);
+        }
+        Self::sort_banks_by_replay_priority(&mut banks_to_process, heaviest_subtree_fork_choice);
+
+        let mut replay_results = Self::replay_banks_in_parallel(
+            banks_to_process
+                .iter()
+                .filter(|bank| bank.collector_id() != my_pubkey),
+            blockstore,
+            progress,
+            transaction_status_sender,
+            replay_vote_sender,
+            verify_recyclers,
+            max_entry_recv_per_iter,
+            report_fee_payer_failures,
+            replay_thread_pool,
+        );
+
+        // Apply the replay results in slot order, so that metrics, dead slot handling
+        // and fork choice updates are independent of the order replay finished in
+        banks_to_process.sort_by_key(|bank| bank.slot());
+        let root_slot = bank_forks.read().unwrap().root();
+        for bank in banks_to_process {
+            let bank_slot = &bank.slot();
+            if let Some(replay_result) = replay_results.remove(bank_slot) {
+                let bank_progress = progress.get(bank_slot).unwrap();
+                let replay_elapsed =
+                    bank_progress.replay_stats.replay_elapsed - replay_elapsed_before[bank_slot];
+                outcome.did_replay_entries |=
+                    bank_progress.replay_progress.num_entries > num_entries_before[bank_slot];
                 match replay_result {
-                    Ok(replay_tx_count) => tx_count += replay_tx_count,
+                    Ok(replay_tx_count) => {
+                        tx_count += replay_tx_count;
+                        replay_timing.record_bank_replay(
+                            *bank_slot,
+                            replay_elapsed,
+                            replay_tx_count,
+                        );
+                    }
                     Err(err) => {
+                        if bank.collector_id() != my_pubkey {
+                            leader_replay_stats
+                                .write()
+                                .unwrap()
+                                .record_dead(bank.collector_id(), bank.epoch());
+                        }
                         // Error means the slot needs to be marked as dead
                         Self::mark_dead_slot(
                             blockstore,
@@ -1743,6 +3992,7 @@ impl ReplayStage {
                             root_slot,
                             &err,
                             rpc_subscriptions,
+                            transaction_status_sender,
                             duplicate_slots_tracker,
                             gossip_duplicate_confirmed_slots,
                             progress,
@@ -1754,22 +4004,60 @@ impl ReplayStage {
                     }
                 }
             }
-            assert_eq!(*bank_slot, bank.slot());
+            let bank_progress = progress.get_mut(bank_slot).unwrap();
             if bank.is_complete() {
                 bank_progress.replay_stats.report_stats(
                     bank.slot(),
                     bank_progress.replay_progress.num_entries,
                     bank_progress.replay_progress.num_shreds,
                 );
-                did_complete_bank = true;
+                bank.freeze();
+                if Self::mark_dead_zero_bank_hash(
+                    bank.hash(),
+                    &bank,
+                    my_pubkey,
+                    root_slot,
+                    blockstore,
+                    rpc_subscriptions,
+                    transaction_status_sender,
+                    duplicate_slots_tracker,
+                    gossip_duplicate_confirmed_slots,
+                    progress,
+                    heaviest_subtree_fork_choice,
+                    leader_replay_stats,
+                    ancestry,
+                    bank_forks,
+                ) {
+                    continue;
+                }
+                let bank_progress = progress.get(bank_slot).unwrap();
+                replay_timing.record_completed_slot(
+                    bank.slot(),
+                    &bank_progress.replay_stats,
+                    bank_progress.replay_progress.num_entries,
+                    bank_progress.replay_progress.num_shreds,
+                );
+                let replay_ms = bank_progress.replay_stats.started.elapsed().as_millis() as u64;
+                replay_duration_histogram.record(replay_ms);
+                outcome.did_complete_bank = true;
                 info!("bank frozen: {}", bank.slot());
-                let _ = cluster_slots_update_sender.send(vec![*bank_slot]);
+                cluster_slots_update_sender.send(vec![*bank_slot]);
                 if let Some(transaction_status_sender) = transaction_status_sender {
-                    transaction_status_sender.send_transaction_status_freeze_message(&bank);
+                    transaction_status_sender.send_transaction_status_frozen_message(
+                        &bank,
+                        &bank_progress.replay_progress,
+                        &bank_progress.replay_stats,
+                    );
+                }
+                if bank.collector_id() != my_pubkey {
+                    Self::record_leader_replay_stats(
+                        &bank,
+                        &bank_progress.replay_progress,
+                        replay_ms,
+                        leader_schedule_cache,
+                        &mut leader_replay_stats.write().unwrap(),
+                    );
                 }
-                bank.freeze();
-                let bank_hash = bank.hash();
-                assert_ne!(bank_hash, Hash::default());
                 // Needs to be updated before `check_slot_agrees_with_cluster()` so that
                 // any updates in `check_slot_agrees_with_cluster()` on fork choice take
                 // effect
@@ -1794,6 +4082,22 @@ impl ReplayStage {
                 }
                 blockstore_processor::cache_block_meta(&bank, cache_block_meta_sender);
 
+                // Send the execute timings of the whole slot to cost_update_service, so
+                // that costs can be attributed to the slot and its leader
+                let mut execute_timings = ExecuteTimings::default();
+                execute_timings.accumulate(
+                    &progress
+                        .get(bank_slot)
+                        .unwrap()
+                        .replay_stats
+                        .execute_timings,
+                );
+                cost_update_sender.send(SlotExecuteTimings {
+                    slot: *bank_slot,
+                    collector_id: *bank.collector_id(),
+                    execute_timings,
+                });
+
                 let bank_hash = bank.hash();
                 if let Some(new_frozen_voters) =
                     unfrozen_gossip_verified_vote_hashes.remove_slot_hash(bank.slot(), &bank_hash)
@@ -1818,13 +4122,36 @@ impl ReplayStage {
             }
         }
 
-        // send accumulated excute-timings to cost_update_service
-        cost_update_sender
-            .send(execute_timings)
-            .unwrap_or_else(|err| warn!("cost_update_sender failed: {:?}", err));
-
+        leader_replay_stats.write().unwrap().maybe_report();
         inc_new_counter_info!("replay_stage-replay_transactions", tx_count);
-        did_complete_bank
+        outcome
+    }
+
+    // Records the replay of the just frozen `bank` in the stats of its leader, and the
+    // leader slots skipped between it and its parent in the stats of theirs
+    fn record_leader_replay_stats(
+        bank: &Bank,
+        replay_progress: &ConfirmationProgress,
+        replay_ms: u64,
+        leader_schedule_cache: &LeaderScheduleCache,
+        leader_replay_stats: &mut LeaderReplayStats,
+    ) {
+        leader_replay_stats.record_replayed(
+            bank.collector_id(),
+            bank.epoch(),
+            replay_ms,
+            replay_progress.num_entries as u64,
+            replay_progress.num_txs as u64,
+        );
+        for skipped_slot in bank.parent_slot() + 1..bank.slot() {
+            if let Some(leader) = leader_schedule_cache.slot_leader_at(skipped_slot, Some(bank)) {
+                leader_replay_stats.record_dropped(
+                    &leader,
+                    bank.epoch_schedule().get_epoch(skipped_slot),
+                    skipped_slot,
+                );
+            }
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -1839,8 +4166,63 @@ impl ReplayStage {
         bank_forks: &RwLock<BankForks>,
         heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
         latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
+        superminority_threshold: f64,
+        replay_thread_pool: &ThreadPool,
     ) -> Vec<Slot> {
         frozen_banks.sort_by_key(|bank| bank.slot());
+
+        // Collecting the vote lockouts of each newly frozen bank only reads shared state, so
+        // it's done for all of them in parallel up front on `replay_thread_pool`. The votes
+        // that landed in each bank are gathered separately and added below in slot order, as
+        // if done serially
+        let uncomputed_banks: Vec<&Arc<Bank>> = frozen_banks
+            .iter()
+            .filter(|bank| {
+                !progress
+                    .get_fork_stats(bank.slot())
+                    .expect("All frozen banks must exist in the Progress map")
+                    .computed
+            })
+            .collect();
+        // The hashes of these banks are only recorded in `progress` below, but votes for
+        // them in later banks must still be attributed to them
+        let uncomputed_bank_hashes: HashMap<Slot, Hash> = uncomputed_banks
+            .iter()
+            .map(|bank| (bank.slot(), bank.hash()))
+            .collect();
+        let collect_vote_lockouts = |bank: &Arc<Bank>| {
+            let mut landed_votes = LatestValidatorVotesForFrozenBanks::default();
+            let computed_bank_state = Tower::collect_vote_lockouts(
+                my_vote_pubkey,
+                bank.slot(),
+                bank.vote_accounts().into_iter(),
+                ancestors,
+                |slot| {
+                    progress
+                        .get_hash(slot)
+                        .or_else(|| uncomputed_bank_hashes.get(&slot).copied())
+                },
+                &mut landed_votes,
+            );
+            (
+                bank.slot(),
+                (computed_bank_state, landed_votes.take_votes_dirty_set(0)),
+            )
+        };
+        let mut computed_bank_states: HashMap<Slot, _> = if uncomputed_banks.len() > 1 {
+            replay_thread_pool.install(|| {
+                uncomputed_banks
+                    .into_par_iter()
+                    .map(collect_vote_lockouts)
+                    .collect()
+            })
+        } else {
+            uncomputed_banks
+                .into_iter()
+                .map(collect_vote_lockouts)
+                .collect()
+        };
+
         let mut new_stats = vec![];
         for bank in frozen_banks {
             let bank_slot = bank.slot();
@@ -1848,19 +4230,17 @@ impl ReplayStage {
             // is if this node was the leader for this slot as those banks
             // are not replayed in replay_active_banks()
             {
-                let is_computed = progress
-                    .get_fork_stats_mut(bank_slot)
-                    .expect("All frozen banks must exist in the Progress map")
-                    .computed;
-                if !is_computed {
-                    let computed_bank_state = Tower::collect_vote_lockouts(
-                        my_vote_pubkey,
-                        bank_slot,
-                        bank.vote_accounts().into_iter(),
-                        ancestors,
-                        |slot| progress.get_hash(slot),
-                        latest_validator_votes_for_frozen_banks,
-                    );
+                if let Some((computed_bank_state, landed_votes)) =
+                    computed_bank_states.remove(&bank_slot)
+                {
+                    for (vote_pubkey, (vote_slot, frozen_hash)) in landed_votes {
+                        latest_validator_votes_for_frozen_banks.check_add_vote(
+                            vote_pubkey,
+                            vote_slot,
+                            Some(frozen_hash),
+                            true,
+                        );
+                    }
                     // Notify any listeners of the votes found in this newly computed
                     // bank
                     heaviest_subtree_fork_choice.compute_bank_stats(
@@ -1909,6 +4289,7 @@ impl ReplayStage {
                 bank_forks,
                 vote_tracker,
                 cluster_slots,
+                superminority_threshold,
             );
 
             let stats = progress
@@ -1935,6 +4316,7 @@ impl ReplayStage {
         bank_forks: &RwLock<BankForks>,
         vote_tracker: &VoteTracker,
         cluster_slots: &ClusterSlots,
+        superminority_threshold: f64,
     ) {
         // If propagation has already been confirmed, return
         if progress.is_propagated(slot) {
@@ -1987,6 +4369,7 @@ impl ReplayStage {
             cluster_slot_pubkeys,
             slot,
             bank_forks,
+            superminority_threshold,
         );
     }
 
@@ -2003,6 +4386,7 @@ impl ReplayStage {
         tower: &mut Tower,
         latest_validator_votes_for_frozen_banks: &LatestValidatorVotesForFrozenBanks,
         fork_choice: &HeaviestSubtreeForkChoice,
+        epoch_stakes_cache: &mut EpochStakesCache,
     ) -> SelectVoteAndResetForkResult {
         // Try to vote on the actual heaviest fork. If the heaviest bank is
         // locked out or fails the threshold check, the validator will:
@@ -2018,22 +4402,21 @@ impl ReplayStage {
         // 3) The best "selected" bank is on a different fork,
         //    switch_threshold succeeds
         let mut failure_reasons = vec![];
+        let epoch_stakes = epoch_stakes_cache.epoch_stakes(heaviest_bank, fork_choice.root().0);
         let selected_fork = {
             let switch_fork_decision = tower.check_switch_threshold(
                 heaviest_bank.slot(),
                 ancestors,
                 descendants,
                 progress,
-                heaviest_bank.total_epoch_stake(),
-                heaviest_bank
-                    .epoch_vote_accounts(heaviest_bank.epoch())
-                    .expect("Bank epoch vote accounts must contain entry for the bank's own epoch"),
+                epoch_stakes.total_stake(),
+                Stakes::vote_accounts(epoch_stakes.stakes()),
                 latest_validator_votes_for_frozen_banks,
                 fork_choice,
             );
 
             match switch_fork_decision {
-                SwitchForkDecision::FailedSwitchThreshold(_, _) => {
+                SwitchForkDecision::FailedSwitchThreshold(switch_proof_stake, total_stake) => {
                     let reset_bank = heaviest_bank_on_same_voted_fork;
                     // If we can't switch and our last vote was on a non-duplicate/confirmed slot, then
                     // reset to the the next votable bank on the same fork as our last vote,
@@ -2063,6 +4446,8 @@ impl ReplayStage {
                     );
                     failure_reasons.push(HeaviestForkFailures::FailedSwitchThreshold(
                         heaviest_bank.slot(),
+                        switch_proof_stake,
+                        total_stake,
                     ));
                     reset_bank.map(|b| (b, switch_fork_decision))
                 }
@@ -2109,8 +4494,12 @@ impl ReplayStage {
                         reset_bank.as_ref().map(|b| b.slot()),
                         latest_duplicate_ancestor,
                     );
+                    // The switch proof isn't computed when rolling back from a duplicate
+                    // ancestor, so no stake is reported as having switched
                     failure_reasons.push(HeaviestForkFailures::FailedSwitchThreshold(
                         heaviest_bank.slot(),
+                        0,
+                        epoch_stakes.total_stake(),
                     ));
                     reset_bank.map(|b| (b, switch_fork_decision))
                 }
@@ -2175,6 +4564,7 @@ impl ReplayStage {
         mut cluster_slot_pubkeys: Vec<Pubkey>,
         fork_tip: Slot,
         bank_forks: &RwLock<BankForks>,
+        superminority_threshold: f64,
     ) {
         let mut current_leader_slot = progress.get_latest_leader_slot(fork_tip);
         let mut did_newly_reach_threshold = false;
@@ -2221,6 +4611,7 @@ impl ReplayStage {
                 &leader_bank,
                 leader_propagated_stats,
                 did_newly_reach_threshold,
+                superminority_threshold,
             ) || did_newly_reach_threshold;
 
             // Now jump to process the previous leader slot
@@ -2234,6 +4625,7 @@ impl ReplayStage {
         leader_bank: &Bank,
         leader_propagated_stats: &mut PropagatedStats,
         did_child_reach_threshold: bool,
+        superminority_threshold: f64,
     ) -> bool {
         // Track whether this slot newly confirm propagation
         // throughout the network (switched from is_propagated == false
@@ -2285,7 +4677,7 @@ impl ReplayStage {
         if leader_propagated_stats.total_epoch_stake == 0
             || leader_propagated_stats.propagated_validators_stake as f64
                 / leader_propagated_stats.total_epoch_stake as f64
-                > SUPERMINORITY_THRESHOLD
+                > superminority_threshold
         {
             leader_propagated_stats.is_propagated = true;
             did_newly_reach_threshold = true
@@ -2294,33 +4686,26 @@ impl ReplayStage {
         did_newly_reach_threshold
     }
 
+    // `confirmed_forks` are the `(slot, bank_hash)` of newly confirmed slots, as visited by
+    // `for_each_confirmed_fork()`
     fn mark_slots_confirmed(
-        confirmed_forks: &[Slot],
-        bank_forks: &RwLock<BankForks>,
+        confirmed_forks: impl IntoIterator<Item = (Slot, Hash)>,
+        root_slot: Slot,
         progress: &mut ProgressMap,
         duplicate_slots_tracker: &mut DuplicateSlotsTracker,
         fork_choice: &mut HeaviestSubtreeForkChoice,
     ) {
-        let (root_slot, bank_hashes) = {
-            let r_bank_forks = bank_forks.read().unwrap();
-            let bank_hashes: Vec<Option<Hash>> = confirmed_forks
-                .iter()
-                .map(|slot| r_bank_forks.get(*slot).map(|bank| bank.hash()))
-                .collect();
-
-            (r_bank_forks.root(), bank_hashes)
-        };
-        for (slot, bank_hash) in confirmed_forks.iter().zip(bank_hashes.into_iter()) {
-            // This case should be guaranteed as false by confirm_forks()
-            if let Some(false) = progress.is_supermajority_confirmed(*slot) {
+        for (slot, bank_hash) in confirmed_forks {
+            // This case should be guaranteed as false by for_each_confirmed_fork()
+            if let Some(false) = progress.is_supermajority_confirmed(slot) {
                 // Because supermajority confirmation will iterate through and update the
                 // subtree in fork choice, only incur this cost if the slot wasn't already
                 // confirmed
-                progress.set_supermajority_confirmed_slot(*slot);
+                progress.set_supermajority_confirmed_slot(slot);
                 check_slot_agrees_with_cluster(
-                    *slot,
+                    slot,
                     root_slot,
-                    bank_hash,
+                    Some(bank_hash),
                     duplicate_slots_tracker,
                     // Don't need to pass the gossip confirmed slots since `slot`
                     // is already marked as confirmed in progress
@@ -2333,6 +4718,7 @@ impl ReplayStage {
         }
     }
 
+    #[cfg(test)]
     fn confirm_forks(
         tower: &Tower,
         voted_stakes: &VotedStakes,
@@ -2341,19 +4727,39 @@ impl ReplayStage {
         bank_forks: &RwLock<BankForks>,
     ) -> Vec<Slot> {
         let mut confirmed_forks = vec![];
+        Self::for_each_confirmed_fork(
+            tower,
+            voted_stakes,
+            total_stake,
+            progress,
+            &bank_forks.read().unwrap(),
+            |slot, _| confirmed_forks.push(slot),
+        );
+        confirmed_forks
+    }
+
+    // Calls `visit` with the slot and bank hash of each frozen bank in `progress` that
+    // `voted_stakes` confirms, and that isn't already marked as supermajority confirmed
+    fn for_each_confirmed_fork<F>(
+        tower: &Tower,
+        voted_stakes: &VotedStakes,
+        total_stake: Stake,
+        progress: &ProgressMap,
+        bank_forks: &BankForks,
+        mut visit: F,
+    ) where
+        F: FnMut(Slot, Hash),
+    {
         for (slot, prog) in progress.iter() {
             if !prog.fork_stats.is_supermajority_confirmed {
                 let bank = bank_forks
-                    .read()
-                    .unwrap()
                     .get(*slot)
-                    .expect("bank in progress must exist in BankForks")
-                    .clone();
+                    .expect("bank in progress must exist in BankForks");
                 let duration = prog.replay_stats.started.elapsed().as_millis();
                 if bank.is_frozen() && tower.is_slot_confirmed(*slot, voted_stakes, total_stake) {
                     info!("validator fork confirmed {} {}ms", *slot, duration);
                     datapoint_info!("validator-confirmation", ("duration_ms", duration, i64));
-                    confirmed_forks.push(*slot);
+                    visit(*slot, bank.hash());
                 } else {
                     debug!(
                         "validator fork not confirmed {} {}ms {:?}",
@@ -2364,7 +4770,6 @@ impl ReplayStage {
                 }
             }
         }
-        confirmed_forks
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -2379,7 +4784,7 @@ impl ReplayStage {
         gossip_duplicate_confirmed_slots: &mut GossipDuplicateConfirmedSlots,
         unfrozen_gossip_verified_vote_hashes: &mut UnfrozenGossipVerifiedVoteHashes,
         has_new_vote_been_rooted: &mut bool,
-        voted_signatures: &mut Vec<Signature>,
+        voted_signatures: &mut VecDeque<Signature>,
     ) {
         bank_forks.write().unwrap().set_root(
             new_root,
@@ -2401,9 +4806,7 @@ impl ReplayStage {
         }
         progress.handle_new_root(&r_bank_forks);
         heaviest_subtree_fork_choice.set_root((new_root, r_bank_forks.root_bank().hash()));
-        let mut slots_ge_root = duplicate_slots_tracker.split_off(&new_root);
-        // duplicate_slots_tracker now only contains entries >= `new_root`
-        std::mem::swap(duplicate_slots_tracker, &mut slots_ge_root);
+        duplicate_slots_tracker.set_root(new_root);
 
         let mut slots_ge_root = gossip_duplicate_confirmed_slots.split_off(&new_root);
         // gossip_confirmed_slots now only contains entries >= `new_root`
@@ -2412,12 +4815,17 @@ impl ReplayStage {
         unfrozen_gossip_verified_vote_hashes.set_root(new_root);
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn generate_new_bank_forks(
         blockstore: &Blockstore,
         bank_forks: &RwLock<BankForks>,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
         rpc_subscriptions: &Arc<RpcSubscriptions>,
         progress: &mut ProgressMap,
+        heaviest_subtree_fork_choice: &HeaviestSubtreeForkChoice,
+        superminority_threshold: f64,
+        max_concurrent_forks: Option<usize>,
+        logged_dropped_slots: &mut HashSet<Slot>,
     ) {
         // Find the next slot that chains to the old slot
         let forks = bank_forks.read().unwrap();
@@ -2436,52 +4844,133 @@ impl ReplayStage {
             next_slots.sort();
             next_slots
         });
-        let mut new_banks = HashMap::new();
+        let mut new_bank_slots = HashSet::new();
+        let mut new_bank_parents = vec![];
         for (parent_slot, children) in next_slots {
             let parent_bank = frozen_banks
                 .get(&parent_slot)
-                .expect("missing parent in bank forks")
-                .clone();
+                .expect("missing parent in bank forks");
             for child_slot in children {
-                if forks.get(child_slot).is_some() || new_banks.get(&child_slot).is_some() {
+                let existing_parent_slot = forks.get(child_slot).map(|bank| bank.parent_slot());
+                if existing_parent_slot == Some(parent_slot) {
                     trace!("child already active or frozen {}", child_slot);
                     continue;
                 }
-                let leader = leader_schedule_cache
-                    .slot_leader_at(child_slot, Some(&parent_bank))
-                    .unwrap();
-                info!(
-                    "new fork:{} parent:{} root:{}",
-                    child_slot,
-                    parent_slot,
-                    forks.root()
-                );
-                let child_bank = Self::new_bank_from_parent_with_notify(
-                    &parent_bank,
-                    child_slot,
-                    forks.root(),
-                    &leader,
-                    rpc_subscriptions,
-                );
-                let empty: Vec<Pubkey> = vec![];
-                Self::update_fork_propagated_threshold_from_votes(
-                    progress,
-                    empty,
-                    vec![leader],
-                    parent_bank.slot(),
-                    bank_forks,
-                );
-                new_banks.insert(child_slot, child_bank);
+                if existing_parent_slot.is_some() || !new_bank_slots.insert(child_slot) {
+                    // The blockstore lists the slot as the child of more than one parent,
+                    // which points at blockstore corruption rather than a slot seen before
+                    datapoint_warn!(
+                        "generate_new_bank_forks-duplicate_child",
+                        ("slot", child_slot, i64),
+                        ("parent", parent_slot, i64)
+                    );
+                    inc_new_counter_info!("replay_stage-duplicate_child_slot", 1);
+                    continue;
+                }
+                new_bank_parents.push((child_slot, parent_bank.clone()));
             }
         }
+        if let Some(max_concurrent_forks) = max_concurrent_forks {
+            Self::limit_new_bank_forks(
+                &mut new_bank_parents,
+                heaviest_subtree_fork_choice,
+                max_concurrent_forks,
+                logged_dropped_slots,
+            );
+        }
+
+        let mut new_banks = Vec::with_capacity(new_bank_parents.len());
+        for (child_slot, parent_bank) in new_bank_parents {
+            let leader = leader_schedule_cache
+                .slot_leader_at(child_slot, Some(&parent_bank))
+                .unwrap();
+            info!(
+                "new fork:{} parent:{} root:{}",
+                child_slot,
+                parent_bank.slot(),
+                forks.root()
+            );
+            let child_bank = Self::new_bank_from_parent_with_notify(
+                &parent_bank,
+                child_slot,
+                forks.root(),
+                &leader,
+                rpc_subscriptions,
+            );
+            let empty: Vec<Pubkey> = vec![];
+            Self::update_fork_propagated_threshold_from_votes(
+                progress,
+                empty,
+                vec![leader],
+                parent_bank.slot(),
+                bank_forks,
+                superminority_threshold,
+            );
+            new_banks.push(child_bank);
+        }
         drop(forks);
 
         let mut forks = bank_forks.write().unwrap();
-        for (_, bank) in new_banks {
+        for bank in new_banks {
             forks.insert(bank);
         }
     }
 
+    // Keeps at most `max_concurrent_forks` of the `(child_slot, parent_bank)` pairs to
+    // create new banks for, preferring children of the heavier subtrees. Children of
+    // the heaviest bank are never dropped, even if there are more of them than allowed.
+    // A dropped slot is dropped again on every call until its bank is created, so only
+    // the slots missing from `logged_dropped_slots` are logged
+    fn limit_new_bank_forks(
+        new_bank_parents: &mut Vec<(Slot, Arc<Bank>)>,
+        heaviest_subtree_fork_choice: &HeaviestSubtreeForkChoice,
+        max_concurrent_forks: usize,
+        logged_dropped_slots: &mut HashSet<Slot>,
+    ) {
+        if new_bank_parents.len() <= max_concurrent_forks {
+            logged_dropped_slots.clear();
+            return;
+        }
+        let (heaviest_slot, _) = heaviest_subtree_fork_choice.best_overall_slot();
+        new_bank_parents.sort_by_key(|(child_slot, parent_bank)| {
+            let parent_stake = heaviest_subtree_fork_choice
+                .stake_voted_subtree(&(parent_bank.slot(), parent_bank.hash()))
+                .unwrap_or(0);
+            (
+                parent_bank.slot() != heaviest_slot,
+                std::cmp::Reverse(parent_stake),
+                *child_slot,
+            )
+        });
+        let num_children_of_heaviest = new_bank_parents
+            .iter()
+            .filter(|(_, parent_bank)| parent_bank.slot() == heaviest_slot)
+            .count();
+        let dropped_slots: Vec<_> = new_bank_parents
+            .split_off(max_concurrent_forks.max(num_children_of_heaviest))
+            .into_iter()
+            .map(|(child_slot, _)| child_slot)
+            .collect();
+        // Forget the slots whose banks have been created since
+        logged_dropped_slots.retain(|slot| dropped_slots.contains(slot));
+        let newly_dropped_slots: Vec<_> = dropped_slots
+            .iter()
+            .filter(|slot| logged_dropped_slots.insert(**slot))
+            .collect();
+        if !newly_dropped_slots.is_empty() {
+            warn!(
+                "more than {} new forks, not creating banks for slots {:?} yet",
+                max_concurrent_forks, newly_dropped_slots
+            );
+            datapoint_info!(
+                "replay_stage-dropped_new_forks",
+                ("num_dropped", dropped_slots.len(), i64),
+                ("num_newly_dropped", newly_dropped_slots.len(), i64),
+                ("max_concurrent_forks", max_concurrent_forks, i64),
+            );
+        }
+    }
+
     fn new_bank_from_parent_with_notify(
         parent: &Arc<Bank>,
         slot: u64,
@@ -2533,15 +5022,21 @@ mod tests {
     use crossbeam_channel::unbounded;
     use solana_gossip::{cluster_info::Node, crds::Cursor};
     use solana_ledger::{
+        block_error::HashFailureKind,
         blockstore::make_slot_entries,
         blockstore::{entries_to_test_shreds, BlockstoreError},
+        blockstore_processor::{
+            DeadSlotInfo, SlotVerificationContext, SlotVerificationReport, TransactionStatusBatch,
+            TransactionStatusMessage,
+        },
         create_new_tmp_ledger,
         entry::{self, Entry},
         genesis_utils::{create_genesis_config, create_genesis_config_with_leader},
         get_tmp_ledger_path,
         shred::{
-            CodingShredHeader, DataShredHeader, Shred, ShredCommonHeader, DATA_COMPLETE_SHRED,
-            SIZE_OF_COMMON_SHRED_HEADER, SIZE_OF_DATA_SHRED_HEADER, SIZE_OF_DATA_SHRED_PAYLOAD,
+            CodingShredHeader, DataShredHeader, Shred, ShredCommonHeader, Shredder,
+            DATA_COMPLETE_SHRED, SIZE_OF_COMMON_SHRED_HEADER, SIZE_OF_DATA_SHRED_HEADER,
+            SIZE_OF_DATA_SHRED_PAYLOAD,
         },
     };
     use solana_rpc::{
@@ -2552,12 +5047,14 @@ mod tests {
         accounts_background_service::AbsRequestSender,
         commitment::BlockCommitment,
         genesis_utils::{GenesisConfigInfo, ValidatorVoteKeypairs},
+        vote_sender_types::ReplayVoteReceiver,
     };
     use solana_sdk::{
         clock::NUM_CONSECUTIVE_LEADER_SLOTS,
         genesis_config,
         hash::{hash, Hash},
         instruction::InstructionError,
+        message::Message,
         packet::PACKET_DATA_SIZE,
         poh_config::PohConfig,
         signature::{Keypair, Signer},
@@ -2566,16 +5063,186 @@ mod tests {
     };
     use solana_transaction_status::TransactionWithStatusMeta;
     use solana_vote_program::{
-        vote_state::{VoteState, VoteStateVersions},
+        vote_instruction,
+        vote_state::{VoteAuthorize, VoteInit, VoteState, VoteStateVersions},
         vote_transaction,
     };
     use std::{
         fs::remove_dir_all,
         iter,
-        sync::{atomic::AtomicU64, Arc, RwLock},
+        sync::{atomic::AtomicU64, mpsc::channel, Arc, RwLock},
     };
     use trees::{tr, Tree};
 
+    #[test]
+    fn test_generate_vote_tx_authorized_voter_rotation() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000_000_000);
+        let bank0 = Bank::new(&genesis_config);
+
+        // Create a vote account, then schedule the rotation of its authorized voter
+        let vote_keypair = Keypair::new();
+        let old_voter_keypair = Arc::new(Keypair::new());
+        let new_voter_keypair = Arc::new(Keypair::new());
+        let lamports = bank0.get_minimum_balance_for_rent_exemption(VoteState::size_of());
+        let instructions = vote_instruction::create_account(
+            &mint_keypair.pubkey(),
+            &vote_keypair.pubkey(),
+            &VoteInit {
+                node_pubkey: Pubkey::new_unique(),
+                authorized_voter: old_voter_keypair.pubkey(),
+                authorized_withdrawer: old_voter_keypair.pubkey(),
+                commission: 0,
+            },
+            lamports,
+        );
+        let message = Message::new(&instructions, Some(&mint_keypair.pubkey()));
+        bank0
+            .process_transaction(&Transaction::new(
+                &[&mint_keypair, &vote_keypair],
+                message,
+                bank0.last_blockhash(),
+            ))
+            .unwrap();
+        let authorize_ix = vote_instruction::authorize(
+            &vote_keypair.pubkey(),
+            &old_voter_keypair.pubkey(),
+            &new_voter_keypair.pubkey(),
+            VoteAuthorize::Voter,
+        );
+        let message = Message::new(&[authorize_ix], Some(&mint_keypair.pubkey()));
+        bank0
+            .process_transaction(&Transaction::new(
+                &[&mint_keypair, old_voter_keypair.as_ref()],
+                message,
+                bank0.last_blockhash(),
+            ))
+            .unwrap();
+        let bank0 = Arc::new(bank0);
+        let rotation_epoch = bank0
+            .epoch_schedule()
+            .get_leader_schedule_epoch(bank0.slot())
+            + 1;
+
+        // Votes with `bank` and lands the vote in a child of it, returning the voter that
+        // signed it and the result of the vote
+        let vote = |bank: &Arc<Bank>, authorized_voter_keypairs: &[Arc<Keypair>]| {
+            bank.freeze();
+            let vote_tx = ReplayStage::generate_vote_tx(
+                &mint_keypair,
+                bank,
+                &vote_keypair.pubkey(),
+                authorized_voter_keypairs,
+                Vote::new(vec![bank.slot()], bank.hash()),
+                &SwitchForkDecision::SameFork,
+                &mut VecDeque::new(),
+                MAX_VOTE_SIGNATURES,
+                false,
+            )?;
+            let child = Bank::new_from_parent(bank, &Pubkey::default(), bank.slot() + 1);
+            assert_eq!(child.epoch(), bank.epoch());
+            Some((
+                vote_tx.message.account_keys[1],
+                child.process_transaction(&vote_tx),
+            ))
+        };
+
+        // Well before the rotation, only the old voter can vote
+        assert_eq!(
+            vote(
+                &bank0,
+                &[old_voter_keypair.clone(), new_voter_keypair.clone()]
+            ),
+            Some((old_voter_keypair.pubkey(), Ok(())))
+        );
+        assert_eq!(vote(&bank0, &[new_voter_keypair.clone()]), None);
+
+        // Right before the rotation too, the new voter's votes would be rejected
+        let last_epoch_slot = bank0
+            .epoch_schedule()
+            .get_last_slot_in_epoch(rotation_epoch - 1);
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            last_epoch_slot - 1,
+        ));
+        assert_eq!(
+            vote(
+                &bank,
+                &[old_voter_keypair.clone(), new_voter_keypair.clone()]
+            ),
+            Some((old_voter_keypair.pubkey(), Ok(())))
+        );
+        assert_eq!(vote(&bank, &[new_voter_keypair.clone()]), None);
+        assert_eq!(vote(&bank, &[Arc::new(Keypair::new())]), None);
+
+        // After the rotation, only the new voter can vote
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            last_epoch_slot + 1,
+        ));
+        assert_eq!(bank.epoch(), rotation_epoch);
+        assert_eq!(
+            vote(&bank, &[new_voter_keypair.clone()]),
+            Some((new_voter_keypair.pubkey(), Ok(())))
+        );
+        assert_eq!(vote(&bank, &[old_voter_keypair]), None);
+    }
+
+    #[test]
+    fn test_replay_loop_context() {
+        let (VoteSimulator { bank_forks, .. }, _) = setup_default_forks(1);
+        let bank6 = bank_forks.read().unwrap().get(6).unwrap().clone();
+
+        // The cache is taken before bank 7 is inserted, and caught up by the context
+        let mut ancestry_cache = AncestryCache::new(&bank_forks.read().unwrap());
+        let bank7 = bank_forks.write().unwrap().insert(Bank::new_from_parent(
+            &bank6,
+            &Pubkey::default(),
+            7,
+        ));
+        let mut context = ReplayLoopContext::new(&bank_forks, &mut ancestry_cache);
+        assert_eq!(context.root, 0);
+        assert_eq!(
+            context.ancestry.ancestors,
+            bank_forks.read().unwrap().ancestors()
+        );
+        assert_eq!(
+            &context.ancestry.descendants,
+            bank_forks.read().unwrap().descendants()
+        );
+        let frozen_slots = |context: &ReplayLoopContext| {
+            let mut slots: Vec<_> = context
+                .frozen_banks()
+                .iter()
+                .map(|bank| bank.slot())
+                .collect();
+            slots.sort_unstable();
+            slots
+        };
+        assert_eq!(frozen_slots(&context), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(context.active_slots().collect::<Vec<_>>(), vec![7]);
+
+        // Banks frozen after the context was taken are picked up
+        bank7.freeze();
+        assert_eq!(frozen_slots(&context), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(context.active_slots().count(), 0);
+
+        // Purged banks aren't
+        let slot_descendants = context.ancestry.descendants.get(&5).unwrap().clone();
+        ReplayStage::purge_ancestors_descendants(
+            5,
+            &slot_descendants,
+            &mut context.ancestry.ancestors,
+            &mut context.ancestry.descendants,
+        );
+        assert_eq!(frozen_slots(&context), vec![0, 1, 2, 3, 4]);
+    }
+
     #[test]
     fn test_is_partition_detected() {
         let (VoteSimulator { bank_forks, .. }, _) = setup_default_forks(1);
@@ -2592,91 +5259,428 @@ mod tests {
         assert!(ReplayStage::is_partition_detected(&ancestors, 4, 3));
     }
 
-    struct ReplayBlockstoreComponents {
-        blockstore: Arc<Blockstore>,
-        validator_node_to_vote_keys: HashMap<Pubkey, Pubkey>,
-        validator_keypairs: HashMap<Pubkey, ValidatorVoteKeypairs>,
-        my_pubkey: Pubkey,
-        progress: ProgressMap,
-        cluster_info: ClusterInfo,
-        leader_schedule_cache: Arc<LeaderScheduleCache>,
-        poh_recorder: Mutex<PohRecorder>,
-        bank_forks: Arc<RwLock<BankForks>>,
-        tower: Tower,
-        rpc_subscriptions: Arc<RpcSubscriptions>,
-    }
-
-    fn replay_blockstore_components(forks: Option<Tree<Slot>>) -> ReplayBlockstoreComponents {
-        // Setup blockstore
-        let (vote_simulator, blockstore) =
-            setup_forks_from_tree(forks.unwrap_or_else(|| tr(0)), 20);
-
-        let VoteSimulator {
-            validator_keypairs,
-            progress,
-            bank_forks,
-            ..
-        } = vote_simulator;
-
-        let blockstore = Arc::new(blockstore);
-        let bank_forks = Arc::new(bank_forks);
-        let validator_node_to_vote_keys: HashMap<Pubkey, Pubkey> = validator_keypairs
-            .iter()
-            .map(|(_, keypairs)| {
-                (
-                    keypairs.node_keypair.pubkey(),
-                    keypairs.vote_keypair.pubkey(),
-                )
-            })
-            .collect();
-
-        // ClusterInfo
-        let my_keypairs = validator_keypairs.values().next().unwrap();
-        let my_pubkey = my_keypairs.node_keypair.pubkey();
+    #[test]
+    fn test_maybe_update_identity_callback() {
+        let keypair = Arc::new(Keypair::new());
         let cluster_info = ClusterInfo::new(
-            Node::new_localhost_with_pubkey(&my_pubkey).info,
-            Arc::new(Keypair::from_bytes(&my_keypairs.node_keypair.to_bytes()).unwrap()),
+            Node::new_localhost_with_pubkey(&keypair.pubkey()).info,
+            keypair.clone(),
         );
-        assert_eq!(my_pubkey, cluster_info.id());
+        let rotations = Arc::new(Mutex::new(vec![]));
+        let rotations_ = rotations.clone();
+        let identity_hotswap_callback: IdentityHotswapCallback =
+            Arc::new(move |old_pubkey, new_pubkey| {
+                rotations_.lock().unwrap().push((old_pubkey, new_pubkey))
+            });
+        let mut identity_keypair = cluster_info.keypair().clone();
+        let mut my_pubkey = identity_keypair.pubkey();
+        let mut update = || {
+            ReplayStage::maybe_update_identity(
+                &mut my_pubkey,
+                &mut identity_keypair,
+                &cluster_info,
+                Some(&identity_hotswap_callback),
+            )
+        };
 
-        // Leader schedule cache
-        let root_bank = bank_forks.read().unwrap().root_bank();
-        let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&root_bank));
+        // Nothing fires until the identity is rotated
+        assert!(!update());
+        assert!(rotations.lock().unwrap().is_empty());
 
-        // PohRecorder
-        let working_bank = bank_forks.read().unwrap().working_bank();
-        let poh_recorder = Mutex::new(
-            PohRecorder::new(
-                working_bank.tick_height(),
-                working_bank.last_blockhash(),
-                working_bank.slot(),
-                None,
-                working_bank.ticks_per_slot(),
-                &Pubkey::default(),
-                &blockstore,
-                &leader_schedule_cache,
-                &Arc::new(PohConfig::default()),
-                Arc::new(AtomicBool::new(false)),
-            )
-            .0,
+        // Each rotation fires once, however many times the loop checks afterwards
+        let new_keypair = Arc::new(Keypair::new());
+        cluster_info.set_keypair(new_keypair.clone());
+        assert!(update());
+        assert!(!update());
+        assert_eq!(
+            *rotations.lock().unwrap(),
+            vec![(keypair.pubkey(), new_keypair.pubkey())]
         );
 
-        // Tower
-        let my_vote_pubkey = my_keypairs.vote_keypair.pubkey();
-        let tower = Tower::new_from_bankforks(
-            &bank_forks.read().unwrap(),
-            blockstore.ledger_path(),
-            &cluster_info.id(),
-            &my_vote_pubkey,
+        let newer_keypair = Arc::new(Keypair::new());
+        cluster_info.set_keypair(newer_keypair.clone());
+        assert!(update());
+        assert!(!update());
+        assert_eq!(
+            *rotations.lock().unwrap(),
+            vec![
+                (keypair.pubkey(), new_keypair.pubkey()),
+                (new_keypair.pubkey(), newer_keypair.pubkey()),
+            ]
         );
+        assert_eq!(my_pubkey, newer_keypair.pubkey());
+        assert_eq!(identity_keypair.pubkey(), newer_keypair.pubkey());
+    }
 
-        // RpcSubscriptions
-        let optimistically_confirmed_bank =
-            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
-        let exit = Arc::new(AtomicBool::new(false));
-        let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
-            &exit,
-            bank_forks.clone(),
+    #[test]
+    fn test_update_partition_state_callbacks() {
+        let detected = Arc::new(Mutex::new(vec![]));
+        let resolved = Arc::new(Mutex::new(vec![]));
+        let detected_ = detected.clone();
+        let resolved_ = resolved.clone();
+        let partition_callback: PartitionCallback =
+            Arc::new(move |last_voted_slot, heaviest_slot| {
+                detected_
+                    .lock()
+                    .unwrap()
+                    .push((last_voted_slot, heaviest_slot))
+            });
+        let partition_resolved_callback: PartitionCallback =
+            Arc::new(move |last_voted_slot, heaviest_slot| {
+                resolved_
+                    .lock()
+                    .unwrap()
+                    .push((last_voted_slot, heaviest_slot))
+            });
+        let mut partition_exists = false;
+        let mut update = |partition_detected, last_voted_slot, heaviest_slot| {
+            ReplayStage::update_partition_state(
+                &mut partition_exists,
+                partition_detected,
+                last_voted_slot,
+                heaviest_slot,
+                heaviest_slot,
+                Some(&partition_callback),
+                Some(&partition_resolved_callback),
+                None,
+            );
+            partition_exists
+        };
+
+        // No partition, nothing fires
+        assert!(!update(false, 1, 3));
+        assert!(detected.lock().unwrap().is_empty());
+        assert!(resolved.lock().unwrap().is_empty());
+
+        // Transition to partitioned fires the detection callback once
+        assert!(update(true, 2, 3));
+        assert!(update(true, 2, 4));
+        assert_eq!(*detected.lock().unwrap(), vec![(2, 3)]);
+        assert!(resolved.lock().unwrap().is_empty());
+
+        // Transition back fires the resolution callback once
+        assert!(!update(false, 5, 5));
+        assert!(!update(false, 5, 6));
+        assert_eq!(*detected.lock().unwrap(), vec![(2, 3)]);
+        assert_eq!(*resolved.lock().unwrap(), vec![(5, 5)]);
+
+        // A second partition fires the detection callback again
+        assert!(update(true, 6, 7));
+        assert_eq!(*detected.lock().unwrap(), vec![(2, 3), (6, 7)]);
+        assert_eq!(*resolved.lock().unwrap(), vec![(5, 5)]);
+    }
+
+    #[test]
+    fn test_partition_events() {
+        let (vote_simulator, _blockstore) = setup_default_forks(1);
+        let ancestors = vote_simulator.bank_forks.read().unwrap().ancestors();
+        let (sender, receiver) = channel();
+        let partition_detection_threshold = 2;
+        let mut partition_exists = false;
+        let mut partition_detected_iterations = 0;
+        // One iteration of the replay loop, voted on `last_voted_slot` and resetting to the
+        // heaviest slot
+        let mut iterate = |last_voted_slot, heaviest_slot| {
+            let partition_detected = ReplayStage::debounce_partition_detection(
+                &mut partition_detected_iterations,
+                ReplayStage::is_partition_detected(&ancestors, last_voted_slot, heaviest_slot),
+                partition_detection_threshold,
+            );
+            ReplayStage::update_partition_state(
+                &mut partition_exists,
+                partition_detected,
+                last_voted_slot,
+                heaviest_slot,
+                heaviest_slot,
+                None,
+                None,
+                Some(&sender),
+            );
+            receiver.try_iter().collect::<Vec<_>>()
+        };
+
+        // Voted on slot 4, the heaviest fork briefly switching to slot 5 is a blip
+        assert!(iterate(4, 4).is_empty());
+        assert!(iterate(4, 5).is_empty());
+        assert!(iterate(4, 4).is_empty());
+
+        // But it's a partition once it holds for two iterations
+        assert!(iterate(4, 5).is_empty());
+        assert_eq!(
+            iterate(4, 6),
+            vec![PartitionEvent::Detected {
+                heaviest_slot: 6,
+                last_voted_slot: 4,
+                reset_slot: 6,
+            }]
+        );
+        assert!(iterate(4, 6).is_empty());
+
+        // Which resolves as soon as the vote is on the heaviest fork again
+        assert_eq!(
+            iterate(6, 6),
+            vec![PartitionEvent::Resolved {
+                heaviest_slot: 6,
+                last_voted_slot: 6,
+                reset_slot: 6,
+            }]
+        );
+        assert!(iterate(6, 6).is_empty());
+    }
+
+    #[test]
+    fn test_debounce_partition_detection() {
+        let mut partition_detected_iterations = 0;
+        let mut debounce = |partition_detected, partition_detection_threshold| {
+            ReplayStage::debounce_partition_detection(
+                &mut partition_detected_iterations,
+                partition_detected,
+                partition_detection_threshold,
+            )
+        };
+
+        // With the default threshold, every detection is reported right away
+        assert!(!debounce(false, DEFAULT_PARTITION_DETECTION_THRESHOLD));
+        assert!(debounce(true, DEFAULT_PARTITION_DETECTION_THRESHOLD));
+        assert!(!debounce(false, DEFAULT_PARTITION_DETECTION_THRESHOLD));
+
+        // A partition resolved before the threshold is never reported
+        assert!(!debounce(true, 3));
+        assert!(!debounce(true, 3));
+        assert!(!debounce(false, 3));
+        assert!(!debounce(true, 3));
+
+        // One persisting for the threshold is, until it resolves
+        assert!(!debounce(true, 3));
+        assert!(debounce(true, 3));
+        assert!(debounce(true, 3));
+        assert!(!debounce(false, 3));
+        assert!(!debounce(true, 3));
+    }
+
+    #[test]
+    fn test_duplicate_slot_recovery_mode_from_str() {
+        assert_eq!(
+            "aggressive".parse::<DuplicateSlotRecoveryMode>(),
+            Ok(DuplicateSlotRecoveryMode::Aggressive)
+        );
+        assert_eq!(
+            "conservative".parse::<DuplicateSlotRecoveryMode>(),
+            Ok(DuplicateSlotRecoveryMode::Conservative)
+        );
+        assert!("Aggressive".parse::<DuplicateSlotRecoveryMode>().is_err());
+        for string in &DuplicateSlotRecoveryMode::STRINGS {
+            assert!(string.parse::<DuplicateSlotRecoveryMode>().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_check_voting_deferred() {
+        let cluster_slots = ClusterSlots::default();
+        // Gossip duplicate confirms a slot far ahead of the restarted validator
+        let gossip_duplicate_confirmed_slots: GossipDuplicateConfirmedSlots =
+            vec![(5, Hash::default()), (100, Hash::default())]
+                .into_iter()
+                .collect();
+        let cluster_tip_slot =
+            ReplayStage::cluster_tip_slot(&cluster_slots, &gossip_duplicate_confirmed_slots);
+        assert_eq!(cluster_tip_slot, Some(100));
+
+        // Never deferred without the option
+        let mut caught_up_to_cluster = false;
+        assert!(!ReplayStage::check_voting_deferred(
+            None,
+            &mut caught_up_to_cluster,
+            1,
+            cluster_tip_slot,
+            Duration::ZERO,
+        ));
+
+        // Deferred until the heaviest bank is within 10 slots of the tip
+        let voting_deferred: Vec<_> = [1, 50, 89, 90, 91, 20]
+            .iter()
+            .map(|heaviest_slot| {
+                ReplayStage::check_voting_deferred(
+                    Some(10),
+                    &mut caught_up_to_cluster,
+                    *heaviest_slot,
+                    cluster_tip_slot,
+                    Duration::ZERO,
+                )
+            })
+            .collect();
+        // Once caught up, falling behind again doesn't defer voting
+        assert_eq!(voting_deferred, vec![true, true, true, false, false, false]);
+
+        // Without any known tip voting is deferred too. The timeout only applies while
+        // there's no tip
+        let timeout = Duration::from_millis(MAX_VOTING_DEFERRED_WITHOUT_CLUSTER_TIP_MS);
+        let mut caught_up_to_cluster = false;
+        assert!(ReplayStage::check_voting_deferred(
+            Some(10),
+            &mut caught_up_to_cluster,
+            1,
+            None,
+            Duration::ZERO,
+        ));
+        assert!(ReplayStage::check_voting_deferred(
+            Some(10),
+            &mut caught_up_to_cluster,
+            1,
+            cluster_tip_slot,
+            timeout,
+        ));
+        assert!(!caught_up_to_cluster);
+
+        // Until no tip has been known for the timeout
+        assert!(!ReplayStage::check_voting_deferred(
+            Some(10),
+            &mut caught_up_to_cluster,
+            1,
+            None,
+            timeout,
+        ));
+        assert!(caught_up_to_cluster);
+    }
+
+    #[test]
+    fn test_maybe_notify_fork_abandoned() {
+        let ReplayBlockstoreComponents {
+            bank_forks,
+            poh_recorder,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(Some(tr(0) / (tr(1) / (tr(2) / tr(4)) / (tr(3) / tr(5)))));
+        let ancestors = bank_forks.read().unwrap().ancestors();
+        let bank = |slot| bank_forks.read().unwrap().get(slot).unwrap().clone();
+        let switch_failures = vec![HeaviestForkFailures::FailedSwitchThreshold(5, 0, 100)];
+
+        // Mirrors the reset branch of the replay loop, returning the notification sent
+        let mut last_reset = bank(2).last_blockhash();
+        poh_recorder.lock().unwrap().reset(last_reset, 2, None);
+        let mut reset = |reset_bank: Arc<Bank>, heaviest_fork_failures: &[HeaviestForkFailures]| {
+            let reason = ReplayStage::maybe_notify_fork_abandoned(
+                &last_reset,
+                &reset_bank,
+                &poh_recorder,
+                &bank_forks,
+                &ancestors,
+                heaviest_fork_failures,
+                &rpc_subscriptions,
+            );
+            if last_reset != reset_bank.last_blockhash() {
+                poh_recorder.lock().unwrap().reset(
+                    reset_bank.last_blockhash(),
+                    reset_bank.slot(),
+                    None,
+                );
+                last_reset = reset_bank.last_blockhash();
+            }
+            reason
+        };
+
+        // Extending the fork abandons nothing
+        assert_eq!(reset(bank(4), &[]), None);
+        // Switching forks notifies once, not again while staying on the new fork
+        assert_eq!(
+            reset(bank(5), &switch_failures),
+            Some(ForkAbandonedReason::SwitchThresholdFailure)
+        );
+        assert_eq!(reset(bank(5), &switch_failures), None);
+        assert_eq!(reset(bank(5), &[]), None);
+        assert_eq!(
+            reset(bank(4), &[]),
+            Some(ForkAbandonedReason::HeaviestForkChanged)
+        );
+        assert_eq!(reset(bank(4), &[]), None);
+    }
+
+    struct ReplayBlockstoreComponents {
+        blockstore: Arc<Blockstore>,
+        validator_node_to_vote_keys: HashMap<Pubkey, Pubkey>,
+        validator_keypairs: HashMap<Pubkey, ValidatorVoteKeypairs>,
+        my_pubkey: Pubkey,
+        progress: ProgressMap,
+        cluster_info: ClusterInfo,
+        leader_schedule_cache: Arc<LeaderScheduleCache>,
+        poh_recorder: Mutex<PohRecorder>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        tower: Tower,
+        rpc_subscriptions: Arc<RpcSubscriptions>,
+    }
+
+    fn replay_blockstore_components(forks: Option<Tree<Slot>>) -> ReplayBlockstoreComponents {
+        // Setup blockstore
+        let (vote_simulator, blockstore) =
+            setup_forks_from_tree(forks.unwrap_or_else(|| tr(0)), 20);
+
+        let VoteSimulator {
+            validator_keypairs,
+            progress,
+            bank_forks,
+            ..
+        } = vote_simulator;
+
+        let blockstore = Arc::new(blockstore);
+        let bank_forks = Arc::new(bank_forks);
+        let validator_node_to_vote_keys: HashMap<Pubkey, Pubkey> = validator_keypairs
+            .iter()
+            .map(|(_, keypairs)| {
+                (
+                    keypairs.node_keypair.pubkey(),
+                    keypairs.vote_keypair.pubkey(),
+                )
+            })
+            .collect();
+
+        // ClusterInfo
+        let my_keypairs = validator_keypairs.values().next().unwrap();
+        let my_pubkey = my_keypairs.node_keypair.pubkey();
+        let cluster_info = ClusterInfo::new(
+            Node::new_localhost_with_pubkey(&my_pubkey).info,
+            Arc::new(Keypair::from_bytes(&my_keypairs.node_keypair.to_bytes()).unwrap()),
+        );
+        assert_eq!(my_pubkey, cluster_info.id());
+
+        // Leader schedule cache
+        let root_bank = bank_forks.read().unwrap().root_bank();
+        let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&root_bank));
+
+        // PohRecorder
+        let working_bank = bank_forks.read().unwrap().working_bank();
+        let poh_recorder = Mutex::new(
+            PohRecorder::new(
+                working_bank.tick_height(),
+                working_bank.last_blockhash(),
+                working_bank.slot(),
+                None,
+                working_bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &blockstore,
+                &leader_schedule_cache,
+                &Arc::new(PohConfig::default()),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .0,
+        );
+
+        // Tower
+        let my_vote_pubkey = my_keypairs.vote_keypair.pubkey();
+        let tower = Tower::new_from_bankforks(
+            &bank_forks.read().unwrap(),
+            blockstore.ledger_path(),
+            &cluster_info.id(),
+            &my_vote_pubkey,
+            SUPERMINORITY_THRESHOLD,
+        );
+
+        // RpcSubscriptions
+        let optimistically_confirmed_bank =
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
+        let exit = Arc::new(AtomicBool::new(false));
+        let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
+            &exit,
+            bank_forks.clone(),
             Arc::new(RwLock::new(BlockCommitmentCache::default())),
             optimistically_confirmed_bank,
         ));
@@ -2696,6 +5700,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generate_new_bank_forks_max_concurrent_forks() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            mut progress,
+            bank_forks,
+            leader_schedule_cache,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(Some(tr(0) / tr(1) / tr(2)));
+        let heaviest_subtree_fork_choice =
+            HeaviestSubtreeForkChoice::new_from_bank_forks(&bank_forks.read().unwrap());
+        // Without any votes, ties are broken in favor of the lower slot
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 1);
+
+        // Slots 3 and 4 are children of the heaviest bank 1, slots 5 through 9 of bank 2
+        for (slot, parent_slot) in &[(3, 1), (4, 1), (5, 2), (6, 2), (7, 2), (8, 2), (9, 2)] {
+            let (shreds, _) = make_slot_entries(*slot, *parent_slot, 8);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+        }
+        let mut logged_dropped_slots = HashSet::new();
+        let mut generate_new_bank_forks =
+            |max_concurrent_forks, logged_dropped_slots: &mut HashSet<Slot>| {
+                ReplayStage::generate_new_bank_forks(
+                    &blockstore,
+                    &bank_forks,
+                    &leader_schedule_cache,
+                    &rpc_subscriptions,
+                    &mut progress,
+                    &heaviest_subtree_fork_choice,
+                    SUPERMINORITY_THRESHOLD,
+                    max_concurrent_forks,
+                    logged_dropped_slots,
+                );
+                let mut active_banks = bank_forks.read().unwrap().active_banks();
+                active_banks.sort_unstable();
+                active_banks
+            };
+
+        // Children of the heaviest bank are created even if they exceed the limit
+        assert_eq!(
+            generate_new_bank_forks(Some(1), &mut logged_dropped_slots),
+            vec![3, 4]
+        );
+        assert_eq!(
+            logged_dropped_slots,
+            vec![5, 6, 7, 8, 9].into_iter().collect::<HashSet<_>>()
+        );
+        // The slots created since are forgotten, the ones still dropped aren't logged again
+        assert_eq!(
+            generate_new_bank_forks(Some(3), &mut logged_dropped_slots),
+            vec![3, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            logged_dropped_slots,
+            vec![8, 9].into_iter().collect::<HashSet<_>>()
+        );
+        assert_eq!(
+            generate_new_bank_forks(None, &mut logged_dropped_slots),
+            vec![3, 4, 5, 6, 7, 8, 9]
+        );
+    }
+
     #[test]
     fn test_child_slots_of_same_parent() {
         let ReplayBlockstoreComponents {
@@ -2726,6 +5793,7 @@ mod tests {
                 Some(0),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
         assert!(progress.get_propagated_stats(1).unwrap().is_leader_slot);
@@ -2747,6 +5815,10 @@ mod tests {
             &leader_schedule_cache,
             &rpc_subscriptions,
             &mut progress,
+            &HeaviestSubtreeForkChoice::new_from_bank_forks(&bank_forks.read().unwrap()),
+            SUPERMINORITY_THRESHOLD,
+            None,
+            &mut HashSet::new(),
         );
         assert!(bank_forks
             .read()
@@ -2769,6 +5841,10 @@ mod tests {
             &leader_schedule_cache,
             &rpc_subscriptions,
             &mut progress,
+            &HeaviestSubtreeForkChoice::new_from_bank_forks(&bank_forks.read().unwrap()),
+            SUPERMINORITY_THRESHOLD,
+            None,
+            &mut HashSet::new(),
         );
         assert!(bank_forks
             .read()
@@ -2800,6 +5876,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_epoch_stakes_cache() {
+        let genesis_config = create_genesis_config(10_000).genesis_config;
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        let mut epoch_stakes_cache = EpochStakesCache::default();
+
+        // Repeated lookups within the root's epoch only fetch the stakes once
+        for _ in 0..1000 {
+            let epoch_stakes = epoch_stakes_cache.epoch_stakes(&bank0, 0);
+            assert_eq!(epoch_stakes.total_stake(), bank0.total_epoch_stake());
+        }
+        assert_eq!(epoch_stakes_cache.num_fetches, 1);
+
+        // The stakes of an epoch past the root's are looked up every time, and don't
+        // replace the cached ones
+        let epoch1_slot = bank0.epoch_schedule().get_first_slot_in_epoch(1);
+        let bank1 = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            epoch1_slot,
+        ));
+        assert_eq!(bank1.epoch(), 1);
+        epoch_stakes_cache.epoch_stakes(&bank1, 0);
+        epoch_stakes_cache.epoch_stakes(&bank1, 0);
+        assert_eq!(epoch_stakes_cache.num_fetches, 3);
+        epoch_stakes_cache.epoch_stakes(&bank0, 0);
+        assert_eq!(epoch_stakes_cache.num_fetches, 3);
+
+        // Once the root reaches the new epoch, the cache is invalidated once
+        for _ in 0..1000 {
+            let epoch_stakes = epoch_stakes_cache.epoch_stakes(&bank1, epoch1_slot);
+            assert_eq!(epoch_stakes.total_stake(), bank1.total_epoch_stake());
+        }
+        assert_eq!(epoch_stakes_cache.num_fetches, 4);
+    }
+
     #[test]
     fn test_handle_new_root() {
         let genesis_config = create_genesis_config(10_000).genesis_config;
@@ -2820,7 +5932,10 @@ mod tests {
 
         let mut progress = ProgressMap::default();
         for i in 0..=root {
-            progress.insert(i, ForkProgress::new(Hash::default(), None, None, 0, 0));
+            progress.insert(
+                i,
+                ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD),
+            );
         }
 
         let mut duplicate_slots_tracker: DuplicateSlotsTracker =
@@ -2848,14 +5963,17 @@ mod tests {
             &mut gossip_duplicate_confirmed_slots,
             &mut unfrozen_gossip_verified_vote_hashes,
             &mut true,
-            &mut Vec::new(),
+            &mut VecDeque::new(),
         );
         assert_eq!(bank_forks.read().unwrap().root(), root);
         assert_eq!(progress.len(), 1);
         assert!(progress.get(&root).is_some());
         // root - 1 is filtered out
         assert_eq!(
-            duplicate_slots_tracker.into_iter().collect::<Vec<Slot>>(),
+            duplicate_slots_tracker
+                .iter()
+                .copied()
+                .collect::<Vec<Slot>>(),
             vec![root, root + 1]
         );
         assert_eq!(
@@ -2906,7 +6024,10 @@ mod tests {
         let mut heaviest_subtree_fork_choice = HeaviestSubtreeForkChoice::new((root, root_hash));
         let mut progress = ProgressMap::default();
         for i in 0..=root {
-            progress.insert(i, ForkProgress::new(Hash::default(), None, None, 0, 0));
+            progress.insert(
+                i,
+                ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD),
+            );
         }
         ReplayStage::handle_new_root(
             root,
@@ -2919,7 +6040,7 @@ mod tests {
             &mut GossipDuplicateConfirmedSlots::default(),
             &mut UnfrozenGossipVerifiedVoteHashes::default(),
             &mut true,
-            &mut Vec::new(),
+            &mut VecDeque::new(),
         );
         assert_eq!(bank_forks.read().unwrap().root(), root);
         assert!(bank_forks.read().unwrap().get(confirmed_root).is_some());
@@ -2932,37 +6053,72 @@ mod tests {
 
     #[test]
     fn test_dead_fork_transaction_error() {
+        let res = check_dead_fork(transaction_error_shreds);
+
+        assert_matches!(
+            res,
+            Err(BlockstoreProcessorError::InvalidTransaction(
+                TransactionError::AccountNotFound
+            ))
+        );
+    }
+
+    fn transaction_error_shreds(_genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
         let keypair1 = Keypair::new();
         let keypair2 = Keypair::new();
         let missing_keypair = Keypair::new();
         let missing_keypair2 = Keypair::new();
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        let entry = entry::next_entry(
+            &blockhash,
+            hashes_per_tick.saturating_sub(1),
+            vec![
+                system_transaction::transfer(&keypair1, &keypair2.pubkey(), 2, blockhash), // should be fine,
+                system_transaction::transfer(
+                    &missing_keypair,
+                    &missing_keypair2.pubkey(),
+                    2,
+                    blockhash,
+                ), // should cause AccountNotFound error
+            ],
+        );
+        entries_to_test_shreds(vec![entry], slot, slot.saturating_sub(1), false, 0)
+    }
 
-        let res = check_dead_fork(|_keypair, bank| {
-            let blockhash = bank.last_blockhash();
-            let slot = bank.slot();
-            let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
-            let entry = entry::next_entry(
-                &blockhash,
-                hashes_per_tick.saturating_sub(1),
-                vec![
-                    system_transaction::transfer(&keypair1, &keypair2.pubkey(), 2, blockhash), // should be fine,
-                    system_transaction::transfer(
-                        &missing_keypair,
-                        &missing_keypair2.pubkey(),
-                        2,
-                        blockhash,
-                    ), // should cause AccountNotFound error
-                ],
-            );
-            entries_to_test_shreds(vec![entry], slot, slot.saturating_sub(1), false, 0)
-        });
+    #[test]
+    fn test_dead_fork_transaction_error_sends_dead_slot() {
+        let (sender, receiver) = unbounded();
+        let transaction_status_sender = TransactionStatusSender {
+            sender,
+            enable_cpi_and_log_storage: false,
+        };
+        let res = check_dead_fork_with_transaction_status_sender(
+            transaction_error_shreds,
+            Some(&transaction_status_sender),
+        );
+        assert!(res.is_err());
 
+        // The batch executed before the failure arrives first, then the dead slot
+        let messages: Vec<_> = receiver.try_iter().collect();
         assert_matches!(
-            res,
-            Err(BlockstoreProcessorError::InvalidTransaction(
-                TransactionError::AccountNotFound
-            ))
+            messages.first(),
+            Some(TransactionStatusMessage::Batch(TransactionStatusBatch { bank, .. }))
+                if bank.slot() == 0
         );
+        match messages.last() {
+            Some(TransactionStatusMessage::DeadSlot(DeadSlotInfo {
+                slot,
+                error,
+                last_entry,
+            })) => {
+                assert_eq!(*slot, 0);
+                assert!(error.contains("AccountNotFound"));
+                assert_ne!(*last_entry, Hash::default());
+            }
+            _ => panic!("expected a dead slot message"),
+        }
     }
 
     #[test]
@@ -2988,7 +6144,13 @@ mod tests {
         });
 
         if let Err(BlockstoreProcessorError::InvalidBlock(block_error)) = res {
-            assert_eq!(block_error, BlockError::InvalidEntryHash);
+            assert_eq!(
+                block_error,
+                BlockError::InvalidEntryHash {
+                    entry_index: 0,
+                    kind: HashFailureKind::Poh,
+                }
+            );
         } else {
             panic!();
         }
@@ -3141,6 +6303,18 @@ mod tests {
     // Given a shred and a fatal expected error, check that replaying that shred causes causes the fork to be
     // marked as dead. Returns the error for caller to verify.
     fn check_dead_fork<F>(shred_to_insert: F) -> result::Result<(), BlockstoreProcessorError>
+    where
+        F: Fn(&Keypair, Arc<Bank>) -> Vec<Shred>,
+    {
+        check_dead_fork_with_transaction_status_sender(shred_to_insert, None)
+    }
+
+    // Like `check_dead_fork()`, also replaying the slot and marking it dead with
+    // `transaction_status_sender`
+    fn check_dead_fork_with_transaction_status_sender<F>(
+        shred_to_insert: F,
+        transaction_status_sender: Option<&TransactionStatusSender>,
+    ) -> result::Result<(), BlockstoreProcessorError>
     where
         F: Fn(&Keypair, Arc<Bank>) -> Vec<Shred>,
     {
@@ -3161,9 +6335,9 @@ mod tests {
             let bank0 = bank_forks.working_bank();
             let mut progress = ProgressMap::default();
             let last_blockhash = bank0.last_blockhash();
-            let mut bank0_progress = progress
-                .entry(bank0.slot())
-                .or_insert_with(|| ForkProgress::new(last_blockhash, None, None, 0, 0));
+            let mut bank0_progress = progress.entry(bank0.slot()).or_insert_with(|| {
+                ForkProgress::new(last_blockhash, None, None, 0, 0, SUPERMINORITY_THRESHOLD)
+            });
             let shreds = shred_to_insert(&mint_keypair, bank0.clone());
             blockstore.insert_shreds(shreds, None, false).unwrap();
             let block_commitment_cache = Arc::new(RwLock::new(BlockCommitmentCache::default()));
@@ -3173,9 +6347,11 @@ mod tests {
                 &bank0,
                 &blockstore,
                 &mut bank0_progress,
-                None,
+                transaction_status_sender,
                 &replay_vote_sender,
                 &VerifyRecyclers::default(),
+                None,
+                false,
             );
 
             let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
@@ -3191,6 +6367,7 @@ mod tests {
                     0,
                     err,
                     &rpc_subscriptions,
+                    transaction_status_sender,
                     &mut DuplicateSlotsTracker::default(),
                     &GossipDuplicateConfirmedSlots::default(),
                     &mut progress,
@@ -3212,6 +6389,659 @@ mod tests {
         res
     }
 
+    fn entry_verification_failure_shreds(genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let bad_hash = hash(&[2; 30]);
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        let entry = entry::next_entry(
+            // Use wrong blockhash so that the entry causes an entry verification failure
+            &bad_hash,
+            hashes_per_tick.saturating_sub(1),
+            vec![system_transaction::transfer(
+                genesis_keypair,
+                &Keypair::new().pubkey(),
+                2,
+                blockhash,
+            )],
+        );
+        entries_to_test_shreds(vec![entry], slot, slot.saturating_sub(1), false, 0)
+    }
+
+    fn invalid_tick_hash_count_shreds(_genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        assert!(hashes_per_tick > 0);
+
+        let too_few_hashes_tick = Entry::new(&blockhash, hashes_per_tick - 1, vec![]);
+        entries_to_test_shreds(
+            vec![too_few_hashes_tick],
+            slot,
+            slot.saturating_sub(1),
+            false,
+            0,
+        )
+    }
+
+    fn too_many_ticks_shreds(_genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        entries_to_test_shreds(
+            entry::create_ticks(bank.ticks_per_slot() + 1, hashes_per_tick, blockhash),
+            slot,
+            slot.saturating_sub(1),
+            false,
+            0,
+        )
+    }
+
+    fn too_few_ticks_shreds(_genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        entries_to_test_shreds(
+            entry::create_ticks(bank.ticks_per_slot() - 1, hashes_per_tick, blockhash),
+            slot,
+            slot.saturating_sub(1),
+            true,
+            0,
+        )
+    }
+
+    fn invalid_last_tick_shreds(_genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        entries_to_test_shreds(
+            entry::create_ticks(bank.ticks_per_slot(), hashes_per_tick, blockhash),
+            slot,
+            slot.saturating_sub(1),
+            false,
+            0,
+        )
+    }
+
+    fn trailing_entry_shreds(genesis_keypair: &Keypair, bank: Arc<Bank>) -> Vec<Shred> {
+        let blockhash = bank.last_blockhash();
+        let slot = bank.slot();
+        let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+        let mut entries = entry::create_ticks(bank.ticks_per_slot(), hashes_per_tick, blockhash);
+        let last_entry_hash = entries.last().unwrap().hash;
+        let tx =
+            system_transaction::transfer(genesis_keypair, &Keypair::new().pubkey(), 2, blockhash);
+        let trailing_entry = entry::next_entry(&last_entry_hash, 1, vec![tx]);
+        entries.push(trailing_entry);
+        entries_to_test_shreds(entries, slot, slot.saturating_sub(1), true, 0)
+    }
+
+    #[test]
+    fn test_verify_slot() {
+        let block_errors: Vec<(fn(&Keypair, Arc<Bank>) -> Vec<Shred>, BlockError)> = vec![
+            (
+                entry_verification_failure_shreds,
+                BlockError::InvalidEntryHash {
+                    entry_index: 0,
+                    kind: HashFailureKind::Poh,
+                },
+            ),
+            (
+                invalid_tick_hash_count_shreds,
+                BlockError::InvalidTickHashCount,
+            ),
+            (too_many_ticks_shreds, BlockError::TooManyTicks),
+            (too_few_ticks_shreds, BlockError::TooFewTicks),
+            (invalid_last_tick_shreds, BlockError::InvalidLastTick),
+            (trailing_entry_shreds, BlockError::TrailingEntry),
+        ];
+        for (shred_to_insert, expected_error) in block_errors {
+            match check_verify_slot(shred_to_insert) {
+                Err(BlockstoreProcessorError::InvalidBlock(block_error)) => {
+                    assert_eq!(block_error, expected_error)
+                }
+                res => panic!("expected {:?}, got {:?}", expected_error, res.err()),
+            }
+        }
+
+        // A complete slot verifies, and its transactions are never executed even though
+        // one of them would fail
+        let keypair = Keypair::new();
+        let report = check_verify_slot(|_genesis_keypair, bank| {
+            let blockhash = bank.last_blockhash();
+            let slot = bank.slot();
+            let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
+            let tx = system_transaction::transfer(&Keypair::new(), &keypair.pubkey(), 2, blockhash);
+            let tx_entry = entry::next_entry(&blockhash, 1, vec![tx]);
+            let first_tick = entry::next_entry(&tx_entry.hash, hashes_per_tick - 1, vec![]);
+            let mut entries =
+                entry::create_ticks(bank.ticks_per_slot() - 1, hashes_per_tick, first_tick.hash);
+            entries.insert(0, first_tick);
+            entries.insert(0, tx_entry);
+            entries_to_test_shreds(entries, slot, slot.saturating_sub(1), true, 0)
+        })
+        .unwrap();
+        assert_eq!(report.num_entries, 65);
+        assert_eq!(report.num_ticks, 64);
+        assert_eq!(report.num_txs, 1);
+    }
+
+    // Inserts the shreds for the genesis bank's slot and verifies them with
+    // `verify_slot()`, checking that the bank is left untouched and the slot isn't
+    // marked dead. Returns the result for the caller to verify.
+    fn check_verify_slot<F>(
+        shred_to_insert: F,
+    ) -> result::Result<SlotVerificationReport, BlockstoreProcessorError>
+    where
+        F: Fn(&Keypair, Arc<Bank>) -> Vec<Shred>,
+    {
+        let ledger_path = get_tmp_ledger_path!();
+        let res = {
+            let blockstore = Blockstore::open(&ledger_path)
+                .expect("Expected to be able to open database ledger");
+            let GenesisConfigInfo {
+                mut genesis_config,
+                mint_keypair,
+                ..
+            } = create_genesis_config(1000);
+            genesis_config.poh_config.hashes_per_tick = Some(2);
+            let bank0 = Arc::new(Bank::new(&genesis_config));
+            let shreds = shred_to_insert(&mint_keypair, bank0.clone());
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+
+            let tick_height = bank0.tick_height();
+            let transaction_count = bank0.transaction_count();
+            let last_blockhash = bank0.last_blockhash();
+            let res = blockstore_processor::verify_slot(
+                &blockstore,
+                &SlotVerificationContext::new_from_bank(&bank0),
+                &VerifyRecyclers::default(),
+            );
+
+            assert_eq!(bank0.tick_height(), tick_height);
+            assert_eq!(bank0.transaction_count(), transaction_count);
+            assert_eq!(bank0.last_blockhash(), last_blockhash);
+            assert!(!blockstore.is_dead(bank0.slot()));
+            res
+        };
+        let _ignored = remove_dir_all(&ledger_path);
+        res
+    }
+
+    #[test]
+    fn test_replay_blockstore_into_bank_max_entry_recv_per_iter() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path).unwrap();
+            let (replay_vote_sender, _replay_vote_receiver) = unbounded();
+            let GenesisConfigInfo {
+                mut genesis_config,
+                mint_keypair,
+                ..
+            } = create_genesis_config(1000);
+            genesis_config.poh_config.hashes_per_tick = Some(2);
+            let bank0 = Arc::new(Bank::new(&genesis_config));
+            let mut bank0_progress = ForkProgress::new(
+                bank0.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            );
+
+            // Insert the slot as four separate data blocks of ticks, with a transfer in
+            // front of every eighth tick
+            let ticks_per_slot = bank0.ticks_per_slot() as usize;
+            let mut last_entry_hash = bank0.last_blockhash();
+            let mut entries = vec![];
+            for i in 0..ticks_per_slot {
+                if i % 8 == 0 {
+                    let tx = system_transaction::transfer(
+                        &mint_keypair,
+                        &solana_sdk::pubkey::new_rand(),
+                        1,
+                        bank0.last_blockhash(),
+                    );
+                    entries.push(entry::next_entry_mut(&mut last_entry_hash, 1, vec![tx]));
+                    entries.push(entry::next_entry_mut(&mut last_entry_hash, 1, vec![]));
+                } else {
+                    entries.push(entry::next_entry_mut(&mut last_entry_hash, 2, vec![]));
+                }
+            }
+            assert_eq!(entries.len() % 4, 0);
+            let shredder = Shredder::new(bank0.slot(), 0, 0, 0).unwrap();
+            let chunk_size = entries.len() / 4;
+            let mut next_shred_index = 0;
+            for (i, chunk) in entries.chunks(chunk_size).enumerate() {
+                let is_last_in_slot = (i + 1) * chunk_size >= entries.len();
+                let (data_shreds, _, last_shred_index) = shredder.entries_to_shreds(
+                    &mint_keypair,
+                    chunk,
+                    is_last_in_slot,
+                    next_shred_index,
+                );
+                blockstore.insert_shreds(data_shreds, None, false).unwrap();
+                next_shred_index = last_shred_index + 1;
+            }
+
+            let mut iterations = 0;
+            while !bank0.is_complete() {
+                let num_entries_before = bank0_progress.replay_progress.num_entries;
+                ReplayStage::replay_blockstore_into_bank(
+                    &bank0,
+                    &blockstore,
+                    &mut bank0_progress,
+                    None,
+                    &replay_vote_sender,
+                    &VerifyRecyclers::default(),
+                    Some(chunk_size),
+                    false,
+                )
+                .unwrap();
+                assert_eq!(
+                    bank0_progress.replay_progress.num_entries - num_entries_before,
+                    chunk_size
+                );
+                iterations += 1;
+            }
+            assert_eq!(iterations, 4);
+            assert_eq!(bank0_progress.replay_progress.num_entries, entries.len());
+            assert_eq!(bank0.tick_height(), bank0.max_tick_height());
+
+            // Replaying the whole slot at once yields the same bank
+            let single_shot_bank = Arc::new(Bank::new(&genesis_config));
+            let mut single_shot_progress = ForkProgress::new(
+                single_shot_bank.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            );
+            ReplayStage::replay_blockstore_into_bank(
+                &single_shot_bank,
+                &blockstore,
+                &mut single_shot_progress,
+                None,
+                &replay_vote_sender,
+                &VerifyRecyclers::default(),
+                None,
+                false,
+            )
+            .unwrap();
+            assert!(single_shot_bank.is_complete());
+            bank0.freeze();
+            single_shot_bank.freeze();
+            assert_eq!(bank0.hash(), single_shot_bank.hash());
+        }
+        Blockstore::destroy(&ledger_path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_timing_snapshot_accumulates() {
+        let mut replay_timing = ReplayTiming::default();
+        for _ in 0..3 {
+            replay_timing.vote_push_us += 2;
+            replay_timing.update(1, 1, 1, 1, 1, 1, 1, 1, 1, 10, 1, 1, 1, 1, 1, 1);
+        }
+        let snapshot = replay_timing.snapshot();
+        assert_eq!(snapshot.replay_active_banks_elapsed, 30);
+        assert_eq!(snapshot.collect_frozen_banks_elapsed, 3);
+        assert_eq!(snapshot.bank_count, 3);
+        assert_eq!(snapshot.vote_push_us, 6);
+
+        // Force the 1 second reporting window to elapse, the cumulative values
+        // must survive the reset of the window
+        replay_timing.last_print = 0;
+        replay_timing.update(1, 1, 1, 1, 1, 1, 1, 1, 1, 10, 1, 1, 1, 1, 1, 1);
+        assert_eq!(replay_timing.replay_active_banks_elapsed, 0);
+        assert_eq!(replay_timing.vote_push_us, 0);
+        let snapshot = replay_timing.snapshot();
+        assert_eq!(snapshot.replay_active_banks_elapsed, 40);
+        assert_eq!(snapshot.bank_count, 4);
+        assert_eq!(snapshot.vote_push_us, 6);
+        assert_eq!(snapshot.last_reset_timestamp, replay_timing.last_print);
+        assert!(snapshot.last_reset_timestamp > 0);
+    }
+
+    #[test]
+    fn test_replay_timing_reset_and_return() {
+        let mut replay_timing = ReplayTiming::default();
+        replay_timing.vote_push_us += 2;
+        replay_timing.record_bank_replay(1, 10, 2);
+        replay_timing.update(1, 1, 1, 1, 1, 1, 1, 1, 1, 10, 1, 1, 1, 1, 1, 1);
+        replay_timing.update(1, 1, 1, 1, 1, 1, 1, 1, 1, 10, 1, 1, 1, 1, 1, 1);
+
+        // The ended window keeps its data, the new one starts out empty
+        let window = replay_timing.reset_and_return();
+        assert_eq!(window.replay_active_banks_elapsed, 20);
+        assert_eq!(window.bank_count, 2);
+        assert_eq!(window.vote_push_us, 2);
+        assert_eq!(window.bank_timings.len(), 1);
+        assert_eq!(replay_timing.replay_active_banks_elapsed, 0);
+        assert_eq!(replay_timing.vote_push_us, 0);
+        assert!(replay_timing.bank_timings.is_empty());
+        assert!(replay_timing.last_print >= window.last_print);
+
+        // Both add up to the same cumulative totals, voting timings included
+        assert_eq!(replay_timing.snapshot().replay_active_banks_elapsed, 20);
+        assert_eq!(replay_timing.snapshot().vote_push_us, 2);
+        assert_eq!(window.snapshot().replay_active_banks_elapsed, 20);
+        assert_eq!(window.snapshot().vote_push_us, 2);
+
+        // Nothing is counted twice over the next window
+        replay_timing.update(1, 1, 1, 1, 1, 1, 1, 1, 1, 10, 1, 1, 1, 1, 1, 1);
+        let window = replay_timing.reset_and_return();
+        assert_eq!(window.replay_active_banks_elapsed, 10);
+        assert_eq!(replay_timing.snapshot().replay_active_banks_elapsed, 30);
+        assert_eq!(replay_timing.snapshot().vote_push_us, 2);
+    }
+
+    #[test]
+    fn test_answer_simulate_vote_requests() {
+        // Two validators voting on different forks
+        let mut vote_simulator = VoteSimulator::new(2);
+        let node_pubkeys = vote_simulator.node_pubkeys.clone();
+        let forks = tr(0) / (tr(1) / (tr(2) / tr(4)) / (tr(3) / tr(5)));
+        let mut cluster_votes = HashMap::new();
+        cluster_votes.insert(node_pubkeys[1], vec![3]);
+        vote_simulator.fill_bank_forks(forks, &cluster_votes);
+        let mut tower = Tower::new_with_key(&node_pubkeys[0]);
+        vote_simulator.simulate_vote(2, &node_pubkeys[0], &mut tower);
+        let bank4 = vote_simulator.bank_forks.read().unwrap()[4].clone();
+        let ancestors = vote_simulator.bank_forks.read().unwrap().ancestors();
+        let descendants = vote_simulator
+            .bank_forks
+            .read()
+            .unwrap()
+            .descendants()
+            .clone();
+
+        let (request_sender, request_receiver) = unbounded();
+        let (response_sender, response_receiver) = unbounded();
+        for slot in &[4, 6] {
+            request_sender
+                .send((*slot, response_sender.clone()))
+                .unwrap();
+        }
+        ReplayStage::answer_simulate_vote_requests(
+            &request_receiver,
+            &vote_simulator.bank_forks,
+            Some(&bank4),
+            &ancestors,
+            &descendants,
+            &vote_simulator.progress,
+            &tower,
+            &vote_simulator.latest_validator_votes_for_frozen_banks,
+            &vote_simulator.heaviest_subtree_fork_choice,
+        );
+        let results: Vec<_> = response_receiver.try_iter().collect();
+        assert_eq!(results.len(), 2);
+
+        // Slot 4 builds on the last vote, slot 6 has no bank
+        let result4 = results[0].as_ref().unwrap();
+        let (vote_bank, switch_fork_decision) = result4.vote_bank.as_ref().unwrap();
+        assert_eq!(vote_bank.slot(), 4);
+        assert_eq!(*switch_fork_decision, SwitchForkDecision::SameFork);
+        assert_eq!(result4.reset_bank.as_ref().unwrap().slot(), 4);
+        assert!(results[1].is_none());
+
+        // The simulation didn't vote
+        assert_eq!(tower.last_voted_slot(), Some(2));
+    }
+
+    #[test]
+    fn test_answer_explain_fork_choice_requests() {
+        // Two validators voting on different forks
+        let mut vote_simulator = VoteSimulator::new(2);
+        let node_pubkeys = vote_simulator.node_pubkeys.clone();
+        let forks = tr(0) / (tr(1) / (tr(2) / tr(4)) / (tr(3) / tr(5)));
+        let mut cluster_votes = HashMap::new();
+        cluster_votes.insert(node_pubkeys[1], vec![3]);
+        vote_simulator.fill_bank_forks(forks, &cluster_votes);
+        let mut tower = Tower::new_with_key(&node_pubkeys[0]);
+        vote_simulator.simulate_vote(2, &node_pubkeys[0], &mut tower);
+        let ancestors = vote_simulator.bank_forks.read().unwrap().ancestors();
+        let descendants = vote_simulator
+            .bank_forks
+            .read()
+            .unwrap()
+            .descendants()
+            .clone();
+
+        let (request_sender, request_receiver) = unbounded();
+        let (response_sender, response_receiver) = unbounded();
+        for slot in &[4, 6] {
+            request_sender
+                .send((*slot, response_sender.clone()))
+                .unwrap();
+        }
+        ReplayStage::answer_explain_fork_choice_requests(
+            &request_receiver,
+            &vote_simulator.bank_forks,
+            &ancestors,
+            &descendants,
+            &vote_simulator.progress,
+            &tower,
+            &vote_simulator.latest_validator_votes_for_frozen_banks,
+            &vote_simulator.heaviest_subtree_fork_choice,
+        );
+        let explanations: Vec<_> = response_receiver.try_iter().collect();
+        assert_eq!(explanations.len(), 2);
+
+        // The fork of the last vote is picked, and can be voted on without switching
+        let explanation4 = explanations[0].as_ref().unwrap();
+        let bank4_hash = vote_simulator.bank_forks.read().unwrap()[4].hash();
+        assert!(explanation4.starts_with(&format!("fork choice for 4 ({})", bank4_hash)));
+        let fork2 = explanation4
+            .lines()
+            .find(|line| line.trim_start().starts_with("picked 2 "))
+            .unwrap();
+        assert!(fork2.contains(
+            "locked out: false, vote threshold: passed, switch threshold: same fork, propagated: true"
+        ));
+        assert!(fork2.ends_with("leads to 4"));
+        assert!(explanation4.ends_with("4 is the best overall slot"));
+        // Slot 6 has no bank
+        assert!(explanations[1].is_none());
+    }
+
+    #[test]
+    fn test_answer_lockout_summary_requests() {
+        // Two validators voting on different forks
+        let mut vote_simulator = VoteSimulator::new(2);
+        let node_pubkeys = vote_simulator.node_pubkeys.clone();
+        let forks = tr(0) / (tr(1) / (tr(2) / tr(4)) / (tr(3) / tr(5)));
+        let mut cluster_votes = HashMap::new();
+        cluster_votes.insert(node_pubkeys[0], vec![2]);
+        cluster_votes.insert(node_pubkeys[1], vec![3]);
+        vote_simulator.fill_bank_forks(forks, &cluster_votes);
+        let mut tower = Tower::new_with_key(&node_pubkeys[0]);
+        vote_simulator.simulate_vote(4, &node_pubkeys[0], &mut tower);
+
+        let (request_sender, request_receiver) = unbounded();
+        let (response_sender, response_receiver) = unbounded();
+        for slot in &[4, 5, 6] {
+            request_sender
+                .send((*slot, response_sender.clone()))
+                .unwrap();
+        }
+        ReplayStage::answer_lockout_summary_requests(
+            &request_receiver,
+            &vote_simulator.progress,
+            &vote_simulator.bank_forks,
+        );
+        let summaries: Vec<_> = response_receiver.try_iter().collect();
+        assert_eq!(summaries.len(), 3);
+
+        // Each fork only sees the lockout of the validator that voted on it
+        let bank4 = vote_simulator.bank_forks.read().unwrap()[4].clone();
+        let stake = |vote_pubkey: &Pubkey| -> Stake {
+            bank4
+                .vote_accounts()
+                .into_iter()
+                .find(|(pubkey, _)| pubkey == vote_pubkey)
+                .map(|(_, (stake, _))| stake)
+                .unwrap()
+        };
+        let summary4 = summaries[0].as_ref().unwrap();
+        assert_eq!(
+            summary4.locked_out_stakes,
+            vec![(4, stake(&vote_simulator.vote_pubkeys[0]))]
+                .into_iter()
+                .collect()
+        );
+        let summary5 = summaries[1].as_ref().unwrap();
+        assert_eq!(
+            summary5.locked_out_stakes,
+            vec![(5, stake(&vote_simulator.vote_pubkeys[1]))]
+                .into_iter()
+                .collect()
+        );
+        // Slot 6 has no bank
+        assert!(summaries[2].is_none());
+    }
+
+    #[test]
+    fn test_replay_wait_catching_up() {
+        let max_wait = Duration::from_millis(DEFAULT_MAX_REPLAY_WAIT_MS);
+        let mut replay_wait = ReplayWaitBackoff::new(max_wait);
+        for _ in 0..6 {
+            replay_wait.back_off();
+        }
+
+        // Idle, or waiting on the shreds of the current slot, the backoff applies
+        assert_eq!(replay_wait.next_wait(0, true), max_wait);
+        assert_eq!(replay_wait.next_wait(1, true), max_wait);
+
+        // A backlog of banks to replay barely waits, whatever the backoff
+        let catchup_wait = Duration::from_millis(CATCHUP_REPLAY_WAIT_MS);
+        assert_eq!(
+            replay_wait.next_wait(MIN_CATCHUP_ACTIVE_BANKS, true),
+            catchup_wait
+        );
+        assert_eq!(replay_wait.next_wait(100, true), catchup_wait);
+        assert!(catchup_wait > Duration::from_millis(0));
+
+        // Banks that didn't replay any entries, e.g. stalled on an abandoned fork, aren't
+        // a backlog
+        assert_eq!(
+            replay_wait.next_wait(MIN_CATCHUP_ACTIVE_BANKS, false),
+            max_wait
+        );
+        assert_eq!(replay_wait.next_wait(100, false), max_wait);
+
+        // The catch-up wait never exceeds the configured maximum
+        let replay_wait = ReplayWaitBackoff::new(Duration::from_millis(1));
+        assert_eq!(replay_wait.next_wait(100, true), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_replay_wait_backoff() {
+        let max_wait = Duration::from_millis(DEFAULT_MAX_REPLAY_WAIT_MS);
+        let mut replay_wait = ReplayWaitBackoff::new(max_wait);
+        assert_eq!(
+            replay_wait.wait(),
+            Duration::from_millis(MIN_REPLAY_WAIT_MS)
+        );
+
+        // Each idle iteration doubles the wait, until it's capped
+        let waits: Vec<_> = (0..6)
+            .map(|_| {
+                replay_wait.back_off();
+                replay_wait.wait().as_millis()
+            })
+            .collect();
+        assert_eq!(waits, vec![20, 40, 80, 160, 200, 200]);
+
+        // Any activity goes back to the shortest wait
+        replay_wait.reset();
+        assert_eq!(
+            replay_wait.wait(),
+            Duration::from_millis(MIN_REPLAY_WAIT_MS)
+        );
+        replay_wait.back_off();
+        assert_eq!(replay_wait.wait(), Duration::from_millis(20));
+
+        // A maximum below the minimum wait always waits the maximum
+        let mut replay_wait = ReplayWaitBackoff::new(Duration::from_millis(5));
+        assert_eq!(replay_wait.wait(), Duration::from_millis(5));
+        replay_wait.back_off();
+        assert_eq!(replay_wait.wait(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_replay_timing_snapshot_recent_slots() {
+        let mut replay_timing = ReplayTiming::default();
+        let timing = ConfirmationTiming {
+            replay_elapsed: 7,
+            ..ConfirmationTiming::default()
+        };
+        let num_slots = MAX_SLOT_TIMINGS_IN_SNAPSHOT as u64 + 3;
+        for slot in 0..num_slots {
+            replay_timing.record_completed_slot(slot, &timing, 1, 2);
+        }
+        let snapshot = replay_timing.snapshot();
+        assert_eq!(snapshot.recent_slots.len(), MAX_SLOT_TIMINGS_IN_SNAPSHOT);
+        assert_eq!(snapshot.recent_slots.front().unwrap().slot, 3);
+        let last = snapshot.recent_slots.back().unwrap();
+        assert_eq!(last.slot, num_slots - 1);
+        assert_eq!(last.replay_elapsed, 7);
+        assert_eq!(last.num_entries, 1);
+        assert_eq!(last.num_shreds, 2);
+
+        // Resetting the metrics window doesn't drop the recorded slots
+        replay_timing.last_print = 0;
+        replay_timing.update(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+        assert_eq!(replay_timing.snapshot().recent_slots, snapshot.recent_slots);
+    }
+
+    #[test]
+    fn test_replay_timing_bank_timings() {
+        let mut replay_timing = ReplayTiming::default();
+
+        // Replays of the same bank within a window are merged
+        replay_timing.record_bank_replay(1, 10, 2);
+        replay_timing.record_bank_replay(2, 30, 1);
+        replay_timing.record_bank_replay(1, 5, 3);
+        assert_eq!(
+            replay_timing.bank_timings,
+            vec![
+                BankReplayTiming {
+                    slot: 1,
+                    replay_elapsed: 15,
+                    num_txs: 5,
+                },
+                BankReplayTiming {
+                    slot: 2,
+                    replay_elapsed: 30,
+                    num_txs: 1,
+                },
+            ]
+        );
+
+        // Only the most recent banks are kept
+        let num_slots = MAX_BANK_TIMINGS_PER_WINDOW as u64 + 3;
+        for slot in 0..num_slots {
+            replay_timing.record_bank_replay(slot + 10, 1, 1);
+        }
+        assert_eq!(
+            replay_timing.bank_timings.len(),
+            MAX_BANK_TIMINGS_PER_WINDOW
+        );
+        assert_eq!(replay_timing.bank_timings.front().unwrap().slot, 13);
+        assert_eq!(
+            replay_timing.bank_timings.back().unwrap().slot,
+            num_slots + 9
+        );
+
+        // Reporting the window resets the bank timings
+        replay_timing.last_print = 0;
+        replay_timing.update(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+        assert!(replay_timing.bank_timings.is_empty());
+    }
+
     #[test]
     fn test_replay_commitment_cache() {
         fn leader_vote(vote_slot: Slot, bank: &Arc<Bank>, pubkey: &Pubkey) {
@@ -3430,6 +7260,10 @@ mod tests {
             .cloned()
             .collect();
         let tower = Tower::new_for_tests(0, 0.67);
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
         let newly_computed = ReplayStage::compute_bank_stats(
             &my_vote_pubkey,
             &ancestors,
@@ -3441,6 +7275,8 @@ mod tests {
             &bank_forks,
             &mut heaviest_subtree_fork_choice,
             &mut latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
 
         // bank 0 has no votes, should not send any votes on the channel
@@ -3464,7 +7300,14 @@ mod tests {
         bank_forks.write().unwrap().insert(bank1);
         progress.insert(
             1,
-            ForkProgress::new(bank0.last_blockhash(), None, None, 0, 0),
+            ForkProgress::new(
+                bank0.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
         );
         let ancestors = bank_forks.read().unwrap().ancestors();
         let mut frozen_banks: Vec<_> = bank_forks
@@ -3485,6 +7328,8 @@ mod tests {
             &bank_forks,
             &mut heaviest_subtree_fork_choice,
             &mut latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
 
         // Bank 1 had one vote
@@ -3521,11 +7366,73 @@ mod tests {
             &bank_forks,
             &mut heaviest_subtree_fork_choice,
             &mut latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
         // No new stats should have been computed
         assert!(newly_computed.is_empty());
     }
 
+    #[test]
+    fn test_for_each_confirmed_fork_many_slots() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank0 = Bank::new(&genesis_config);
+        bank0.freeze();
+        let mut bank_forks = BankForks::new(bank0);
+        let mut progress = ProgressMap::default();
+        let num_slots: Slot = 256;
+        for slot in 0..num_slots {
+            if slot > 0 {
+                let parent = bank_forks.get(slot - 1).unwrap().clone();
+                let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+                // The last few banks are still being replayed
+                if slot < num_slots - 4 {
+                    bank.freeze();
+                }
+                bank_forks.insert(bank);
+            }
+            progress.insert(
+                slot,
+                ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD),
+            );
+        }
+        // Every third slot is already confirmed
+        for slot in (0..num_slots).step_by(3) {
+            progress.set_supermajority_confirmed_slot(slot);
+        }
+        // Every even slot has enough stake to be confirmed
+        let total_stake = 100;
+        let voted_stakes: VotedStakes = (0..num_slots)
+            .map(|slot| (slot, if slot % 2 == 0 { 68 } else { 50 }))
+            .collect();
+        let tower = Tower::new_for_tests(0, 0.67);
+
+        let mut visited = vec![];
+        ReplayStage::for_each_confirmed_fork(
+            &tower,
+            &voted_stakes,
+            total_stake,
+            &progress,
+            &bank_forks,
+            |slot, bank_hash| {
+                assert_eq!(bank_hash, bank_forks.get(slot).unwrap().hash());
+                visited.push(slot);
+            },
+        );
+        visited.sort_unstable();
+        let expected: Vec<Slot> = (0..num_slots)
+            .filter(|slot| slot % 2 == 0 && slot % 3 != 0 && *slot < num_slots - 4)
+            .collect();
+        assert_eq!(visited, expected);
+
+        // The Vec returning wrapper sees exactly the same slots
+        let bank_forks = RwLock::new(bank_forks);
+        let mut confirmed_forks =
+            ReplayStage::confirm_forks(&tower, &voted_stakes, total_stake, &progress, &bank_forks);
+        confirmed_forks.sort_unstable();
+        assert_eq!(confirmed_forks, visited);
+    }
+
     #[test]
     fn test_same_weight_select_lower_slot() {
         // Init state
@@ -3550,6 +7457,10 @@ mod tests {
         let ancestors = vote_simulator.bank_forks.read().unwrap().ancestors();
 
         let my_vote_pubkey = vote_simulator.vote_pubkeys[0];
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
         ReplayStage::compute_bank_stats(
             &my_vote_pubkey,
             &ancestors,
@@ -3561,6 +7472,8 @@ mod tests {
             &vote_simulator.bank_forks,
             &mut heaviest_subtree_fork_choice,
             &mut latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
 
         let bank1 = vote_simulator
@@ -3631,6 +7544,10 @@ mod tests {
             .collect();
 
         let my_vote_pubkey = vote_simulator.vote_pubkeys[0];
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
         ReplayStage::compute_bank_stats(
             &my_vote_pubkey,
             &vote_simulator.bank_forks.read().unwrap().ancestors(),
@@ -3642,6 +7559,8 @@ mod tests {
             &vote_simulator.bank_forks,
             &mut vote_simulator.heaviest_subtree_fork_choice,
             &mut vote_simulator.latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
 
         frozen_banks.sort_by_key(|bank| bank.slot());
@@ -3671,6 +7590,105 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compute_bank_stats_votes_for_uncomputed_banks() {
+        let mut vote_simulator = VoteSimulator::new(2);
+        let node_pubkeys = vote_simulator.node_pubkeys.clone();
+
+        // Each validator votes on a different fork, and those votes land in banks that are
+        // computed in the same batch as the voted on banks
+        let forks = tr(0) / (tr(1) / (tr(2))) / (tr(3) / (tr(4)));
+        let mut cluster_votes = HashMap::new();
+        cluster_votes.insert(node_pubkeys[0], vec![1]);
+        cluster_votes.insert(node_pubkeys[1], vec![3]);
+        vote_simulator.fill_bank_forks(forks, &cluster_votes);
+
+        let mut frozen_banks: Vec<_> = vote_simulator
+            .bank_forks
+            .read()
+            .unwrap()
+            .frozen_banks()
+            .values()
+            .cloned()
+            .collect();
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let new_stats = ReplayStage::compute_bank_stats(
+            &vote_simulator.vote_pubkeys[0],
+            &vote_simulator.bank_forks.read().unwrap().ancestors(),
+            &mut frozen_banks,
+            &Tower::new_with_key(&node_pubkeys[0]),
+            &mut vote_simulator.progress,
+            &VoteTracker::default(),
+            &ClusterSlots::default(),
+            &vote_simulator.bank_forks,
+            &mut vote_simulator.heaviest_subtree_fork_choice,
+            &mut vote_simulator.latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
+        );
+        assert_eq!(new_stats, vec![0, 1, 2, 3, 4]);
+
+        let bank_forks = vote_simulator.bank_forks.read().unwrap();
+        for slot in &[1, 3] {
+            let bank_hash = bank_forks.get(*slot).unwrap().hash();
+            assert!(
+                vote_simulator
+                    .heaviest_subtree_fork_choice
+                    .stake_voted_at(&(*slot, bank_hash))
+                    .unwrap()
+                    > 0
+            );
+        }
+    }
+
+    #[test]
+    fn test_skipped_slots_info_record_skipped_slot() {
+        let mut skipped_slots_info = SkippedSlotsInfo::default();
+
+        // Re-checking the same leader slot only counts it once
+        assert!(skipped_slots_info.record_skipped_slot(4));
+        assert!(!skipped_slots_info.record_skipped_slot(4));
+        assert_eq!(skipped_slots_info.skipped_count, 1);
+
+        assert!(skipped_slots_info.record_skipped_slot(5));
+        assert!(skipped_slots_info.record_skipped_slot(8));
+        assert_eq!(skipped_slots_info.skipped_count, 3);
+        assert_eq!(skipped_slots_info.last_skipped_slot, 8);
+
+        skipped_slots_info.maybe_report();
+        assert!(skipped_slots_info.last_report > 0);
+    }
+
+    #[test]
+    fn test_send_vote_to_tpu_failure() {
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = ClusterInfo::new(
+            Node::new_localhost_with_pubkey(&keypair.pubkey()).info,
+            keypair.clone(),
+        );
+        let vote_tx = system_transaction::transfer(
+            &keypair,
+            &solana_sdk::pubkey::new_rand(),
+            1,
+            Hash::default(),
+        );
+
+        // The gossip socket is bound to an IPv4 address, so sending to an IPv6
+        // target always fails
+        let unreachable_tpu: SocketAddr = "[::1]:8001".parse().unwrap();
+        assert!(!ReplayStage::send_vote_to_tpu(
+            &cluster_info,
+            &vote_tx,
+            Some(unreachable_tpu)
+        ));
+
+        // Falls back to our own TPU when the next leader is unknown
+        assert!(ReplayStage::send_vote_to_tpu(&cluster_info, &vote_tx, None));
+    }
+
     #[test]
     fn test_should_retransmit() {
         let poh_slot = 4;
@@ -3781,6 +7799,7 @@ mod tests {
                     &root_bank,
                     &mut propagated_stats,
                     child_reached_threshold,
+                    SUPERMINORITY_THRESHOLD,
                 );
 
             // Only the i'th voted pubkey should be new (everything else was
@@ -3839,6 +7858,7 @@ mod tests {
             &root_bank,
             &mut propagated_stats,
             child_reached_threshold,
+            SUPERMINORITY_THRESHOLD,
         ));
 
         // If propagation already happened (propagated_stats.is_propagated = true),
@@ -3855,6 +7875,7 @@ mod tests {
             &root_bank,
             &mut propagated_stats,
             child_reached_threshold,
+            SUPERMINORITY_THRESHOLD,
         ));
 
         let child_reached_threshold = false;
@@ -3864,6 +7885,7 @@ mod tests {
             &root_bank,
             &mut propagated_stats,
             child_reached_threshold,
+            SUPERMINORITY_THRESHOLD,
         ));
     }
 
@@ -3897,6 +7919,7 @@ mod tests {
                 }),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
         progress_map.insert(
@@ -3910,6 +7933,7 @@ mod tests {
                 }),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
 
@@ -3925,6 +7949,7 @@ mod tests {
             &RwLock::new(bank_forks),
             &vote_tracker,
             &ClusterSlots::default(),
+            SUPERMINORITY_THRESHOLD,
         );
 
         let propagated_stats = &progress_map.get(&10).unwrap().propagated_stats;
@@ -3998,6 +8023,7 @@ mod tests {
                     },
                     0,
                     0,
+                    SUPERMINORITY_THRESHOLD,
                 ),
             );
         }
@@ -4016,6 +8042,7 @@ mod tests {
             &RwLock::new(bank_forks),
             &vote_tracker,
             &ClusterSlots::default(),
+            SUPERMINORITY_THRESHOLD,
         );
 
         for i in 1..=10 {
@@ -4071,6 +8098,7 @@ mod tests {
                 }),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             );
 
             let end_range = {
@@ -4101,6 +8129,7 @@ mod tests {
             &RwLock::new(bank_forks),
             &vote_tracker,
             &ClusterSlots::default(),
+            SUPERMINORITY_THRESHOLD,
         );
 
         // Only the first 5 banks should have reached the threshold
@@ -4124,7 +8153,7 @@ mod tests {
         // should succeed
         progress_map.insert(
             parent_slot,
-            ForkProgress::new(Hash::default(), None, None, 0, 0),
+            ForkProgress::new(Hash::default(), None, None, 0, 0, SUPERMINORITY_THRESHOLD),
         );
         assert!(ReplayStage::check_propagation_for_start_leader(
             poh_slot,
@@ -4143,6 +8172,7 @@ mod tests {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
         assert!(!ReplayStage::check_propagation_for_start_leader(
@@ -4166,7 +8196,14 @@ mod tests {
         let previous_leader_slot = parent_slot - 1;
         progress_map.insert(
             parent_slot,
-            ForkProgress::new(Hash::default(), Some(previous_leader_slot), None, 0, 0),
+            ForkProgress::new(
+                Hash::default(),
+                Some(previous_leader_slot),
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
         );
         progress_map.insert(
             previous_leader_slot,
@@ -4176,6 +8213,7 @@ mod tests {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
 
@@ -4236,6 +8274,7 @@ mod tests {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
 
@@ -4271,6 +8310,7 @@ mod tests {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
 
@@ -4294,6 +8334,7 @@ mod tests {
                 Some(ValidatorStakeInfo::default()),
                 0,
                 0,
+                SUPERMINORITY_THRESHOLD,
             ),
         );
         assert!(!ReplayStage::check_propagation_for_start_leader(
@@ -4303,32 +8344,294 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_maybe_start_leader_min_online_stake() {
+        let my_pubkey = solana_sdk::pubkey::new_rand();
+        let GenesisConfigInfo { genesis_config, .. } =
+            create_genesis_config_with_leader(10_000, &my_pubkey, 100);
+        let bank0 = Bank::new(&genesis_config);
+        bank0.freeze();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank0)));
+        let bank0 = bank_forks.read().unwrap().root_bank();
+        let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank0));
+        let ledger_path = get_tmp_ledger_path!();
+        let blockstore = Arc::new(Blockstore::open(&ledger_path).unwrap());
+        let exit = Arc::new(AtomicBool::new(false));
+        let (poh_recorder, _entry_receiver, _record_receiver) = PohRecorder::new(
+            bank0.tick_height(),
+            bank0.last_blockhash(),
+            0,
+            None,
+            bank0.ticks_per_slot(),
+            &my_pubkey,
+            &blockstore,
+            &leader_schedule_cache,
+            &Arc::new(PohConfig::default()),
+            exit.clone(),
+        );
+        let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+        // We're the only staked node, so PoH immediately reaches our leader slot 1
+        poh_recorder
+            .lock()
+            .unwrap()
+            .reset(bank0.last_blockhash(), 0, Some((1, 1)));
+        let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
+            &exit,
+            bank_forks.clone(),
+            Arc::new(RwLock::new(BlockCommitmentCache::default())),
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+        ));
+        let mut progress = ProgressMap::default();
+        progress.insert(
+            0,
+            ForkProgress::new(
+                bank0.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
+        );
+        let (retransmit_slots_sender, _retransmit_slots_receiver) = unbounded();
+        let mut skipped_slots_info = SkippedSlotsInfo::default();
+
+        // Nobody has voted yet, so none of the stake is online
+        assert_eq!(ReplayStage::online_stake_fraction(&bank0), 0.0);
+        ReplayStage::maybe_start_leader(
+            &my_pubkey,
+            &bank_forks,
+            &poh_recorder,
+            &leader_schedule_cache,
+            &rpc_subscriptions,
+            &progress,
+            &retransmit_slots_sender,
+            &mut skipped_slots_info,
+            true,
+            Some(0.5),
+        );
+        assert!(!poh_recorder.lock().unwrap().has_bank());
+        assert!(bank_forks.read().unwrap().get(1).is_none());
+        assert_eq!(skipped_slots_info.skipped_count, 1);
+
+        ReplayStage::maybe_start_leader(
+            &my_pubkey,
+            &bank_forks,
+            &poh_recorder,
+            &leader_schedule_cache,
+            &rpc_subscriptions,
+            &progress,
+            &retransmit_slots_sender,
+            &mut skipped_slots_info,
+            true,
+            Some(0.0),
+        );
+        assert!(poh_recorder.lock().unwrap().has_bank());
+        assert!(bank_forks.read().unwrap().get(1).is_some());
+        assert_eq!(skipped_slots_info.skipped_count, 1);
+    }
+
+    #[test]
+    fn test_recover_dead_duplicate_confirmed_slots() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path).unwrap();
+            let (replay_vote_sender, _replay_vote_receiver) = unbounded();
+            let GenesisConfigInfo {
+                mut genesis_config, ..
+            } = create_genesis_config(1000);
+            genesis_config.poh_config.hashes_per_tick = Some(2);
+            let bank0 = Arc::new(Bank::new(&genesis_config));
+            bank0.freeze();
+            let bank_forks = Arc::new(RwLock::new(BankForks::new_from_banks(&[bank0.clone()], 0)));
+            let mut heaviest_subtree_fork_choice =
+                HeaviestSubtreeForkChoice::new((0, bank0.hash()));
+            let mut progress = ProgressMap::default();
+            progress.insert(
+                0,
+                ForkProgress::new(
+                    bank0.last_blockhash(),
+                    None,
+                    None,
+                    0,
+                    0,
+                    SUPERMINORITY_THRESHOLD,
+                ),
+            );
+            let exit = Arc::new(AtomicBool::new(false));
+            let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
+                &exit,
+                bank_forks.clone(),
+                Arc::new(RwLock::new(BlockCommitmentCache::default())),
+                OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            ));
+
+            // Replays the version of slot 1 currently in the blockstore into a new bank
+            let replay_slot_1 = |progress: &mut ProgressMap| {
+                let bank1 = bank_forks.write().unwrap().insert(Bank::new_from_parent(
+                    &bank0,
+                    &Pubkey::default(),
+                    1,
+                ));
+                let bank1_progress = progress.entry(1).or_insert_with(|| {
+                    ForkProgress::new(
+                        bank0.last_blockhash(),
+                        None,
+                        None,
+                        0,
+                        0,
+                        SUPERMINORITY_THRESHOLD,
+                    )
+                });
+                let res = ReplayStage::replay_blockstore_into_bank(
+                    &bank1,
+                    &blockstore,
+                    bank1_progress,
+                    None,
+                    &replay_vote_sender,
+                    &VerifyRecyclers::default(),
+                    None,
+                    false,
+                );
+                (bank1, res)
+            };
+
+            // Slot 1 dies replaying a version with too few ticks
+            let hashes_per_tick = bank0.hashes_per_tick().unwrap();
+            let entries = entry::create_ticks(
+                bank0.ticks_per_slot() - 1,
+                hashes_per_tick,
+                bank0.last_blockhash(),
+            );
+            blockstore
+                .insert_shreds(entries_to_test_shreds(entries, 1, 0, true, 0), None, false)
+                .unwrap();
+            let (bank1, res) = replay_slot_1(&mut progress);
+            let err = res.unwrap_err();
+            let mut gossip_duplicate_confirmed_slots = GossipDuplicateConfirmedSlots::default();
+            ReplayStage::mark_dead_slot(
+                &blockstore,
+                &bank1,
+                0,
+                &err,
+                &rpc_subscriptions,
+                None,
+                &mut DuplicateSlotsTracker::default(),
+                &gossip_duplicate_confirmed_slots,
+                &mut progress,
+                &mut heaviest_subtree_fork_choice,
+            );
+            assert!(progress.is_dead(1).unwrap());
+            assert!(blockstore.is_dead(1));
+
+            // Nothing is recovered until the cluster confirms a version of the slot
+            let mut ancestry_cache = AncestryCache::new(&bank_forks.read().unwrap());
+            let mut context = ReplayLoopContext::new(&bank_forks, &mut ancestry_cache);
+            ReplayStage::recover_dead_duplicate_confirmed_slots(
+                &gossip_duplicate_confirmed_slots,
+                context.ancestry,
+                &mut progress,
+                &bank_forks,
+                &blockstore,
+                &mut heaviest_subtree_fork_choice,
+            );
+            assert!(progress.is_dead(1).unwrap());
+
+            // Once it does, the dead version is purged
+            gossip_duplicate_confirmed_slots.insert(1, Hash::new_unique());
+            ReplayStage::recover_dead_duplicate_confirmed_slots(
+                &gossip_duplicate_confirmed_slots,
+                context.ancestry,
+                &mut progress,
+                &bank_forks,
+                &blockstore,
+                &mut heaviest_subtree_fork_choice,
+            );
+            assert!(bank_forks.read().unwrap().get(1).is_none());
+            assert!(progress.get(&1).is_none());
+            assert!(!blockstore.is_dead(1));
+            assert!(!blockstore.is_full(1));
+            assert!(!context.ancestry.ancestors.contains_key(&1));
+
+            // So another version of the slot can be inserted and replayed
+            let entries = entry::create_ticks(
+                bank0.ticks_per_slot(),
+                hashes_per_tick,
+                bank0.last_blockhash(),
+            );
+            blockstore
+                .insert_shreds(entries_to_test_shreds(entries, 1, 0, true, 0), None, false)
+                .unwrap();
+            // The bank is created again by the next iteration of the replay loop, before
+            // the context is taken
+            let (bank1, res) = replay_slot_1(&mut progress);
+            let mut context = ReplayLoopContext::new(&bank_forks, &mut ancestry_cache);
+            assert!(res.is_ok());
+            assert!(bank1.is_complete());
+            bank1.freeze();
+            assert!(!progress.is_dead(1).unwrap());
+
+            // And it's considered by fork choice again
+            assert_eq!(
+                context.ancestry.ancestors[&1],
+                vec![0].into_iter().collect::<HashSet<_>>()
+            );
+            assert!(context.frozen_banks().iter().any(|bank| bank.slot() == 1));
+
+            // Slots at or below the root are never resurrected
+            progress.get_mut(&0).unwrap().is_dead = true;
+            assert!(!ReplayStage::undead_slot(
+                0,
+                context.ancestry,
+                &mut progress,
+                &bank_forks,
+                &blockstore,
+                &mut heaviest_subtree_fork_choice,
+            ));
+            assert!(bank_forks.read().unwrap().get(0).is_some());
+        }
+        Blockstore::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_purge_unconfirmed_duplicate_slot() {
-        let (vote_simulator, _) = setup_default_forks(2);
+        let (vote_simulator, blockstore) = setup_default_forks(2);
         let VoteSimulator {
             bank_forks,
             mut progress,
+            mut heaviest_subtree_fork_choice,
             ..
         } = vote_simulator;
         let mut descendants = bank_forks.read().unwrap().descendants().clone();
         let mut ancestors = bank_forks.read().unwrap().ancestors();
+        let bank_hashes: HashMap<Slot, Hash> = bank_forks
+            .read()
+            .unwrap()
+            .banks()
+            .iter()
+            .map(|(slot, bank)| (*slot, bank.hash()))
+            .collect();
 
         // Purging slot 5 should purge only slots 5 and its descendant 6
-        ReplayStage::purge_unconfirmed_duplicate_slot(
+        let surviving_parent = ReplayStage::purge_unconfirmed_duplicate_slot(
             5,
             &mut ancestors,
             &mut descendants,
             &mut progress,
             &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
         );
+        assert_eq!(surviving_parent.unwrap().slot(), 3);
         for i in 5..=6 {
             assert!(bank_forks.read().unwrap().get(i).is_none());
             assert!(progress.get(&i).is_none());
+            assert!(!heaviest_subtree_fork_choice.contains_block(&(i, bank_hashes[&i])));
         }
         for i in 0..=4 {
             assert!(bank_forks.read().unwrap().get(i).is_some());
             assert!(progress.get(&i).is_some());
+            assert!(heaviest_subtree_fork_choice.contains_block(&(i, bank_hashes[&i])));
         }
 
         // Purging slot 4 should purge only slot 4
@@ -4340,14 +8643,18 @@ mod tests {
             &mut descendants,
             &mut progress,
             &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
         );
         for i in 4..=6 {
             assert!(bank_forks.read().unwrap().get(i).is_none());
             assert!(progress.get(&i).is_none());
+            assert!(!heaviest_subtree_fork_choice.contains_block(&(i, bank_hashes[&i])));
         }
         for i in 0..=3 {
             assert!(bank_forks.read().unwrap().get(i).is_some());
             assert!(progress.get(&i).is_some());
+            assert!(heaviest_subtree_fork_choice.contains_block(&(i, bank_hashes[&i])));
         }
 
         // Purging slot 1 should purge both forks 2 and 3
@@ -4359,64 +8666,378 @@ mod tests {
             &mut descendants,
             &mut progress,
             &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
         );
         for i in 1..=6 {
             assert!(bank_forks.read().unwrap().get(i).is_none());
             assert!(progress.get(&i).is_none());
+            assert!(!heaviest_subtree_fork_choice.contains_block(&(i, bank_hashes[&i])));
         }
         assert!(bank_forks.read().unwrap().get(0).is_some());
         assert!(progress.get(&0).is_some());
-    }
-
-    #[test]
-    fn test_purge_ancestors_descendants() {
-        let (VoteSimulator { bank_forks, .. }, _) = setup_default_forks(1);
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 0);
 
-        // Purge branch rooted at slot 2
+        // The root can't be purged
         let mut descendants = bank_forks.read().unwrap().descendants().clone();
         let mut ancestors = bank_forks.read().unwrap().ancestors();
-        let slot_2_descendants = descendants.get(&2).unwrap().clone();
-        ReplayStage::purge_ancestors_descendants(
-            2,
-            &slot_2_descendants,
+        assert!(ReplayStage::purge_unconfirmed_duplicate_slot(
+            0,
             &mut ancestors,
             &mut descendants,
-        );
-
-        // Result should be equivalent to removing slot from BankForks
-        // and regenerating the `ancestor` `descendant` maps
-        for d in slot_2_descendants {
-            bank_forks.write().unwrap().remove(d);
-        }
-        bank_forks.write().unwrap().remove(2);
-        assert!(check_map_eq(
-            &ancestors,
-            &bank_forks.read().unwrap().ancestors()
-        ));
-        assert!(check_map_eq(
-            &descendants,
-            bank_forks.read().unwrap().descendants()
-        ));
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+        )
+        .is_none());
+        assert!(bank_forks.read().unwrap().get(0).is_some());
+    }
 
-        // Try to purge the root
-        bank_forks
-            .write()
-            .unwrap()
-            .set_root(3, &AbsRequestSender::default(), None);
+    #[test]
+    fn test_purge_unconfirmed_duplicate_slot_clears_blockstore() {
+        let (vote_simulator, blockstore) = setup_default_forks(1);
+        let VoteSimulator {
+            bank_forks,
+            mut progress,
+            mut heaviest_subtree_fork_choice,
+            ..
+        } = vote_simulator;
         let mut descendants = bank_forks.read().unwrap().descendants().clone();
         let mut ancestors = bank_forks.read().unwrap().ancestors();
-        let slot_3_descendants = descendants.get(&3).unwrap().clone();
-        ReplayStage::purge_ancestors_descendants(
-            3,
-            &slot_3_descendants,
+
+        // Slot 5 was marked dead while replaying the locally bad version
+        blockstore.set_dead_slot(5).unwrap();
+        for i in 5..=6 {
+            assert!(blockstore.meta(i).unwrap().unwrap().consumed > 0);
+        }
+        ReplayStage::purge_unconfirmed_duplicate_slot(
+            5,
             &mut ancestors,
             &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
         );
 
-        assert!(ancestors.is_empty());
-        // Only remaining keys should be ones < root
-        for k in descendants.keys() {
-            assert!(*k < 3);
+        // The shreds and dead flag are cleared, but the slot is still chained to its
+        // parent so that the repaired version gets replayed
+        assert!(!blockstore.is_dead(5));
+        for i in 5..=6 {
+            let meta = blockstore.meta(i).unwrap().unwrap();
+            assert_eq!(meta.consumed, 0);
+            assert!(blockstore.get_data_shred(i, 0).unwrap().is_none());
+        }
+        assert_eq!(blockstore.meta(5).unwrap().unwrap().next_slots, vec![6]);
+        assert!(blockstore.meta(4).unwrap().unwrap().consumed > 0);
+    }
+
+    #[test]
+    fn test_purge_unconfirmed_duplicate_slot_with_active_bank() {
+        let (vote_simulator, blockstore) = setup_default_forks(1);
+        let VoteSimulator {
+            bank_forks,
+            mut progress,
+            mut heaviest_subtree_fork_choice,
+            ..
+        } = vote_simulator;
+
+        // Slot 7 is still being replayed on top of slot 6
+        let bank6 = bank_forks.read().unwrap().get(6).unwrap().clone();
+        let bank7 = Bank::new_from_parent(&bank6, &Pubkey::default(), 7);
+        bank_forks.write().unwrap().insert(bank7);
+        progress.insert(
+            7,
+            ForkProgress::new(
+                bank6.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
+        );
+        assert!(bank_forks.read().unwrap().active_banks().contains(&7));
+
+        // Purging the active bank itself only removes that bank, it was never added to
+        // fork choice
+        let mut descendants = bank_forks.read().unwrap().descendants().clone();
+        let mut ancestors = bank_forks.read().unwrap().ancestors();
+        let surviving_parent = ReplayStage::purge_unconfirmed_duplicate_slot(
+            7,
+            &mut ancestors,
+            &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+        );
+        assert_eq!(surviving_parent.unwrap().slot(), 6);
+        assert!(bank_forks.read().unwrap().get(7).is_none());
+        assert!(progress.get(&7).is_none());
+        assert!(bank_forks.read().unwrap().active_banks().is_empty());
+        assert!(heaviest_subtree_fork_choice.contains_block(&(6, bank6.hash())));
+
+        // Purging an ancestor of an active bank removes the active bank too
+        let bank7 = Bank::new_from_parent(&bank6, &Pubkey::default(), 7);
+        bank_forks.write().unwrap().insert(bank7);
+        progress.insert(
+            7,
+            ForkProgress::new(
+                bank6.last_blockhash(),
+                None,
+                None,
+                0,
+                0,
+                SUPERMINORITY_THRESHOLD,
+            ),
+        );
+        let mut descendants = bank_forks.read().unwrap().descendants().clone();
+        let mut ancestors = bank_forks.read().unwrap().ancestors();
+        ReplayStage::purge_unconfirmed_duplicate_slot(
+            5,
+            &mut ancestors,
+            &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+        );
+        for i in 5..=7 {
+            assert!(bank_forks.read().unwrap().get(i).is_none());
+            assert!(progress.get(&i).is_none());
+        }
+        assert!(bank_forks.read().unwrap().active_banks().is_empty());
+        assert!(check_map_eq(
+            &ancestors,
+            &bank_forks.read().unwrap().ancestors()
+        ));
+        assert!(check_map_eq(
+            &descendants,
+            bank_forks.read().unwrap().descendants()
+        ));
+    }
+
+    #[test]
+    fn test_reset_duplicate_slots() {
+        let forks = tr(0) / (tr(1) / (tr(2) / (tr(4))) / (tr(3) / (tr(5) / (tr(6)))));
+        let ReplayBlockstoreComponents {
+            blockstore,
+            my_pubkey,
+            mut progress,
+            leader_schedule_cache,
+            poh_recorder,
+            bank_forks,
+            ..
+        } = replay_blockstore_components(Some(forks));
+        let mut heaviest_subtree_fork_choice =
+            HeaviestSubtreeForkChoice::new_from_bank_forks(&bank_forks.read().unwrap());
+        let mut descendants = bank_forks.read().unwrap().descendants().clone();
+        let mut ancestors = bank_forks.read().unwrap().ancestors();
+        let (duplicate_slots_reset_sender, duplicate_slots_reset_receiver) = unbounded();
+
+        // PoH was reset onto slot 6, which descends from slot 5
+        let bank6 = bank_forks.read().unwrap().get(6).unwrap().clone();
+        ReplayStage::reset_poh_recorder(
+            &my_pubkey,
+            &blockstore,
+            &bank6,
+            &poh_recorder,
+            &leader_schedule_cache,
+        );
+        let mut last_reset = bank6.last_blockhash();
+
+        // Slot 2 isn't on the fork PoH is building on, so PoH isn't touched
+        duplicate_slots_reset_sender.send(2).unwrap();
+        ReplayStage::reset_duplicate_slots(
+            &duplicate_slots_reset_receiver,
+            &mut ancestors,
+            &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+            &poh_recorder,
+            &my_pubkey,
+            &leader_schedule_cache,
+            &mut last_reset,
+        );
+        assert_eq!(poh_recorder.lock().unwrap().last_reset_slot(), 6);
+        assert_eq!(last_reset, bank6.last_blockhash());
+
+        // Slot 5 is an ancestor of the PoH reset bank, so PoH is moved back onto slot 3.
+        // Slot 7 doesn't exist, so resetting it should be a no-op
+        for slot in &[5, 7] {
+            duplicate_slots_reset_sender.send(*slot).unwrap();
+        }
+        ReplayStage::reset_duplicate_slots(
+            &duplicate_slots_reset_receiver,
+            &mut ancestors,
+            &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+            &poh_recorder,
+            &my_pubkey,
+            &leader_schedule_cache,
+            &mut last_reset,
+        );
+        assert!(duplicate_slots_reset_receiver.is_empty());
+        let bank3 = bank_forks.read().unwrap().get(3).unwrap().clone();
+        assert_eq!(poh_recorder.lock().unwrap().last_reset_slot(), 3);
+        assert_eq!(last_reset, bank3.last_blockhash());
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 3);
+
+        // The purged forks 2 -> 4 and 5 -> 6 are gone from BankForks and progress
+        for i in &[2, 4, 5, 6] {
+            assert!(bank_forks.read().unwrap().get(*i).is_none());
+            assert!(progress.get(i).is_none());
+        }
+        for i in &[0, 1, 3] {
+            assert!(bank_forks.read().unwrap().get(*i).is_some());
+            assert!(progress.get(i).is_some());
+        }
+
+        // The `ancestors` and `descendants` maps are consistent with BankForks
+        assert!(check_map_eq(
+            &ancestors,
+            &bank_forks.read().unwrap().ancestors()
+        ));
+        assert!(check_map_eq(
+            &descendants,
+            bank_forks.read().unwrap().descendants()
+        ));
+
+        // Resetting an already purged slot, or one of its purged descendants, changes nothing
+        duplicate_slots_reset_sender.send(2).unwrap();
+        duplicate_slots_reset_sender.send(6).unwrap();
+        ReplayStage::reset_duplicate_slots(
+            &duplicate_slots_reset_receiver,
+            &mut ancestors,
+            &mut descendants,
+            &mut progress,
+            &bank_forks,
+            &blockstore,
+            &mut heaviest_subtree_fork_choice,
+            &poh_recorder,
+            &my_pubkey,
+            &leader_schedule_cache,
+            &mut last_reset,
+        );
+        assert_eq!(progress.len(), 3);
+        assert_eq!(last_reset, bank3.last_blockhash());
+        assert!(check_map_eq(
+            &ancestors,
+            &bank_forks.read().unwrap().ancestors()
+        ));
+        assert!(check_map_eq(
+            &descendants,
+            bank_forks.read().unwrap().descendants()
+        ));
+    }
+
+    #[test]
+    fn test_purge_ancestors_descendants() {
+        let (VoteSimulator { bank_forks, .. }, _) = setup_default_forks(1);
+
+        // Purge branch rooted at slot 2
+        let mut descendants = bank_forks.read().unwrap().descendants().clone();
+        let mut ancestors = bank_forks.read().unwrap().ancestors();
+        let slot_2_descendants = descendants.get(&2).unwrap().clone();
+        ReplayStage::purge_ancestors_descendants(
+            2,
+            &slot_2_descendants,
+            &mut ancestors,
+            &mut descendants,
+        );
+
+        // Result should be equivalent to removing slot from BankForks
+        // and regenerating the `ancestor` `descendant` maps
+        for d in slot_2_descendants {
+            bank_forks.write().unwrap().remove(d);
+        }
+        bank_forks.write().unwrap().remove(2);
+        assert!(check_map_eq(
+            &ancestors,
+            &bank_forks.read().unwrap().ancestors()
+        ));
+        assert!(check_map_eq(
+            &descendants,
+            bank_forks.read().unwrap().descendants()
+        ));
+
+        // Try to purge the root
+        bank_forks
+            .write()
+            .unwrap()
+            .set_root(3, &AbsRequestSender::default(), None);
+        let mut descendants = bank_forks.read().unwrap().descendants().clone();
+        let mut ancestors = bank_forks.read().unwrap().ancestors();
+        let slot_3_descendants = descendants.get(&3).unwrap().clone();
+        ReplayStage::purge_ancestors_descendants(
+            3,
+            &slot_3_descendants,
+            &mut ancestors,
+            &mut descendants,
+        );
+
+        assert!(ancestors.is_empty());
+        // Only remaining keys should be ones < root
+        for k in descendants.keys() {
+            assert!(*k < 3);
+        }
+    }
+
+    #[test]
+    fn test_purge_ancestors_descendants_random_forks() {
+        use rand::{thread_rng, Rng};
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            // A random fork tree of 50 banks rooted at slot 0
+            let mut bank_forks = BankForks::new(Bank::new(&genesis_config));
+            for slot in 1..50 {
+                let parent = bank_forks[rng.gen_range(0, slot)].clone();
+                bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+            }
+            let mut ancestors = bank_forks.ancestors();
+            let mut descendants = bank_forks.descendants().clone();
+
+            // Purge random forks until only the root is left, checking the maps against
+            // `BankForks` after each purge
+            while ancestors.len() > 1 {
+                let mut slots: Vec<Slot> = ancestors
+                    .keys()
+                    .copied()
+                    .filter(|slot| *slot != 0)
+                    .collect();
+                slots.sort_unstable();
+                let slot = slots[rng.gen_range(0, slots.len())];
+                let slot_descendants = descendants.get(&slot).unwrap().clone();
+                ReplayStage::purge_ancestors_descendants(
+                    slot,
+                    &slot_descendants,
+                    &mut ancestors,
+                    &mut descendants,
+                );
+
+                for d in slot_descendants.iter().chain(std::iter::once(&slot)) {
+                    bank_forks.remove(*d).unwrap();
+                }
+                assert!(check_map_eq(&ancestors, &bank_forks.ancestors()));
+                assert!(check_map_eq(&descendants, bank_forks.descendants()));
+
+                // Purging the same slot again is a no-op
+                ReplayStage::purge_ancestors_descendants(
+                    slot,
+                    &slot_descendants,
+                    &mut ancestors,
+                    &mut descendants,
+                );
+                assert!(check_map_eq(&ancestors, &bank_forks.ancestors()));
+            }
         }
     }
 
@@ -4466,6 +9087,10 @@ mod tests {
 
         // Update propagation status
         let tower = Tower::new_for_tests(0, 0.67);
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
         ReplayStage::compute_bank_stats(
             &validator_node_to_vote_keys[&my_pubkey],
             &ancestors,
@@ -4477,6 +9102,8 @@ mod tests {
             &bank_forks,
             &mut HeaviestSubtreeForkChoice::new_from_bank_forks(&bank_forks.read().unwrap()),
             &mut LatestValidatorVotesForFrozenBanks::default(),
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
         );
 
         // Check status is true
@@ -4519,16 +9146,18 @@ mod tests {
             Blockstore::open(&ledger_path).expect("Expected to be able to open database ledger"),
         );
         let mut tower = Tower::new_for_tests(8, 0.67);
+        let mut events = vec![];
 
         // All forks have same weight so heaviest bank to vote/reset on should be the tip of
         // the fork with the lower slot
-        let (vote_fork, reset_fork) = run_compute_and_select_forks(
+        events.push(run_compute_and_select_forks_with_event(
             &bank_forks,
             &mut progress,
             &mut tower,
             &mut vote_simulator.heaviest_subtree_fork_choice,
             &mut vote_simulator.latest_validator_votes_for_frozen_banks,
-        );
+        ));
+        let (vote_fork, reset_fork) = vote_and_reset_slots(events.last().unwrap());
         assert_eq!(vote_fork.unwrap(), 4);
         assert_eq!(reset_fork.unwrap(), 4);
 
@@ -4556,13 +9185,14 @@ mod tests {
             SlotStateUpdate::Duplicate,
         );
 
-        let (vote_fork, reset_fork) = run_compute_and_select_forks(
+        events.push(run_compute_and_select_forks_with_event(
             &bank_forks,
             &mut progress,
             &mut tower,
             &mut vote_simulator.heaviest_subtree_fork_choice,
             &mut vote_simulator.latest_validator_votes_for_frozen_banks,
-        );
+        ));
+        let (vote_fork, reset_fork) = vote_and_reset_slots(events.last().unwrap());
         assert!(vote_fork.is_none());
         assert_eq!(reset_fork.unwrap(), 3);
 
@@ -4581,13 +9211,14 @@ mod tests {
             SlotStateUpdate::Duplicate,
         );
 
-        let (vote_fork, reset_fork) = run_compute_and_select_forks(
+        events.push(run_compute_and_select_forks_with_event(
             &bank_forks,
             &mut progress,
             &mut tower,
             &mut vote_simulator.heaviest_subtree_fork_choice,
             &mut vote_simulator.latest_validator_votes_for_frozen_banks,
-        );
+        ));
+        let (vote_fork, reset_fork) = vote_and_reset_slots(events.last().unwrap());
 
         // Should now pick the next heaviest fork that is not a descendant of 2, which is 6.
         // However the lockout from vote 4 should still apply, so 6 should not be votable
@@ -4607,65 +9238,462 @@ mod tests {
             &mut vote_simulator.heaviest_subtree_fork_choice,
             SlotStateUpdate::DuplicateConfirmed,
         );
-        let (vote_fork, reset_fork) = run_compute_and_select_forks(
+        events.push(run_compute_and_select_forks_with_event(
             &bank_forks,
             &mut progress,
             &mut tower,
             &mut vote_simulator.heaviest_subtree_fork_choice,
             &mut vote_simulator.latest_validator_votes_for_frozen_banks,
-        );
+        ));
+        let (vote_fork, reset_fork) = vote_and_reset_slots(events.last().unwrap());
         // Should now pick the heaviest fork 4 again, but lockouts apply so fork 4
         // is not votable, which avoids voting for 4 again.
         assert!(vote_fork.is_none());
         assert_eq!(reset_fork.unwrap(), 4);
+
+        // Each fork selection above is reported with the heaviest bank it was made
+        // from, and every selection after the vote on 4 is blocked by its lockout
+        let bank_hash = |slot| bank_forks.read().unwrap().get(slot).unwrap().hash();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            ForkChoiceEvent::SelectedForks {
+                heaviest_slot: 4,
+                heaviest_hash: bank4_hash,
+                heaviest_on_same_voted_fork_slot: None,
+                switch_fork_decision: Some(SwitchForkDecision::SameFork),
+                heaviest_fork_failures: vec![],
+                vote_slot: Some(4),
+                reset_slot: Some(4),
+            }
+        );
+        for (event, expected_slot) in events[1..].iter().zip(&[3, 6, 4]) {
+            match event {
+                ForkChoiceEvent::SelectedForks {
+                    heaviest_slot,
+                    heaviest_hash,
+                    heaviest_on_same_voted_fork_slot,
+                    switch_fork_decision,
+                    heaviest_fork_failures,
+                    vote_slot,
+                    reset_slot,
+                } => {
+                    assert_eq!(heaviest_slot, expected_slot);
+                    assert_eq!(*heaviest_hash, bank_hash(*expected_slot));
+                    assert!(heaviest_on_same_voted_fork_slot.is_none());
+                    assert!(switch_fork_decision.is_none());
+                    assert!(heaviest_fork_failures
+                        .contains(&HeaviestForkFailures::LockedOut(*expected_slot)));
+                    assert!(vote_slot.is_none());
+                    assert_eq!(reset_slot, &Some(*expected_slot));
+                }
+            }
+        }
     }
 
     #[test]
-    fn test_gossip_vote_doesnt_affect_fork_choice() {
-        let (
-            VoteSimulator {
-                bank_forks,
-                mut heaviest_subtree_fork_choice,
-                mut latest_validator_votes_for_frozen_banks,
-                vote_pubkeys,
-                ..
-            },
-            _,
-        ) = setup_default_forks(1);
-
-        let vote_pubkey = vote_pubkeys[0];
-        let mut unfrozen_gossip_verified_vote_hashes = UnfrozenGossipVerifiedVoteHashes::default();
-        let (gossip_verified_vote_hash_sender, gossip_verified_vote_hash_receiver) = unbounded();
+    fn test_recent_vote_failures_locked_out() {
+        let forks = tr(0) / (tr(1) / tr(2));
+        let mut vote_simulator = VoteSimulator::new(1);
+        vote_simulator.fill_bank_forks(forks, &HashMap::new());
+        let VoteSimulator {
+            bank_forks,
+            mut progress,
+            mut heaviest_subtree_fork_choice,
+            mut latest_validator_votes_for_frozen_banks,
+            ..
+        } = vote_simulator;
+        let mut tower = Tower::new_for_tests(8, 0.67);
+        let recent_vote_failures = RwLock::new(VecDeque::new());
 
-        // Best slot is 4
-        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+        let mut select_forks = |tower: &mut Tower| {
+            let event = run_compute_and_select_forks_with_event(
+                &bank_forks,
+                &mut progress,
+                tower,
+                &mut heaviest_subtree_fork_choice,
+                &mut latest_validator_votes_for_frozen_banks,
+            );
+            let ForkChoiceEvent::SelectedForks {
+                heaviest_slot,
+                heaviest_fork_failures,
+                ..
+            } = event;
+            ReplayStage::record_vote_failures(
+                &recent_vote_failures,
+                heaviest_slot,
+                &heaviest_fork_failures,
+            );
+        };
 
-        // Cast a vote for slot 3 on one fork
-        let vote_slot = 3;
-        let vote_bank = bank_forks.read().unwrap().get(vote_slot).unwrap().clone();
-        gossip_verified_vote_hash_sender
-            .send((vote_pubkey, vote_slot, vote_bank.hash()))
-            .expect("Send should succeed");
-        ReplayStage::process_gossip_verified_vote_hashes(
-            &gossip_verified_vote_hash_receiver,
-            &mut unfrozen_gossip_verified_vote_hashes,
-            &heaviest_subtree_fork_choice,
-            &mut latest_validator_votes_for_frozen_banks,
-        );
+        // Slot 2 can be voted on, nothing is recorded
+        select_forks(&mut tower);
+        assert!(ReplayStage::most_recent_vote_failures(&recent_vote_failures, 10).is_empty());
 
-        // Pick the best fork. Gossip votes shouldn't affect fork choice
-        heaviest_subtree_fork_choice.compute_bank_stats(
-            &vote_bank,
-            &Tower::default(),
-            &mut latest_validator_votes_for_frozen_banks,
+        // Once voted on, the still heaviest slot 2 is locked out. Later evaluations
+        // failing the same way aren't recorded again
+        tower.record_bank_vote(
+            bank_forks.read().unwrap().get(2).unwrap(),
+            &Pubkey::default(),
         );
-
-        // Best slot is still 4
-        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+        select_forks(&mut tower);
+        select_forks(&mut tower);
+        let failures = ReplayStage::most_recent_vote_failures(&recent_vote_failures, 10);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].slot, 2);
+        assert!(failures[0]
+            .failures
+            .contains(&HeaviestForkFailures::LockedOut(2)));
+        assert!(ReplayStage::most_recent_vote_failures(&recent_vote_failures, 0).is_empty());
     }
 
     #[test]
-    fn test_replay_stage_refresh_last_vote() {
+    fn test_record_vote_failures_bounded() {
+        let recent_vote_failures = RwLock::new(VecDeque::new());
+        for slot in 0..MAX_RECENT_VOTE_FAILURES as Slot + 2 {
+            ReplayStage::record_vote_failures(
+                &recent_vote_failures,
+                slot,
+                &[HeaviestForkFailures::FailedThreshold(slot)],
+            );
+        }
+        let failures = ReplayStage::most_recent_vote_failures(&recent_vote_failures, usize::MAX);
+        assert_eq!(failures.len(), MAX_RECENT_VOTE_FAILURES);
+        assert_eq!(
+            failures.first().unwrap().slot,
+            MAX_RECENT_VOTE_FAILURES as Slot + 1
+        );
+        assert_eq!(failures.last().unwrap().slot, 2);
+    }
+
+    #[test]
+    fn test_sort_banks_by_replay_priority() {
+        /*
+            Build fork structure:
+
+                 slot 0
+                   |
+                 slot 1
+                 /    \
+            slot 2    |
+               |    slot 3
+            slot 4    |
+                    slot 5
+                      |
+                    slot 6
+        */
+        let forks = tr(0) / (tr(1) / (tr(2) / (tr(4))) / (tr(3) / (tr(5) / (tr(6)))));
+        let mut vote_simulator = VoteSimulator::new(3);
+        let validator_votes: HashMap<Pubkey, Vec<u64>> = vec![
+            (vote_simulator.node_pubkeys[0], vec![4]),
+            (vote_simulator.node_pubkeys[1], vec![6]),
+            (vote_simulator.node_pubkeys[2], vec![6]),
+        ]
+        .into_iter()
+        .collect();
+        vote_simulator.fill_bank_forks(forks, &validator_votes);
+        let (bank_forks, mut progress) = (vote_simulator.bank_forks, vote_simulator.progress);
+        let mut tower = Tower::new_for_tests(8, 0.67);
+        run_compute_and_select_forks_with_event(
+            &bank_forks,
+            &mut progress,
+            &mut tower,
+            &mut vote_simulator.heaviest_subtree_fork_choice,
+            &mut vote_simulator.latest_validator_votes_for_frozen_banks,
+        );
+        let fork_choice = &vote_simulator.heaviest_subtree_fork_choice;
+        let bank_hash = |slot| bank_forks.read().unwrap().get(slot).unwrap().hash();
+        assert!(
+            fork_choice.stake_voted_subtree(&(6, bank_hash(6)))
+                > fork_choice.stake_voted_subtree(&(4, bank_hash(4)))
+        );
+
+        // Active banks on both forks, and one whose parent is still being replayed
+        let new_bank = |parent_slot, slot| {
+            let parent = bank_forks.read().unwrap().get(parent_slot).unwrap().clone();
+            Arc::new(Bank::new_from_parent(&parent, &Pubkey::default(), slot))
+        };
+        let bank7 = new_bank(4, 7);
+        let bank10 = Arc::new(Bank::new_from_parent(&bank7, &Pubkey::default(), 10));
+        let mut banks = vec![bank10, bank7, new_bank(6, 9), new_bank(6, 8)];
+
+        // The heavier fork is replayed first, and a bank is never replayed before its parent
+        ReplayStage::sort_banks_by_replay_priority(&mut banks, fork_choice);
+        let slots: Vec<Slot> = banks.iter().map(|bank| bank.slot()).collect();
+        assert_eq!(slots, vec![8, 9, 7, 10]);
+    }
+
+    #[test]
+    fn test_publish_replay_status() {
+        let forks = tr(0) / (tr(1) / (tr(2) / (tr(4))) / (tr(3) / (tr(5) / (tr(6)))));
+        let mut vote_simulator = VoteSimulator::new(1);
+        let validator_votes: HashMap<Pubkey, Vec<u64>> =
+            vec![(vote_simulator.node_pubkeys[0], vec![6])]
+                .into_iter()
+                .collect();
+        vote_simulator.fill_bank_forks(forks, &validator_votes);
+        let (bank_forks, mut progress) = (vote_simulator.bank_forks, vote_simulator.progress);
+
+        // Our last vote is on the other fork
+        let mut tower = Tower::new_for_tests(8, 0.67);
+        tower.record_bank_vote(
+            bank_forks.read().unwrap().get(4).unwrap(),
+            &Pubkey::default(),
+        );
+
+        let event = run_compute_and_select_forks_with_event(
+            &bank_forks,
+            &mut progress,
+            &mut tower,
+            &mut vote_simulator.heaviest_subtree_fork_choice,
+            &mut vote_simulator.latest_validator_votes_for_frozen_banks,
+        );
+        let ForkChoiceEvent::SelectedForks { heaviest_slot, .. } = event;
+        assert_eq!(heaviest_slot, 6);
+
+        // Slot 7 is still being replayed on top of slot 6
+        let bank6 = bank_forks.read().unwrap().get(6).unwrap().clone();
+        bank_forks
+            .write()
+            .unwrap()
+            .insert(Bank::new_from_parent(&bank6, &Pubkey::default(), 7));
+        let ancestors = bank_forks.read().unwrap().ancestors();
+        let partition_exists = ReplayStage::is_partition_detected(&ancestors, 4, heaviest_slot);
+
+        let replay_status = RwLock::new(ReplayStatus::default());
+        ReplayStage::publish_replay_status(
+            &replay_status,
+            &bank_forks,
+            heaviest_slot,
+            &tower,
+            partition_exists,
+        );
+        assert_eq!(
+            *replay_status.read().unwrap(),
+            ReplayStatus {
+                active_slots: vec![7],
+                heaviest_slot: 6,
+                last_voted_slot: Some(4),
+                partition_exists: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_process_duplicate_slots_recovery_mode() {
+        for duplicate_slot_recovery_mode in &[
+            DuplicateSlotRecoveryMode::Aggressive,
+            DuplicateSlotRecoveryMode::Conservative,
+        ] {
+            // Same fork structure as `test_unconfirmed_duplicate_slots_and_lockouts()`
+            let forks = tr(0) / (tr(1) / (tr(2) / (tr(3) / (tr(4)))) / (tr(5) / (tr(6))));
+            let mut vote_simulator = VoteSimulator::new(2);
+            let validator_votes: HashMap<Pubkey, Vec<u64>> = vec![
+                (vote_simulator.node_pubkeys[0], vec![5]),
+                (vote_simulator.node_pubkeys[1], vec![2]),
+            ]
+            .into_iter()
+            .collect();
+            vote_simulator.fill_bank_forks(forks, &validator_votes);
+
+            let (bank_forks, mut progress) = (vote_simulator.bank_forks, vote_simulator.progress);
+            let ledger_path = get_tmp_ledger_path!();
+            let blockstore = Arc::new(
+                Blockstore::open(&ledger_path)
+                    .expect("Expected to be able to open database ledger"),
+            );
+            let mut tower = Tower::new_for_tests(8, 0.67);
+            let mut descendants = bank_forks.read().unwrap().descendants().clone();
+            let mut ancestors = bank_forks.read().unwrap().ancestors();
+            let bank4_hash = bank_forks.read().unwrap().get(4).unwrap().hash();
+
+            // Signal 4 as duplicate
+            let (duplicate_slots_sender, duplicate_slots_receiver) = unbounded();
+            duplicate_slots_sender.send(4).unwrap();
+            let mut duplicate_slots_tracker = DuplicateSlotsTracker::default();
+            ReplayStage::process_duplicate_slots(
+                &duplicate_slots_receiver,
+                &mut duplicate_slots_tracker,
+                &GossipDuplicateConfirmedSlots::default(),
+                &mut ancestors,
+                &mut descendants,
+                &bank_forks,
+                &blockstore,
+                &mut progress,
+                &mut vote_simulator.heaviest_subtree_fork_choice,
+                *duplicate_slot_recovery_mode,
+            );
+            assert!(duplicate_slots_tracker.contains(&4));
+
+            match duplicate_slot_recovery_mode {
+                DuplicateSlotRecoveryMode::Aggressive => {
+                    // 4 is purged without waiting for the cluster
+                    assert!(bank_forks.read().unwrap().get(4).is_none());
+                    assert!(progress.get(&4).is_none());
+                    assert!(!ancestors.contains_key(&4));
+                    assert!(!vote_simulator
+                        .heaviest_subtree_fork_choice
+                        .contains_block(&(4, bank4_hash)));
+                }
+                DuplicateSlotRecoveryMode::Conservative => {
+                    // 4 is kept around, but can't be built on
+                    assert!(bank_forks.read().unwrap().get(4).is_some());
+                    assert!(progress.get(&4).is_some());
+                    assert_eq!(
+                        vote_simulator
+                            .heaviest_subtree_fork_choice
+                            .is_unconfirmed_duplicate(&(4, bank4_hash)),
+                        Some(true)
+                    );
+                }
+            }
+
+            // Either way 3 is now the heaviest slot to reset to
+            let event = run_compute_and_select_forks_with_event(
+                &bank_forks,
+                &mut progress,
+                &mut tower,
+                &mut vote_simulator.heaviest_subtree_fork_choice,
+                &mut vote_simulator.latest_validator_votes_for_frozen_banks,
+            );
+            let (_vote_fork, reset_fork) = vote_and_reset_slots(&event);
+            assert_eq!(reset_fork.unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn test_gossip_vote_doesnt_affect_fork_choice() {
+        let (
+            VoteSimulator {
+                bank_forks,
+                mut heaviest_subtree_fork_choice,
+                mut latest_validator_votes_for_frozen_banks,
+                vote_pubkeys,
+                ..
+            },
+            _,
+        ) = setup_default_forks(1);
+
+        let vote_pubkey = vote_pubkeys[0];
+        let mut unfrozen_gossip_verified_vote_hashes = UnfrozenGossipVerifiedVoteHashes::default();
+        let (gossip_verified_vote_hash_sender, gossip_verified_vote_hash_receiver) = unbounded();
+
+        // Best slot is 4
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+
+        // Cast a vote for slot 3 on one fork
+        let vote_slot = 3;
+        let vote_bank = bank_forks.read().unwrap().get(vote_slot).unwrap().clone();
+        gossip_verified_vote_hash_sender
+            .send((vote_pubkey, vote_slot, vote_bank.hash()))
+            .expect("Send should succeed");
+        ReplayStage::process_gossip_verified_vote_hashes(
+            &gossip_verified_vote_hash_receiver,
+            &mut unfrozen_gossip_verified_vote_hashes,
+            &heaviest_subtree_fork_choice,
+            &mut latest_validator_votes_for_frozen_banks,
+        );
+
+        // Pick the best fork. Gossip votes shouldn't affect fork choice
+        heaviest_subtree_fork_choice.compute_bank_stats(
+            &vote_bank,
+            &Tower::default(),
+            &mut latest_validator_votes_for_frozen_banks,
+        );
+
+        // Best slot is still 4
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+    }
+
+    #[test]
+    fn test_handle_votable_bank_voting_disabled() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            mut validator_keypairs,
+            my_pubkey,
+            mut progress,
+            cluster_info,
+            leader_schedule_cache,
+            poh_recorder,
+            bank_forks,
+            mut tower,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(Some(tr(0) / (tr(1) / (tr(2) / tr(3)))));
+        let cluster_info = Arc::new(cluster_info);
+        let poh_recorder = Arc::new(poh_recorder);
+        let identity_keypair = cluster_info.keypair().clone();
+        let my_vote_keypair = vec![Arc::new(
+            validator_keypairs.remove(&my_pubkey).unwrap().vote_keypair,
+        )];
+        let my_vote_pubkey = my_vote_keypair[0].pubkey();
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        let (lockouts_sender, _lockouts_receiver) = channel();
+        let block_commitment_cache = Arc::new(RwLock::new(BlockCommitmentCache::default()));
+        let mut heaviest_subtree_fork_choice = HeaviestSubtreeForkChoice::new((0, bank0.hash()));
+        let mut voted_signatures = VecDeque::new();
+        let mut has_new_vote_been_rooted = false;
+        let mut cursor = Cursor::default();
+        let last_voted_slot_and_hash = RwLock::new(None);
+
+        for slot in 1..=3 {
+            let bank = bank_forks.read().unwrap().get(slot).unwrap().clone();
+            ReplayStage::handle_votable_bank(
+                &bank,
+                &poh_recorder,
+                &SwitchForkDecision::SameFork,
+                &bank_forks,
+                &mut tower,
+                &mut progress,
+                &my_vote_pubkey,
+                &identity_keypair,
+                &identity_keypair,
+                &my_vote_keypair,
+                &cluster_info,
+                &blockstore,
+                &leader_schedule_cache,
+                &lockouts_sender,
+                &AbsRequestSender::default(),
+                &[],
+                &rpc_subscriptions,
+                &block_commitment_cache,
+                &mut heaviest_subtree_fork_choice,
+                &None,
+                &mut DuplicateSlotsTracker::default(),
+                &mut GossipDuplicateConfirmedSlots::default(),
+                &mut UnfrozenGossipVerifiedVoteHashes::default(),
+                &mut voted_signatures,
+                MAX_VOTE_SIGNATURES,
+                &mut has_new_vote_been_rooted,
+                &mut ReplayTiming::default(),
+                &last_voted_slot_and_hash,
+                true,
+            );
+
+            // The tower tracks the vote, but nothing is ever sent to gossip
+            assert_eq!(tower.last_voted_slot(), Some(slot));
+            assert_eq!(
+                *last_voted_slot_and_hash.read().unwrap(),
+                Some((slot, bank.hash()))
+            );
+            let (_, votes) = cluster_info.get_votes(&mut cursor);
+            assert!(votes.is_empty());
+            assert!(voted_signatures.is_empty());
+        }
+    }
+
+    fn fill_bank_with_ticks(bank: &Bank) {
+        let parent_distance = bank.slot() - bank.parent_slot();
+        for _ in 0..parent_distance {
+            let last_blockhash = bank.last_blockhash();
+            while bank.last_blockhash() == last_blockhash {
+                bank.register_tick(&Hash::new_unique())
+            }
+        }
+    }
+
+    fn run_test_replay_stage_refresh_last_vote(vote_refresh_interval_millis: u64) {
+        let vote_refresh_interval = Duration::from_millis(vote_refresh_interval_millis);
         let ReplayBlockstoreComponents {
             mut validator_keypairs,
             cluster_info,
@@ -4676,12 +9704,13 @@ mod tests {
             ..
         } = replay_blockstore_components(None);
 
+        let mut now = Instant::now();
         let mut last_vote_refresh_time = LastVoteRefreshTime {
-            last_refresh_time: Instant::now(),
-            last_print_time: Instant::now(),
+            last_refresh_time: now,
+            last_print_time: now,
         };
         let has_new_vote_been_rooted = false;
-        let mut voted_signatures = vec![];
+        let mut voted_signatures = VecDeque::new();
 
         let identity_keypair = cluster_info.keypair().clone();
         let my_vote_keypair = vec![Arc::new(
@@ -4690,16 +9719,6 @@ mod tests {
         let my_vote_pubkey = my_vote_keypair[0].pubkey();
         let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
 
-        fn fill_bank_with_ticks(bank: &Bank) {
-            let parent_distance = bank.slot() - bank.parent_slot();
-            for _ in 0..parent_distance {
-                let last_blockhash = bank.last_blockhash();
-                while bank.last_blockhash() == last_blockhash {
-                    bank.register_tick(&Hash::new_unique())
-                }
-            }
-        }
-
         // Simulate landing a vote for slot 0 landing in slot 1
         let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
         fill_bank_with_ticks(&bank1);
@@ -4714,6 +9733,7 @@ mod tests {
             &mut tower,
             &SwitchForkDecision::SameFork,
             &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
             has_new_vote_been_rooted,
             &mut ReplayTiming::default(),
         );
@@ -4743,8 +9763,11 @@ mod tests {
                 &identity_keypair,
                 &my_vote_keypair,
                 &mut voted_signatures,
+                MAX_VOTE_SIGNATURES,
                 has_new_vote_been_rooted,
                 &mut last_vote_refresh_time,
+                vote_refresh_interval,
+                now,
             );
 
             // No new votes have been submitted to gossip
@@ -4768,6 +9791,7 @@ mod tests {
             &mut tower,
             &SwitchForkDecision::SameFork,
             &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
             has_new_vote_been_rooted,
             &mut ReplayTiming::default(),
         );
@@ -4790,8 +9814,11 @@ mod tests {
             &identity_keypair,
             &my_vote_keypair,
             &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
             has_new_vote_been_rooted,
             &mut last_vote_refresh_time,
+            vote_refresh_interval,
+            now,
         );
         // No new votes have been submitted to gossip
         let (_, votes) = cluster_info.get_votes(&mut cursor);
@@ -4810,12 +9837,7 @@ mod tests {
 
         // Now trying to refresh the vote for slot 1 will succeed because the recent blockhash
         // of the last vote transaction has expired
-        last_vote_refresh_time.last_refresh_time = last_vote_refresh_time
-            .last_refresh_time
-            .checked_sub(Duration::from_millis(
-                MAX_VOTE_REFRESH_INTERVAL_MILLIS as u64 + 1,
-            ))
-            .unwrap();
+        now += Duration::from_millis(vote_refresh_interval_millis + 1);
         let clone_refresh_time = last_vote_refresh_time.last_refresh_time;
         ReplayStage::refresh_last_vote(
             &mut tower,
@@ -4827,8 +9849,11 @@ mod tests {
             &identity_keypair,
             &my_vote_keypair,
             &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
             has_new_vote_been_rooted,
             &mut last_vote_refresh_time,
+            vote_refresh_interval,
+            now,
         );
         assert!(last_vote_refresh_time.last_refresh_time > clone_refresh_time);
         let (_, votes) = cluster_info.get_votes(&mut cursor);
@@ -4864,7 +9889,7 @@ mod tests {
         // Trying to refresh the vote on a sibling bank where:
         // 1) The vote for slot 1 hasn't landed
         // 2) The latest refresh vote transaction's recent blockhash (the sibling's hash) doesn't exist
-        // This will still not refresh because `MAX_VOTE_REFRESH_INTERVAL_MILLIS` has not expired yet
+        // This will still not refresh because the vote refresh interval has not expired yet
         let expired_bank_sibling = Arc::new(Bank::new_from_parent(
             &bank2,
             &Pubkey::default(),
@@ -4873,7 +9898,7 @@ mod tests {
         fill_bank_with_ticks(&expired_bank_sibling);
         expired_bank_sibling.freeze();
         // Set the last refresh to now, shouldn't refresh because the last refresh just happened.
-        last_vote_refresh_time.last_refresh_time = Instant::now();
+        last_vote_refresh_time.last_refresh_time = now;
         ReplayStage::refresh_last_vote(
             &mut tower,
             &cluster_info,
@@ -4884,8 +9909,11 @@ mod tests {
             &identity_keypair,
             &my_vote_keypair,
             &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
             has_new_vote_been_rooted,
             &mut last_vote_refresh_time,
+            vote_refresh_interval,
+            now,
         );
         let (_, votes) = cluster_info.get_votes(&mut cursor);
         assert!(votes.is_empty());
@@ -4899,55 +9927,1001 @@ mod tests {
         );
         assert_eq!(tower.last_voted_slot().unwrap(), 1);
     }
-    fn run_compute_and_select_forks(
-        bank_forks: &RwLock<BankForks>,
-        progress: &mut ProgressMap,
-        tower: &mut Tower,
-        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
-        latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
-    ) -> (Option<Slot>, Option<Slot>) {
-        let mut frozen_banks: Vec<_> = bank_forks
-            .read()
-            .unwrap()
-            .frozen_banks()
-            .values()
-            .cloned()
-            .collect();
-        let ancestors = &bank_forks.read().unwrap().ancestors();
-        let descendants = &bank_forks.read().unwrap().descendants().clone();
-        ReplayStage::compute_bank_stats(
-            &Pubkey::default(),
-            &bank_forks.read().unwrap().ancestors(),
-            &mut frozen_banks,
-            tower,
-            progress,
-            &VoteTracker::default(),
-            &ClusterSlots::default(),
+
+    #[test]
+    fn test_replay_stage_refresh_last_vote() {
+        run_test_replay_stage_refresh_last_vote(MAX_VOTE_REFRESH_INTERVAL_MILLIS);
+    }
+
+    #[test]
+    fn test_replay_stage_refresh_last_vote_min_interval() {
+        run_test_replay_stage_refresh_last_vote(MIN_VOTE_REFRESH_INTERVAL_MILLIS);
+    }
+
+    #[test]
+    fn test_replay_stage_refresh_last_vote_max_interval() {
+        run_test_replay_stage_refresh_last_vote(MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS);
+    }
+
+    #[test]
+    fn test_replay_stage_vote_fee_payer() {
+        let ReplayBlockstoreComponents {
+            mut validator_keypairs,
+            cluster_info,
+            poh_recorder,
             bank_forks,
-            heaviest_subtree_fork_choice,
-            latest_validator_votes_for_frozen_banks,
-        );
-        let (heaviest_bank, heaviest_bank_on_same_fork) = heaviest_subtree_fork_choice
-            .select_forks(&frozen_banks, tower, progress, ancestors, bank_forks);
-        assert!(heaviest_bank_on_same_fork.is_none());
-        let SelectVoteAndResetForkResult {
-            vote_bank,
-            reset_bank,
+            mut tower,
+            my_pubkey,
             ..
-        } = ReplayStage::select_vote_and_reset_forks(
-            &heaviest_bank,
-            heaviest_bank_on_same_fork.as_ref(),
-            ancestors,
-            descendants,
-            progress,
-            tower,
-            latest_validator_votes_for_frozen_banks,
-            heaviest_subtree_fork_choice,
-        );
-        (
-            vote_bank.map(|(b, _)| b.slot()),
-            reset_bank.map(|b| b.slot()),
-        )
+        } = replay_blockstore_components(None);
+
+        let mut now = Instant::now();
+        let mut last_vote_refresh_time = LastVoteRefreshTime {
+            last_refresh_time: now,
+            last_print_time: now,
+        };
+        let has_new_vote_been_rooted = false;
+        let mut voted_signatures = VecDeque::new();
+
+        let vote_fee_payer_keypair = Keypair::new();
+        let my_vote_keypair = vec![Arc::new(
+            validator_keypairs.remove(&my_pubkey).unwrap().vote_keypair,
+        )];
+        let my_vote_pubkey = my_vote_keypair[0].pubkey();
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        fill_bank_with_ticks(&bank1);
+        bank1.freeze();
+
+        let check_vote_tx = |vote_tx: &Transaction, voted_signatures: &VecDeque<Signature>| {
+            // The fee payer signs first, which is the signature tracked for rooting
+            assert_eq!(
+                vote_tx.message.account_keys[0],
+                vote_fee_payer_keypair.pubkey()
+            );
+            assert!(vote_tx.signatures[0].verify(
+                vote_fee_payer_keypair.pubkey().as_ref(),
+                &vote_tx.message_data()
+            ));
+            assert_eq!(voted_signatures.back(), Some(&vote_tx.signatures[0]));
+            assert!(!vote_tx.message.account_keys.contains(&my_pubkey));
+            assert!(vote_tx.verify().is_ok());
+        };
+
+        let mut cursor = Cursor::default();
+        for bank in &[&bank0, &bank1] {
+            tower.record_bank_vote(bank, &my_vote_pubkey);
+            ReplayStage::push_vote(
+                &cluster_info,
+                bank,
+                &poh_recorder,
+                &my_vote_pubkey,
+                &vote_fee_payer_keypair,
+                &my_vote_keypair,
+                &mut tower,
+                &SwitchForkDecision::SameFork,
+                &mut voted_signatures,
+                MAX_VOTE_SIGNATURES,
+                has_new_vote_been_rooted,
+                &mut ReplayTiming::default(),
+            );
+            let (_, votes) = cluster_info.get_votes(&mut cursor);
+            assert_eq!(votes.len(), 1);
+            check_vote_tx(&votes[0], &voted_signatures);
+        }
+
+        // The refreshed vote for slot 1 is paid for by the same account
+        let expired_bank = Arc::new(Bank::new_from_parent(
+            &bank1,
+            &Pubkey::default(),
+            bank1.slot() + MAX_PROCESSING_AGE as Slot + 1,
+        ));
+        fill_bank_with_ticks(&expired_bank);
+        expired_bank.freeze();
+        now += Duration::from_millis(MAX_VOTE_REFRESH_INTERVAL_MILLIS + 1);
+        ReplayStage::refresh_last_vote(
+            &mut tower,
+            &cluster_info,
+            &expired_bank,
+            &poh_recorder,
+            0,
+            &my_vote_pubkey,
+            &vote_fee_payer_keypair,
+            &my_vote_keypair,
+            &mut voted_signatures,
+            MAX_VOTE_SIGNATURES,
+            has_new_vote_been_rooted,
+            &mut last_vote_refresh_time,
+            Duration::from_millis(MAX_VOTE_REFRESH_INTERVAL_MILLIS),
+            now,
+        );
+        let (_, votes) = cluster_info.get_votes(&mut cursor);
+        assert_eq!(votes.len(), 1);
+        assert_eq!(
+            votes[0].message.recent_blockhash,
+            expired_bank.last_blockhash()
+        );
+        check_vote_tx(&votes[0], &voted_signatures);
+        assert_eq!(voted_signatures.len(), 3);
+    }
+
+    #[test]
+    fn test_replay_stage_refresh_last_vote_interval() {
+        let ReplayBlockstoreComponents {
+            mut validator_keypairs,
+            cluster_info,
+            poh_recorder,
+            bank_forks,
+            mut tower,
+            my_pubkey,
+            ..
+        } = replay_blockstore_components(None);
+
+        let mut voted_signatures = VecDeque::new();
+        let identity_keypair = cluster_info.keypair().clone();
+        let my_vote_keypair = vec![Arc::new(
+            validator_keypairs.remove(&my_pubkey).unwrap().vote_keypair,
+        )];
+        let my_vote_pubkey = my_vote_keypair[0].pubkey();
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+
+        // Land a vote for slot 0 in slot 1, then vote on slot 1 without that vote landing
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        fill_bank_with_ticks(&bank1);
+        let mut cursor = Cursor::default();
+        for bank in &[&bank0, &bank1] {
+            tower.record_bank_vote(bank, &my_vote_pubkey);
+            ReplayStage::push_vote(
+                &cluster_info,
+                bank,
+                &poh_recorder,
+                &my_vote_pubkey,
+                &identity_keypair,
+                &my_vote_keypair,
+                &mut tower,
+                &SwitchForkDecision::SameFork,
+                &mut voted_signatures,
+                MAX_VOTE_SIGNATURES,
+                false,
+                &mut ReplayTiming::default(),
+            );
+            let (_, votes) = cluster_info.get_votes(&mut cursor);
+            assert_eq!(votes.len(), 1);
+            if bank.slot() == 0 {
+                bank1.process_transaction(&votes[0]).unwrap();
+                bank1.freeze();
+            }
+        }
+
+        // The last vote transaction's blockhash has expired in this bank
+        let expired_bank = Arc::new(Bank::new_from_parent(
+            &bank1,
+            &Pubkey::default(),
+            bank1.slot() + MAX_PROCESSING_AGE as Slot + 1,
+        ));
+        fill_bank_with_ticks(&expired_bank);
+        expired_bank.freeze();
+        let my_latest_landed_vote =
+            Tower::last_voted_slot_in_bank(&expired_bank, &my_vote_pubkey).unwrap();
+
+        // The last refresh happened longer ago than the shortest allowed interval, but
+        // not longer than the default
+        let last_refresh_time = Instant::now();
+        let mut last_vote_refresh_time = LastVoteRefreshTime {
+            last_refresh_time,
+            last_print_time: last_refresh_time,
+        };
+        let now = last_refresh_time + Duration::from_millis(MIN_VOTE_REFRESH_INTERVAL_MILLIS + 500);
+        for (vote_refresh_interval, expected_votes) in &[
+            // Refreshing is disabled entirely
+            (Duration::ZERO, 0),
+            (Duration::from_millis(MAX_VOTE_REFRESH_INTERVAL_MILLIS), 0),
+            (Duration::from_millis(MIN_VOTE_REFRESH_INTERVAL_MILLIS), 1),
+        ] {
+            ReplayStage::refresh_last_vote(
+                &mut tower,
+                &cluster_info,
+                &expired_bank,
+                &poh_recorder,
+                my_latest_landed_vote,
+                &my_vote_pubkey,
+                &identity_keypair,
+                &my_vote_keypair,
+                &mut voted_signatures,
+                MAX_VOTE_SIGNATURES,
+                false,
+                &mut last_vote_refresh_time,
+                *vote_refresh_interval,
+                now,
+            );
+            let (_, votes) = cluster_info.get_votes(&mut cursor);
+            assert_eq!(votes.len(), *expected_votes);
+        }
+        assert_eq!(
+            tower.last_vote_tx_blockhash(),
+            expired_bank.last_blockhash()
+        );
+    }
+
+    #[test]
+    fn test_process_gossip_duplicate_confirmed_slots_repeated_signal() {
+        let (
+            VoteSimulator {
+                bank_forks,
+                mut progress,
+                mut heaviest_subtree_fork_choice,
+                ..
+            },
+            _,
+        ) = setup_default_forks(1);
+        let bank_hash = |slot| bank_forks.read().unwrap()[slot].hash();
+        let (sender, receiver) = unbounded();
+        let mut duplicate_slots_tracker = DuplicateSlotsTracker::default();
+        let mut gossip_duplicate_confirmed_slots = GossipDuplicateConfirmedSlots::default();
+
+        sender.send(vec![(2, bank_hash(2))]).unwrap();
+        ReplayStage::process_gossip_duplicate_confirmed_slots(
+            &receiver,
+            &mut duplicate_slots_tracker,
+            &mut gossip_duplicate_confirmed_slots,
+            &bank_forks,
+            &mut progress,
+            &mut heaviest_subtree_fork_choice,
+        );
+
+        // The repeated signal for slot 2 comes first, the new confirmations behind it in
+        // the same batch and in a later one are still processed
+        sender
+            .send(vec![(2, bank_hash(2)), (4, bank_hash(4))])
+            .unwrap();
+        sender.send(vec![(3, bank_hash(3))]).unwrap();
+        ReplayStage::process_gossip_duplicate_confirmed_slots(
+            &receiver,
+            &mut duplicate_slots_tracker,
+            &mut gossip_duplicate_confirmed_slots,
+            &bank_forks,
+            &mut progress,
+            &mut heaviest_subtree_fork_choice,
+        );
+        assert!(receiver.is_empty());
+        for slot in &[2, 3, 4] {
+            let hash = bank_hash(*slot);
+            assert_eq!(gossip_duplicate_confirmed_slots.get(slot), Some(&hash));
+            assert_eq!(
+                heaviest_subtree_fork_choice.is_duplicate_confirmed(&(*slot, hash)),
+                Some(true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_external_dead_slots() {
+        let (
+            VoteSimulator {
+                bank_forks,
+                mut progress,
+                mut heaviest_subtree_fork_choice,
+                ..
+            },
+            blockstore,
+        ) = setup_default_forks(1);
+        let exit = Arc::new(AtomicBool::new(false));
+        let rpc_subscriptions = Arc::new(RpcSubscriptions::new(
+            &exit,
+            bank_forks.clone(),
+            Arc::new(RwLock::new(BlockCommitmentCache::default())),
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+        ));
+        let mut ancestry_cache = AncestryCache::new(&bank_forks.read().unwrap());
+        let bank3_hash = bank_forks.read().unwrap().get(3).unwrap().hash();
+        let (sender, receiver) = channel();
+
+        // The request for the root is ignored, slot 3 is abandoned along with its
+        // descendants 5 and 6
+        sender.send((0, "root".to_string())).unwrap();
+        sender
+            .send((3, "rejected by the cluster".to_string()))
+            .unwrap();
+        ReplayStage::process_external_dead_slots(
+            &receiver,
+            &blockstore,
+            &bank_forks,
+            &mut ancestry_cache,
+            &rpc_subscriptions,
+            None,
+            &mut DuplicateSlotsTracker::default(),
+            &GossipDuplicateConfirmedSlots::default(),
+            &mut progress,
+            &mut heaviest_subtree_fork_choice,
+        );
+        assert!(!blockstore.is_dead(0));
+        assert!(progress.is_dead(3).unwrap());
+        assert!(blockstore.is_dead(3));
+        assert!(!heaviest_subtree_fork_choice.contains_block(&(3, bank3_hash)));
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot().0, 4);
+        for slot in [3, 5, 6].iter() {
+            assert!(bank_forks.read().unwrap().get(*slot).is_none());
+            assert!(!ancestry_cache.ancestors.contains_key(slot));
+        }
+        assert!(progress.get(&5).is_none());
+        assert!(progress.get(&6).is_none());
+    }
+
+    #[test]
+    fn test_generate_vote_tx_max_vote_signatures() {
+        let ReplayBlockstoreComponents {
+            mut validator_keypairs,
+            bank_forks,
+            my_pubkey,
+            ..
+        } = replay_blockstore_components(None);
+        let my_vote_keypair = vec![Arc::new(
+            validator_keypairs.remove(&my_pubkey).unwrap().vote_keypair,
+        )];
+        let my_vote_pubkey = my_vote_keypair[0].pubkey();
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        let max_vote_signatures = 3;
+        let generate_vote_tx = |voted_signatures: &mut VecDeque<Signature>,
+                                has_new_vote_been_rooted| {
+            // A new fee payer every time makes for distinct signatures
+            ReplayStage::generate_vote_tx(
+                &Keypair::new(),
+                &bank0,
+                &my_vote_pubkey,
+                &my_vote_keypair,
+                Vote::new(vec![0], bank0.hash()),
+                &SwitchForkDecision::SameFork,
+                voted_signatures,
+                max_vote_signatures,
+                has_new_vote_been_rooted,
+            )
+            .unwrap()
+            .signatures[0]
+        };
+
+        // Past the cap, the oldest signatures are evicted first
+        let mut voted_signatures = VecDeque::new();
+        let signatures: Vec<Signature> = (0..5)
+            .map(|_| generate_vote_tx(&mut voted_signatures, false))
+            .collect();
+        assert_eq!(voted_signatures.len(), max_vote_signatures);
+        assert_eq!(
+            voted_signatures.iter().copied().collect::<Vec<_>>(),
+            signatures[2..].to_vec()
+        );
+
+        // Nothing is tracked anymore once one of our votes has been rooted
+        generate_vote_tx(&mut voted_signatures, true);
+        assert!(voted_signatures.is_empty());
+    }
+
+    // What `ReplayStage::replay_active_banks()` needs besides the blockstore components,
+    // kept across calls so that tests can check what the replay sent and recorded
+    struct ReplayActiveBanksContext {
+        my_pubkey: Pubkey,
+        vote_account: Pubkey,
+        heaviest_subtree_fork_choice: HeaviestSubtreeForkChoice,
+        replay_vote_sender: ReplayVoteSender,
+        _replay_vote_receiver: ReplayVoteReceiver,
+        cluster_slots_update_sender: SenderWithBackpressure<Vec<Slot>>,
+        cluster_slots_update_receiver: CrossbeamReceiver<Vec<Slot>>,
+        cost_update_sender: SenderWithBackpressure<SlotExecuteTimings>,
+        cost_update_receiver: CrossbeamReceiver<SlotExecuteTimings>,
+        replay_thread_pool: ThreadPool,
+        replay_duration_histogram: ReplayDurationHistogram,
+        leader_replay_stats: RwLock<LeaderReplayStats>,
+        leader_schedule_cache: LeaderScheduleCache,
+        ancestry_cache: AncestryCache,
+    }
+
+    impl ReplayActiveBanksContext {
+        // Fork choice starts out with the frozen banks of `bank_forks`
+        fn new(
+            bank_forks: &RwLock<BankForks>,
+            my_pubkey: Pubkey,
+            vote_account: Pubkey,
+            num_threads: usize,
+        ) -> Self {
+            let r_bank_forks = bank_forks.read().unwrap();
+            let (replay_vote_sender, replay_vote_receiver) = unbounded();
+            let (cluster_slots_update_sender, cluster_slots_update_receiver) = unbounded();
+            let (cost_update_sender, cost_update_receiver) = unbounded();
+            Self {
+                my_pubkey,
+                vote_account,
+                heaviest_subtree_fork_choice: HeaviestSubtreeForkChoice::new_from_bank_forks(
+                    &r_bank_forks,
+                ),
+                replay_vote_sender,
+                _replay_vote_receiver: replay_vote_receiver,
+                cluster_slots_update_sender: SenderWithBackpressure::new(
+                    "cluster_slots_update",
+                    cluster_slots_update_sender,
+                    None,
+                ),
+                cluster_slots_update_receiver,
+                cost_update_sender: SenderWithBackpressure::new(
+                    "cost_update",
+                    cost_update_sender,
+                    None,
+                ),
+                cost_update_receiver,
+                replay_thread_pool: rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .unwrap(),
+                replay_duration_histogram: ReplayDurationHistogram::default(),
+                leader_replay_stats: RwLock::new(LeaderReplayStats::default()),
+                leader_schedule_cache: LeaderScheduleCache::new_from_bank(
+                    &r_bank_forks.root_bank(),
+                ),
+                ancestry_cache: AncestryCache::new(&r_bank_forks),
+            }
+        }
+
+        fn replay_active_banks(
+            &mut self,
+            blockstore: &Blockstore,
+            bank_forks: &RwLock<BankForks>,
+            rpc_subscriptions: &Arc<RpcSubscriptions>,
+            progress: &mut ProgressMap,
+            paused_slots: &HashSet<Slot>,
+            logged_paused_slots: &mut HashSet<Slot>,
+        ) -> ReplayActiveBanksOutcome {
+            ReplayStage::replay_active_banks(
+                blockstore,
+                bank_forks,
+                &self.my_pubkey,
+                &self.vote_account,
+                progress,
+                None,
+                None,
+                &VerifyRecyclers::default(),
+                &mut self.heaviest_subtree_fork_choice,
+                &self.replay_vote_sender,
+                &None,
+                &None,
+                rpc_subscriptions,
+                &mut DuplicateSlotsTracker::default(),
+                &GossipDuplicateConfirmedSlots::default(),
+                &mut UnfrozenGossipVerifiedVoteHashes::default(),
+                &mut LatestValidatorVotesForFrozenBanks::default(),
+                &self.cluster_slots_update_sender,
+                &self.cost_update_sender,
+                None,
+                false,
+                &mut ReplayTiming::default(),
+                paused_slots,
+                logged_paused_slots,
+                SUPERMINORITY_THRESHOLD,
+                &self.replay_thread_pool,
+                &self.replay_duration_histogram,
+                &self.leader_replay_stats,
+                &self.leader_schedule_cache,
+                &mut self.ancestry_cache,
+            )
+        }
+    }
+
+    #[test]
+    fn test_replay_active_banks_paused_slot() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            validator_node_to_vote_keys,
+            my_pubkey,
+            mut progress,
+            bank_forks,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(None);
+        let vote_account = validator_node_to_vote_keys[&my_pubkey];
+
+        // Slot 1 is complete in the blockstore but has not been replayed yet
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let shreds = entries_to_test_shreds(
+            entry::create_ticks(
+                bank1.ticks_per_slot(),
+                bank1.hashes_per_tick().unwrap_or(0),
+                bank1.last_blockhash(),
+            ),
+            1,
+            0,
+            true,
+            0,
+        );
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+        bank_forks.write().unwrap().insert(bank1);
+
+        let mut context = ReplayActiveBanksContext::new(&bank_forks, my_pubkey, vote_account, 1);
+        let mut logged_paused_slots = HashSet::new();
+        let mut replay_active_banks =
+            |progress: &mut ProgressMap,
+             paused_slots: &HashSet<Slot>,
+             logged_paused_slots: &mut HashSet<Slot>| {
+                context.replay_active_banks(
+                    &blockstore,
+                    &bank_forks,
+                    &rpc_subscriptions,
+                    progress,
+                    paused_slots,
+                    logged_paused_slots,
+                )
+            };
+
+        // While paused, the slot is neither replayed nor marked dead
+        let paused_slots: HashSet<Slot> = vec![1].into_iter().collect();
+        for _ in 0..2 {
+            assert!(
+                !replay_active_banks(&mut progress, &paused_slots, &mut logged_paused_slots)
+                    .did_complete_bank
+            );
+            assert_eq!(bank_forks.read().unwrap().active_banks(), vec![1]);
+            assert!(!bank_forks.read().unwrap().get(1).unwrap().is_frozen());
+            assert!(progress.get(&1).is_none());
+            assert_eq!(logged_paused_slots, paused_slots);
+        }
+
+        // Once resumed, the slot is replayed as usual
+        assert!(
+            replay_active_banks(&mut progress, &HashSet::new(), &mut logged_paused_slots)
+                .did_complete_bank
+        );
+        assert!(bank_forks.read().unwrap().active_banks().is_empty());
+        assert!(bank_forks.read().unwrap().get(1).unwrap().is_frozen());
+        assert!(!progress.get(&1).unwrap().is_dead);
+        assert!(logged_paused_slots.is_empty());
+    }
+
+    #[test]
+    fn test_replay_active_banks_parallel_forks() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            validator_node_to_vote_keys,
+            my_pubkey,
+            mut progress,
+            bank_forks,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(Some(tr(0) / tr(1) / tr(2)));
+        let vote_account = validator_node_to_vote_keys[&my_pubkey];
+
+        // Slot 3 builds on 1, slots 4 and 5 build on 2. Slot 4 has too many ticks and
+        // must be marked dead, without affecting its sibling 5 or the other fork
+        for (slot, parent_slot, num_ticks_delta) in &[(3, 1, 0), (4, 2, 1), (5, 2, 0)] {
+            let parent = bank_forks
+                .read()
+                .unwrap()
+                .get(*parent_slot)
+                .unwrap()
+                .clone();
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), *slot);
+            let shreds = entries_to_test_shreds(
+                entry::create_ticks(
+                    bank.ticks_per_slot() + num_ticks_delta,
+                    bank.hashes_per_tick().unwrap_or(0),
+                    bank.last_blockhash(),
+                ),
+                *slot,
+                *parent_slot,
+                true,
+                0,
+            );
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            bank_forks.write().unwrap().insert(bank);
+        }
+
+        let mut context = ReplayActiveBanksContext::new(&bank_forks, my_pubkey, vote_account, 2);
+        assert!(
+            context
+                .replay_active_banks(
+                    &blockstore,
+                    &bank_forks,
+                    &rpc_subscriptions,
+                    &mut progress,
+                    &HashSet::new(),
+                    &mut HashSet::new(),
+                )
+                .did_complete_bank
+        );
+
+        // Both healthy banks froze in a single call, the dead one did not
+        for slot in &[3, 5] {
+            assert!(bank_forks.read().unwrap().get(*slot).unwrap().is_frozen());
+            assert!(!progress.get(slot).unwrap().is_dead);
+            let bank_hash = bank_forks.read().unwrap().get(*slot).unwrap().hash();
+            assert!(context
+                .heaviest_subtree_fork_choice
+                .contains_block(&(*slot, bank_hash)));
+        }
+        assert!(!bank_forks.read().unwrap().get(4).unwrap().is_frozen());
+        assert!(progress.get(&4).unwrap().is_dead);
+        assert!(blockstore.is_dead(4));
+        assert_eq!(bank_forks.read().unwrap().active_banks(), vec![4]);
+
+        // Each completed bank sent its own execute timings, the dead one did not
+        let cost_updates: Vec<_> = context
+            .cost_update_receiver
+            .try_iter()
+            .map(|cost_update| (cost_update.slot, cost_update.collector_id))
+            .collect();
+        assert_eq!(
+            cost_updates,
+            vec![(3, Pubkey::default()), (5, Pubkey::default())]
+        );
+
+        // Only the two completed banks are recorded in the replay duration histogram
+        let buckets = context.replay_duration_histogram.buckets();
+        assert_eq!(buckets.iter().map(|(_, count)| count).sum::<u64>(), 2);
+        context.replay_duration_histogram.reset();
+        assert!(context
+            .replay_duration_histogram
+            .buckets()
+            .iter()
+            .all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn test_replay_active_banks_leader_replay_stats() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            validator_node_to_vote_keys,
+            my_pubkey,
+            mut progress,
+            bank_forks,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(Some(tr(0) / tr(1) / tr(2)));
+        let vote_account = validator_node_to_vote_keys[&my_pubkey];
+        let leader1 = solana_sdk::pubkey::new_rand();
+        let leader2 = solana_sdk::pubkey::new_rand();
+
+        // Slots 3 and 6 of leader1 build on 1, slots 4 and 5 of leader2 build on 2. Slot 4
+        // has too many ticks and must be marked dead
+        for (slot, parent_slot, leader, num_ticks_delta) in &[
+            (3, 1, leader1, 0),
+            (4, 2, leader2, 1),
+            (5, 2, leader2, 0),
+            (6, 1, leader1, 0),
+        ] {
+            let parent = bank_forks
+                .read()
+                .unwrap()
+                .get(*parent_slot)
+                .unwrap()
+                .clone();
+            let bank = Bank::new_from_parent(&parent, leader, *slot);
+            let shreds = entries_to_test_shreds(
+                entry::create_ticks(
+                    bank.ticks_per_slot() + num_ticks_delta,
+                    bank.hashes_per_tick().unwrap_or(0),
+                    bank.last_blockhash(),
+                ),
+                *slot,
+                *parent_slot,
+                true,
+                0,
+            );
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            bank_forks.write().unwrap().insert(bank);
+        }
+
+        let mut context = ReplayActiveBanksContext::new(&bank_forks, my_pubkey, vote_account, 2);
+        assert!(
+            context
+                .replay_active_banks(
+                    &blockstore,
+                    &bank_forks,
+                    &rpc_subscriptions,
+                    &mut progress,
+                    &HashSet::new(),
+                    &mut HashSet::new(),
+                )
+                .did_complete_bank
+        );
+
+        let stats = context.leader_replay_stats.read().unwrap().snapshot();
+        let leader1_stats = &stats[&leader1];
+        assert_eq!(leader1_stats.blocks_replayed, 2);
+        assert_eq!(leader1_stats.blocks_dead, 0);
+        assert_eq!(
+            leader1_stats.num_entries,
+            (progress.get(&3).unwrap().replay_progress.num_entries
+                + progress.get(&6).unwrap().replay_progress.num_entries) as u64
+        );
+        let leader2_stats = &stats[&leader2];
+        assert_eq!(leader2_stats.blocks_replayed, 1);
+        assert_eq!(leader2_stats.blocks_dead, 1);
+        assert_eq!(
+            leader2_stats.average_replay_ms(),
+            leader2_stats.total_replay_ms
+        );
+
+        // Slot 2 was skipped on the fork of 3, slots 3 and 4 on the fork of 5, and 2 to 5
+        // on the fork of 6. Each slot counts once however many forks skipped it, and
+        // slot 4 being dead doesn't stop it from counting
+        let bank6 = bank_forks.read().unwrap().get(6).unwrap().clone();
+        let mut expected_dropped: HashMap<Pubkey, u64> = HashMap::new();
+        for skipped_slot in &[2, 3, 4, 5] {
+            let leader = context
+                .leader_schedule_cache
+                .slot_leader_at(*skipped_slot, Some(&bank6))
+                .unwrap();
+            *expected_dropped.entry(leader).or_default() += 1;
+        }
+        let dropped: HashMap<Pubkey, u64> = stats
+            .iter()
+            .filter(|(_, stat)| stat.blocks_dropped > 0)
+            .map(|(leader, stat)| (*leader, stat.blocks_dropped))
+            .collect();
+        assert_eq!(dropped, expected_dropped);
+    }
+
+    #[test]
+    fn test_mark_dead_zero_bank_hash() {
+        let ReplayBlockstoreComponents {
+            blockstore,
+            validator_node_to_vote_keys,
+            my_pubkey,
+            mut progress,
+            bank_forks,
+            rpc_subscriptions,
+            ..
+        } = replay_blockstore_components(None);
+        let vote_account = validator_node_to_vote_keys[&my_pubkey];
+
+        // Slots 1 and 2 both build on 0 and are complete in the blockstore
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        for slot in 1..=2 {
+            let bank = Bank::new_from_parent(&bank0, &Pubkey::default(), slot);
+            let shreds = entries_to_test_shreds(
+                entry::create_ticks(
+                    bank.ticks_per_slot(),
+                    bank.hashes_per_tick().unwrap_or(0),
+                    bank.last_blockhash(),
+                ),
+                slot,
+                0,
+                true,
+                0,
+            );
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            bank_forks.write().unwrap().insert(bank);
+        }
+
+        let mut context = ReplayActiveBanksContext::new(&bank_forks, my_pubkey, vote_account, 1);
+        context.replay_active_banks(
+            &blockstore,
+            &bank_forks,
+            &rpc_subscriptions,
+            &mut progress,
+            &HashSet::new(),
+            &mut HashSet::new(),
+        );
+        let bank1 = bank_forks.read().unwrap().get(1).unwrap().clone();
+        let bank2 = bank_forks.read().unwrap().get(2).unwrap().clone();
+        assert!(bank1.is_frozen());
+        assert!(bank2.is_frozen());
+
+        let mut mark_dead_zero_bank_hash = |bank_hash: Hash, bank: &Bank| {
+            ReplayStage::mark_dead_zero_bank_hash(
+                bank_hash,
+                bank,
+                &my_pubkey,
+                0,
+                &blockstore,
+                &rpc_subscriptions,
+                None,
+                &mut DuplicateSlotsTracker::default(),
+                &GossipDuplicateConfirmedSlots::default(),
+                &mut progress,
+                &mut context.heaviest_subtree_fork_choice,
+                &context.leader_replay_stats,
+                &mut context.ancestry_cache,
+                &bank_forks,
+            )
+        };
+        // A slot that froze with its real hash is left alone, while one that froze with a
+        // default hash is marked dead instead of panicking
+        assert!(!mark_dead_zero_bank_hash(bank2.hash(), &bank2));
+        assert!(mark_dead_zero_bank_hash(Hash::default(), &bank1));
+
+        // Slot 1 is dropped from fork choice and bank forks, slot 2 is untouched
+        assert!(progress.get(&1).unwrap().is_dead);
+        assert!(blockstore.is_dead(1));
+        assert!(bank_forks.read().unwrap().get(1).is_none());
+        assert!(!context
+            .heaviest_subtree_fork_choice
+            .contains_block(&(1, bank1.hash())));
+        assert!(!context.ancestry_cache.ancestors.contains_key(&1));
+        assert!(!progress.get(&2).unwrap().is_dead);
+        assert!(!blockstore.is_dead(2));
+        assert!(bank_forks.read().unwrap().get(2).is_some());
+        assert!(context
+            .heaviest_subtree_fork_choice
+            .contains_block(&(2, bank2.hash())));
+    }
+
+    #[test]
+    fn test_replay_duration_histogram() {
+        let histogram = ReplayDurationHistogram::default();
+        for duration_ms in &[0, 10, 11, 20, 25, 100, 10 << 10, (10 << 10) + 1, u64::MAX] {
+            histogram.record(*duration_ms);
+        }
+        assert_eq!(
+            histogram.buckets(),
+            vec![
+                (10, 2),
+                (20, 2),
+                (40, 1),
+                (80, 0),
+                (160, 1),
+                (320, 0),
+                (640, 0),
+                (1280, 0),
+                (2560, 0),
+                (5120, 0),
+                (10240, 1),
+                (u64::MAX, 2),
+            ]
+        );
+        histogram.reset();
+        assert!(histogram.buckets().iter().all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn test_fork_tree_snapshot() {
+        let (VoteSimulator { bank_forks, .. }, _) = setup_default_forks(1);
+        let fork_tree = fork_tree_snapshot(&bank_forks);
+        assert_eq!(
+            fork_tree
+                .iter()
+                .map(|(slot, parent_slot, _)| (*slot, *parent_slot))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 3), (6, 5)]
+        );
+
+        // Every parent appears before its children
+        for (i, (slot, parent_slot, bank_hash)) in fork_tree.iter().enumerate().skip(1) {
+            assert!(fork_tree[..i]
+                .iter()
+                .any(|(ancestor_slot, _, _)| ancestor_slot == parent_slot));
+            assert_eq!(
+                *bank_hash,
+                bank_forks.read().unwrap().get(*slot).unwrap().hash()
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_paused_forks() {
+        let (
+            VoteSimulator {
+                bank_forks,
+                mut heaviest_subtree_fork_choice,
+                ..
+            },
+            _,
+        ) = setup_default_forks(1);
+        let key = |slot: Slot| (slot, bank_forks.read().unwrap().get(slot).unwrap().hash());
+        let duplicate_slots_tracker = DuplicateSlotsTracker::default();
+        let mut paused_forks = HashSet::new();
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), key(4));
+
+        // Pausing slot 2 takes it and its descendant 4 out of fork choice, however often
+        // the paused slots are checked
+        let paused_slots: HashSet<Slot> = vec![2].into_iter().collect();
+        for _ in 0..2 {
+            ReplayStage::update_paused_forks(
+                &paused_slots,
+                &mut paused_forks,
+                &bank_forks,
+                &duplicate_slots_tracker,
+                &mut heaviest_subtree_fork_choice,
+            );
+            assert_eq!(paused_forks, vec![key(2)].into_iter().collect());
+            assert_eq!(
+                heaviest_subtree_fork_choice.is_candidate(&key(4)),
+                Some(false)
+            );
+            assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), key(6));
+        }
+
+        // Resuming it makes the fork a candidate again, without duplicate confirming it
+        ReplayStage::update_paused_forks(
+            &HashSet::new(),
+            &mut paused_forks,
+            &bank_forks,
+            &duplicate_slots_tracker,
+            &mut heaviest_subtree_fork_choice,
+        );
+        assert!(paused_forks.is_empty());
+        assert_eq!(
+            heaviest_subtree_fork_choice.is_candidate(&key(4)),
+            Some(true)
+        );
+        assert_eq!(
+            heaviest_subtree_fork_choice.is_duplicate_confirmed(&key(2)),
+            Some(false)
+        );
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), key(4));
+    }
+
+    fn run_compute_and_select_forks(
+        bank_forks: &RwLock<BankForks>,
+        progress: &mut ProgressMap,
+        tower: &mut Tower,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+        latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
+    ) -> (Option<Slot>, Option<Slot>) {
+        vote_and_reset_slots(&run_compute_and_select_forks_with_event(
+            bank_forks,
+            progress,
+            tower,
+            heaviest_subtree_fork_choice,
+            latest_validator_votes_for_frozen_banks,
+        ))
+    }
+
+    fn vote_and_reset_slots(event: &ForkChoiceEvent) -> (Option<Slot>, Option<Slot>) {
+        match event {
+            ForkChoiceEvent::SelectedForks {
+                vote_slot,
+                reset_slot,
+                ..
+            } => (*vote_slot, *reset_slot),
+        }
+    }
+
+    fn run_compute_and_select_forks_with_event(
+        bank_forks: &RwLock<BankForks>,
+        progress: &mut ProgressMap,
+        tower: &mut Tower,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+        latest_validator_votes_for_frozen_banks: &mut LatestValidatorVotesForFrozenBanks,
+    ) -> ForkChoiceEvent {
+        let mut frozen_banks: Vec<_> = bank_forks
+            .read()
+            .unwrap()
+            .frozen_banks()
+            .values()
+            .cloned()
+            .collect();
+        let ancestors = &bank_forks.read().unwrap().ancestors();
+        let descendants = &bank_forks.read().unwrap().descendants().clone();
+        let replay_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        ReplayStage::compute_bank_stats(
+            &Pubkey::default(),
+            &bank_forks.read().unwrap().ancestors(),
+            &mut frozen_banks,
+            tower,
+            progress,
+            &VoteTracker::default(),
+            &ClusterSlots::default(),
+            bank_forks,
+            heaviest_subtree_fork_choice,
+            latest_validator_votes_for_frozen_banks,
+            SUPERMINORITY_THRESHOLD,
+            &replay_thread_pool,
+        );
+        let (heaviest_bank, heaviest_bank_on_same_fork) = heaviest_subtree_fork_choice
+            .select_forks(&frozen_banks, tower, progress, ancestors, bank_forks);
+        assert!(heaviest_bank_on_same_fork.is_none());
+        let result = ReplayStage::select_vote_and_reset_forks(
+            &heaviest_bank,
+            heaviest_bank_on_same_fork.as_ref(),
+            ancestors,
+            descendants,
+            progress,
+            tower,
+            latest_validator_votes_for_frozen_banks,
+            heaviest_subtree_fork_choice,
+            &mut EpochStakesCache::default(),
+        );
+        ForkChoiceEvent::new(&heaviest_bank, heaviest_bank_on_same_fork.as_ref(), &result)
     }
 
     fn setup_forks_from_tree(tree: Tree<Slot>, num_keys: usize) -> (VoteSimulator, Blockstore) {
@@ -0,0 +1,154 @@
+//! The `sender_with_backpressure` module provides a channel sender for threads, like
+//! the replay loop, that must never block on or fail because of a wedged downstream
+//! service. Messages that can't be queued are dropped and counted instead.
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use solana_sdk::timing::timestamp;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+// Number of times a send to a full channel is retried before a message is dropped
+const MAX_SEND_RETRIES: u32 = 3;
+// Delay before the first retry, doubled on each following one
+const INITIAL_SEND_BACKOFF_US: u64 = 100;
+// How often the number of dropped messages of a channel is reported
+const DROPPED_REPORT_INTERVAL_MS: u64 = 1000;
+
+pub struct SenderWithBackpressure<T> {
+    name: &'static str,
+    sender: Sender<T>,
+    // Another handle on the receiving end of `sender`, used to drop the oldest queued
+    // message when the channel stays full. Without it the new message is dropped
+    // instead. Note that holding it means the channel is never disconnected
+    receiver: Option<Receiver<T>>,
+    dropped: AtomicU64,
+    // Dropped messages not yet included in a datapoint
+    unreported_dropped: AtomicU64,
+    last_report: AtomicU64,
+}
+
+impl<T> SenderWithBackpressure<T> {
+    /// Wraps `sender`, reporting drops under the channel `name`. Pass a `receiver` of
+    /// the same channel to have the oldest queued message dropped rather than the
+    /// newest when a bounded channel is full
+    pub fn new(name: &'static str, sender: Sender<T>, receiver: Option<Receiver<T>>) -> Self {
+        Self {
+            name,
+            sender,
+            receiver,
+            dropped: AtomicU64::default(),
+            unreported_dropped: AtomicU64::default(),
+            last_report: AtomicU64::default(),
+        }
+    }
+
+    /// Queues `msg` without ever blocking for more than a few retries. Returns false if
+    /// `msg` was dropped because the channel is disconnected or stayed full
+    pub fn send(&self, mut msg: T) -> bool {
+        let mut backoff_us = INITIAL_SEND_BACKOFF_US;
+        for retry in 0..=MAX_SEND_RETRIES {
+            match self.sender.try_send(msg) {
+                Ok(()) => return true,
+                Err(TrySendError::Disconnected(_)) => {
+                    self.record_dropped();
+                    return false;
+                }
+                Err(TrySendError::Full(returned)) => {
+                    msg = returned;
+                    if retry < MAX_SEND_RETRIES {
+                        thread::sleep(Duration::from_micros(backoff_us));
+                        backoff_us *= 2;
+                    }
+                }
+            }
+        }
+
+        // The channel stayed full, make room by dropping the oldest queued message
+        if let Some(receiver) = &self.receiver {
+            if receiver.try_recv().is_ok() {
+                self.record_dropped();
+            }
+            if self.sender.try_send(msg).is_ok() {
+                return true;
+            }
+        }
+        self.record_dropped();
+        false
+    }
+
+    /// Returns the total number of messages dropped on this channel
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        self.unreported_dropped.fetch_add(1, Ordering::Relaxed);
+        let now = timestamp();
+        let last_report = self.last_report.load(Ordering::Relaxed);
+        if now.saturating_sub(last_report) >= DROPPED_REPORT_INTERVAL_MS
+            && self
+                .last_report
+                .compare_exchange(last_report, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            let dropped = self.unreported_dropped.swap(0, Ordering::Relaxed);
+            warn!("{} channel dropped {} messages", self.name, dropped);
+            datapoint_info!(
+                "sender_with_backpressure",
+                ("channel", self.name, String),
+                ("dropped", dropped, i64),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::{bounded, unbounded};
+    use std::time::Instant;
+
+    #[test]
+    fn test_send_drops_oldest_when_full() {
+        let (sender, receiver) = bounded(1);
+        let sender = SenderWithBackpressure::new("test", sender, Some(receiver.clone()));
+
+        let start = Instant::now();
+        for i in 0..5 {
+            assert!(sender.send(i));
+        }
+        // Nobody is receiving, yet sending never blocks for long
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(sender.dropped_count(), 4);
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    fn test_send_drops_newest_without_receiver() {
+        let (sender, receiver) = bounded(1);
+        let sender = SenderWithBackpressure::new("test", sender, None);
+
+        assert!(sender.send(0));
+        assert!(!sender.send(1));
+        assert_eq!(sender.dropped_count(), 1);
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![0]);
+
+        drop(receiver);
+        assert!(!sender.send(2));
+        assert_eq!(sender.dropped_count(), 2);
+    }
+
+    #[test]
+    fn test_send_unbounded() {
+        let (sender, receiver) = unbounded();
+        let sender = SenderWithBackpressure::new("test", sender, None);
+        for i in 0..10 {
+            assert!(sender.send(i));
+        }
+        assert_eq!(sender.dropped_count(), 0);
+        assert_eq!(receiver.try_iter().count(), 10);
+    }
+}
@@ -1,5 +1,8 @@
 use {
-    crate::validator::{Validator, ValidatorConfig, ValidatorStartProgress},
+    crate::{
+        replay_stage::{ReplayControl, VoteSimulationHandle},
+        validator::{Validator, ValidatorConfig, ValidatorStartProgress},
+    },
     solana_client::rpc_client::RpcClient,
     solana_gossip::{
         cluster_info::{ClusterInfo, Node},
@@ -10,6 +13,7 @@ use {
     solana_net_utils::PortRange,
     solana_rpc::rpc::JsonRpcConfig,
     solana_runtime::{
+        bank_forks::BankForks,
         genesis_utils::create_genesis_config_with_leader_ex,
         hardened_unpack::MAX_GENESIS_ARCHIVE_UNPACKED_SIZE,
         snapshot_config::SnapshotConfig,
@@ -33,7 +37,7 @@ use {
         fs::remove_dir_all,
         net::{IpAddr, Ipv4Addr, SocketAddr},
         path::{Path, PathBuf},
-        sync::{Arc, RwLock},
+        sync::{mpsc::Sender, Arc, RwLock},
         thread::sleep,
         time::Duration,
     },
@@ -607,6 +611,18 @@ impl TestValidator {
     pub fn cluster_info(&self) -> Arc<ClusterInfo> {
         self.validator.as_ref().unwrap().cluster_info.clone()
     }
+
+    pub fn bank_forks(&self) -> Arc<RwLock<BankForks>> {
+        self.validator.as_ref().unwrap().bank_forks.clone()
+    }
+
+    pub fn vote_simulation_handle(&self) -> VoteSimulationHandle {
+        self.validator.as_ref().unwrap().vote_simulation_handle()
+    }
+
+    pub fn replay_control_sender(&self) -> Sender<ReplayControl> {
+        self.validator.as_ref().unwrap().replay_control_sender()
+    }
 }
 
 impl Drop for TestValidator {
@@ -15,15 +15,21 @@ use crate::{
     cost_model::CostModel,
     cost_update_service::CostUpdateService,
     ledger_cleanup_service::LedgerCleanupService,
-    replay_stage::{ReplayStage, ReplayStageConfig},
+    replay_stage::{
+        DuplicateSlotRecoveryMode, ForkChoiceEvent, IdentityHotswapCallback, PartitionCallback,
+        PartitionEvent, ReplayControl, ReplayStage, ReplayStageConfig, ReplayStageHandle,
+        VoteSimulationHandle, DEFAULT_MAX_REPLAY_WAIT_MS, DEFAULT_PARTITION_DETECTION_THRESHOLD,
+        MAX_VOTE_REFRESH_INTERVAL_MILLIS, MAX_VOTE_SIGNATURES, SUPERMINORITY_THRESHOLD,
+    },
     retransmit_stage::RetransmitStage,
     rewards_recorder_service::RewardsRecorderSender,
+    sender_with_backpressure::SenderWithBackpressure,
     shred_fetch_stage::ShredFetchStage,
     sigverify_shreds::ShredSigVerifier,
     sigverify_stage::SigVerifyStage,
     snapshot_packager_service::PendingSnapshotPackage,
 };
-use crossbeam_channel::unbounded;
+use crossbeam_channel::{bounded, unbounded};
 use solana_gossip::cluster_info::ClusterInfo;
 use solana_ledger::{
     blockstore::Blockstore, blockstore_processor::TransactionStatusSender,
@@ -39,25 +45,30 @@ use solana_runtime::{
         AbsRequestHandler, AbsRequestSender, AccountsBackgroundService, SnapshotRequestHandler,
     },
     accounts_db::AccountShrinkThreshold,
-    bank::ExecuteTimings,
     bank_forks::BankForks,
     commitment::BlockCommitmentCache,
     snapshot_config::SnapshotConfig,
     vote_sender_types::ReplayVoteSender,
 };
-use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Keypair};
 use std::{
     boxed::Box,
     collections::HashSet,
     net::UdpSocket,
     sync::{
-        atomic::AtomicBool,
+        atomic::{AtomicBool, AtomicU64},
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex, RwLock,
     },
     thread,
+    time::Duration,
 };
 
+// Updates queued by replay for the cluster slots and cost update services. Once a service
+// falls that far behind, replay drops the oldest update instead of waiting on it
+const MAX_QUEUED_CLUSTER_SLOTS_UPDATES: usize = 1024;
+const MAX_QUEUED_COST_UPDATES: usize = 1024;
+
 pub struct Tvu {
     fetch_stage: ShredFetchStage,
     sigverify_stage: SigVerifyStage,
@@ -91,6 +102,29 @@ pub struct TvuConfig {
     pub rocksdb_max_compaction_jitter: Option<u64>,
     pub wait_for_vote_to_start_leader: bool,
     pub accounts_shrink_ratio: AccountShrinkThreshold,
+    pub max_entry_recv_per_iter: Option<usize>,
+    pub report_fee_payer_failures: bool,
+    pub voting_disabled: bool,
+    pub vote_refresh_interval: Option<Duration>,
+    pub partition_callback: Option<PartitionCallback>,
+    pub partition_resolved_callback: Option<PartitionCallback>,
+    pub partition_event_sender: Option<Sender<PartitionEvent>>,
+    pub identity_hotswap_callback: Option<IdentityHotswapCallback>,
+    pub paused_slots: Arc<RwLock<HashSet<Slot>>>,
+    pub fork_choice_event_sender: Option<Sender<ForkChoiceEvent>>,
+    pub superminority_threshold: Option<f64>,
+    pub replay_control_receiver: Option<Receiver<ReplayControl>>,
+    pub external_dead_slot_receiver: Option<Receiver<(Slot, String)>>,
+    pub max_concurrent_forks: Option<usize>,
+    pub vote_fee_payer_keypair: Option<Arc<Keypair>>,
+    pub replay_last_iteration_timestamp: Arc<AtomicU64>,
+    pub duplicate_slot_recovery_mode: DuplicateSlotRecoveryMode,
+    pub recover_dead_duplicate_confirmed_slots: bool,
+    pub max_vote_signatures: Option<usize>,
+    pub partition_detection_threshold: Option<usize>,
+    pub defer_voting_until_within_slots: Option<u64>,
+    pub min_online_stake_to_lead: Option<f64>,
+    pub max_replay_wait: Option<Duration>,
 }
 
 impl Tvu {
@@ -166,7 +200,13 @@ impl Tvu {
         let compaction_interval = tvu_config.rocksdb_compaction_interval;
         let max_compaction_jitter = tvu_config.rocksdb_max_compaction_jitter;
         let (duplicate_slots_sender, duplicate_slots_receiver) = unbounded();
-        let (cluster_slots_update_sender, cluster_slots_update_receiver) = unbounded();
+        let (cluster_slots_update_sender, cluster_slots_update_receiver) =
+            bounded(MAX_QUEUED_CLUSTER_SLOTS_UPDATES);
+        let cluster_slots_update_sender = SenderWithBackpressure::new(
+            "cluster_slots_update",
+            cluster_slots_update_sender,
+            Some(cluster_slots_update_receiver.clone()),
+        );
         let retransmit_stage = RetransmitStage::new(
             bank_forks.clone(),
             leader_schedule_cache,
@@ -271,12 +311,48 @@ impl Tvu {
             cache_block_meta_sender,
             bank_notification_sender,
             wait_for_vote_to_start_leader: tvu_config.wait_for_vote_to_start_leader,
+            max_entry_recv_per_iter: tvu_config.max_entry_recv_per_iter,
+            report_fee_payer_failures: tvu_config.report_fee_payer_failures,
+            voting_disabled: tvu_config.voting_disabled,
+            vote_refresh_interval: tvu_config
+                .vote_refresh_interval
+                .unwrap_or_else(|| Duration::from_millis(MAX_VOTE_REFRESH_INTERVAL_MILLIS)),
+            partition_callback: tvu_config.partition_callback,
+            partition_resolved_callback: tvu_config.partition_resolved_callback,
+            partition_event_sender: tvu_config.partition_event_sender,
+            identity_hotswap_callback: tvu_config.identity_hotswap_callback,
+            paused_slots: tvu_config.paused_slots,
+            fork_choice_event_sender: tvu_config.fork_choice_event_sender,
+            superminority_threshold: tvu_config
+                .superminority_threshold
+                .unwrap_or(SUPERMINORITY_THRESHOLD),
+            replay_control_receiver: tvu_config.replay_control_receiver,
+            external_dead_slot_receiver: tvu_config.external_dead_slot_receiver,
+            max_concurrent_forks: tvu_config.max_concurrent_forks,
+            vote_fee_payer_keypair: tvu_config.vote_fee_payer_keypair,
+            last_iteration_timestamp: tvu_config.replay_last_iteration_timestamp,
+            duplicate_slot_recovery_mode: tvu_config.duplicate_slot_recovery_mode,
+            recover_dead_duplicate_confirmed_slots: tvu_config
+                .recover_dead_duplicate_confirmed_slots,
+            max_vote_signatures: tvu_config
+                .max_vote_signatures
+                .unwrap_or(MAX_VOTE_SIGNATURES),
+            partition_detection_threshold: tvu_config
+                .partition_detection_threshold
+                .unwrap_or(DEFAULT_PARTITION_DETECTION_THRESHOLD),
+            defer_voting_until_within_slots: tvu_config.defer_voting_until_within_slots,
+            min_online_stake_to_lead: tvu_config.min_online_stake_to_lead,
+            max_replay_wait: tvu_config
+                .max_replay_wait
+                .unwrap_or_else(|| Duration::from_millis(DEFAULT_MAX_REPLAY_WAIT_MS)),
         };
 
-        let (cost_update_sender, cost_update_receiver): (
-            Sender<ExecuteTimings>,
-            Receiver<ExecuteTimings>,
-        ) = channel();
+        let (cost_update_sender, cost_update_receiver) = bounded(MAX_QUEUED_COST_UPDATES);
+        let cost_update_sender = SenderWithBackpressure::new(
+            "cost_update",
+            cost_update_sender,
+            Some(cost_update_receiver.clone()),
+        );
         let cost_update_service = CostUpdateService::new(
             exit.clone(),
             blockstore.clone(),
@@ -336,6 +412,14 @@ impl Tvu {
         }
     }
 
+    pub fn vote_simulation_handle(&self) -> VoteSimulationHandle {
+        self.replay_stage.vote_simulation_handle()
+    }
+
+    pub fn replay_stage_handle(&self) -> ReplayStageHandle {
+        self.replay_stage.replay_stage_handle()
+    }
+
     pub fn join(self) -> thread::Result<()> {
         self.retransmit_stage.join()?;
         self.fetch_stage.join()?;
@@ -354,36 +438,90 @@ impl Tvu {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::{
+        cluster_info_vote_listener::GossipDuplicateConfirmedSlotsSender,
+        replay_stage::MIN_VOTE_REFRESH_INTERVAL_MILLIS,
+    };
     use serial_test::serial;
     use solana_gossip::cluster_info::{ClusterInfo, Node};
+    use solana_gossip::crds::Cursor;
     use solana_ledger::{
-        blockstore::BlockstoreSignals,
+        blockstore::{entries_to_test_shreds, BlockstoreSignals},
+        blockstore_processor::fill_blockstore_slot_with_ticks,
         create_new_tmp_ledger,
-        genesis_utils::{create_genesis_config, GenesisConfigInfo},
+        entry::create_ticks,
     };
-    use solana_poh::poh_recorder::create_test_recorder;
+    use solana_poh::{poh_recorder::create_test_recorder, poh_service::PohService};
     use solana_rpc::optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank;
-    use solana_runtime::bank::Bank;
-    use solana_sdk::signature::Signer;
-    use std::sync::atomic::Ordering;
+    use solana_runtime::{
+        bank::Bank,
+        genesis_utils::{
+            create_genesis_config_with_vote_accounts, GenesisConfigInfo, ValidatorVoteKeypairs,
+        },
+    };
+    use solana_sdk::{hash::Hash, signature::Signer};
+    use solana_vote_program::vote_transaction;
+    use std::{sync::atomic::Ordering, time::Instant};
 
-    #[ignore]
-    #[test]
-    #[serial]
-    fn test_tvu_exit() {
+    struct TestTvu {
+        tvu: Tvu,
+        exit: Arc<AtomicBool>,
+        poh_service: PohService,
+        blockstore: Arc<Blockstore>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        cluster_info: Arc<ClusterInfo>,
+        gossip_confirmed_slots_sender: GossipDuplicateConfirmedSlotsSender,
+    }
+
+    impl TestTvu {
+        fn join(self) {
+            self.exit.store(true, Ordering::Relaxed);
+            self.tvu.join().unwrap();
+            self.poh_service.join().unwrap();
+        }
+    }
+
+    fn start_test_tvu(tvu_config: TvuConfig) -> TestTvu {
+        start_test_tvu_with_tower(tvu_config, |_, _, _, _| ())
+    }
+
+    // Starts a Tvu for a validator with a vote account, staked little enough that the leader
+    // essentially always leads instead. Before the Tvu starts, `restore_tower` can add frozen
+    // banks and votes, as if they were loaded from the ledger and the saved tower
+    fn start_test_tvu_with_tower(
+        tvu_config: TvuConfig,
+        restore_tower: impl FnOnce(&mut BankForks, &ClusterInfo, &ValidatorVoteKeypairs, &mut Tower),
+    ) -> TestTvu {
         solana_logger::setup();
-        let leader = Node::new_localhost();
-        let target1_keypair = Keypair::new();
-        let target1 = Node::new_localhost_with_pubkey(&target1_keypair.pubkey());
+        let leader_keypairs = ValidatorVoteKeypairs::new_rand();
+        let leader = Node::new_localhost_with_pubkey(&leader_keypairs.node_keypair.pubkey());
+        let validator_keypairs = ValidatorVoteKeypairs::new_rand();
+        let target1 = Node::new_localhost_with_pubkey(&validator_keypairs.node_keypair.pubkey());
 
         let starting_balance = 10_000;
-        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(starting_balance);
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config_with_vote_accounts(
+            starting_balance,
+            &[&validator_keypairs, &leader_keypairs],
+            vec![100, 100_000_000_000],
+        );
 
-        let bank_forks = BankForks::new(Bank::new(&genesis_config));
+        let bank0 = Bank::new(&genesis_config);
+        bank0.freeze();
+        let mut bank_forks = BankForks::new(bank0);
 
         //start cluster_info1
-        let cluster_info1 = ClusterInfo::new_with_invalid_keypair(target1.info.clone());
+        let cluster_info1 = ClusterInfo::new(
+            target1.info.clone(),
+            Arc::new(Keypair::from_bytes(&validator_keypairs.node_keypair.to_bytes()).unwrap()),
+        );
         cluster_info1.insert_info(leader.info);
+        let mut tower = Tower::new_with_key(&validator_keypairs.node_keypair.pubkey());
+        restore_tower(
+            &mut bank_forks,
+            &cluster_info1,
+            &validator_keypairs,
+            &mut tower,
+        );
         let cref1 = Arc::new(cluster_info1);
 
         let (blockstore_path, _) = create_new_tmp_ledger!(&genesis_config);
@@ -394,10 +532,11 @@ pub mod tests {
         } = Blockstore::open_with_signal(&blockstore_path, None, true)
             .expect("Expected to successfully open ledger");
         let blockstore = Arc::new(blockstore);
-        let bank = bank_forks.working_bank();
+        let bank = bank_forks.root_bank();
         let (exit, poh_recorder, poh_service, _entry_receiver) =
             create_test_recorder(&bank, &blockstore, None);
-        let vote_keypair = Keypair::new();
+        let vote_keypair =
+            Keypair::from_bytes(&validator_keypairs.vote_keypair.to_bytes()).unwrap();
         let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank));
         let block_commitment_cache = Arc::new(RwLock::new(BlockCommitmentCache::default()));
         let (retransmit_slots_sender, _retransmit_slots_receiver) = unbounded();
@@ -405,9 +544,8 @@ pub mod tests {
         let (_verified_vote_sender, verified_vote_receiver) = unbounded();
         let (replay_vote_sender, _replay_vote_receiver) = unbounded();
         let (completed_data_sets_sender, _completed_data_sets_receiver) = unbounded();
-        let (_, gossip_confirmed_slots_receiver) = unbounded();
+        let (gossip_confirmed_slots_sender, gossip_confirmed_slots_receiver) = unbounded();
         let bank_forks = Arc::new(RwLock::new(bank_forks));
-        let tower = Tower::new_with_key(&target1_keypair.pubkey());
         let tvu = Tvu::new(
             &vote_keypair.pubkey(),
             Arc::new(RwLock::new(vec![Arc::new(vote_keypair)])),
@@ -421,7 +559,7 @@ pub mod tests {
                     forwards: target1.sockets.tvu_forwards,
                 }
             },
-            blockstore,
+            blockstore.clone(),
             ledger_signal_receiver,
             &Arc::new(RpcSubscriptions::new(
                 &exit,
@@ -447,12 +585,229 @@ pub mod tests {
             completed_data_sets_sender,
             None,
             gossip_confirmed_slots_receiver,
-            TvuConfig::default(),
+            tvu_config,
             &Arc::new(MaxSlots::default()),
             &Arc::new(RwLock::new(CostModel::default())),
         );
-        exit.store(true, Ordering::Relaxed);
-        tvu.join().unwrap();
-        poh_service.join().unwrap();
+        TestTvu {
+            tvu,
+            exit,
+            poh_service,
+            blockstore,
+            bank_forks,
+            cluster_info: cref1,
+            gossip_confirmed_slots_sender,
+        }
+    }
+
+    // Polls `condition` until it holds, failing the test if it takes more than 10 seconds
+    fn wait_for(mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[ignore]
+    #[test]
+    #[serial]
+    fn test_tvu_exit() {
+        start_test_tvu(TvuConfig::default()).join();
+    }
+
+    #[test]
+    #[serial]
+    fn test_tvu_replay_control_pause_resume() {
+        let (replay_control_sender, replay_control_receiver) = channel();
+        let test_tvu = start_test_tvu(TvuConfig {
+            replay_control_receiver: Some(replay_control_receiver),
+            ..TvuConfig::default()
+        });
+        replay_control_sender.send(ReplayControl::Pause).unwrap();
+        wait_for(|| test_tvu.tvu.replay_stage_handle().is_replay_paused());
+
+        // A complete slot 1 shows up in the blockstore while replay is paused
+        let bank0 = test_tvu.bank_forks.read().unwrap().root_bank();
+        let shreds = entries_to_test_shreds(
+            create_ticks(bank0.ticks_per_slot(), 0, bank0.last_blockhash()),
+            1,
+            0,
+            true,
+            0,
+        );
+        test_tvu
+            .blockstore
+            .insert_shreds(shreds, None, false)
+            .unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert!(test_tvu.bank_forks.read().unwrap().get(1).is_none());
+        // The paused loop doesn't count as stalled
+        assert!(test_tvu.tvu.replay_stage_handle().is_healthy(300));
+
+        // Once resumed, the bank for slot 1 is created
+        replay_control_sender.send(ReplayControl::Resume).unwrap();
+        wait_for(|| test_tvu.bank_forks.read().unwrap().get(1).is_some());
+        assert!(!test_tvu.tvu.replay_stage_handle().is_replay_paused());
+
+        test_tvu.join();
+    }
+
+    #[test]
+    #[serial]
+    fn test_tvu_replay_timing_snapshot() {
+        let test_tvu = start_test_tvu(TvuConfig::default());
+        let replay_stage_handle = test_tvu.tvu.replay_stage_handle();
+        assert_eq!(replay_stage_handle.latest_timing_snapshot().bank_count, 0);
+
+        let bank0 = test_tvu.bank_forks.read().unwrap().root_bank();
+        let shreds = entries_to_test_shreds(
+            create_ticks(bank0.ticks_per_slot(), 0, bank0.last_blockhash()),
+            1,
+            0,
+            true,
+            0,
+        );
+        test_tvu
+            .blockstore
+            .insert_shreds(shreds, None, false)
+            .unwrap();
+
+        // Replaying slot 1 to completion shows up in the timings read through the handle
+        wait_for(|| replay_stage_handle.latest_timing_snapshot().bank_count == 1);
+        let snapshot = replay_stage_handle.latest_timing_snapshot();
+        assert!(snapshot.recent_slots.iter().any(|timing| timing.slot == 1));
+        assert!(snapshot.replay_active_banks_elapsed > 0);
+        assert!(test_tvu
+            .bank_forks
+            .read()
+            .unwrap()
+            .get(1)
+            .unwrap()
+            .is_frozen());
+
+        test_tvu.join();
+    }
+
+    // A restarted validator's saved tower holds a vote for slot 1 that made it to gossip but
+    // hasn't landed, bank 1 only holds its earlier vote for slot 0
+    fn restore_unlanded_vote(
+        bank_forks: &mut BankForks,
+        cluster_info: &ClusterInfo,
+        validator_keypairs: &ValidatorVoteKeypairs,
+        tower: &mut Tower,
+    ) {
+        let vote_pubkey = validator_keypairs.vote_keypair.pubkey();
+        let new_vote_tx = |bank: &Bank| {
+            vote_transaction::new_vote_transaction(
+                vec![bank.slot()],
+                bank.hash(),
+                bank.last_blockhash(),
+                &validator_keypairs.node_keypair,
+                &validator_keypairs.vote_keypair,
+                &validator_keypairs.vote_keypair,
+                None,
+            )
+        };
+        let bank0 = bank_forks.root_bank();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        bank1.process_transaction(&new_vote_tx(&bank0)).unwrap();
+        while bank1.tick_height() < bank1.max_tick_height() {
+            bank1.register_tick(&Hash::new_unique());
+        }
+        bank1.freeze();
+        tower.record_bank_vote(&bank0, &vote_pubkey);
+        tower.record_bank_vote(&bank1, &vote_pubkey);
+        cluster_info.push_vote(&tower.tower_slots(), new_vote_tx(&bank1));
+        bank_forks.insert(bank1);
+    }
+
+    // Returns the last slot of each vote pushed to gossip since `cursor`
+    fn get_voted_slots(cluster_info: &ClusterInfo, cursor: &mut Cursor) -> Vec<Slot> {
+        let (_, votes) = cluster_info.get_votes(cursor);
+        votes
+            .iter()
+            .map(|vote_tx| {
+                let (_, vote, _) = vote_transaction::parse_vote_transaction(vote_tx).unwrap();
+                *vote.slots.last().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    #[serial]
+    fn test_tvu_defer_voting_until_within_slots() {
+        let vote_refresh_interval = Duration::from_millis(MIN_VOTE_REFRESH_INTERVAL_MILLIS);
+
+        // Without deferring, the vote for slot 1 that hasn't landed is refreshed
+        let test_tvu = start_test_tvu_with_tower(
+            TvuConfig {
+                vote_refresh_interval: Some(vote_refresh_interval),
+                ..TvuConfig::default()
+            },
+            restore_unlanded_vote,
+        );
+        let mut cursor = Cursor::default();
+        assert_eq!(
+            get_voted_slots(&test_tvu.cluster_info, &mut cursor),
+            vec![1]
+        );
+        wait_for(|| get_voted_slots(&test_tvu.cluster_info, &mut cursor).contains(&1));
+        test_tvu.join();
+
+        // Slot 6 is confirmed through gossip, far ahead of the heaviest bank
+        let test_tvu = start_test_tvu_with_tower(
+            TvuConfig {
+                vote_refresh_interval: Some(vote_refresh_interval),
+                defer_voting_until_within_slots: Some(2),
+                ..TvuConfig::default()
+            },
+            restore_unlanded_vote,
+        );
+        test_tvu
+            .gossip_confirmed_slots_sender
+            .send(vec![(6, Hash::new_unique())])
+            .unwrap();
+        let mut cursor = Cursor::default();
+        assert_eq!(
+            get_voted_slots(&test_tvu.cluster_info, &mut cursor),
+            vec![1]
+        );
+
+        // While catching up, the vote for slot 1 isn't refreshed
+        thread::sleep(3 * vote_refresh_interval);
+        assert!(get_voted_slots(&test_tvu.cluster_info, &mut cursor).is_empty());
+
+        // Nor are slots 2 and 3 voted on, they're still more than 2 slots behind slot 6
+        let bank1 = test_tvu.bank_forks.read().unwrap().get(1).unwrap().clone();
+        let mut last_entry_hash = bank1.last_blockhash();
+        let mut insert_slot = |slot| {
+            last_entry_hash = fill_blockstore_slot_with_ticks(
+                &test_tvu.blockstore,
+                bank1.ticks_per_slot(),
+                slot,
+                slot - 1,
+                last_entry_hash,
+            );
+        };
+        insert_slot(2);
+        insert_slot(3);
+        wait_for(|| {
+            test_tvu
+                .bank_forks
+                .read()
+                .unwrap()
+                .get(3)
+                .map(|bank| bank.is_frozen())
+                .unwrap_or(false)
+        });
+        thread::sleep(3 * vote_refresh_interval);
+        assert!(get_voted_slots(&test_tvu.cluster_info, &mut cursor).is_empty());
+
+        // Slot 4 is within 2 slots of slot 6, so voting resumes with it
+        insert_slot(4);
+        wait_for(|| get_voted_slots(&test_tvu.cluster_info, &mut cursor).contains(&4));
+
+        test_tvu.join();
     }
 }
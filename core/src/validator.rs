@@ -7,6 +7,10 @@ use crate::{
     completed_data_sets_service::CompletedDataSetsService,
     consensus::{reconcile_blockstore_roots_with_tower, Tower},
     cost_model::{CostModel, ACCOUNT_MAX_COST, BLOCK_MAX_COST},
+    replay_stage::{
+        DuplicateSlotRecoveryMode, ReplayControl, ReplayStageHandle, VoteSimulationHandle,
+        SUPERMINORITY_THRESHOLD,
+    },
     rewards_recorder_service::{RewardsRecorderSender, RewardsRecorderService},
     sample_performance_service::SamplePerformanceService,
     serve_repair::ServeRepair,
@@ -30,7 +34,9 @@ use solana_ledger::{
     bank_forks_utils,
     blockstore::{Blockstore, BlockstoreSignals, CompletedSlotsReceiver, PurgeType},
     blockstore_db::BlockstoreRecoveryMode,
-    blockstore_processor::{self, TransactionStatusSender},
+    blockstore_processor::{
+        self, BlockstoreProcessorProgress, ProgressCallback, TransactionStatusSender,
+    },
     leader_schedule::FixedSchedule,
     leader_schedule_cache::LeaderScheduleCache,
     poh::compute_hash_time_ns,
@@ -80,7 +86,7 @@ use std::{
     ops::Deref,
     path::{Path, PathBuf},
     sync::atomic::{AtomicBool, AtomicU64, Ordering},
-    sync::mpsc::Receiver,
+    sync::mpsc::{channel, Receiver, Sender},
     sync::{Arc, Mutex, RwLock},
     thread::{sleep, Builder, JoinHandle},
     time::{Duration, Instant},
@@ -109,6 +115,7 @@ pub struct ValidatorConfig {
     pub fixed_leader_schedule: Option<FixedSchedule>,
     pub wait_for_supermajority: Option<Slot>,
     pub new_hard_forks: Option<Vec<Slot>>,
+    pub allow_obsolete_hard_forks: bool,
     pub trusted_validators: Option<HashSet<Pubkey>>, // None = trust all
     pub repair_validators: Option<HashSet<Pubkey>>,  // None = repair from all
     pub gossip_validators: Option<HashSet<Pubkey>>,  // None = gossip with all
@@ -143,6 +150,20 @@ pub struct ValidatorConfig {
     pub validator_exit: Arc<RwLock<Exit>>,
     pub no_wait_for_vote_to_start_leader: bool,
     pub accounts_shrink_ratio: AccountShrinkThreshold,
+    pub max_entry_recv_per_iter: Option<usize>,
+    pub report_fee_payer_failures: bool,
+    pub vote_refresh_interval: Option<Duration>,
+    pub paused_slots: Arc<RwLock<HashSet<Slot>>>,
+    pub superminority_threshold: Option<f64>,
+    pub max_concurrent_forks: Option<usize>,
+    pub vote_fee_payer_keypair: Option<Arc<Keypair>>,
+    pub duplicate_slot_recovery_mode: DuplicateSlotRecoveryMode,
+    pub recover_dead_duplicate_confirmed_slots: bool,
+    pub max_vote_signatures: Option<usize>,
+    pub partition_detection_threshold: Option<usize>,
+    pub defer_voting_until_within_slots: Option<u64>,
+    pub min_online_stake_to_lead: Option<f64>,
+    pub max_replay_wait: Option<Duration>,
 }
 
 impl Default for ValidatorConfig {
@@ -166,6 +187,7 @@ impl Default for ValidatorConfig {
             fixed_leader_schedule: None,
             wait_for_supermajority: None,
             new_hard_forks: None,
+            allow_obsolete_hard_forks: false,
             trusted_validators: None,
             repair_validators: None,
             gossip_validators: None,
@@ -200,6 +222,20 @@ impl Default for ValidatorConfig {
             validator_exit: Arc::new(RwLock::new(Exit::default())),
             no_wait_for_vote_to_start_leader: true,
             accounts_shrink_ratio: AccountShrinkThreshold::default(),
+            max_entry_recv_per_iter: None,
+            report_fee_payer_failures: false,
+            vote_refresh_interval: None,
+            paused_slots: Arc::new(RwLock::new(HashSet::new())),
+            superminority_threshold: None,
+            max_concurrent_forks: None,
+            vote_fee_payer_keypair: None,
+            duplicate_slot_recovery_mode: DuplicateSlotRecoveryMode::default(),
+            recover_dead_duplicate_confirmed_slots: false,
+            max_vote_signatures: None,
+            partition_detection_threshold: None,
+            defer_voting_until_within_slots: None,
+            min_online_stake_to_lead: None,
+            max_replay_wait: None,
         }
     }
 }
@@ -215,6 +251,7 @@ pub enum ValidatorStartProgress {
     CleaningBlockStore,
     CleaningAccounts,
     LoadingLedger,
+    ProcessingLedger { slot: Slot, max_slot: Slot },
     StartingServices,
     Halted, // Validator halted due to `--dev-halt-at-slot` argument
     WaitingForSupermajority,
@@ -259,8 +296,11 @@ pub struct Validator {
     poh_service: PohService,
     tpu: Tpu,
     tvu: Tvu,
+    external_dead_slot_sender: Sender<(Slot, String)>,
+    replay_control_sender: Sender<ReplayControl>,
     ip_echo_server: Option<solana_net_utils::IpEchoServer>,
     pub cluster_info: Arc<ClusterInfo>,
+    pub bank_forks: Arc<RwLock<BankForks>>,
 }
 
 // in the distant future, get rid of ::new()/exit() and use Result properly...
@@ -505,6 +545,9 @@ impl Validator {
         let poh_recorder = Arc::new(Mutex::new(poh_recorder));
 
         let rpc_override_health_check = Arc::new(AtomicBool::new(false));
+        let replay_last_iteration_timestamp = Arc::new(AtomicU64::default());
+        let (external_dead_slot_sender, external_dead_slot_receiver) = channel();
+        let (replay_control_sender, replay_control_receiver) = channel();
         let (
             json_rpc_service,
             pubsub_service,
@@ -538,6 +581,7 @@ impl Validator {
                     max_slots.clone(),
                     leader_schedule_cache.clone(),
                     max_complete_transaction_status_slot,
+                    replay_last_iteration_timestamp.clone(),
                 )),
                 if config.rpc_config.minimal_api {
                     None
@@ -739,6 +783,30 @@ impl Validator {
                 rocksdb_max_compaction_jitter: config.rocksdb_compaction_interval,
                 wait_for_vote_to_start_leader,
                 accounts_shrink_ratio: config.accounts_shrink_ratio,
+                max_entry_recv_per_iter: config.max_entry_recv_per_iter,
+                report_fee_payer_failures: config.report_fee_payer_failures,
+                vote_refresh_interval: config.vote_refresh_interval,
+                voting_disabled: config.voting_disabled,
+                partition_callback: None,
+                partition_resolved_callback: None,
+                partition_event_sender: None,
+                identity_hotswap_callback: None,
+                paused_slots: config.paused_slots.clone(),
+                fork_choice_event_sender: None,
+                replay_control_receiver: Some(replay_control_receiver),
+                external_dead_slot_receiver: Some(external_dead_slot_receiver),
+                superminority_threshold: config.superminority_threshold,
+                max_concurrent_forks: config.max_concurrent_forks,
+                vote_fee_payer_keypair: config.vote_fee_payer_keypair.clone(),
+                replay_last_iteration_timestamp,
+                duplicate_slot_recovery_mode: config.duplicate_slot_recovery_mode,
+                recover_dead_duplicate_confirmed_slots: config
+                    .recover_dead_duplicate_confirmed_slots,
+                max_vote_signatures: config.max_vote_signatures,
+                partition_detection_threshold: config.partition_detection_threshold,
+                defer_voting_until_within_slots: config.defer_voting_until_within_slots,
+                min_online_stake_to_lead: config.min_online_stake_to_lead,
+                max_replay_wait: config.max_replay_wait,
             },
             &max_slots,
             &cost_model,
@@ -759,7 +827,7 @@ impl Validator {
             &exit,
             node.info.shred_version,
             vote_tracker,
-            bank_forks,
+            bank_forks.clone(),
             verified_vote_sender,
             gossip_verified_vote_hash_sender,
             replay_vote_receiver,
@@ -787,11 +855,14 @@ impl Validator {
             completed_data_sets_service,
             tpu,
             tvu,
+            external_dead_slot_sender,
+            replay_control_sender,
             poh_service,
             poh_recorder,
             ip_echo_server,
             validator_exit: config.validator_exit.clone(),
             cluster_info,
+            bank_forks,
         }
     }
 
@@ -830,6 +901,30 @@ impl Validator {
         );
     }
 
+    /// Returns a handle for asking the replay stage what it would vote on
+    pub fn vote_simulation_handle(&self) -> VoteSimulationHandle {
+        self.tvu.vote_simulation_handle()
+    }
+
+    /// Returns a handle for reading the replay stage's state and pausing the replay
+    /// of slots
+    pub fn replay_stage_handle(&self) -> ReplayStageHandle {
+        self.tvu.replay_stage_handle()
+    }
+
+    /// Asks the replay stage to mark `slot` dead as if it had failed to replay, e.g. once
+    /// an operator knows the cluster rejected the block. Rooted slots are left alone
+    pub fn mark_slot_dead(&self, slot: Slot, reason: String) {
+        // Only fails once the replay stage has exited
+        let _ = self.external_dead_slot_sender.send((slot, reason));
+    }
+
+    /// Returns a sender for pausing and resuming the replay loop, e.g. while the ledger
+    /// disk is swapped
+    pub fn replay_control_sender(&self) -> Sender<ReplayControl> {
+        self.replay_control_sender.clone()
+    }
+
     pub fn join(self) {
         drop(self.cluster_info);
 
@@ -1049,6 +1144,9 @@ fn post_process_restored_tower(
                 tower_path,
                 validator_identity,
                 vote_account,
+                config
+                    .superminority_threshold
+                    .unwrap_or(SUPERMINORITY_THRESHOLD),
             )
         })
 }
@@ -1141,16 +1239,35 @@ fn new_banks_from_ledger(
         None
     };
 
+    // Reports how far the ledger has been replayed through the start progress
+    let progress_callback: ProgressCallback = {
+        let start_progress = start_progress.clone();
+        let max_slot = config
+            .dev_halt_at_slot
+            .map_or(blockstore.highest_slot(), |halt_slot| {
+                halt_slot.min(blockstore.highest_slot())
+            });
+        Arc::new(move |progress: BlockstoreProcessorProgress| {
+            *start_progress.write().unwrap() = ValidatorStartProgress::ProcessingLedger {
+                slot: progress.slot,
+                max_slot,
+            };
+        })
+    };
+
     let process_options = blockstore_processor::ProcessOptions {
         bpf_jit: config.bpf_jit,
         poh_verify,
         dev_halt_at_slot: config.dev_halt_at_slot,
         new_hard_forks: config.new_hard_forks.clone(),
+        allow_obsolete_hard_forks: config.allow_obsolete_hard_forks,
         frozen_accounts: config.frozen_accounts.clone(),
         debug_keys: config.debug_keys.clone(),
         account_indexes: config.account_indexes.clone(),
         accounts_db_caching_enabled: config.accounts_db_caching_enabled,
         shrink_ratio: config.accounts_shrink_ratio,
+        report_fee_payer_failures: config.report_fee_payer_failures,
+        progress_callback: Some(progress_callback),
         ..blockstore_processor::ProcessOptions::default()
     };
 
@@ -22,7 +22,7 @@ use solana_ledger::{
     bank_forks_utils,
     blockstore::{create_new_ledger, Blockstore, PurgeType},
     blockstore_db::{self, AccessType, BlockstoreRecoveryMode, Column, Database},
-    blockstore_processor::ProcessOptions,
+    blockstore_processor::{self, ProcessOptions},
     shred::Shred,
 };
 use solana_runtime::{
@@ -65,6 +65,8 @@ use std::{
 
 mod bigtable;
 use bigtable::*;
+mod progress_reporter;
+use progress_reporter::CliProgressReporter;
 
 #[derive(PartialEq)]
 enum LedgerOutputMethod {
@@ -1129,12 +1131,39 @@ fn main() {
                     .takes_value(false)
                     .help("Skip ledger PoH verification"),
             )
+            .arg(
+                Arg::with_name("full_signature_audit")
+                    .long("full-signature-audit")
+                    .takes_value(false)
+                    .help("Verify the signatures of every transaction, even with --skip-poh-verify, \
+                           and report the first transaction that fails verification"),
+            )
             .arg(
                 Arg::with_name("print_accounts_stats")
                     .long("print-accounts-stats")
                     .takes_value(false)
                     .help("After verifying the ledger, print some information about the account stores"),
             )
+            .arg(
+                Arg::with_name("progress_bar")
+                    .long("progress-bar")
+                    .takes_value(false)
+                    .help("Show the progress of replaying the ledger on a progress bar instead of in the log"),
+            )
+            .arg(
+                Arg::with_name("report_fee_payer_failures")
+                    .long("report-fee-payer-failures")
+                    .takes_value(false)
+                    .help("Report transactions whose fee payer can't pay for them separately \
+                           from other transaction errors"),
+            )
+        ).subcommand(
+            SubCommand::with_name("verify-slots")
+            .about("Verify the ticks, PoH and transaction signatures of a range of slots without replaying their transactions")
+            .arg(&starting_slot_arg)
+            .arg(&ending_slot_arg)
+            .arg(&allow_dead_slots_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
         ).subcommand(
             SubCommand::with_name("graph")
             .about("Create a Graphviz rendering of the ledger")
@@ -1829,10 +1858,11 @@ fn main() {
             }
         }
         ("verify", Some(arg_matches)) => {
-            let process_options = ProcessOptions {
+            let mut process_options = ProcessOptions {
                 dev_halt_at_slot: value_t!(arg_matches, "halt_at_slot", Slot).ok(),
                 new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
                 poh_verify: !arg_matches.is_present("skip_poh_verify"),
+                full_signature_audit: arg_matches.is_present("full_signature_audit"),
                 bpf_jit: !matches.is_present("no_bpf_jit"),
                 accounts_db_caching_enabled: !arg_matches.is_present("no_accounts_db_caching"),
                 limit_load_slot_count_from_snapshot: value_t!(
@@ -1844,6 +1874,7 @@ fn main() {
                 allow_dead_slots: arg_matches.is_present("allow_dead_slots"),
                 accounts_db_test_hash_calculation: arg_matches
                     .is_present("accounts_db_test_hash_calculation"),
+                report_fee_payer_failures: arg_matches.is_present("report_fee_payer_failures"),
                 ..ProcessOptions::default()
             };
             let print_accounts_stats = arg_matches.is_present("print_accounts_stats");
@@ -1857,6 +1888,18 @@ fn main() {
                 AccessType::TryPrimaryThenSecondary,
                 wal_recovery_mode,
             );
+            if arg_matches.is_present("progress_bar") {
+                let last_slot = blockstore
+                    .slot_meta_iterator(blockstore.lowest_slot())
+                    .ok()
+                    .and_then(|slot_meta_iterator| slot_meta_iterator.map(|(slot, _)| slot).last())
+                    .unwrap_or_default();
+                let last_slot = process_options
+                    .dev_halt_at_slot
+                    .map_or(last_slot, |halt_slot| halt_slot.min(last_slot));
+                process_options.progress_reporter =
+                    Some(Arc::new(CliProgressReporter::new(last_slot)));
+            }
             let (bank_forks, _, _) = load_bank_forks(
                 arg_matches,
                 &open_genesis_config_by(&ledger_path, arg_matches),
@@ -1874,6 +1917,41 @@ fn main() {
             }
             println!("Ok");
         }
+        ("verify-slots", Some(arg_matches)) => {
+            let starting_slot = value_t_or_exit!(arg_matches, "starting_slot", Slot);
+            let ending_slot = value_t!(arg_matches, "ending_slot", Slot).unwrap_or(Slot::MAX);
+            let process_options = ProcessOptions {
+                allow_dead_slots: arg_matches.is_present("allow_dead_slots"),
+                ..ProcessOptions::default()
+            };
+            let blockstore = open_blockstore(
+                &ledger_path,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+            );
+            let results = blockstore_processor::verify_slot_range(
+                &blockstore,
+                &open_genesis_config_by(&ledger_path, arg_matches),
+                starting_slot,
+                ending_slot,
+                &process_options,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("Slot verification failed: {:?}", err);
+                exit(1);
+            });
+            let mut num_failed = 0;
+            for result in &results {
+                if let Some(error) = &result.error {
+                    println!("Slot {}: {}", result.slot, error);
+                    num_failed += 1;
+                }
+            }
+            println!("{} slots verified, {} failed", results.len(), num_failed);
+            if num_failed > 0 {
+                exit(1);
+            }
+        }
         ("graph", Some(arg_matches)) => {
             let output_file = value_t_or_exit!(arg_matches, "graph_filename", String);
 
@@ -0,0 +1,38 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use solana_ledger::blockstore_processor::ProcessProgressReporter;
+use solana_sdk::clock::Slot;
+
+/// Shows how far the blockstore has been processed on a progress bar, up to `last_slot`
+pub struct CliProgressReporter {
+    progress_bar: ProgressBar,
+}
+
+impl CliProgressReporter {
+    pub fn new(last_slot: Slot) -> Self {
+        let progress_bar = ProgressBar::new(last_slot);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{prefix} [{bar:40.cyan/blue}] slot {pos}/{len} {wide_msg} ({eta})")
+                .progress_chars("=> "),
+        );
+        Self { progress_bar }
+    }
+}
+
+impl ProcessProgressReporter for CliProgressReporter {
+    fn on_slot(&self, slot: Slot, _root: Slot, total_txs: usize) {
+        self.progress_bar.set_position(slot);
+        self.progress_bar
+            .set_message(format!("{} transactions", total_txs));
+    }
+
+    fn on_root(&self, new_root: Slot) {
+        self.progress_bar.set_prefix(format!("root {}", new_root));
+    }
+}
+
+impl Drop for CliProgressReporter {
+    fn drop(&mut self) {
+        self.progress_bar.finish();
+    }
+}
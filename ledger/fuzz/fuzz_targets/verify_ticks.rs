@@ -0,0 +1,82 @@
+//! Feeds arbitrary entry sequences to `verify_ticks()`, which must reject malformed ones
+//! with a `BlockError` instead of panicking.
+//!
+//! Run with `cargo +nightly fuzz run verify_ticks` from `ledger/`
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use solana_ledger::{
+    block_error::BlockError,
+    blockstore_processor::verify_ticks,
+    entry::Entry,
+    genesis_utils::{create_genesis_config, GenesisConfigInfo},
+};
+use solana_runtime::bank::Bank;
+use solana_sdk::{hash::Hash, pubkey::Pubkey, transaction::Transaction};
+use std::sync::Arc;
+
+const HASHES_PER_TICK: u64 = 4;
+
+lazy_static! {
+    static ref BANK0: Arc<Bank> = {
+        let GenesisConfigInfo {
+            mut genesis_config, ..
+        } = create_genesis_config(10_000);
+        genesis_config.poh_config.hashes_per_tick = Some(HASHES_PER_TICK);
+        Arc::new(Bank::new(&genesis_config))
+    };
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzEntry {
+    // Kept small so that tick hash counts regularly match `HASHES_PER_TICK`
+    num_hashes: u8,
+    is_tick: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    // Ticks already registered in the bank before these entries
+    registered_ticks: u8,
+    tick_hash_count: u8,
+    entries: Vec<FuzzEntry>,
+    slot_full: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let bank = Arc::new(Bank::new_from_parent(&BANK0, &Pubkey::default(), 1));
+    let registered_ticks = u64::from(input.registered_ticks) % (bank.ticks_per_slot() + 1);
+    for _ in 0..registered_ticks {
+        bank.register_tick(&Hash::default());
+    }
+
+    let entries: Vec<Entry> = input
+        .entries
+        .iter()
+        .map(|entry| Entry {
+            num_hashes: u64::from(entry.num_hashes % 8),
+            hash: Hash::default(),
+            transactions: if entry.is_tick {
+                vec![]
+            } else {
+                vec![Transaction::default()]
+            },
+        })
+        .collect();
+
+    let mut tick_hash_count = u64::from(input.tick_hash_count) % HASHES_PER_TICK;
+    match verify_ticks(&bank, &entries, input.slot_full, &mut tick_hash_count) {
+        Ok(()) => {}
+        Err(err) => assert!(matches!(
+            err,
+            BlockError::TooManyTicks
+                | BlockError::TooFewTicks
+                | BlockError::TrailingEntry
+                | BlockError::InvalidLastTick
+                | BlockError::InvalidTickHashCount
+        )),
+    }
+});
@@ -8,8 +8,11 @@ pub enum BlockError {
     Incomplete,
 
     /// Block entries hashes must all be valid
-    #[error("invalid entry hash")]
-    InvalidEntryHash,
+    #[error("invalid entry hash at entry {entry_index}, {kind:?} check failed")]
+    InvalidEntryHash {
+        entry_index: usize,
+        kind: HashFailureKind,
+    },
 
     /// Blocks must end in a tick that has been marked as the last tick.
     #[error("invalid last tick")]
@@ -37,4 +40,17 @@ pub enum BlockError {
 
     #[error("duplicate block")]
     DuplicateBlock,
+
+    /// The bank of the block froze with a default hash, which is never expected to happen
+    #[error("zero bank hash")]
+    ZeroBankHash,
+}
+
+/// The check that failed for a `BlockError::InvalidEntryHash`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HashFailureKind {
+    /// The entry hash doesn't follow from the previous entry in proof of history
+    Poh,
+    /// A transaction of the entry failed signature verification
+    TransactionSignature,
 }
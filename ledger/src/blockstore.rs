@@ -8,7 +8,7 @@ use crate::{
         IteratorMode, LedgerColumn, Result, WriteBatch,
     },
     blockstore_meta::*,
-    entry::{create_ticks, Entry},
+    entry::{create_ticks, install_in_par_thread_pool, Entry},
     erasure::ErasureConfig,
     leader_schedule_cache::LeaderScheduleCache,
     next_slots_iterator::NextSlotsIterator,
@@ -144,6 +144,7 @@ pub struct Blockstore {
     perf_samples_cf: LedgerColumn<cf::PerfSamples>,
     block_height_cf: LedgerColumn<cf::BlockHeight>,
     program_costs_cf: LedgerColumn<cf::ProgramCosts>,
+    dead_slot_reasons_cf: LedgerColumn<cf::DeadSlotReasons>,
     last_root: Arc<RwLock<Slot>>,
     insert_shreds_lock: Arc<Mutex<()>>,
     pub new_shreds_signals: Vec<SyncSender<bool>>,
@@ -344,6 +345,7 @@ impl Blockstore {
         let perf_samples_cf = db.column();
         let block_height_cf = db.column();
         let program_costs_cf = db.column();
+        let dead_slot_reasons_cf = db.column();
 
         let db = Arc::new(db);
 
@@ -393,6 +395,7 @@ impl Blockstore {
             perf_samples_cf,
             block_height_cf,
             program_costs_cf,
+            dead_slot_reasons_cf,
             new_shreds_signals: vec![],
             completed_slots_senders: vec![],
             insert_shreds_lock: Arc::new(Mutex::new(())),
@@ -2722,6 +2725,20 @@ impl Blockstore {
         slot: Slot,
         start_index: u64,
         allow_dead_slots: bool,
+    ) -> Result<(Vec<Entry>, u64, bool)> {
+        self.get_slot_entries_with_shred_info_and_limit(slot, start_index, allow_dead_slots, None)
+    }
+
+    /// Same as `get_slot_entries_with_shred_info()`, but stops at the first completed data
+    /// block boundary where at least `max_entries` entries have been collected. At least one
+    /// data block is always returned if available, and the slot is only reported as full if
+    /// every remaining data block was consumed.
+    pub fn get_slot_entries_with_shred_info_and_limit(
+        &self,
+        slot: Slot,
+        start_index: u64,
+        allow_dead_slots: bool,
+        max_entries: Option<usize>,
     ) -> Result<(Vec<Entry>, u64, bool)> {
         let (completed_ranges, slot_meta) = self.get_completed_ranges(slot, start_index)?;
 
@@ -2736,29 +2753,49 @@ impl Blockstore {
         }
 
         let slot_meta = slot_meta.unwrap();
-        let num_shreds = completed_ranges
+        // With a limit, data blocks are read in order a chunk at a time, one block per thread,
+        // so that no more than a chunk is read past the block that reaches the limit
+        let chunk_size = match max_entries {
+            Some(_) => get_thread_count().max(1),
+            None => completed_ranges.len(),
+        };
+        let mut entries = vec![];
+        let mut num_entries = 0;
+        let mut num_consumed_ranges = 0;
+        'chunks: for chunk in completed_ranges.chunks(chunk_size) {
+            let chunk_entries: Result<Vec<Vec<Entry>>> =
+                install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+                    chunk
+                        .par_iter()
+                        .map(|(start_index, end_index)| {
+                            self.get_entries_in_data_block(
+                                slot,
+                                *start_index,
+                                *end_index,
+                                Some(&slot_meta),
+                            )
+                        })
+                        .collect()
+                });
+            for block in chunk_entries? {
+                num_entries += block.len();
+                num_consumed_ranges += 1;
+                entries.extend(block);
+                if max_entries.map_or(false, |max_entries| num_entries >= max_entries) {
+                    break 'chunks;
+                }
+            }
+        }
+
+        let num_shreds = completed_ranges[..num_consumed_ranges]
             .last()
             .map(|(_, end_index)| u64::from(*end_index) - start_index + 1)
             .unwrap_or(0);
-
-        let entries: Result<Vec<Vec<Entry>>> = PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                completed_ranges
-                    .par_iter()
-                    .map(|(start_index, end_index)| {
-                        self.get_entries_in_data_block(
-                            slot,
-                            *start_index,
-                            *end_index,
-                            Some(&slot_meta),
-                        )
-                    })
-                    .collect()
-            })
-        });
-
-        let entries: Vec<Entry> = entries?.into_iter().flatten().collect();
-        Ok((entries, num_shreds, slot_meta.is_full()))
+        Ok((
+            entries,
+            num_shreds,
+            slot_meta.is_full() && num_consumed_ranges == completed_ranges.len(),
+        ))
     }
 
     fn get_completed_ranges(
@@ -2980,6 +3017,35 @@ impl Blockstore {
         self.dead_slots_cf.put(slot, &true)
     }
 
+    /// Marks `slot` dead, recording why in the same write so that it survives a restart
+    pub fn set_dead_slot_with_reason(&self, slot: Slot, reason: String) -> Result<()> {
+        let mut write_batch = self.db.batch()?;
+        write_batch.put::<cf::DeadSlotReasons>(slot, &reason)?;
+        write_batch.put::<cf::DeadSlots>(slot, &true)?;
+        self.db.write(write_batch)
+    }
+
+    /// Records why `slot` was marked dead, leaving its dead flag as is
+    pub fn set_dead_slot_reason(&self, slot: Slot, reason: String) -> Result<()> {
+        self.dead_slot_reasons_cf.put(slot, &reason)
+    }
+
+    /// Returns why `slot` was marked dead, if it was marked with
+    /// `set_dead_slot_with_reason()`
+    pub fn get_dead_slot_reason(&self, slot: Slot) -> Option<String> {
+        self.dead_slot_reasons_cf
+            .get(slot)
+            .expect("fetch from DeadSlotReasons column family failed")
+    }
+
+    /// Clears the dead flag of `slot`, keeping its shreds, so that it can be replayed again
+    pub fn remove_dead_slot(&self, slot: Slot) -> Result<()> {
+        let mut write_batch = self.db.batch()?;
+        write_batch.delete::<cf::DeadSlotReasons>(slot)?;
+        write_batch.delete::<cf::DeadSlots>(slot)?;
+        self.db.write(write_batch)
+    }
+
     pub fn store_duplicate_if_not_existing(
         &self,
         slot: Slot,
@@ -3085,6 +3151,16 @@ impl Blockstore {
         self.last_root()
     }
 
+    // find the highest slot in blockstore with a slot meta, or 0 if there is none
+    pub fn highest_slot(&self) -> Slot {
+        self.db
+            .iter::<cf::SlotMeta>(IteratorMode::End)
+            .expect("Couldn't get slot meta iterator for highest_slot()")
+            .next()
+            .map(|(slot, _)| slot)
+            .unwrap_or(0)
+    }
+
     pub fn lowest_cleanup_slot(&self) -> Slot {
         *self.lowest_cleanup_slot.read().unwrap()
     }
@@ -7943,6 +8019,21 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_highest_slot() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            assert_eq!(blockstore.highest_slot(), 0);
+            for slot in vec![3, 9, 5] {
+                let (shreds, _) = make_slot_entries(slot, 0, 1);
+                blockstore.insert_shreds(shreds, None, false).unwrap();
+            }
+            assert_eq!(blockstore.highest_slot(), 9);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_recovery() {
         let slot = 1;
@@ -8275,6 +8366,58 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_remove_dead_slot() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let (shreds, _) = make_slot_entries(1, 0, 1);
+            let num_shreds = shreds.len() as u64;
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            blockstore.set_dead_slot(1).unwrap();
+            assert!(blockstore.is_dead(1));
+
+            blockstore.remove_dead_slot(1).unwrap();
+            assert!(!blockstore.is_dead(1));
+            // The shreds are kept
+            assert!(blockstore.is_full(1));
+            assert_eq!(blockstore.get_slot_entries(1, 0).unwrap().len() as u64, 1);
+            assert!(blockstore
+                .get_data_shred(1, num_shreds - 1)
+                .unwrap()
+                .is_some());
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_dead_slot_reason() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            blockstore
+                .set_dead_slot_with_reason(1, "too few ticks".to_string())
+                .unwrap();
+            blockstore.set_dead_slot(2).unwrap();
+        }
+        {
+            // The reason survives reopening the blockstore
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            assert!(blockstore.is_dead(1));
+            assert_eq!(
+                blockstore.get_dead_slot_reason(1),
+                Some("too few ticks".to_string())
+            );
+            assert!(blockstore.is_dead(2));
+            assert_eq!(blockstore.get_dead_slot_reason(2), None);
+
+            // And is cleared along with the dead flag
+            blockstore.remove_dead_slot(1).unwrap();
+            assert_eq!(blockstore.get_dead_slot_reason(1), None);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_update_completed_data_indexes() {
         let mut completed_data_indexes: Vec<u32> = vec![];
@@ -73,6 +73,8 @@ const PERF_SAMPLES_CF: &str = "perf_samples";
 const BLOCK_HEIGHT_CF: &str = "block_height";
 /// Column family for ProgramCosts
 const PROGRAM_COSTS_CF: &str = "program_costs";
+/// Column family for DeadSlotReasons
+const DEAD_SLOT_REASONS_CF: &str = "dead_slot_reasons";
 
 // 1 day is chosen for the same reasoning of DEFAULT_COMPACTION_SLOT_INTERVAL
 const PERIODIC_COMPACTION_SECONDS: u64 = 60 * 60 * 24;
@@ -180,6 +182,10 @@ pub mod columns {
     #[derive(Debug)]
     // The program costs column
     pub struct ProgramCosts;
+
+    #[derive(Debug)]
+    /// The dead slot reasons column
+    pub struct DeadSlotReasons;
 }
 
 pub enum AccessType {
@@ -263,9 +269,9 @@ impl Rocks {
         recovery_mode: Option<BlockstoreRecoveryMode>,
     ) -> Result<Rocks> {
         use columns::{
-            AddressSignatures, BlockHeight, Blocktime, DeadSlots, DuplicateSlots, ErasureMeta,
-            Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode, ShredData,
-            SlotMeta, TransactionStatus, TransactionStatusIndex,
+            AddressSignatures, BlockHeight, Blocktime, DeadSlotReasons, DeadSlots, DuplicateSlots,
+            ErasureMeta, Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode,
+            ShredData, SlotMeta, TransactionStatus, TransactionStatusIndex,
         };
 
         fs::create_dir_all(&path)?;
@@ -350,6 +356,10 @@ impl Rocks {
             ProgramCosts::NAME,
             get_cf_options::<ProgramCosts>(&access_type, &oldest_slot),
         );
+        let dead_slot_reasons_cf_descriptor = ColumnFamilyDescriptor::new(
+            DeadSlotReasons::NAME,
+            get_cf_options::<DeadSlotReasons>(&access_type, &oldest_slot),
+        );
         // Don't forget to add to both run_purge_with_stats() and
         // compact_storage() in ledger/src/blockstore/blockstore_purge.rs!!
 
@@ -374,6 +384,7 @@ impl Rocks {
             (PerfSamples::NAME, perf_samples_cf_descriptor),
             (BlockHeight::NAME, block_height_cf_descriptor),
             (ProgramCosts::NAME, program_costs_cf_descriptor),
+            (DeadSlotReasons::NAME, dead_slot_reasons_cf_descriptor),
         ];
         let cf_names: Vec<_> = cfs.iter().map(|c| c.0).collect();
 
@@ -473,9 +484,9 @@ impl Rocks {
 
     fn columns(&self) -> Vec<&'static str> {
         use columns::{
-            AddressSignatures, BlockHeight, Blocktime, DeadSlots, DuplicateSlots, ErasureMeta,
-            Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode, ShredData,
-            SlotMeta, TransactionStatus, TransactionStatusIndex,
+            AddressSignatures, BlockHeight, Blocktime, DeadSlotReasons, DeadSlots, DuplicateSlots,
+            ErasureMeta, Index, Orphans, PerfSamples, ProgramCosts, Rewards, Root, ShredCode,
+            ShredData, SlotMeta, TransactionStatus, TransactionStatusIndex,
         };
 
         vec![
@@ -496,6 +507,7 @@ impl Rocks {
             PerfSamples::NAME,
             BlockHeight::NAME,
             ProgramCosts::NAME,
+            DeadSlotReasons::NAME,
         ]
     }
 
@@ -871,6 +883,14 @@ impl TypedColumn for columns::DeadSlots {
     type Type = bool;
 }
 
+impl SlotColumn for columns::DeadSlotReasons {}
+impl ColumnName for columns::DeadSlotReasons {
+    const NAME: &'static str = DEAD_SLOT_REASONS_CF;
+}
+impl TypedColumn for columns::DeadSlotReasons {
+    type Type = String;
+}
+
 impl SlotColumn for columns::DuplicateSlots {}
 impl ColumnName for columns::DuplicateSlots {
     const NAME: &'static str = DUPLICATE_SLOTS_CF;
@@ -1,9 +1,12 @@
 use crate::{
-    block_error::BlockError,
+    block_error::{BlockError, HashFailureKind},
     blockstore::Blockstore,
     blockstore_db::BlockstoreError,
     blockstore_meta::SlotMeta,
-    entry::{create_ticks, Entry, EntrySlice, EntryType, EntryVerificationStatus, VerifyRecyclers},
+    entry::{
+        self, create_ticks, install_in_par_thread_pool, Entry, EntrySlice, EntryType,
+        EntryVerificationStatus, VerifyRecyclers,
+    },
     leader_schedule_cache::LeaderScheduleCache,
 };
 use chrono_humanize::{Accuracy, HumanTime, Tense};
@@ -13,7 +16,9 @@ use log::*;
 use rand::{seq::SliceRandom, thread_rng};
 use rayon::{prelude::*, ThreadPool};
 use solana_measure::measure::Measure;
-use solana_metrics::{datapoint_error, inc_new_counter_debug};
+use solana_metrics::{
+    datapoint_error, datapoint_warn, inc_new_counter_debug, inc_new_counter_info,
+};
 use solana_rayon_threadlimit::get_thread_count;
 use solana_runtime::{
     accounts_db::AccountShrinkThreshold,
@@ -25,12 +30,14 @@ use solana_runtime::{
     bank_forks::BankForks,
     bank_utils,
     commitment::VOTE_THRESHOLD_SIZE,
+    hashed_transaction::HashedTransaction,
     snapshot_utils::BankFromArchiveTimings,
     transaction_batch::TransactionBatch,
     vote_account::ArcVoteAccount,
     vote_sender_types::ReplayVoteSender,
 };
 use solana_sdk::{
+    account::ReadableAccount,
     clock::{Slot, MAX_PROCESSING_AGE},
     genesis_config::GenesisConfig,
     hash::Hash,
@@ -40,7 +47,8 @@ use solana_sdk::{
     transaction::{Result, Transaction, TransactionError},
 };
 use solana_transaction_status::token_balances::{
-    collect_token_balances, TransactionTokenBalancesSet,
+    batch_references_token_program, collect_token_balances, TransactionTokenBalances,
+    TransactionTokenBalancesSet,
 };
 
 use std::{
@@ -48,7 +56,10 @@ use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
     result,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 use thiserror::Error;
@@ -72,10 +83,24 @@ fn first_err(results: &[Result<()>]) -> Result<()> {
     Ok(())
 }
 
-// Includes transaction signature for unit-testing
+// Whether `err` means the fee payer of the transaction couldn't pay for it
+fn is_fee_payer_failure(err: &TransactionError) -> bool {
+    // The fee payer is the only account whose absence fails loading, a missing fee
+    // payer has no balance to pay the fee with either
+    matches!(
+        err,
+        TransactionError::InsufficientFundsForFee | TransactionError::AccountNotFound
+    )
+}
+
+// Includes transaction signature for unit-testing. Fee payers that can't pay are
+// reported under their own tag, and counted in `timings`, when `report_fee_payer_failures`
+// is set
 fn get_first_error(
     batch: &TransactionBatch,
     fee_collection_results: Vec<Result<()>>,
+    report_fee_payer_failures: bool,
+    timings: &mut ExecuteTimings,
 ) -> Option<(Result<()>, Signature)> {
     let mut first_err = None;
     for (result, transaction) in fee_collection_results.iter().zip(batch.transactions_iter()) {
@@ -87,10 +112,17 @@ fn get_first_error(
                 "Unexpected validator error: {:?}, transaction: {:?}",
                 err, transaction
             );
+            let tag = if report_fee_payer_failures && is_fee_payer_failure(err) {
+                timings.num_fee_payer_failures += 1;
+                inc_new_counter_info!("validator_process_entry_error-insufficient_fee_payer", 1);
+                "insufficient_fee_payer"
+            } else {
+                "error"
+            };
             datapoint_error!(
                 "validator_process_entry_error",
                 (
-                    "error",
+                    tag,
                     format!("error: {:?}, transaction: {:?}", err, transaction),
                     String
                 )
@@ -100,19 +132,36 @@ fn get_first_error(
     first_err
 }
 
+// Token balances of the transactions of `batch`. When none of them references a token
+// program, their balances are all empty and none of their accounts needs to be loaded
+fn collect_batch_token_balances(
+    bank: &Bank,
+    batch: &TransactionBatch,
+    batch_has_token_program: bool,
+    mint_decimals: &mut HashMap<Pubkey, u8>,
+) -> TransactionTokenBalances {
+    if batch_has_token_program {
+        collect_token_balances(bank, batch, mint_decimals)
+    } else {
+        batch.transactions_iter().map(|_| vec![]).collect()
+    }
+}
+
 fn execute_batch(
     batch: &TransactionBatch,
     bank: &Arc<Bank>,
     transaction_status_sender: Option<&TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
+    report_fee_payer_failures: bool,
     timings: &mut ExecuteTimings,
 ) -> Result<()> {
     let record_token_balances = transaction_status_sender.is_some();
+    let batch_has_token_program = record_token_balances && batch_references_token_program(batch);
 
     let mut mint_decimals: HashMap<Pubkey, u8> = HashMap::new();
 
     let pre_token_balances = if record_token_balances {
-        collect_token_balances(bank, batch, &mut mint_decimals)
+        collect_batch_token_balances(bank, batch, batch_has_token_program, &mut mint_decimals)
     } else {
         vec![]
     };
@@ -139,7 +188,7 @@ fn execute_batch(
     if let Some(transaction_status_sender) = transaction_status_sender {
         let txs = batch.transactions_iter().cloned().collect();
         let post_token_balances = if record_token_balances {
-            collect_token_balances(bank, batch, &mut mint_decimals)
+            collect_batch_token_balances(bank, batch, batch_has_token_program, &mut mint_decimals)
         } else {
             vec![]
         };
@@ -159,7 +208,12 @@ fn execute_batch(
         );
     }
 
-    let first_err = get_first_error(batch, fee_collection_results);
+    let first_err = get_first_error(
+        batch,
+        fee_collection_results,
+        report_fee_payer_failures,
+        timings,
+    );
     first_err.map(|(result, _)| result).unwrap_or(Ok(()))
 }
 
@@ -169,39 +223,45 @@ fn execute_batches(
     entry_callback: Option<&ProcessCallback>,
     transaction_status_sender: Option<&TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
+    report_fee_payer_failures: bool,
     timings: &mut ExecuteTimings,
-) -> Result<()> {
+) -> result::Result<(), BlockstoreProcessorError> {
     inc_new_counter_debug!("bank-par_execute_entries-count", batches.len());
-    let (results, new_timings): (Vec<Result<()>>, Vec<ExecuteTimings>) =
-        PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                batches
-                    .into_par_iter()
-                    .map(|batch| {
-                        let mut timings = ExecuteTimings::default();
-                        let result = execute_batch(
-                            batch,
-                            bank,
-                            transaction_status_sender,
-                            replay_vote_sender,
-                            &mut timings,
-                        );
-                        if let Some(entry_callback) = entry_callback {
-                            entry_callback(bank);
-                        }
-                        (result, timings)
-                    })
-                    .unzip()
+    let batch_results: Vec<_> = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+        batches
+            .into_par_iter()
+            .map(|batch| {
+                let mut timings = ExecuteTimings::default();
+                let result = execute_batch(
+                    batch,
+                    bank,
+                    transaction_status_sender,
+                    replay_vote_sender,
+                    report_fee_payer_failures,
+                    &mut timings,
+                );
+                let callback_result = entry_callback
+                    .map_or(Ok(()), |entry_callback| entry_callback(bank))
+                    .map_err(|err| BlockstoreProcessorError::EntryCallbackAborted(Box::new(err)));
+                (result, callback_result, timings)
             })
-        });
+            .collect()
+    });
 
     timings.total_batches_len += batches.len();
     timings.num_execute_batches += 1;
-    for timing in new_timings {
+    let mut results = Vec::with_capacity(batch_results.len());
+    let mut first_callback_err = Ok(());
+    for (result, callback_result, timing) in batch_results {
         timings.accumulate(&timing);
+        results.push(result);
+        if first_callback_err.is_ok() {
+            first_callback_err = callback_result;
+        }
     }
 
-    first_err(&results)
+    first_err(&results)?;
+    first_callback_err
 }
 
 /// Process an ordered list of entries in parallel
@@ -223,25 +283,37 @@ pub fn process_entries(
         &mut entry_types,
         randomize,
         None,
+        None,
         transaction_status_sender,
         replay_vote_sender,
+        false,
+        None,
         &mut timings,
-    );
+    )
+    .map_err(|err| match err {
+        BlockstoreProcessorError::InvalidTransaction(err) => err,
+        // Without an entry callback or abort flag only transactions can fail
+        err => unreachable!("unexpected error processing entries: {:?}", err),
+    });
 
     debug!("process_entries: {:?}", timings);
     result
 }
 
 // Note: If randomize is true this will shuffle entries' transactions in-place.
+#[allow(clippy::too_many_arguments)]
 fn process_entries_with_callback(
     bank: &Arc<Bank>,
     entries: &mut [EntryType],
     randomize: bool,
     entry_callback: Option<&ProcessCallback>,
+    program_blacklist: Option<&HashSet<Pubkey>>,
     transaction_status_sender: Option<&TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
+    report_fee_payer_failures: bool,
+    abort: Option<&Arc<AtomicBool>>,
     timings: &mut ExecuteTimings,
-) -> Result<()> {
+) -> result::Result<(), BlockstoreProcessorError> {
     // accumulator for entries that can be processed in parallel
     let mut batches = vec![];
     let mut tick_hashes = vec![];
@@ -261,8 +333,10 @@ fn process_entries_with_callback(
                         entry_callback,
                         transaction_status_sender,
                         replay_vote_sender,
+                        report_fee_payer_failures,
                         timings,
                     )?;
+                    check_abort(abort)?;
                     batches.clear();
                     for hash in &tick_hashes {
                         bank.register_tick(hash);
@@ -271,6 +345,11 @@ fn process_entries_with_callback(
                 }
             }
             EntryType::Transactions(transactions) => {
+                // Check before locking any accounts so that a rejected entry doesn't
+                // execute the batches queued ahead of it
+                if let Some(program_blacklist) = program_blacklist {
+                    check_program_blacklist(transactions, program_blacklist)?;
+                }
                 if randomize {
                     transactions.shuffle(&mut rng);
                 }
@@ -312,8 +391,10 @@ fn process_entries_with_callback(
                             entry_callback,
                             transaction_status_sender,
                             replay_vote_sender,
+                            report_fee_payer_failures,
                             timings,
                         )?;
+                        check_abort(abort)?;
                         batches.clear();
                     }
                 }
@@ -326,14 +407,46 @@ fn process_entries_with_callback(
         entry_callback,
         transaction_status_sender,
         replay_vote_sender,
+        report_fee_payer_failures,
         timings,
     )?;
+    check_abort(abort)?;
     for hash in tick_hashes {
         bank.register_tick(hash);
     }
     Ok(())
 }
 
+// Stops processing between batches once `abort` is set. Batches are never interrupted
+// midway, so the bank is left with whole batches executed
+fn check_abort(abort: Option<&Arc<AtomicBool>>) -> result::Result<(), BlockstoreProcessorError> {
+    if abort.map_or(false, |abort| abort.load(Ordering::Relaxed)) {
+        return Err(BlockstoreProcessorError::Cancelled);
+    }
+    Ok(())
+}
+
+fn check_program_blacklist(
+    transactions: &[HashedTransaction],
+    program_blacklist: &HashSet<Pubkey>,
+) -> result::Result<(), BlockstoreProcessorError> {
+    for hashed_transaction in transactions {
+        let transaction = hashed_transaction.transaction();
+        if let Some(program_id) = transaction
+            .message
+            .account_keys
+            .iter()
+            .find(|key| program_blacklist.contains(key))
+        {
+            return Err(BlockstoreProcessorError::BlacklistedProgram(
+                transaction.signatures.get(0).cloned().unwrap_or_default(),
+                *program_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Error, Debug)]
 pub enum BlockstoreProcessorError {
     #[error("failed to load entries")]
@@ -342,10 +455,10 @@ pub enum BlockstoreProcessorError {
     #[error("failed to load meta")]
     FailedToLoadMeta,
 
-    #[error("invalid block")]
+    #[error("invalid block: {0}")]
     InvalidBlock(#[from] BlockError),
 
-    #[error("invalid transaction")]
+    #[error("invalid transaction: {0}")]
     InvalidTransaction(#[from] TransactionError),
 
     #[error("no valid forks found")]
@@ -354,12 +467,127 @@ pub enum BlockstoreProcessorError {
     #[error("invalid hard fork")]
     InvalidHardFork(Slot),
 
-    #[error("root bank with mismatched capitalization at {0}")]
-    RootBankWithMismatchedCapitalization(Slot),
+    #[error("root bank with mismatched capitalization at {0}: calculated {2}, expected {1}")]
+    RootBankWithMismatchedCapitalization(Slot, u64, u64),
+
+    #[error("slot {0} is corrupted: {1}")]
+    CorruptedSlot(Slot, String),
+
+    #[error("entry callback aborted processing: {0}")]
+    EntryCallbackAborted(Box<BlockstoreProcessorError>),
+
+    #[error("bank hash at halt slot {0} is {2}, expected {1}")]
+    HaltHashMismatch(Slot, Hash, Hash),
+
+    #[error("slot was marked dead externally: {0}")]
+    ExternallyMarkedDead(String),
+
+    #[error("transaction {0} references blacklisted program {1}")]
+    BlacklistedProgram(Signature, Pubkey),
+
+    #[error("bank of slot {0} is not frozen")]
+    UnfrozenBank(Slot),
+
+    #[error("transaction {1} in slot {0} failed signature verification")]
+    InvalidSignature(Slot, Signature),
+
+    #[error("roots are not sorted from largest to smallest: root {0} follows root {1}")]
+    UnsortedRoots(Slot, Slot),
+
+    #[error("processing was cancelled")]
+    Cancelled,
+
+    #[error("supermajority threshold must be in (0.5, 1.0], got {0}")]
+    InvalidSupermajorityThreshold(f64),
+}
+
+/// Callback for accessing bank state while processing the blockstore, returning an
+/// error aborts processing
+pub type ProcessCallback =
+    Arc<dyn Fn(&Bank) -> result::Result<(), BlockstoreProcessorError> + Sync + Send>;
+
+/// Snapshot of how far `process_blockstore` has gotten replaying the ledger
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockstoreProcessorProgress {
+    pub slot: Slot,
+    pub last_root: Slot,
+    pub slots_processed: usize,
+    pub txs_processed: usize,
+    pub pending_slots: usize,
+    pub elapsed: Duration,
+}
+
+/// Callback invoked after every slot replayed while processing the blockstore, must be
+/// cheap since it runs on the replay path
+pub type ProgressCallback = Arc<dyn Fn(BlockstoreProcessorProgress) + Sync + Send>;
+
+/// Receives updates on how far `process_blockstore` has gotten, e.g. to drive a progress
+/// bar. Called from the thread processing the blockstore, so must be cheap
+pub trait ProcessProgressReporter: Send + Sync {
+    /// Called after every slot replayed, with the current root and the number of
+    /// transactions replayed so far
+    fn on_slot(&self, slot: Slot, root: Slot, total_txs: usize);
+    /// Called whenever processing moves the root
+    fn on_root(&self, new_root: Slot);
 }
 
-/// Callback for accessing bank state while processing the blockstore
-pub type ProcessCallback = Arc<dyn Fn(&Bank) + Sync + Send>;
+/// Logs the replay rate every couple of seconds, used when `ProcessOptions` doesn't set
+/// a `progress_reporter`
+pub struct LogProgressReporter {
+    state: Mutex<LogProgressState>,
+}
+
+struct LogProgressState {
+    last_report: Instant,
+    // Slots replayed since the last report
+    slots: usize,
+    // Total number of transactions replayed as of the last report
+    total_txs: usize,
+}
+
+impl Default for LogProgressReporter {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(LogProgressState {
+                last_report: Instant::now(),
+                slots: 0,
+                total_txs: 0,
+            }),
+        }
+    }
+}
+
+impl ProcessProgressReporter for LogProgressReporter {
+    fn on_slot(&self, slot: Slot, root: Slot, total_txs: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.slots += 1;
+        if state.last_report.elapsed() > Duration::from_secs(2) {
+            let secs = state.last_report.elapsed().as_secs_f32();
+            info!(
+                "processing ledger: slot={}, last root slot={} slots={} slots/s={:?} txs/s={}",
+                slot,
+                root,
+                state.slots,
+                state.slots as f32 / secs,
+                total_txs.saturating_sub(state.total_txs) as f32 / secs,
+            );
+            state.last_report = Instant::now();
+            state.slots = 0;
+            state.total_txs = total_txs;
+        }
+    }
+
+    fn on_root(&self, new_root: Slot) {
+        trace!("processing ledger: new root slot={}", new_root);
+    }
+}
+
+/// Callback invoked once for every bank frozen while processing the blockstore, right
+/// after it is frozen. Banks are passed in replay order, so a bank always comes after
+/// its parent, but the order across forks is unspecified when `max_parallel_slots`
+/// replays several slots at once. The root bank loaded from a snapshot isn't replayed
+/// and isn't passed
+pub type SlotFrozenCallback = Arc<dyn Fn(&Bank) + Sync + Send>;
 
 #[derive(Default, Clone)]
 pub struct ProcessOptions {
@@ -367,9 +595,22 @@ pub struct ProcessOptions {
     pub poh_verify: bool,
     pub full_leader_cache: bool,
     pub dev_halt_at_slot: Option<Slot>,
+    // Stop processing once the bank for this slot is frozen, erroring out if its hash
+    // doesn't match
+    pub dev_halt_at_bank_hash: Option<(Slot, Hash)>,
+    // Stop processing once a bank with this hash is frozen, whatever its slot. When
+    // combined with `dev_halt_at_slot` or `dev_halt_at_bank_hash`, whichever triggers
+    // first wins
+    pub dev_halt_at_hash: Option<Hash>,
+    // Never replay slots past this one, even if they're in the blockstore. Unlike
+    // `dev_halt_at_slot`, other forks are still replayed up to this slot
+    pub halt_at_slot_inclusive: Option<Slot>,
     pub entry_callback: Option<ProcessCallback>,
     pub override_num_threads: Option<usize>,
     pub new_hard_forks: Option<Vec<Slot>>,
+    // Ignore requested hard forks at or before the starting root with a warning, instead
+    // of failing with `InvalidHardFork`
+    pub allow_obsolete_hard_forks: bool,
     pub frozen_accounts: Vec<Pubkey>,
     pub debug_keys: Option<Arc<HashSet<Pubkey>>>,
     pub account_indexes: AccountSecondaryIndexes,
@@ -378,6 +619,53 @@ pub struct ProcessOptions {
     pub allow_dead_slots: bool,
     pub accounts_db_test_hash_calculation: bool,
     pub shrink_ratio: AccountShrinkThreshold,
+    // Replay up to this many slots on independent forks concurrently while loading
+    // the ledger, `None` replays one slot at a time. Slots at or past `dev_halt_at_slot`
+    // are only replayed once no slot below it is pending, and slots are replayed one at
+    // a time when halting at a bank hash
+    pub max_parallel_slots: Option<usize>,
+    // Don't verify the capitalization of the root bank, for snapshots that were already
+    // validated out of band
+    pub skip_capitalization_check: bool,
+    // Fraction of the epoch stake that must have rooted a slot for it to be treated as
+    // a cluster confirmed root, processing fails unless it is in `(0.5, 1.0]`. `None` uses
+    // `VOTE_THRESHOLD_SIZE`
+    pub supermajority_threshold: Option<f64>,
+    // Refuse to replay any slot with a transaction that references one of these programs,
+    // marking the slot dead
+    pub program_blacklist: Option<Arc<HashSet<Pubkey>>>,
+    pub progress_callback: Option<ProgressCallback>,
+    // Only verify the proof of history of this fraction of the entries of each slot,
+    // sampled deterministically from the parent bank hash. Tampering with unsampled
+    // entries may go unnoticed, `None` verifies every entry
+    pub poh_verify_sample_rate: Option<f64>,
+    // Verify the signatures and precompiles of every transaction even when `poh_verify`
+    // is off, reporting the first transaction that fails as `InvalidSignature`
+    pub full_signature_audit: bool,
+    // Report fee payers that can't pay for their transaction under the dedicated
+    // `insufficient_fee_payer` tag of `validator_process_entry_error`, and count them
+    // separately, instead of as generic errors
+    pub report_fee_payer_failures: bool,
+    pub on_slot_frozen: Option<SlotFrozenCallback>,
+    pub progress_reporter: Option<Arc<dyn ProcessProgressReporter>>,
+    // Stop replaying as soon as this is set, between two batches of transactions, and
+    // fail with `Cancelled`. The slot being replayed is left unfrozen and isn't marked
+    // dead
+    pub abort: Option<Arc<AtomicBool>>,
+}
+
+impl ProcessOptions {
+    // `supermajority_threshold`, or `VOTE_THRESHOLD_SIZE` when it isn't set
+    fn supermajority_threshold(&self) -> result::Result<f64, BlockstoreProcessorError> {
+        let supermajority_threshold = self.supermajority_threshold.unwrap_or(VOTE_THRESHOLD_SIZE);
+        if supermajority_threshold > 0.5 && supermajority_threshold <= 1.0 {
+            Ok(supermajority_threshold)
+        } else {
+            Err(BlockstoreProcessorError::InvalidSupermajorityThreshold(
+                supermajority_threshold,
+            ))
+        }
+    }
 }
 
 pub fn process_blockstore(
@@ -468,18 +756,7 @@ fn do_process_blockstore_from_root(
     let mut root = start_slot;
 
     if let Some(ref new_hard_forks) = opts.new_hard_forks {
-        let hard_forks = bank.hard_forks();
-
-        for hard_fork_slot in new_hard_forks.iter() {
-            if *hard_fork_slot > start_slot {
-                hard_forks.write().unwrap().register(*hard_fork_slot);
-            } else {
-                warn!(
-                    "Hard fork at {} ignored, --hard-fork option can be removed.",
-                    hard_fork_slot
-                );
-            }
-        }
+        register_hard_forks(&bank, new_hard_forks, opts.allow_obsolete_hard_forks)?;
     }
 
     // ensure start_slot is rooted for correct replay
@@ -544,11 +821,32 @@ fn do_process_blockstore_from_root(
     // We might be promptly restarted after bad capitalization was detected while creating newer snapshot.
     // In that case, we're most likely restored from the last good snapshot and replayed up to this root.
     // So again check here for the bad capitalization to avoid to continue until the next snapshot creation.
-    if !bank_forks
-        .root_bank()
-        .calculate_and_verify_capitalization(debug_verify)
-    {
-        return Err(BlockstoreProcessorError::RootBankWithMismatchedCapitalization(root));
+    let root_bank = bank_forks.root_bank();
+    if opts.skip_capitalization_check {
+        warn!(
+            "Skipping the capitalization check of root bank {} with capitalization {}, the snapshot must have been validated out of band!",
+            root,
+            root_bank.capitalization()
+        );
+        datapoint_warn!(
+            "process_blockstore_from_root-unverified_capitalization",
+            ("slot", root, i64),
+            ("capitalization", root_bank.capitalization(), i64)
+        );
+    } else {
+        let calculated = root_bank.calculate_capitalization(debug_verify);
+        let expected = root_bank.capitalization();
+        if calculated != expected {
+            warn!(
+                "Capitalization mismatch: calculated: {} != expected: {}",
+                calculated, expected
+            );
+            return Err(
+                BlockstoreProcessorError::RootBankWithMismatchedCapitalization(
+                    root, expected, calculated,
+                ),
+            );
+        }
     }
     time_cap.stop();
 
@@ -596,6 +894,63 @@ fn do_process_blockstore_from_root(
     Ok((bank_forks, leader_schedule_cache))
 }
 
+// Registers the requested hard forks past the root `bank`, after checking all of them. A
+// requested hard fork at or before the root is refused, as it's most likely a typo that
+// would silently end up on the wrong fork, unless `allow_obsolete_hard_forks` is set or the
+// bank already has it, like a snapshot taken at the hard fork. Slots already registered
+// past the root are only accepted if requested as many times as they're registered
+fn register_hard_forks(
+    bank: &Bank,
+    new_hard_forks: &[Slot],
+    allow_obsolete_hard_forks: bool,
+) -> result::Result<(), BlockstoreProcessorError> {
+    let start_slot = bank.slot();
+    let hard_forks = bank.hard_forks();
+    let registered_counts: HashMap<Slot, usize> =
+        hard_forks.read().unwrap().iter().copied().collect();
+    let requested_counts = new_hard_forks.iter().counts();
+
+    let mut slots_to_register = vec![];
+    for (hard_fork_slot, requested_count) in requested_counts.into_iter().sorted() {
+        let registered_count = registered_counts.get(hard_fork_slot).copied();
+        if *hard_fork_slot <= start_slot {
+            if registered_count.is_some() {
+                warn!(
+                    "Hard fork at {} is already in the root bank, --hard-fork option can be removed.",
+                    hard_fork_slot
+                );
+            } else if allow_obsolete_hard_forks {
+                warn!(
+                    "Hard fork at {} ignored, --hard-fork option can be removed.",
+                    hard_fork_slot
+                );
+            } else {
+                error!(
+                    "Hard fork at {} is not past the root {}",
+                    hard_fork_slot, start_slot
+                );
+                return Err(BlockstoreProcessorError::InvalidHardFork(*hard_fork_slot));
+            }
+        } else if let Some(registered_count) = registered_count {
+            if registered_count != requested_count {
+                error!(
+                    "Hard fork at {} requested {} times, but already registered {} times",
+                    hard_fork_slot, requested_count, registered_count
+                );
+                return Err(BlockstoreProcessorError::InvalidHardFork(*hard_fork_slot));
+            }
+        } else {
+            slots_to_register.extend(std::iter::repeat(*hard_fork_slot).take(requested_count));
+        }
+    }
+
+    let mut hard_forks = hard_forks.write().unwrap();
+    for hard_fork_slot in slots_to_register {
+        hard_forks.register(hard_fork_slot);
+    }
+    Ok(())
+}
+
 /// Verify that a segment of entries has the correct number of ticks and hashes
 pub fn verify_ticks(
     bank: &Arc<Bank>,
@@ -603,44 +958,410 @@ pub fn verify_ticks(
     slot_full: bool,
     tick_hash_count: &mut u64,
 ) -> std::result::Result<(), BlockError> {
-    let next_bank_tick_height = bank.tick_height() + entries.tick_count();
-    let max_bank_tick_height = bank.max_tick_height();
+    verify_tick_counts(
+        bank.slot(),
+        bank.tick_height(),
+        bank.max_tick_height(),
+        bank.hashes_per_tick().unwrap_or(0),
+        entries,
+        slot_full,
+        tick_hash_count,
+    )
+}
+
+// `verify_ticks()` for a slot whose bank starts at `tick_height` and ends at
+// `max_tick_height`
+fn verify_tick_counts(
+    slot: Slot,
+    tick_height: u64,
+    max_tick_height: u64,
+    hashes_per_tick: u64,
+    entries: &[Entry],
+    slot_full: bool,
+    tick_hash_count: &mut u64,
+) -> std::result::Result<(), BlockError> {
+    let next_tick_height = tick_height + entries.tick_count();
 
-    if next_bank_tick_height > max_bank_tick_height {
-        warn!("Too many entry ticks found in slot: {}", bank.slot());
+    if next_tick_height > max_tick_height {
+        warn!("Too many entry ticks found in slot: {}", slot);
         return Err(BlockError::TooManyTicks);
     }
 
-    if next_bank_tick_height < max_bank_tick_height && slot_full {
-        info!("Too few entry ticks found in slot: {}", bank.slot());
+    if next_tick_height < max_tick_height && slot_full {
+        info!("Too few entry ticks found in slot: {}", slot);
         return Err(BlockError::TooFewTicks);
     }
 
-    if next_bank_tick_height == max_bank_tick_height {
+    if next_tick_height == max_tick_height {
         let has_trailing_entry = entries.last().map(|e| !e.is_tick()).unwrap_or_default();
         if has_trailing_entry {
-            warn!("Slot: {} did not end with a tick entry", bank.slot());
+            warn!("Slot: {} did not end with a tick entry", slot);
             return Err(BlockError::TrailingEntry);
         }
 
         if !slot_full {
-            warn!("Slot: {} was not marked full", bank.slot());
+            warn!("Slot: {} was not marked full", slot);
             return Err(BlockError::InvalidLastTick);
         }
     }
 
-    let hashes_per_tick = bank.hashes_per_tick().unwrap_or(0);
     if !entries.verify_tick_hash_count(tick_hash_count, hashes_per_tick) {
-        warn!(
-            "Tick with invalid number of hashes found in slot: {}",
-            bank.slot()
-        );
+        warn!("Tick with invalid number of hashes found in slot: {}", slot);
         return Err(BlockError::InvalidTickHashCount);
     }
 
     Ok(())
 }
 
+// `verify_and_hash_transactions()` only reports that some transaction failed, so look
+// for the offending entry one at a time. `None` if no entry fails on its own
+fn first_failed_transaction_entry(
+    entries: &[Entry],
+    skip_verification: bool,
+    secp256k1_program_enabled: bool,
+    verify_tx_signatures_len_enabled: bool,
+) -> Option<usize> {
+    entries.iter().position(|entry| {
+        std::slice::from_ref(entry)
+            .verify_and_hash_transactions(
+                skip_verification,
+                secp256k1_program_enabled,
+                verify_tx_signatures_len_enabled,
+            )
+            .is_none()
+    })
+}
+
+// Signature of the first transaction of `entries` that fails verification
+fn first_failed_transaction(entries: &[Entry], bank: &Bank) -> Option<Signature> {
+    entries
+        .iter()
+        .flat_map(|entry| entry.transactions.iter())
+        .find(|tx| {
+            entry::verify_transaction(
+                tx,
+                bank.secp256k1_program_enabled(),
+                bank.verify_tx_signatures_len_enabled(),
+            )
+            .is_none()
+        })
+        .map(|tx| tx.signatures.first().copied().unwrap_or_default())
+}
+
+/// Summary of a slot checked by `verify_slot()`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlotVerificationReport {
+    pub num_entries: usize,
+    pub num_ticks: u64,
+    pub num_txs: usize,
+    pub poh_verify_elapsed: u64,
+    // Where the proof of history of the children of the slot starts
+    pub last_entry_hash: Hash,
+}
+
+/// What `verify_slot()` checks the entries of a slot against: where its bank starts and
+/// ends in ticks and proof of history, and which transaction checks are enabled
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotVerificationContext {
+    pub slot: Slot,
+    pub tick_height: u64,
+    pub max_tick_height: u64,
+    pub hashes_per_tick: u64,
+    pub start_hash: Hash,
+    pub secp256k1_program_enabled: bool,
+    pub verify_tx_signatures_len_enabled: bool,
+}
+
+impl SlotVerificationContext {
+    /// Context of the slot of `bank`, which must not have replayed any of the slot yet
+    pub fn new_from_bank(bank: &Bank) -> Self {
+        Self {
+            slot: bank.slot(),
+            tick_height: bank.tick_height(),
+            max_tick_height: bank.max_tick_height(),
+            hashes_per_tick: bank.hashes_per_tick().unwrap_or(0),
+            start_hash: bank.last_blockhash(),
+            secp256k1_program_enabled: bank.secp256k1_program_enabled(),
+            verify_tx_signatures_len_enabled: bank.verify_tx_signatures_len_enabled(),
+        }
+    }
+}
+
+/// Verifies the ticks, proof of history and transaction signatures of every entry of
+/// `context.slot` in `blockstore`, without executing any transactions or registering any
+/// ticks, so no bank is touched
+pub fn verify_slot(
+    blockstore: &Blockstore,
+    context: &SlotVerificationContext,
+    recyclers: &VerifyRecyclers,
+) -> result::Result<SlotVerificationReport, BlockstoreProcessorError> {
+    let slot = context.slot;
+    let (entries, _num_shreds, slot_full) =
+        blockstore.get_slot_entries_with_shred_info(slot, 0, false)?;
+
+    let mut tick_hash_count = 0;
+    verify_tick_counts(
+        slot,
+        context.tick_height,
+        context.max_tick_height,
+        context.hashes_per_tick,
+        &entries,
+        slot_full,
+        &mut tick_hash_count,
+    )?;
+
+    // Proof of history failures take precedence, as they do in `confirm_slot()`
+    let mut verifier = entries.start_verify(&context.start_hash, recyclers.clone());
+    if !verifier.finish_verify() {
+        let entry_index = verifier.first_failed_entry().unwrap_or_default();
+        warn!(
+            "Proof of history verification failed at slot: {}, entry: {}",
+            slot, entry_index
+        );
+        return Err(BlockError::InvalidEntryHash {
+            entry_index,
+            kind: HashFailureKind::Poh,
+        }
+        .into());
+    }
+
+    let check_result = entries.verify_and_hash_transactions(
+        false,
+        context.secp256k1_program_enabled,
+        context.verify_tx_signatures_len_enabled,
+    );
+    if check_result.is_none() {
+        let err: BlockstoreProcessorError = match first_failed_transaction_entry(
+            &entries,
+            false,
+            context.secp256k1_program_enabled,
+            context.verify_tx_signatures_len_enabled,
+        ) {
+            Some(entry_index) => {
+                warn!(
+                    "Transaction verification failed at slot: {}, entry: {}",
+                    slot, entry_index
+                );
+                BlockError::InvalidEntryHash {
+                    entry_index,
+                    kind: HashFailureKind::TransactionSignature,
+                }
+                .into()
+            }
+            None => {
+                warn!("Transaction verification failed at slot: {}", slot);
+                TransactionError::SignatureFailure.into()
+            }
+        };
+        return Err(err);
+    }
+
+    Ok(SlotVerificationReport {
+        num_entries: entries.len(),
+        num_ticks: entries.tick_count(),
+        num_txs: entries.iter().map(|entry| entry.transactions.len()).sum(),
+        poh_verify_elapsed: verifier.poh_duration_us(),
+        last_entry_hash: entries
+            .last()
+            .map_or(context.start_hash, |entry| entry.hash),
+    })
+}
+
+/// Outcome of checking one slot with `verify_slot_range()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotVerificationResult {
+    pub slot: Slot,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Runs `verify_slot()` on every slot in `[start, end]` in `blockstore`, chaining each
+/// from the last entry of its parent, without executing any transactions. Much faster
+/// than replaying the slots, for auditing a ledger offline. Without a bank to tell which
+/// features are active, transactions get the strictest checks. Slots without any shreds
+/// are skipped, as are dead slots if `opts.allow_dead_slots` is set. Otherwise they're
+/// reported as failures
+pub fn verify_slot_range(
+    blockstore: &Blockstore,
+    genesis_config: &GenesisConfig,
+    start: Slot,
+    end: Slot,
+    opts: &ProcessOptions,
+) -> result::Result<Vec<SlotVerificationResult>, BlockstoreProcessorError> {
+    let recyclers = VerifyRecyclers::default();
+    // Hash of the last entry of each slot verified so far, where the proof of history of
+    // its children starts
+    let mut last_entry_hashes = HashMap::new();
+    let mut results = vec![];
+    for (slot, meta) in blockstore
+        .slot_meta_iterator(start)?
+        .take_while(|(slot, _)| *slot <= end)
+    {
+        if meta.received == 0 || (opts.allow_dead_slots && blockstore.is_dead(slot)) {
+            continue;
+        }
+        let result = if meta.is_full() {
+            slot_verification_context(blockstore, genesis_config, &meta, &last_entry_hashes)
+                .and_then(|context| {
+                    verify_slot(blockstore, &context, &recyclers).map_err(|err| err.to_string())
+                })
+        } else {
+            Err(BlockError::Incomplete.to_string())
+        };
+        match &result {
+            Ok(report) => {
+                last_entry_hashes.insert(slot, report.last_entry_hash);
+            }
+            Err(err) => warn!("Slot {} failed verification: {}", slot, err),
+        }
+        results.push(SlotVerificationResult {
+            slot,
+            ok: result.is_ok(),
+            error: result.err(),
+        });
+    }
+    Ok(results)
+}
+
+// Context of the slot of `meta` for `verify_slot_range()`. Slot 0 starts from the genesis
+// hash, any other slot where its parent ended
+fn slot_verification_context(
+    blockstore: &Blockstore,
+    genesis_config: &GenesisConfig,
+    meta: &SlotMeta,
+    last_entry_hashes: &HashMap<Slot, Hash>,
+) -> result::Result<SlotVerificationContext, String> {
+    let slot = meta.slot;
+    let ticks_per_slot = genesis_config.ticks_per_slot;
+    let (tick_height, start_hash) = if slot == 0 {
+        (0, genesis_config.hash())
+    } else {
+        if !meta.is_parent_set() {
+            return Err("parent slot is unknown".to_string());
+        }
+        let parent_slot = meta.parent_slot;
+        let start_hash = match last_entry_hashes.get(&parent_slot) {
+            Some(hash) => *hash,
+            None => blockstore
+                .get_slot_entries_with_shred_info(parent_slot, 0, true)
+                .ok()
+                .and_then(|(parent_entries, _, _)| parent_entries.last().map(|entry| entry.hash))
+                .ok_or_else(|| format!("parent slot {} has no entries", parent_slot))?,
+        };
+        ((parent_slot + 1) * ticks_per_slot, start_hash)
+    };
+    Ok(SlotVerificationContext {
+        slot,
+        tick_height,
+        max_tick_height: (slot + 1) * ticks_per_slot,
+        hashes_per_tick: genesis_config.poh_config.hashes_per_tick.unwrap_or(0),
+        start_hash,
+        secp256k1_program_enabled: true,
+        verify_tx_signatures_len_enabled: true,
+    })
+}
+
+/// A transaction executed by `simulate_slot()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedTransaction {
+    pub signature: Signature,
+    pub result: Result<()>,
+    // Lamports of each account of the transaction, before and after it executed
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+}
+
+/// Replays `slot` from `blockstore` with `process_entries()` on a throwaway child of the
+/// frozen bank `parent`, returning the result of each transaction in block order. The
+/// entries are verified first, as `verify_slot()` does. Nothing is written to `parent`,
+/// and the accounts and signatures the child bank stored for `slot` are dropped along
+/// with it, so must not be called while `slot` is being replayed
+pub fn simulate_slot(
+    blockstore: &Blockstore,
+    parent: &Arc<Bank>,
+    slot: Slot,
+) -> result::Result<Vec<SimulatedTransaction>, BlockstoreProcessorError> {
+    if !parent.is_frozen() {
+        return Err(BlockstoreProcessorError::UnfrozenBank(parent.slot()));
+    }
+    let (mut entries, _num_shreds, slot_full) =
+        blockstore.get_slot_entries_with_shred_info(slot, 0, false)?;
+    if !slot_full {
+        return Err(BlockError::Incomplete.into());
+    }
+
+    let leader = LeaderScheduleCache::new_from_bank(parent)
+        .slot_leader_at(slot, Some(parent))
+        .unwrap_or_default();
+    let bank = Arc::new(Bank::new_from_parent(parent, &leader, slot));
+    let context = SlotVerificationContext::new_from_bank(&bank);
+    let result = verify_slot(blockstore, &context, &VerifyRecyclers::default()).and_then(|_| {
+        // Collect the results and balances the same way replay reports them to RPC
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let transaction_status_sender = TransactionStatusSender {
+            sender,
+            enable_cpi_and_log_storage: false,
+        };
+        process_entries(
+            &bank,
+            &mut entries,
+            false,
+            Some(&transaction_status_sender),
+            None,
+        )?;
+        drop(transaction_status_sender);
+        Ok(receiver.iter().collect::<Vec<_>>())
+    });
+    // Forget the signatures the child bank registered, a later replay of the slot would
+    // otherwise reject them as already processed. Its accounts are purged once it's dropped
+    bank.clear_slot_signatures(slot);
+    let messages = result?;
+
+    let mut simulated_transactions: Vec<_> = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            TransactionStatusMessage::Batch(batch) => Some(batch),
+            _ => None,
+        })
+        .flat_map(|batch| {
+            let TransactionBalancesSet {
+                pre_balances,
+                post_balances,
+            } = batch.balances;
+            batch
+                .transactions
+                .into_iter()
+                .zip(batch.statuses)
+                .zip(pre_balances.into_iter().zip(post_balances))
+                .map(
+                    |((transaction, (result, _)), (pre_balances, post_balances))| {
+                        SimulatedTransaction {
+                            signature: transaction.signatures.get(0).cloned().unwrap_or_default(),
+                            result,
+                            pre_balances,
+                            post_balances,
+                        }
+                    },
+                )
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    // Batches of the same entries execute in parallel, so restore the block order
+    let mut block_order = HashMap::new();
+    for (i, transaction) in entries
+        .iter()
+        .flat_map(|entry| entry.transactions.iter())
+        .enumerate()
+    {
+        block_order
+            .entry(transaction.signatures.get(0).cloned().unwrap_or_default())
+            .or_insert(i);
+    }
+    simulated_transactions.sort_by_key(|transaction| block_order.get(&transaction.signature));
+    Ok(simulated_transactions)
+}
+
 fn confirm_full_slot(
     blockstore: &Blockstore,
     bank: &Arc<Bank>,
@@ -653,18 +1374,26 @@ fn confirm_full_slot(
 ) -> result::Result<(), BlockstoreProcessorError> {
     let mut confirmation_timing = ConfirmationTiming::default();
     let skip_verification = !opts.poh_verify;
-    confirm_slot(
+    let confirm_result = confirm_slot(
         blockstore,
         bank,
         &mut confirmation_timing,
         progress,
         skip_verification,
+        opts.poh_verify_sample_rate,
+        opts.full_signature_audit,
         transaction_status_sender,
         replay_vote_sender,
         opts.entry_callback.as_ref(),
+        opts.program_blacklist.as_deref(),
+        opts.report_fee_payer_failures,
+        opts.abort.as_ref(),
         recyclers,
         opts.allow_dead_slots,
-    )?;
+        None,
+    );
+    confirmation_timing.report_stats(bank.slot());
+    confirm_result?;
 
     timing.accumulate(&confirmation_timing.execute_timings);
 
@@ -701,6 +1430,29 @@ impl Default for ConfirmationTiming {
     }
 }
 
+impl ConfirmationTiming {
+    pub fn report_stats(&self, slot: Slot) {
+        datapoint_info!(
+            "confirm_slot_timing",
+            ("slot", slot as i64, i64),
+            ("fetch_elapsed", self.fetch_elapsed as i64, i64),
+            ("fetch_fail_elapsed", self.fetch_fail_elapsed as i64, i64),
+            ("poh_verify_elapsed", self.poh_verify_elapsed as i64, i64),
+            (
+                "transaction_verify_elapsed",
+                self.transaction_verify_elapsed as i64,
+                i64
+            ),
+            ("replay_elapsed", self.replay_elapsed as i64, i64),
+            (
+                "total_elapsed",
+                self.started.elapsed().as_micros() as i64,
+                i64
+            ),
+        );
+    }
+}
+
 #[derive(Default)]
 pub struct ConfirmationProgress {
     pub last_entry: Hash,
@@ -726,18 +1478,29 @@ pub fn confirm_slot(
     timing: &mut ConfirmationTiming,
     progress: &mut ConfirmationProgress,
     skip_verification: bool,
+    poh_verify_sample_rate: Option<f64>,
+    full_signature_audit: bool,
     transaction_status_sender: Option<&TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
     entry_callback: Option<&ProcessCallback>,
+    program_blacklist: Option<&HashSet<Pubkey>>,
+    report_fee_payer_failures: bool,
+    abort: Option<&Arc<AtomicBool>>,
     recyclers: &VerifyRecyclers,
     allow_dead_slots: bool,
+    max_entries: Option<usize>,
 ) -> result::Result<(), BlockstoreProcessorError> {
     let slot = bank.slot();
 
     let (entries, num_shreds, slot_full) = {
         let mut load_elapsed = Measure::start("load_elapsed");
         let load_result = blockstore
-            .get_slot_entries_with_shred_info(slot, progress.num_shreds, allow_dead_slots)
+            .get_slot_entries_with_shred_info_and_limit(
+                slot,
+                progress.num_shreds,
+                allow_dead_slots,
+                max_entries,
+            )
             .map_err(BlockstoreProcessorError::FailedToLoadEntries);
         load_elapsed.stop();
         if load_result.is_err() {
@@ -778,29 +1541,93 @@ pub fn confirm_slot(
     }
 
     let last_entry_hash = entries.last().map(|e| e.hash);
-    let verifier = if !skip_verification {
+    if !skip_verification {
         datapoint_debug!("verify-batch-size", ("size", num_entries as i64, i64));
-        let entry_state = entries.start_verify(&progress.last_entry, recyclers.clone());
+    }
+    // Start the proof of history verification and verify the transaction signatures
+    // concurrently. On the GPU, proof of history verification is still in flight when
+    // this returns and is only joined in `finish_verify()` below
+    let last_entry = progress.last_entry;
+    let skip_transaction_verification = skip_verification && !full_signature_audit;
+    let (verifier, (check_result, transaction_duration_us)) =
+        install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            rayon::join(
+                || {
+                    if !skip_verification {
+                        Some(match poh_verify_sample_rate {
+                            Some(sample_rate) => entries.verify_sampled(
+                                &last_entry,
+                                sample_rate,
+                                &bank.parent_hash(),
+                            ),
+                            None => entries.start_verify(&last_entry, recyclers.clone()),
+                        })
+                    } else {
+                        None
+                    }
+                },
+                || {
+                    let check_start = Instant::now();
+                    let check_result = entries.verify_and_hash_transactions(
+                        skip_transaction_verification,
+                        bank.secp256k1_program_enabled(),
+                        bank.verify_tx_signatures_len_enabled(),
+                    );
+                    (check_result, timing::duration_as_us(&check_start.elapsed()))
+                },
+            )
+        });
+    if let Some(entry_state) = &verifier {
         if entry_state.status() == EntryVerificationStatus::Failure {
-            warn!("Ledger proof of history failed at slot: {}", slot);
-            return Err(BlockError::InvalidEntryHash.into());
+            let entry_index =
+                progress.num_entries + entry_state.first_failed_entry().unwrap_or_default();
+            warn!(
+                "Ledger proof of history failed at slot: {}, entry: {}",
+                slot, entry_index
+            );
+            return Err(BlockError::InvalidEntryHash {
+                entry_index,
+                kind: HashFailureKind::Poh,
+            }
+            .into());
         }
-        Some(entry_state)
-    } else {
-        None
-    };
-
-    let check_start = Instant::now();
-    let check_result = entries.verify_and_hash_transactions(
-        skip_verification,
-        bank.secp256k1_program_enabled(),
-        bank.verify_tx_signatures_len_enabled(),
-    );
+    }
     if check_result.is_none() {
-        warn!("Ledger proof of history failed at slot: {}", slot);
-        return Err(BlockError::InvalidEntryHash.into());
+        if full_signature_audit {
+            if let Some(signature) = first_failed_transaction(&entries, bank) {
+                warn!(
+                    "Ledger signature audit failed at slot: {}, transaction: {}",
+                    slot, signature
+                );
+                return Err(BlockstoreProcessorError::InvalidSignature(slot, signature));
+            }
+        }
+        let err: BlockstoreProcessorError = match first_failed_transaction_entry(
+            &entries,
+            skip_transaction_verification,
+            bank.secp256k1_program_enabled(),
+            bank.verify_tx_signatures_len_enabled(),
+        ) {
+            Some(entry_index) => {
+                let entry_index = progress.num_entries + entry_index;
+                warn!(
+                    "Ledger transaction verification failed at slot: {}, entry: {}",
+                    slot, entry_index
+                );
+                BlockError::InvalidEntryHash {
+                    entry_index,
+                    kind: HashFailureKind::TransactionSignature,
+                }
+                .into()
+            }
+            // Only the entries as a whole fail, there's no single entry to point at
+            None => {
+                warn!("Ledger transaction verification failed at slot: {}", slot);
+                TransactionError::SignatureFailure.into()
+            }
+        };
+        return Err(err);
     }
-    let transaction_duration_us = timing::duration_as_us(&check_start.elapsed());
 
     let mut entries = check_result.unwrap();
     let mut replay_elapsed = Measure::start("replay_elapsed");
@@ -811,11 +1638,13 @@ pub fn confirm_slot(
         &mut entries,
         true, // shuffle transactions.
         entry_callback,
+        program_blacklist,
         transaction_status_sender,
         replay_vote_sender,
+        report_fee_payer_failures,
+        abort,
         &mut execute_timings,
-    )
-    .map_err(BlockstoreProcessorError::from);
+    );
     replay_elapsed.stop();
     timing.replay_elapsed += replay_elapsed.as_us();
 
@@ -826,8 +1655,18 @@ pub fn confirm_slot(
         timing.poh_verify_elapsed += verifier.poh_duration_us();
         timing.transaction_verify_elapsed += transaction_duration_us;
         if !verified {
-            warn!("Ledger proof of history failed at slot: {}", bank.slot());
-            return Err(BlockError::InvalidEntryHash.into());
+            let entry_index =
+                progress.num_entries + verifier.first_failed_entry().unwrap_or_default();
+            warn!(
+                "Ledger proof of history failed at slot: {}, entry: {}",
+                bank.slot(),
+                entry_index
+            );
+            return Err(BlockError::InvalidEntryHash {
+                entry_index,
+                kind: HashFailureKind::Poh,
+            }
+            .into());
         }
     }
 
@@ -852,6 +1691,11 @@ fn process_bank_0(
     cache_block_meta_sender: Option<&CacheBlockMetaSender>,
 ) {
     assert_eq!(bank0.slot(), 0);
+    // Slot 0 only holds ticks and must always be processed, so it ignores the abort flag
+    let opts = &ProcessOptions {
+        abort: None,
+        ..opts.clone()
+    };
     let mut progress = ConfirmationProgress::new(bank0.last_blockhash());
     confirm_full_slot(
         blockstore,
@@ -865,6 +1709,9 @@ fn process_bank_0(
     )
     .expect("processing for bank 0 must succeed");
     bank0.freeze();
+    if let Some(on_slot_frozen) = &opts.on_slot_frozen {
+        on_slot_frozen(bank0);
+    }
     cache_block_meta(bank0, cache_block_meta_sender);
 }
 
@@ -877,6 +1724,7 @@ fn process_next_slots(
     leader_schedule_cache: &LeaderScheduleCache,
     pending_slots: &mut Vec<(SlotMeta, Arc<Bank>, Hash)>,
     initial_forks: &mut HashMap<Slot, Arc<Bank>>,
+    halt_at_slot_inclusive: Option<Slot>,
 ) -> result::Result<(), BlockstoreProcessorError> {
     if let Some(parent) = bank.parent() {
         initial_forks.remove(&parent.slot());
@@ -889,6 +1737,9 @@ fn process_next_slots(
 
     // This is a fork point if there are multiple children, create a new child bank for each fork
     for next_slot in &meta.next_slots {
+        if halt_at_slot_inclusive.map_or(false, |halt_slot| *next_slot > halt_slot) {
+            continue;
+        }
         let next_meta = blockstore
             .meta(*next_slot)
             .map_err(|err| {
@@ -936,14 +1787,15 @@ fn load_frozen_forks(
     cache_block_meta_sender: Option<&CacheBlockMetaSender>,
     timing: &mut ExecuteTimings,
 ) -> result::Result<Vec<Arc<Bank>>, BlockstoreProcessorError> {
+    let supermajority_threshold = opts.supermajority_threshold()?;
     let mut initial_forks = HashMap::new();
     let mut all_banks = HashMap::new();
-    let mut last_status_report = Instant::now();
     let mut last_free = Instant::now();
     let mut pending_slots = vec![];
     let mut last_root = root_bank.slot();
-    let mut slots_elapsed = 0;
-    let mut txs = 0;
+    let processing_start = Instant::now();
+    let mut total_slots_processed = 0;
+    let mut total_txs_processed = 0;
     let blockstore_max_root = blockstore.max_root();
     let max_root = std::cmp::max(root_bank.slot(), blockstore_max_root);
     info!(
@@ -957,176 +1809,360 @@ fn load_frozen_forks(
         leader_schedule_cache,
         &mut pending_slots,
         &mut initial_forks,
+        opts.halt_at_slot_inclusive,
     )?;
 
+    let log_progress_reporter;
+    let progress_reporter: &dyn ProcessProgressReporter = match &opts.progress_reporter {
+        Some(progress_reporter) => progress_reporter.as_ref(),
+        None => {
+            log_progress_reporter = LogProgressReporter::default();
+            &log_progress_reporter
+        }
+    };
+
     let dev_halt_at_slot = opts.dev_halt_at_slot.unwrap_or(std::u64::MAX);
     if root_bank.slot() != dev_halt_at_slot {
         while !pending_slots.is_empty() {
-            let (meta, bank, last_entry_hash) = pending_slots.pop().unwrap();
-            let slot = bank.slot();
-            if last_status_report.elapsed() > Duration::from_secs(2) {
-                let secs = last_status_report.elapsed().as_secs() as f32;
-                last_status_report = Instant::now();
-                info!(
-                    "processing ledger: slot={}, last root slot={} slots={} slots/s={:?} txs/s={}",
-                    slot,
-                    last_root,
-                    slots_elapsed,
-                    slots_elapsed as f32 / secs,
-                    txs as f32 / secs,
-                );
-                slots_elapsed = 0;
-                txs = 0;
-            }
-
-            let mut progress = ConfirmationProgress::new(last_entry_hash);
+            // Every pending slot's parent has already been processed, so pending slots are
+            // never ancestors of one another and can be replayed independently. Which bank
+            // hits a halt hash can't be known before replaying it, so slots are replayed one
+            // at a time then
+            let batch_size =
+                if opts.dev_halt_at_bank_hash.is_some() || opts.dev_halt_at_hash.is_some() {
+                    1
+                } else {
+                    opts.max_parallel_slots.unwrap_or(1).max(1)
+                };
+            let batch = next_pending_batch(&mut pending_slots, batch_size, dev_halt_at_slot);
 
-            if process_single_slot(
+            let processed_slots = process_pending_slots(
+                batch,
                 blockstore,
-                &bank,
                 opts,
                 recyclers,
-                &mut progress,
                 transaction_status_sender,
                 cache_block_meta_sender,
-                None,
-                timing,
-            )
-            .is_err()
-            {
-                continue;
-            }
-            txs += progress.num_txs;
+            );
 
-            // Block must be frozen by this point, otherwise `process_single_slot` would
-            // have errored above
-            assert!(bank.is_frozen());
-            all_banks.insert(bank.slot(), bank.clone());
-
-            // If we've reached the last known root in blockstore, start looking
-            // for newer cluster confirmed roots
-            let new_root_bank = {
-                if *root >= max_root {
-                    supermajority_root_from_vote_accounts(
-                        bank.slot(),
-                        bank.total_epoch_stake(),
-                        bank.vote_accounts(),
-                    ).and_then(|supermajority_root| {
-                        if supermajority_root > *root {
-                            // If there's a cluster confirmed root greater than our last
-                            // replayed root, then because the cluster confirmed root should
-                            // be descended from our last root, it must exist in `all_banks`
-                            let cluster_root_bank = all_banks.get(&supermajority_root).unwrap();
-
-                            // cluster root must be a descendant of our root, otherwise something
-                            // is drastically wrong
-                            assert!(cluster_root_bank.ancestors.contains_key(root));
-                            info!("blockstore processor found new cluster confirmed root: {}, observed in bank: {}", cluster_root_bank.slot(), bank.slot());
-
-                            // Ensure cluster-confirmed root and parents are set as root in blockstore
-                            let mut rooted_slots = vec![];
-                            let mut new_root_bank = cluster_root_bank.clone();
-                            loop {
-                                if new_root_bank.slot() == *root { break; } // Found the last root in the chain, yay!
-                                assert!(new_root_bank.slot() > *root);
-
-                                rooted_slots.push((new_root_bank.slot(), new_root_bank.hash()));
-                                // As noted, the cluster confirmed root should be descended from
-                                // our last root; therefore parent should be set
-                                new_root_bank = new_root_bank.parent().unwrap();
+            for (meta, bank, progress, result, slot_timing) in processed_slots {
+                timing.accumulate(&slot_timing);
+                if let Err(err) = result {
+                    match err {
+                        BlockstoreProcessorError::EntryCallbackAborted(_)
+                        | BlockstoreProcessorError::Cancelled => return Err(err),
+                        _ => continue,
+                    }
+                }
+                // A new root found while processing an earlier slot of this batch may have
+                // pruned this fork
+                if !bank.ancestors.contains_key(root) {
+                    continue;
+                }
+                let slot = bank.slot();
+                total_txs_processed += progress.num_txs;
+
+                // Block must be frozen by this point, otherwise `process_single_slot` would
+                // have errored above
+                assert!(bank.is_frozen());
+                all_banks.insert(bank.slot(), bank.clone());
+
+                // If we've reached the last known root in blockstore, start looking
+                // for newer cluster confirmed roots
+                let new_root_bank = {
+                    if *root >= max_root {
+                        supermajority_root_from_vote_accounts(
+                            bank.slot(),
+                            bank.total_epoch_stake(),
+                            bank.vote_accounts(),
+                            supermajority_threshold,
+                        ).and_then(|supermajority_root| {
+                            if supermajority_root > *root {
+                                // If there's a cluster confirmed root greater than our last
+                                // replayed root, then because the cluster confirmed root should
+                                // be descended from our last root, it must exist in `all_banks`
+                                let cluster_root_bank = all_banks.get(&supermajority_root).unwrap();
+
+                                // cluster root must be a descendant of our root, otherwise something
+                                // is drastically wrong
+                                assert!(cluster_root_bank.ancestors.contains_key(root));
+                                info!("blockstore processor found new cluster confirmed root: {}, observed in bank: {}", cluster_root_bank.slot(), bank.slot());
+
+                                // Ensure cluster-confirmed root and parents are set as root in blockstore
+                                let mut rooted_slots = vec![];
+                                let mut new_root_bank = cluster_root_bank.clone();
+                                loop {
+                                    if new_root_bank.slot() == *root { break; } // Found the last root in the chain, yay!
+                                    assert!(new_root_bank.slot() > *root);
+
+                                    rooted_slots.push((new_root_bank.slot(), new_root_bank.hash()));
+                                    // As noted, the cluster confirmed root should be descended from
+                                    // our last root; therefore parent should be set
+                                    new_root_bank = new_root_bank.parent().unwrap();
+                                }
+                                inc_new_counter_info!("load_frozen_forks-cluster-confirmed-root", rooted_slots.len());
+                                blockstore.set_roots(rooted_slots.iter().map(|(slot, _hash)| slot)).expect("Blockstore::set_roots should succeed");
+                                Some(cluster_root_bank)
+                            } else {
+                                None
                             }
-                            inc_new_counter_info!("load_frozen_forks-cluster-confirmed-root", rooted_slots.len());
-                            blockstore.set_roots(rooted_slots.iter().map(|(slot, _hash)| slot)).expect("Blockstore::set_roots should succeed");
-                            Some(cluster_root_bank)
-                        } else {
-                            None
-                        }
-                    })
-                } else if blockstore.is_root(slot) {
-                    Some(&bank)
-                } else {
-                    None
+                        })
+                    } else if blockstore.is_root(slot) {
+                        Some(&bank)
+                    } else {
+                        None
+                    }
+                };
+
+                if let Some(new_root_bank) = new_root_bank {
+                    *root = new_root_bank.slot();
+                    last_root = new_root_bank.slot();
+                    progress_reporter.on_root(last_root);
+
+                    leader_schedule_cache.set_root(new_root_bank);
+                    new_root_bank.squash();
+
+                    if last_free.elapsed() > Duration::from_secs(10) {
+                        // Must be called after `squash()`, so that AccountsDb knows what
+                        // the roots are for the cache flushing in exhaustively_free_unused_resource().
+                        // This could take few secs; so update last_free later
+                        new_root_bank.exhaustively_free_unused_resource();
+                        last_free = Instant::now();
+                    }
+
+                    // Filter out all non descendants of the new root
+                    pending_slots
+                        .retain(|(_, pending_bank, _)| pending_bank.ancestors.contains_key(root));
+                    initial_forks
+                        .retain(|_, fork_tip_bank| fork_tip_bank.ancestors.contains_key(root));
+                    all_banks.retain(|_, bank| bank.ancestors.contains_key(root));
                 }
-            };
 
-            if let Some(new_root_bank) = new_root_bank {
-                *root = new_root_bank.slot();
-                last_root = new_root_bank.slot();
+                total_slots_processed += 1;
 
-                leader_schedule_cache.set_root(new_root_bank);
-                new_root_bank.squash();
+                trace!(
+                    "Bank for {}slot {} is complete",
+                    if last_root == slot { "root " } else { "" },
+                    slot,
+                );
 
-                if last_free.elapsed() > Duration::from_secs(10) {
-                    // Must be called after `squash()`, so that AccountsDb knows what
-                    // the roots are for the cache flushing in exhaustively_free_unused_resource().
-                    // This could take few secs; so update last_free later
-                    new_root_bank.exhaustively_free_unused_resource();
-                    last_free = Instant::now();
+                process_next_slots(
+                    &bank,
+                    &meta,
+                    blockstore,
+                    leader_schedule_cache,
+                    &mut pending_slots,
+                    &mut initial_forks,
+                    opts.halt_at_slot_inclusive,
+                )?;
+
+                progress_reporter.on_slot(slot, last_root, total_txs_processed);
+                if let Some(progress_callback) = &opts.progress_callback {
+                    progress_callback(BlockstoreProcessorProgress {
+                        slot,
+                        last_root,
+                        slots_processed: total_slots_processed,
+                        txs_processed: total_txs_processed,
+                        pending_slots: pending_slots.len(),
+                        elapsed: processing_start.elapsed(),
+                    });
                 }
 
-                // Filter out all non descendants of the new root
-                pending_slots
-                    .retain(|(_, pending_bank, _)| pending_bank.ancestors.contains_key(root));
-                initial_forks.retain(|_, fork_tip_bank| fork_tip_bank.ancestors.contains_key(root));
-                all_banks.retain(|_, bank| bank.ancestors.contains_key(root));
+                if slot >= dev_halt_at_slot
+                    || reached_halt_bank_hash(&bank, opts)?
+                    || reached_halt_hash(&bank, opts)
+                {
+                    pending_slots.clear();
+                    break;
+                }
             }
+        }
+    }
 
-            slots_elapsed += 1;
-
-            trace!(
-                "Bank for {}slot {} is complete",
-                if last_root == slot { "root " } else { "" },
-                slot,
-            );
+    Ok(initial_forks.values().cloned().collect::<Vec<_>>())
+}
 
-            process_next_slots(
-                &bank,
-                &meta,
-                blockstore,
-                leader_schedule_cache,
-                &mut pending_slots,
-                &mut initial_forks,
-            )?;
+// Takes the next `batch_size` pending slots to replay off the top of `pending_slots`.
+// Replaying a slot at or past `dev_halt_at_slot` ends processing, so any other slot in its
+// batch would be replayed, and have its transaction statuses written, for nothing. With
+// batches of more than one slot, such slots are left pending until nothing below the halt
+// slot is, and then the lowest of them is replayed on its own
+fn next_pending_batch(
+    pending_slots: &mut Vec<(SlotMeta, Arc<Bank>, Hash)>,
+    batch_size: usize,
+    dev_halt_at_slot: Slot,
+) -> Vec<(SlotMeta, Arc<Bank>, Hash)> {
+    if batch_size == 1 {
+        return pending_slots.pop().into_iter().collect();
+    }
+    let (mut past_halt, mut below_halt): (Vec<_>, Vec<_>) = pending_slots
+        .drain(..)
+        .partition(|(_, bank, _)| bank.slot() >= dev_halt_at_slot);
+    let batch = if below_halt.is_empty() {
+        let lowest = past_halt
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (_, bank, _))| bank.slot())
+            .map(|(i, _)| i);
+        lowest.map(|i| past_halt.remove(i)).into_iter().collect()
+    } else {
+        below_halt.split_off(below_halt.len().saturating_sub(batch_size))
+    };
+    *pending_slots = past_halt;
+    pending_slots.extend(below_halt);
+    batch
+}
 
-            if slot >= dev_halt_at_slot {
-                break;
+// Returns whether `bank` is the bank processing should halt at according to
+// `ProcessOptions::dev_halt_at_bank_hash`
+fn reached_halt_bank_hash(
+    bank: &Bank,
+    opts: &ProcessOptions,
+) -> result::Result<bool, BlockstoreProcessorError> {
+    match opts.dev_halt_at_bank_hash {
+        Some((halt_slot, halt_hash)) if halt_slot == bank.slot() => {
+            let bank_hash = bank.hash();
+            if bank_hash == halt_hash {
+                info!(
+                    "halting blockstore processing at slot {}, bank hash {} matches",
+                    halt_slot, bank_hash
+                );
+                Ok(true)
+            } else {
+                warn!(
+                    "halting blockstore processing at slot {}, bank hash {} doesn't match {}",
+                    halt_slot, bank_hash, halt_hash
+                );
+                Err(BlockstoreProcessorError::HaltHashMismatch(
+                    halt_slot, halt_hash, bank_hash,
+                ))
             }
         }
+        _ => Ok(false),
     }
+}
 
-    Ok(initial_forks.values().cloned().collect::<Vec<_>>())
+// Returns whether `bank` has the hash processing should halt at according to
+// `ProcessOptions::dev_halt_at_hash`
+fn reached_halt_hash(bank: &Bank, opts: &ProcessOptions) -> bool {
+    let reached = opts.dev_halt_at_hash == Some(bank.hash());
+    if reached {
+        info!(
+            "halting blockstore processing at slot {}, reached bank hash {}",
+            bank.slot(),
+            bank.hash()
+        );
+    }
+    reached
+}
+
+type ProcessedSlot = (
+    SlotMeta,
+    Arc<Bank>,
+    ConfirmationProgress,
+    result::Result<(), BlockstoreProcessorError>,
+    ExecuteTimings,
+);
+
+// Replays each of the pending `slots` into its bank, in parallel on `PAR_THREAD_POOL`
+// when there is more than one, returning the results in slot order
+fn process_pending_slots(
+    slots: Vec<(SlotMeta, Arc<Bank>, Hash)>,
+    blockstore: &Blockstore,
+    opts: &ProcessOptions,
+    recyclers: &VerifyRecyclers,
+    transaction_status_sender: Option<&TransactionStatusSender>,
+    cache_block_meta_sender: Option<&CacheBlockMetaSender>,
+) -> Vec<ProcessedSlot> {
+    let process_slot = |(meta, bank, last_entry_hash): (SlotMeta, Arc<Bank>, Hash)| {
+        let mut progress = ConfirmationProgress::new(last_entry_hash);
+        let mut timing = ExecuteTimings::default();
+        let result = process_single_slot(
+            blockstore,
+            &bank,
+            opts,
+            recyclers,
+            &mut progress,
+            transaction_status_sender,
+            cache_block_meta_sender,
+            None,
+            &mut timing,
+        );
+        (meta, bank, progress, result, timing)
+    };
+
+    let mut processed_slots: Vec<ProcessedSlot> = if slots.len() > 1 {
+        install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            slots.into_par_iter().map(process_slot).collect()
+        })
+    } else {
+        slots.into_iter().map(process_slot).collect()
+    };
+    processed_slots.sort_by_key(|(_, bank, _, _, _)| bank.slot());
+    processed_slots
 }
 
-// `roots` is sorted largest to smallest by root slot
-fn supermajority_root(roots: &[(Slot, u64)], total_epoch_stake: u64) -> Option<Slot> {
+/// Returns the largest root slot that more than `threshold` of `total_epoch_stake` has
+/// rooted, `VOTE_THRESHOLD_SIZE` for a supermajority. `roots` are the `(root_slot, stake)`
+/// of each vote account, sorted largest to smallest by root slot, and an error is
+/// returned if they aren't
+pub fn supermajority_root(
+    roots: &[(Slot, u64)],
+    total_epoch_stake: u64,
+    threshold: f64,
+) -> result::Result<Option<Slot>, BlockstoreProcessorError> {
     if roots.is_empty() {
-        return None;
+        return Ok(None);
     }
 
     // Find latest root
     let mut total = 0;
     let mut prev_root = roots[0].0;
     for (root, stake) in roots.iter() {
-        assert!(*root <= prev_root);
+        if *root > prev_root {
+            return Err(BlockstoreProcessorError::UnsortedRoots(*root, prev_root));
+        }
         total += stake;
-        if total as f64 / total_epoch_stake as f64 > VOTE_THRESHOLD_SIZE {
-            return Some(*root);
+        if total as f64 / total_epoch_stake as f64 > threshold {
+            return Ok(Some(*root));
         }
         prev_root = *root;
     }
 
-    None
+    Ok(None)
+}
+
+/// Same as `supermajority_root()`, for `(root_slot, stake)` pairs in any order
+pub fn supermajority_root_from_roots<I>(
+    roots: I,
+    total_epoch_stake: u64,
+    threshold: f64,
+) -> Option<Slot>
+where
+    I: IntoIterator<Item = (Slot, u64)>,
+{
+    let mut roots_stakes: Vec<(Slot, u64)> = roots.into_iter().collect();
+
+    // Sort from greatest to smallest slot
+    roots_stakes.sort_unstable_by(|a, b| a.0.cmp(&b.0).reverse());
+
+    supermajority_root(&roots_stakes, total_epoch_stake, threshold)
+        .expect("roots were sorted above")
 }
 
-fn supermajority_root_from_vote_accounts<I>(
+/// Returns the supermajority root, as in `supermajority_root()`, of the vote accounts of
+/// a bank at `bank_slot`. Unstaked accounts, and accounts whose vote state can't be
+/// deserialized, are ignored
+pub fn supermajority_root_from_vote_accounts<I>(
     bank_slot: Slot,
     total_epoch_stake: u64,
     vote_accounts: I,
+    threshold: f64,
 ) -> Option<Slot>
 where
     I: IntoIterator<Item = (Pubkey, (u64, ArcVoteAccount))>,
 {
-    let mut roots_stakes: Vec<(Slot, u64)> = vote_accounts
+    let roots_stakes = vote_accounts
         .into_iter()
         .filter_map(|(key, (stake, account))| {
             if stake == 0 {
@@ -1143,14 +2179,9 @@ where
                 }
                 Ok(vote_state) => vote_state.root_slot.map(|root_slot| (root_slot, stake)),
             }
-        })
-        .collect();
-
-    // Sort from greatest to smallest slot
-    roots_stakes.sort_unstable_by(|a, b| a.0.cmp(&b.0).reverse());
+        });
 
-    // Find latest root
-    supermajority_root(&roots_stakes, total_epoch_stake)
+    supermajority_root_from_roots(roots_stakes, total_epoch_stake, threshold)
 }
 
 // Processes and replays the contents of a single slot, returns Error
@@ -1169,11 +2200,19 @@ fn process_single_slot(
     // Mark corrupt slots as dead so validators don't replay this slot and
     // see AlreadyProcessed errors later in ReplayStage
     confirm_full_slot(blockstore, bank, opts, recyclers, progress, transaction_status_sender, replay_vote_sender, timing).map_err(|err| {
+        // The slot isn't corrupted if the entry callback or the abort flag stopped
+        // processing
+        if let BlockstoreProcessorError::EntryCallbackAborted(_)
+        | BlockstoreProcessorError::Cancelled = err
+        {
+            return err;
+        }
         let slot = bank.slot();
-        warn!("slot {} failed to verify: {}", slot, err);
+        let err = BlockstoreProcessorError::CorruptedSlot(slot, err.to_string());
+        warn!("{}", err);
         if blockstore.is_primary_access() {
             blockstore
-                .set_dead_slot(slot)
+                .set_dead_slot_with_reason(slot, err.to_string())
                 .expect("Failed to mark slot as dead in blockstore");
         } else if !blockstore.is_dead(slot) {
             panic!("Failed slot isn't dead and can't update due to being secondary blockstore access: {}", slot);
@@ -1182,6 +2221,9 @@ fn process_single_slot(
     })?;
 
     bank.freeze(); // all banks handled by this routine are created from complete slots
+    if let Some(on_slot_frozen) = &opts.on_slot_frozen {
+        on_slot_frozen(bank);
+    }
     cache_block_meta(bank, cache_block_meta_sender);
 
     Ok(())
@@ -1190,6 +2232,11 @@ fn process_single_slot(
 pub enum TransactionStatusMessage {
     Batch(TransactionStatusBatch),
     Freeze(Slot),
+    // Like `Freeze`, but also carries the replay stats of the slot
+    Frozen(FrozenSlotStats),
+    // The slot failed to replay and was marked dead, so it will never be frozen. Any
+    // batches sent for it before were from a block the cluster can't confirm
+    DeadSlot(DeadSlotInfo),
 }
 
 pub struct TransactionStatusBatch {
@@ -1203,6 +2250,47 @@ pub struct TransactionStatusBatch {
     pub rent_debits: Vec<RentDebits>,
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrozenSlotStats {
+    pub slot: Slot,
+    pub num_shreds: u64,
+    pub num_entries: usize,
+    pub num_txs: usize,
+    pub fetch_elapsed: u64,
+    pub fetch_fail_elapsed: u64,
+    pub poh_verify_elapsed: u64,
+    pub transaction_verify_elapsed: u64,
+    pub replay_elapsed: u64,
+    // Time from the start of replay of the slot until it was frozen, in microseconds
+    pub total_elapsed: u64,
+}
+
+impl FrozenSlotStats {
+    pub fn new(slot: Slot, progress: &ConfirmationProgress, timing: &ConfirmationTiming) -> Self {
+        Self {
+            slot,
+            num_shreds: progress.num_shreds,
+            num_entries: progress.num_entries,
+            num_txs: progress.num_txs,
+            fetch_elapsed: timing.fetch_elapsed,
+            fetch_fail_elapsed: timing.fetch_fail_elapsed,
+            poh_verify_elapsed: timing.poh_verify_elapsed,
+            transaction_verify_elapsed: timing.transaction_verify_elapsed,
+            replay_elapsed: timing.replay_elapsed,
+            total_elapsed: timing.started.elapsed().as_micros() as u64,
+        }
+    }
+}
+
+/// Why a slot was marked dead, sent in a `TransactionStatusMessage::DeadSlot`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeadSlotInfo {
+    pub slot: Slot,
+    pub error: String,
+    // Hash of the last entry replayed before the slot failed
+    pub last_entry: Hash,
+}
+
 #[derive(Clone)]
 pub struct TransactionStatusSender {
     pub sender: Sender<TransactionStatusMessage>,
@@ -1222,24 +2310,17 @@ impl TransactionStatusSender {
         rent_debits: Vec<RentDebits>,
     ) {
         let slot = bank.slot();
-        let (inner_instructions, transaction_logs) = if !self.enable_cpi_and_log_storage {
-            (None, None)
-        } else {
-            (Some(inner_instructions), Some(transaction_logs))
-        };
-        if let Err(e) = self
-            .sender
-            .send(TransactionStatusMessage::Batch(TransactionStatusBatch {
-                bank,
-                transactions,
-                statuses,
-                balances,
-                token_balances,
-                inner_instructions,
-                transaction_logs,
-                rent_debits,
-            }))
-        {
+        let message = self.new_batch_message(
+            bank,
+            transactions,
+            statuses,
+            balances,
+            token_balances,
+            inner_instructions,
+            transaction_logs,
+            rent_debits,
+        );
+        if let Err(e) = self.sender.send(message) {
             trace!(
                 "Slot {} transaction_status send batch failed: {:?}",
                 slot,
@@ -1248,6 +2329,34 @@ impl TransactionStatusSender {
         }
     }
 
+    /// Like `send_transaction_status_batch`, but drops the batch instead of blocking if the
+    /// channel is full
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_send_transaction_status_batch(
+        &self,
+        bank: Arc<Bank>,
+        transactions: Vec<Transaction>,
+        statuses: Vec<TransactionExecutionResult>,
+        balances: TransactionBalancesSet,
+        token_balances: TransactionTokenBalancesSet,
+        inner_instructions: Vec<Option<InnerInstructionsList>>,
+        transaction_logs: Vec<TransactionLogMessages>,
+        rent_debits: Vec<RentDebits>,
+    ) {
+        let slot = bank.slot();
+        let message = self.new_batch_message(
+            bank,
+            transactions,
+            statuses,
+            balances,
+            token_balances,
+            inner_instructions,
+            transaction_logs,
+            rent_debits,
+        );
+        self.try_send_message(slot, message);
+    }
+
     pub fn send_transaction_status_freeze_message(&self, bank: &Arc<Bank>) {
         let slot = bank.slot();
         if let Err(e) = self.sender.send(TransactionStatusMessage::Freeze(slot)) {
@@ -1258,6 +2367,85 @@ impl TransactionStatusSender {
             );
         }
     }
+
+    /// Like `send_transaction_status_freeze_message`, but drops the message instead of
+    /// blocking if the channel is full
+    pub fn try_send_transaction_status_freeze_message(&self, bank: &Arc<Bank>) {
+        let slot = bank.slot();
+        self.try_send_message(slot, TransactionStatusMessage::Freeze(slot));
+    }
+
+    /// Sends a `Frozen` message for `bank`, carrying the stats from replaying the slot
+    pub fn send_transaction_status_frozen_message(
+        &self,
+        bank: &Arc<Bank>,
+        progress: &ConfirmationProgress,
+        timing: &ConfirmationTiming,
+    ) {
+        let slot = bank.slot();
+        let message =
+            TransactionStatusMessage::Frozen(FrozenSlotStats::new(slot, progress, timing));
+        if let Err(e) = self.sender.send(message) {
+            trace!(
+                "Slot {} transaction_status send frozen message failed: {:?}",
+                slot,
+                e
+            );
+        }
+    }
+
+    /// Sends a `DeadSlot` message for `slot`. It follows any batches already sent for the
+    /// slot on the same channel, so it's received after them
+    pub fn send_dead_slot(&self, slot: Slot, error: String, last_entry: Hash) {
+        let message = TransactionStatusMessage::DeadSlot(DeadSlotInfo {
+            slot,
+            error,
+            last_entry,
+        });
+        if let Err(e) = self.sender.send(message) {
+            trace!(
+                "Slot {} transaction_status send dead slot message failed: {:?}",
+                slot,
+                e
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_batch_message(
+        &self,
+        bank: Arc<Bank>,
+        transactions: Vec<Transaction>,
+        statuses: Vec<TransactionExecutionResult>,
+        balances: TransactionBalancesSet,
+        token_balances: TransactionTokenBalancesSet,
+        inner_instructions: Vec<Option<InnerInstructionsList>>,
+        transaction_logs: Vec<TransactionLogMessages>,
+        rent_debits: Vec<RentDebits>,
+    ) -> TransactionStatusMessage {
+        let (inner_instructions, transaction_logs) = if !self.enable_cpi_and_log_storage {
+            (None, None)
+        } else {
+            (Some(inner_instructions), Some(transaction_logs))
+        };
+        TransactionStatusMessage::Batch(TransactionStatusBatch {
+            bank,
+            transactions,
+            statuses,
+            balances,
+            token_balances,
+            inner_instructions,
+            transaction_logs,
+            rent_debits,
+        })
+    }
+
+    fn try_send_message(&self, slot: Slot, message: TransactionStatusMessage) {
+        if let Err(e) = self.sender.try_send(message) {
+            inc_new_counter_info!("transaction_status_sender-dropped_batch", 1);
+            trace!("Slot {} transaction_status try send failed: {:?}", slot, e);
+        }
+    }
 }
 
 pub type CacheBlockMetaSender = Sender<Arc<Bank>>;
@@ -1323,7 +2511,7 @@ pub mod tests {
         pubkey::Pubkey,
         signature::{Keypair, Signer},
         system_instruction::SystemError,
-        system_transaction,
+        system_program, system_transaction,
         transaction::{Transaction, TransactionError},
     };
     use solana_vote_program::{
@@ -1331,7 +2519,10 @@ pub mod tests {
         vote_state::{VoteState, VoteStateVersions, MAX_LOCKOUT_HISTORY},
         vote_transaction,
     };
-    use std::{collections::BTreeSet, sync::RwLock};
+    use std::{
+        collections::BTreeSet,
+        sync::{Mutex, RwLock},
+    };
     use trees::tr;
 
     #[test]
@@ -1382,29 +2573,119 @@ pub mod tests {
     }
 
     #[test]
-    fn test_process_blockstore_with_invalid_slot_tick_count() {
+    fn test_verify_slot_range() {
         solana_logger::setup();
 
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
         let ticks_per_slot = genesis_config.ticks_per_slot;
-
-        // Create a new ledger with slot 0 full of ticks
         let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
         let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let write_slot = |slot, parent_slot, entries, is_full_slot| {
+            blockstore
+                .write_entries(
+                    slot,
+                    0,
+                    0,
+                    ticks_per_slot,
+                    Some(parent_slot),
+                    is_full_slot,
+                    &Arc::new(Keypair::new()),
+                    entries,
+                    0,
+                )
+                .unwrap();
+        };
 
-        // Write slot 1 with one tick missing
-        let parent_slot = 0;
-        let slot = 1;
-        let entries = create_ticks(ticks_per_slot - 1, 0, blockhash);
-        assert_matches!(
-            blockstore.write_entries(
-                slot,
-                0,
-                0,
-                ticks_per_slot,
-                Some(parent_slot),
-                true,
-                &Arc::new(Keypair::new()),
+        /*
+            slot 0
+              |
+            slot 1 (valid) ---- slot 3 (ticks chained from the wrong hash)
+              |
+            slot 2 (too few ticks)
+              |
+            slot 4 (not full)
+        */
+        let slot1_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        write_slot(
+            2,
+            1,
+            create_ticks(ticks_per_slot - 1, 0, slot1_blockhash),
+            true,
+        );
+        write_slot(
+            3,
+            1,
+            create_ticks(2 * ticks_per_slot, 0, Hash::new_unique()),
+            true,
+        );
+        let mut entries = create_ticks(ticks_per_slot * 2, 0, slot1_blockhash);
+        entries.pop();
+        write_slot(4, 2, entries, false);
+
+        let opts = ProcessOptions::default();
+        let results = verify_slot_range(&blockstore, &genesis_config, 0, 4, &opts).unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.slot, result.ok))
+                .collect::<Vec<_>>(),
+            vec![(0, true), (1, true), (2, false), (3, false), (4, false)]
+        );
+        assert_eq!(results[2].error, Some(BlockError::TooFewTicks.to_string()));
+        assert_eq!(
+            results[3].error,
+            Some(
+                BlockError::InvalidEntryHash {
+                    entry_index: 0,
+                    kind: HashFailureKind::Poh,
+                }
+                .to_string()
+            )
+        );
+        assert_eq!(results[4].error, Some(BlockError::Incomplete.to_string()));
+
+        // A range starting past slot 0 chains from the parents in the blockstore
+        let results = verify_slot_range(&blockstore, &genesis_config, 3, 3, &opts).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].ok);
+
+        // Dead slots fail unless they're allowed
+        blockstore.set_dead_slot(1).unwrap();
+        let results = verify_slot_range(&blockstore, &genesis_config, 1, 1, &opts).unwrap();
+        assert!(!results[0].ok);
+        let opts = ProcessOptions {
+            allow_dead_slots: true,
+            ..opts
+        };
+        let results = verify_slot_range(&blockstore, &genesis_config, 1, 1, &opts).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_process_blockstore_with_invalid_slot_tick_count() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+
+        // Create a new ledger with slot 0 full of ticks
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+
+        // Write slot 1 with one tick missing
+        let parent_slot = 0;
+        let slot = 1;
+        let entries = create_ticks(ticks_per_slot - 1, 0, blockhash);
+        assert_matches!(
+            blockstore.write_entries(
+                slot,
+                0,
+                0,
+                ticks_per_slot,
+                Some(parent_slot),
+                true,
+                &Arc::new(Keypair::new()),
                 entries,
                 0,
             ),
@@ -1447,6 +2728,58 @@ pub mod tests {
         assert_eq!(bank_forks.root(), 0);
     }
 
+    #[test]
+    fn test_process_single_slot_corrupted_slot() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+
+        // Write slot 1 with one tick missing
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                ticks_per_slot,
+                Some(0),
+                true,
+                &Arc::new(Keypair::new()),
+                create_ticks(ticks_per_slot - 1, 0, blockhash),
+                0,
+            )
+            .unwrap();
+
+        let opts = ProcessOptions {
+            poh_verify: true,
+            ..ProcessOptions::default()
+        };
+        let recyclers = VerifyRecyclers::default();
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        process_bank_0(&bank0, &blockstore, &opts, &recyclers, None);
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let err = process_single_slot(
+            &blockstore,
+            &bank1,
+            &opts,
+            &recyclers,
+            &mut ConfirmationProgress::new(bank0.last_blockhash()),
+            None,
+            None,
+            None,
+            &mut ExecuteTimings::default(),
+        )
+        .unwrap_err();
+
+        assert_matches!(err, BlockstoreProcessorError::CorruptedSlot(1, _));
+        assert_eq!(
+            err.to_string(),
+            "slot 1 is corrupted: invalid block: too few ticks"
+        );
+        assert!(blockstore.is_dead(1));
+        assert!(!bank1.is_frozen());
+    }
+
     #[test]
     fn test_process_blockstore_with_slot_with_trailing_entry() {
         solana_logger::setup();
@@ -1498,6 +2831,62 @@ pub mod tests {
         assert_eq!(frozen_bank_slots(&bank_forks), vec![0]);
     }
 
+    #[test]
+    fn test_process_blockstore_with_poh_verify_sample_rate() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+
+        // Slot 1 is valid, while the last entry of slot 2 doesn't chain in proof of history
+        let entries = create_ticks(ticks_per_slot, 0, blockhash);
+        let last_entry_hash = entries.last().unwrap().hash;
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                ticks_per_slot,
+                Some(0),
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+        let mut entries = create_ticks(ticks_per_slot, 0, last_entry_hash);
+        entries.last_mut().unwrap().hash = Hash::new_unique();
+        blockstore
+            .write_entries(
+                2,
+                0,
+                0,
+                ticks_per_slot,
+                Some(1),
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        // The last entry is always sampled, so the tampering is caught even when no other
+        // entry is verified
+        let opts = ProcessOptions {
+            poh_verify: true,
+            poh_verify_sample_rate: Some(0.0),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1]);
+        assert!(blockstore.is_dead(2));
+    }
+
     #[test]
     fn test_process_blockstore_with_incomplete_slot() {
         solana_logger::setup();
@@ -1666,6 +3055,120 @@ pub mod tests {
         assert_eq!(bank_forks.root(), 4);
     }
 
+    // Processes the blockstore once in full, then again with the options `halt_opts`
+    // builds from the resulting banks
+    fn run_test_process_blockstore_dev_halt(
+        mut halt_opts: impl FnMut(&BankForks) -> ProcessOptions,
+    ) -> BlockstoreProcessorResult {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+
+        /*
+            Build a blockstore in the ledger with the following fork structure:
+
+                 slot 0
+                   |
+                 slot 1
+                 /   \
+            slot 2   |
+               |   slot 4
+            slot 3   |
+                   slot 5
+        */
+        let last_slot1_entry_hash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        let last_slot2_entry_hash = fill_blockstore_slot_with_ticks(
+            &blockstore,
+            ticks_per_slot,
+            2,
+            1,
+            last_slot1_entry_hash,
+        );
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 2, last_slot2_entry_hash);
+        let last_slot4_entry_hash = fill_blockstore_slot_with_ticks(
+            &blockstore,
+            ticks_per_slot,
+            4,
+            1,
+            last_slot1_entry_hash,
+        );
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 5, 4, last_slot4_entry_hash);
+
+        let opts = ProcessOptions {
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 2, 3, 4, 5]);
+
+        let opts = ProcessOptions {
+            accounts_db_test_hash_calculation: true,
+            ..halt_opts(&bank_forks)
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None)
+    }
+
+    fn run_test_process_blockstore_dev_halt_at_bank_hash(
+        mut halt_hash: impl FnMut(&BankForks) -> Hash,
+    ) -> BlockstoreProcessorResult {
+        run_test_process_blockstore_dev_halt(|bank_forks| ProcessOptions {
+            dev_halt_at_bank_hash: Some((4, halt_hash(bank_forks))),
+            ..ProcessOptions::default()
+        })
+    }
+
+    #[test]
+    fn test_process_blockstore_dev_halt_at_bank_hash() {
+        let (bank_forks, _leader_schedule) =
+            run_test_process_blockstore_dev_halt_at_bank_hash(|bank_forks| bank_forks[4].hash())
+                .unwrap();
+        // Replay stops at slot 4 on the minority fork, without descending to slot 5
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_process_blockstore_dev_halt_at_hash() {
+        // Halts at the hash of slot 4 without being told its slot, before slot 5
+        let (bank_forks, _leader_schedule) =
+            run_test_process_blockstore_dev_halt(|bank_forks| ProcessOptions {
+                dev_halt_at_hash: Some(bank_forks[4].hash()),
+                ..ProcessOptions::default()
+            })
+            .unwrap();
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 2, 3, 4]);
+
+        // A hash that's never reached lets `dev_halt_at_slot` trigger first
+        let (bank_forks, _leader_schedule) =
+            run_test_process_blockstore_dev_halt(|_bank_forks| ProcessOptions {
+                dev_halt_at_slot: Some(2),
+                dev_halt_at_hash: Some(Hash::new_unique()),
+                ..ProcessOptions::default()
+            })
+            .unwrap();
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_process_blockstore_dev_halt_at_bank_hash_mismatch() {
+        let expected_hash = Hash::new_unique();
+        let mut computed_hash = Hash::default();
+        let result = run_test_process_blockstore_dev_halt_at_bank_hash(|bank_forks| {
+            computed_hash = bank_forks[4].hash();
+            expected_hash
+        });
+        match result {
+            Err(BlockstoreProcessorError::HaltHashMismatch(slot, expected, computed)) => {
+                assert_eq!(slot, 4);
+                assert_eq!(expected, expected_hash);
+                assert_eq!(computed, computed_hash);
+            }
+            _ => panic!("expected a halt hash mismatch"),
+        }
+    }
+
     #[test]
     fn test_process_blockstore_with_two_forks() {
         solana_logger::setup();
@@ -1758,70 +3261,185 @@ pub mod tests {
     }
 
     #[test]
-    fn test_process_blockstore_with_dead_slot() {
+    fn test_process_blockstore_with_max_parallel_slots() {
         solana_logger::setup();
 
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
         let ticks_per_slot = genesis_config.ticks_per_slot;
         let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
-        debug!("ledger_path: {:?}", ledger_path);
 
         /*
-                   slot 0
-                     |
-                   slot 1
-                  /     \
-                 /       \
-           slot 2 (dead)  \
-                           \
-                        slot 3
+                        slot 0
+                          |
+                        slot 1
+                     /    |    \
+                slot 2  slot 4  slot 7 (dead)
+                  |       |
+                slot 3  slot 5
+                          |
+                        slot 6
         */
         let blockstore = Blockstore::open(&ledger_path).unwrap();
         let slot1_blockhash =
             fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
-        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, slot1_blockhash);
-        blockstore.set_dead_slot(2).unwrap();
-        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 1, slot1_blockhash);
-
-        let (bank_forks, _leader_schedule) = process_blockstore(
-            &genesis_config,
-            &blockstore,
-            Vec::new(),
-            ProcessOptions::default(),
-            None,
-        )
-        .unwrap();
+        let slot2_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, slot1_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 2, slot2_blockhash);
+        let slot4_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 4, 1, slot1_blockhash);
+        let slot5_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 5, 4, slot4_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 6, 5, slot5_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 7, 1, slot1_blockhash);
+        blockstore.set_dead_slot(7).unwrap();
+
+        let process = |max_parallel_slots| {
+            let opts = ProcessOptions {
+                poh_verify: true,
+                max_parallel_slots,
+                ..ProcessOptions::default()
+            };
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None)
+                .unwrap()
+                .0
+        };
+        let serial_bank_forks = process(None);
+        let parallel_bank_forks = process(Some(3));
 
-        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 3]);
-        assert_eq!(bank_forks.working_bank().slot(), 3);
+        // Replaying the forks in parallel must produce exactly the same banks
         assert_eq!(
-            &bank_forks[3]
-                .parents()
-                .iter()
-                .map(|bank| bank.slot())
-                .collect::<Vec<_>>(),
-            &[1, 0]
+            frozen_bank_slots(&parallel_bank_forks),
+            vec![0, 1, 2, 3, 4, 5, 6]
         );
-        verify_fork_infos(&bank_forks);
+        assert_eq!(
+            frozen_bank_slots(&parallel_bank_forks),
+            frozen_bank_slots(&serial_bank_forks)
+        );
+        for slot in frozen_bank_slots(&serial_bank_forks) {
+            assert_eq!(
+                parallel_bank_forks[slot].hash(),
+                serial_bank_forks[slot].hash()
+            );
+        }
+        verify_fork_infos(&parallel_bank_forks);
     }
 
     #[test]
-    fn test_process_blockstore_with_dead_child() {
+    fn test_process_blockstore_with_max_parallel_slots_and_dev_halt_at_slot() {
         solana_logger::setup();
 
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
         let ticks_per_slot = genesis_config.ticks_per_slot;
         let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
-        debug!("ledger_path: {:?}", ledger_path);
 
         /*
-                   slot 0
-                     |
-                   slot 1
-                  /     \
-                 /       \
-              slot 2      \
-               /           \
+                        slot 0
+                          |
+                        slot 1
+                     /    |    \
+                slot 2  slot 4  slot 6
+                  |       |
+                slot 3  slot 5
+        */
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let slot1_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        let slot2_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, slot1_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 2, slot2_blockhash);
+        let slot4_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 4, 1, slot1_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 5, 4, slot4_blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 6, 1, slot1_blockhash);
+
+        let frozen_slots = Arc::new(Mutex::new(vec![]));
+        let opts = ProcessOptions {
+            poh_verify: true,
+            max_parallel_slots: Some(3),
+            dev_halt_at_slot: Some(4),
+            on_slot_frozen: Some({
+                let frozen_slots = frozen_slots.clone();
+                Arc::new(move |bank: &Bank| frozen_slots.lock().unwrap().push(bank.slot()))
+            }),
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+
+        // Slots 4 and 6 were pending alongside slot 2, but only slots below the halt slot
+        // were replayed before slot 4, and nothing after it
+        let mut frozen_slots = frozen_slots.lock().unwrap().clone();
+        assert_eq!(frozen_slots.pop(), Some(4));
+        frozen_slots.sort_unstable();
+        assert_eq!(frozen_slots, vec![1, 2, 3]);
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_process_blockstore_with_dead_slot() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        debug!("ledger_path: {:?}", ledger_path);
+
+        /*
+                   slot 0
+                     |
+                   slot 1
+                  /     \
+                 /       \
+           slot 2 (dead)  \
+                           \
+                        slot 3
+        */
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let slot1_blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, slot1_blockhash);
+        blockstore.set_dead_slot(2).unwrap();
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 1, slot1_blockhash);
+
+        let (bank_forks, _leader_schedule) = process_blockstore(
+            &genesis_config,
+            &blockstore,
+            Vec::new(),
+            ProcessOptions::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1, 3]);
+        assert_eq!(bank_forks.working_bank().slot(), 3);
+        assert_eq!(
+            &bank_forks[3]
+                .parents()
+                .iter()
+                .map(|bank| bank.slot())
+                .collect::<Vec<_>>(),
+            &[1, 0]
+        );
+        verify_fork_infos(&bank_forks);
+    }
+
+    #[test]
+    fn test_process_blockstore_with_dead_child() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        debug!("ledger_path: {:?}", ledger_path);
+
+        /*
+                   slot 0
+                     |
+                   slot 1
+                  /     \
+                 /       \
+              slot 2      \
+               /           \
            slot 4 (dead)   slot 3
         */
         let blockstore = Blockstore::open(&ledger_path).unwrap();
@@ -1992,6 +3610,26 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_process_blockstore_invalid_supermajority_threshold() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+
+        // A bare majority isn't a supermajority, and more than all of the stake can never
+        // root a slot
+        for supermajority_threshold in &[0.5, 1.01] {
+            let opts = ProcessOptions {
+                supermajority_threshold: Some(*supermajority_threshold),
+                ..ProcessOptions::default()
+            };
+            assert_matches!(
+                process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None),
+                Err(BlockstoreProcessorError::InvalidSupermajorityThreshold(_))
+            );
+        }
+    }
+
     #[test]
     fn test_process_empty_entry_is_registered() {
         solana_logger::setup();
@@ -2105,6 +3743,258 @@ pub mod tests {
         assert_eq!(bank.last_blockhash(), last_blockhash);
     }
 
+    #[test]
+    fn test_confirm_slot_concurrent_verification() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let (ledger_path, _) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let recyclers = VerifyRecyclers::default();
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        process_bank_0(
+            &bank0,
+            &blockstore,
+            &ProcessOptions::default(),
+            &recyclers,
+            None,
+        );
+
+        // A slot with many transaction entries, one of which optionally carries a bad
+        // signature while still chaining correctly in proof of history
+        let make_entries = |corrupt_signature: bool| {
+            let mut last_entry_hash = bank0.last_blockhash();
+            let mut entries: Vec<_> = (0..64)
+                .map(|i| {
+                    let mut tx = system_transaction::transfer(
+                        &mint_keypair,
+                        &solana_sdk::pubkey::new_rand(),
+                        1,
+                        bank0.last_blockhash(),
+                    );
+                    if corrupt_signature && i == 32 {
+                        tx.signatures[0] = solana_sdk::signature::Signature::default();
+                    }
+                    next_entry_mut(&mut last_entry_hash, 1, vec![tx])
+                })
+                .collect();
+            entries.extend(create_ticks(
+                genesis_config.ticks_per_slot,
+                0,
+                last_entry_hash,
+            ));
+            entries
+        };
+
+        for corrupt_signature in &[false, true] {
+            let entries = make_entries(*corrupt_signature);
+            let slot = if *corrupt_signature { 2 } else { 1 };
+            blockstore
+                .write_entries(
+                    slot,
+                    0,
+                    0,
+                    genesis_config.ticks_per_slot,
+                    Some(0),
+                    true,
+                    &Arc::new(Keypair::new()),
+                    entries.clone(),
+                    0,
+                )
+                .unwrap();
+
+            // Serial path: proof of history, then signatures, then execution
+            let serial_bank = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), slot));
+            let serial_verified = entries.verify(&bank0.last_blockhash())
+                && entries
+                    .verify_and_hash_transactions(
+                        false,
+                        serial_bank.secp256k1_program_enabled(),
+                        serial_bank.verify_tx_signatures_len_enabled(),
+                    )
+                    .is_some();
+            assert_eq!(serial_verified, !*corrupt_signature);
+
+            let bank = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), slot));
+            let mut timing = ConfirmationTiming::default();
+            let mut progress = ConfirmationProgress::new(bank0.last_blockhash());
+            let result = confirm_slot(
+                &blockstore,
+                &bank,
+                &mut timing,
+                &mut progress,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                &recyclers,
+                false,
+                None,
+            );
+            info!(
+                "slot {} poh_verify_elapsed: {}us transaction_verify_elapsed: {}us",
+                slot, timing.poh_verify_elapsed, timing.transaction_verify_elapsed
+            );
+
+            if *corrupt_signature {
+                assert_matches!(
+                    result,
+                    Err(BlockstoreProcessorError::InvalidBlock(
+                        BlockError::InvalidEntryHash {
+                            entry_index: 32,
+                            kind: HashFailureKind::TransactionSignature,
+                        }
+                    ))
+                );
+                assert_eq!(progress.num_entries, 0);
+            } else {
+                assert!(result.is_ok());
+                assert_eq!(progress.num_entries, entries.len());
+                assert_eq!(progress.last_entry, entries.last().unwrap().hash);
+                process_entries(&serial_bank, &mut entries.clone(), true, None, None).unwrap();
+                serial_bank.freeze();
+                bank.freeze();
+                assert_eq!(bank.hash(), serial_bank.hash());
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_failed_transaction_entry() {
+        let mint_keypair = Keypair::new();
+        let mut last_entry_hash = Hash::default();
+        let mut entries: Vec<_> = (0..3)
+            .map(|_| {
+                let tx = system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    1,
+                    Hash::default(),
+                );
+                next_entry_mut(&mut last_entry_hash, 1, vec![tx])
+            })
+            .collect();
+
+        // Every entry verifies, so there's no entry to report, not even entry 0
+        assert_eq!(
+            first_failed_transaction_entry(&entries, false, true, true),
+            None
+        );
+
+        let mut signature_bytes = entries[1].transactions[0].signatures[0].as_ref().to_vec();
+        signature_bytes[0] ^= 1;
+        entries[1].transactions[0].signatures[0] = Signature::new(&signature_bytes);
+        assert_eq!(
+            first_failed_transaction_entry(&entries, false, true, true),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_confirm_slot_full_signature_audit() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let (ledger_path, _) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let recyclers = VerifyRecyclers::default();
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        process_bank_0(
+            &bank0,
+            &blockstore,
+            &ProcessOptions::default(),
+            &recyclers,
+            None,
+        );
+
+        // Corrupt the signature of one of the transactions of the slot, still chaining
+        // correctly in proof of history
+        let mut last_entry_hash = bank0.last_blockhash();
+        let mut corrupted_signature = None;
+        let mut entries: Vec<_> = (0..8)
+            .map(|i| {
+                let mut tx = system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    1,
+                    bank0.last_blockhash(),
+                );
+                if i == 5 {
+                    let mut signature_bytes = tx.signatures[0].as_ref().to_vec();
+                    signature_bytes[0] ^= 1;
+                    tx.signatures[0] = Signature::new(&signature_bytes);
+                    corrupted_signature = Some(tx.signatures[0]);
+                }
+                next_entry_mut(&mut last_entry_hash, 1, vec![tx])
+            })
+            .collect();
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                Some(0),
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        // Without verification the bad signature goes unnoticed, the audit reports it
+        // even so
+        for full_signature_audit in &[false, true] {
+            let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+            let mut progress = ConfirmationProgress::new(bank0.last_blockhash());
+            let result = confirm_slot(
+                &blockstore,
+                &bank1,
+                &mut ConfirmationTiming::default(),
+                &mut progress,
+                true,
+                None,
+                *full_signature_audit,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                &recyclers,
+                false,
+                None,
+            );
+            if *full_signature_audit {
+                assert_matches!(
+                    result,
+                    Err(BlockstoreProcessorError::InvalidSignature(1, signature))
+                        if Some(signature) == corrupted_signature
+                );
+                assert_eq!(progress.num_entries, 0);
+            } else {
+                assert!(result.is_ok());
+                assert!(bank1.is_complete());
+            }
+        }
+    }
+
     #[test]
     fn test_process_ledger_with_one_tick_per_slot() {
         let GenesisConfigInfo {
@@ -2208,6 +4098,7 @@ pub mod tests {
                 assert_eq!(bank.get_balance(&pubkeys[*counter]), 1);
                 assert_eq!(bank.get_balance(&pubkeys[*counter + 1]), 0);
                 *counter += 1;
+                Ok(())
             })
         };
 
@@ -2221,6 +4112,538 @@ pub mod tests {
         assert_eq!(*callback_counter.write().unwrap(), 2);
     }
 
+    #[test]
+    fn test_process_ledger_options_abort() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let (ledger_path, last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let blockhash = genesis_config.hash();
+        let keypairs = [Keypair::new(), Keypair::new()];
+
+        // Both transactions use the mint, so they execute in separate batches
+        let tx = system_transaction::transfer(&mint_keypair, &keypairs[0].pubkey(), 1, blockhash);
+        let entry_1 = next_entry(&last_entry_hash, 1, vec![tx]);
+        let tx = system_transaction::transfer(&mint_keypair, &keypairs[1].pubkey(), 1, blockhash);
+        let entry_2 = next_entry(&entry_1.hash, 1, vec![tx]);
+
+        let mut entries = vec![entry_1, entry_2];
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                None,
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        // Raise the abort flag from within the first batch of slot 1
+        let abort = Arc::new(AtomicBool::new(false));
+        let callback_counter: Arc<RwLock<usize>> = Arc::default();
+        let entry_callback = {
+            let abort = abort.clone();
+            let counter = callback_counter.clone();
+            Arc::new(move |_bank: &Bank| {
+                *counter.write().unwrap() += 1;
+                abort.store(true, Ordering::Relaxed);
+                Ok(())
+            })
+        };
+
+        let opts = ProcessOptions {
+            override_num_threads: Some(1),
+            entry_callback: Some(entry_callback),
+            abort: Some(abort),
+            ..ProcessOptions::default()
+        };
+        assert_matches!(
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None),
+            Err(BlockstoreProcessorError::Cancelled)
+        );
+        // The second batch never executed and the slot wasn't blamed for it
+        assert_eq!(*callback_counter.read().unwrap(), 1);
+        assert!(!blockstore.is_dead(1));
+    }
+
+    #[test]
+    fn test_process_entries_abort() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let keypair1 = Keypair::new();
+        let keypair2 = Keypair::new();
+        let blockhash = bank.last_blockhash();
+
+        let tx = system_transaction::transfer(&mint_keypair, &keypair1.pubkey(), 2, blockhash);
+        let entry_1 = next_entry(&blockhash, 1, vec![tx]);
+        let tx = system_transaction::transfer(&mint_keypair, &keypair2.pubkey(), 2, blockhash);
+        let entry_2 = next_entry(&entry_1.hash, 1, vec![tx]);
+        let mut entries: Vec<_> = [entry_1, entry_2].iter().map(EntryType::from).collect();
+
+        // An unset flag changes nothing
+        let abort = Arc::new(AtomicBool::new(false));
+        assert!(process_entries_with_callback(
+            &bank,
+            &mut entries[..1],
+            false,
+            None,
+            None,
+            None,
+            None,
+            false,
+            Some(&abort),
+            &mut ExecuteTimings::default(),
+        )
+        .is_ok());
+        assert_eq!(bank.get_balance(&keypair1.pubkey()), 2);
+
+        // The batch in flight completes before processing stops
+        abort.store(true, Ordering::Relaxed);
+        assert_matches!(
+            process_entries_with_callback(
+                &bank,
+                &mut entries[1..],
+                false,
+                None,
+                None,
+                None,
+                None,
+                false,
+                Some(&abort),
+                &mut ExecuteTimings::default(),
+            ),
+            Err(BlockstoreProcessorError::Cancelled)
+        );
+        assert_eq!(bank.get_balance(&keypair2.pubkey()), 2);
+    }
+
+    #[test]
+    fn test_simulate_slot() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let (ledger_path, last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let (bank_forks, _leader_schedule) = process_blockstore(
+            &genesis_config,
+            &blockstore,
+            Vec::new(),
+            ProcessOptions::default(),
+            None,
+        )
+        .unwrap();
+        let bank0 = bank_forks[0].clone();
+        let blockhash = bank0.last_blockhash();
+        let keypair = Keypair::new();
+        let keypair2 = Keypair::new();
+
+        let tx = system_transaction::transfer(&mint_keypair, &keypair.pubkey(), 10, blockhash);
+        let signature = tx.signatures[0];
+        let entry_1 = next_entry(&last_entry_hash, 1, vec![tx]);
+        // Only funded by the transfer of the previous entry
+        let tx = system_transaction::transfer(&keypair, &keypair2.pubkey(), 5, blockhash);
+        let entry_2 = next_entry(&entry_1.hash, 1, vec![tx]);
+        // More than the mint has left
+        let tx = system_transaction::transfer(&mint_keypair, &keypair.pubkey(), 1_000, blockhash);
+        let entry_3 = next_entry(&entry_2.hash, 1, vec![tx]);
+
+        let mut entries = vec![entry_1, entry_2, entry_3];
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            entries.last().unwrap().hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                None,
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let unfrozen_bank = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 2));
+        assert_matches!(
+            simulate_slot(&blockstore, &unfrozen_bank, 1),
+            Err(BlockstoreProcessorError::UnfrozenBank(2))
+        );
+        let bank0_hash = bank0.hash();
+        let mint_balance = bank0.get_balance(&mint_keypair.pubkey());
+
+        let simulated_transactions = simulate_slot(&blockstore, &bank0, 1).unwrap();
+        assert_eq!(simulated_transactions.len(), 3);
+
+        // The balances of the transfer are reported
+        let transfer = &simulated_transactions[0];
+        assert_eq!(transfer.signature, signature);
+        assert_eq!(transfer.result, Ok(()));
+        assert_eq!(transfer.pre_balances[0], mint_balance);
+        assert!(transfer.post_balances[0] <= mint_balance - 10);
+        assert_eq!(transfer.pre_balances[1], 0);
+        assert_eq!(transfer.post_balances[1], 10);
+        // Later transactions see what earlier ones changed
+        let transfer = &simulated_transactions[1];
+        assert_eq!(transfer.result, Ok(()));
+        assert_eq!(transfer.pre_balances, vec![10, 0, 1]);
+        assert_eq!(transfer.post_balances, vec![5, 5, 1]);
+        assert!(simulated_transactions[2].result.is_err());
+
+        // But nothing was applied to the parent
+        assert_eq!(bank0.get_balance(&mint_keypair.pubkey()), mint_balance);
+        assert_eq!(bank0.get_balance(&keypair.pubkey()), 0);
+        assert_eq!(bank0.hash(), bank0_hash);
+        assert!(bank0.get_signature_status(&signature).is_none());
+
+        // And the slot still replays into its own bank, the transfer isn't already
+        // processed
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        confirm_full_slot(
+            &blockstore,
+            &bank1,
+            &ProcessOptions::default(),
+            &VerifyRecyclers::default(),
+            &mut ConfirmationProgress::new(last_entry_hash),
+            None,
+            None,
+            &mut ExecuteTimings::default(),
+        )
+        .unwrap();
+        assert_eq!(bank1.get_balance(&keypair.pubkey()), 5);
+        assert_eq!(bank1.get_balance(&keypair2.pubkey()), 5);
+        assert_eq!(bank1.get_signature_status(&signature), Some(Ok(())));
+    }
+
+    #[test]
+    fn test_process_ledger_options_entry_callback_abort() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let (ledger_path, last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let blockhash = genesis_config.hash();
+
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_unique(), 1, blockhash);
+        let mut entries = vec![next_entry(&last_entry_hash, 1, vec![tx])];
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                None,
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let entry_callback: ProcessCallback =
+            Arc::new(|bank: &Bank| Err(BlockstoreProcessorError::InvalidHardFork(bank.slot())));
+        let opts = ProcessOptions {
+            override_num_threads: Some(1),
+            entry_callback: Some(entry_callback),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        let result = process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "entry callback aborted processing: invalid hard fork"
+        );
+
+        // Aborting doesn't mark the slot as dead
+        assert!(!blockstore.is_dead(1));
+    }
+
+    #[test]
+    fn test_process_blockstore_on_slot_frozen() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+
+        /*
+                 slot 0
+                   |
+                 slot 1
+                 /   \
+            slot 2   slot 4
+               |
+            slot 3
+        */
+        let slot1_hash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        let slot2_hash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, slot1_hash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 2, slot2_hash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 4, 1, slot1_hash);
+
+        let frozen_slots = Arc::new(Mutex::new(vec![]));
+        let frozen_slots_ = frozen_slots.clone();
+        let on_slot_frozen: SlotFrozenCallback = Arc::new(move |bank: &Bank| {
+            assert!(bank.is_frozen());
+            frozen_slots_.lock().unwrap().push(bank.slot());
+        });
+        let opts = ProcessOptions {
+            on_slot_frozen: Some(on_slot_frozen),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+
+        // Every bank is passed exactly once, after its parent
+        let frozen_slots = frozen_slots.lock().unwrap().clone();
+        assert_eq!(frozen_slots[0], 0);
+        for (i, slot) in frozen_slots.iter().enumerate().skip(1) {
+            let parent_slot = bank_forks[*slot].parent_slot();
+            assert!(frozen_slots[..i].contains(&parent_slot));
+        }
+        let mut sorted_frozen_slots = frozen_slots.clone();
+        sorted_frozen_slots.sort_unstable();
+        assert_eq!(sorted_frozen_slots, frozen_bank_slots(&bank_forks));
+        assert_eq!(sorted_frozen_slots, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_process_ledger_options_halt_at_slot_inclusive() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(100);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, mut last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        for slot in 1..=10 {
+            last_entry_hash = fill_blockstore_slot_with_ticks(
+                &blockstore,
+                ticks_per_slot,
+                slot,
+                slot - 1,
+                last_entry_hash,
+            );
+        }
+
+        let opts = ProcessOptions {
+            halt_at_slot_inclusive: Some(5),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+
+        let mut frozen_slots: Vec<Slot> = bank_forks.frozen_banks().keys().cloned().collect();
+        frozen_slots.sort_unstable();
+        assert_eq!(frozen_slots, vec![0, 1, 2, 3, 4, 5]);
+        assert!(bank_forks.root() <= 5);
+        assert_eq!(bank_forks.working_bank().slot(), 5);
+        assert!(bank_forks.get(6).is_none());
+    }
+
+    #[test]
+    fn test_process_ledger_options_progress_callback() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(100);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, mut last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        for slot in 1..=3 {
+            last_entry_hash = fill_blockstore_slot_with_ticks(
+                &blockstore,
+                ticks_per_slot,
+                slot,
+                slot - 1,
+                last_entry_hash,
+            );
+        }
+
+        let progress_reports: Arc<RwLock<Vec<BlockstoreProcessorProgress>>> = Arc::default();
+        let progress_callback = {
+            let progress_reports = progress_reports.clone();
+            Arc::new(move |progress: BlockstoreProcessorProgress| {
+                progress_reports.write().unwrap().push(progress);
+            })
+        };
+
+        let opts = ProcessOptions {
+            override_num_threads: Some(1),
+            progress_callback: Some(progress_callback),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+
+        let progress_reports = progress_reports.read().unwrap();
+        assert_eq!(progress_reports.len(), 3);
+        for (i, progress) in progress_reports.iter().enumerate() {
+            assert_eq!(progress.slot, i as Slot + 1);
+            assert_eq!(progress.last_root, 0);
+            assert_eq!(progress.slots_processed, i + 1);
+            assert_eq!(progress.txs_processed, 0);
+        }
+        // Slot 3 has no children left to replay
+        assert_eq!(progress_reports.last().unwrap().pending_slots, 0);
+    }
+
+    #[test]
+    fn test_process_ledger_options_progress_reporter() {
+        #[derive(Debug, PartialEq)]
+        enum ProgressEvent {
+            Slot(Slot, Slot, usize),
+            Root(Slot),
+        }
+
+        #[derive(Default)]
+        struct RecordingProgressReporter(Mutex<Vec<ProgressEvent>>);
+
+        impl ProcessProgressReporter for RecordingProgressReporter {
+            fn on_slot(&self, slot: Slot, root: Slot, total_txs: usize) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(ProgressEvent::Slot(slot, root, total_txs));
+            }
+
+            fn on_root(&self, new_root: Slot) {
+                self.0.lock().unwrap().push(ProgressEvent::Root(new_root));
+            }
+        }
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(100);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, mut last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        for slot in 1..=3 {
+            last_entry_hash = fill_blockstore_slot_with_ticks(
+                &blockstore,
+                ticks_per_slot,
+                slot,
+                slot - 1,
+                last_entry_hash,
+            );
+        }
+        blockstore.set_roots(vec![1, 2].iter()).unwrap();
+
+        let progress_reporter = Arc::new(RecordingProgressReporter::default());
+        let opts = ProcessOptions {
+            override_num_threads: Some(1),
+            progress_reporter: Some(progress_reporter.clone()),
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+
+        // A slot that becomes the root is reported with the new root
+        assert_eq!(
+            *progress_reporter.0.lock().unwrap(),
+            vec![
+                ProgressEvent::Root(1),
+                ProgressEvent::Slot(1, 1, 0),
+                ProgressEvent::Root(2),
+                ProgressEvent::Slot(2, 2, 0),
+                ProgressEvent::Slot(3, 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_ledger_options_program_blacklist() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let (ledger_path, last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let blockhash = genesis_config.hash();
+
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_unique(), 1, blockhash);
+        let mut entries = vec![next_entry(&last_entry_hash, 1, vec![tx])];
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                None,
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let opts = ProcessOptions {
+            program_blacklist: Some(Arc::new(vec![system_program::id()].into_iter().collect())),
+            ..ProcessOptions::default()
+        };
+        let result = confirm_full_slot(
+            &blockstore,
+            &bank1,
+            &opts,
+            &VerifyRecyclers::default(),
+            &mut ConfirmationProgress::new(bank0.last_blockhash()),
+            None,
+            None,
+            &mut ExecuteTimings::default(),
+        );
+        assert_matches!(
+            result,
+            Err(BlockstoreProcessorError::BlacklistedProgram(_, program_id))
+                if program_id == system_program::id()
+        );
+        // Nothing from the slot was executed
+        assert_eq!(bank1.transaction_count(), 0);
+
+        // Replaying the ledger marks the slot dead
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).unwrap();
+        assert!(blockstore.is_dead(1));
+        assert!(bank_forks.get(1).is_none());
+    }
+
     #[test]
     fn test_process_entries_tick() {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(1000);
@@ -2879,6 +5302,70 @@ pub mod tests {
         assert!(bank_forks.get(0).is_some());
     }
 
+    #[test]
+    fn test_register_hard_forks() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(123);
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        let bank5 = Bank::new_from_parent(&bank0, &Pubkey::default(), 5);
+        let registered_hard_forks = || {
+            bank5
+                .hard_forks()
+                .read()
+                .unwrap()
+                .iter()
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        // A hard fork at or before the root is refused, unless obsolete ones are allowed
+        assert_matches!(
+            register_hard_forks(&bank5, &[3], false),
+            Err(BlockstoreProcessorError::InvalidHardFork(3))
+        );
+        assert_matches!(
+            register_hard_forks(&bank5, &[7, 5], false),
+            Err(BlockstoreProcessorError::InvalidHardFork(5))
+        );
+        assert!(registered_hard_forks().is_empty());
+        assert!(register_hard_forks(&bank5, &[3], true).is_ok());
+        assert!(registered_hard_forks().is_empty());
+
+        // Hard forks past the root are registered as many times as requested
+        assert!(register_hard_forks(&bank5, &[9, 7, 7], false).is_ok());
+        assert_eq!(registered_hard_forks(), vec![(7, 2), (9, 1)]);
+
+        // Requesting them again as many times leaves them alone, a different count is refused
+        assert!(register_hard_forks(&bank5, &[7, 9, 7], false).is_ok());
+        assert_eq!(registered_hard_forks(), vec![(7, 2), (9, 1)]);
+        assert_matches!(
+            register_hard_forks(&bank5, &[7], false),
+            Err(BlockstoreProcessorError::InvalidHardFork(7))
+        );
+        assert_eq!(registered_hard_forks(), vec![(7, 2), (9, 1)]);
+
+        // A hard fork the root bank already has, like a snapshot taken at it, is accepted
+        bank5.hard_forks().write().unwrap().register(5);
+        assert!(register_hard_forks(&bank5, &[5, 7, 7, 9], false).is_ok());
+        assert_eq!(registered_hard_forks(), vec![(5, 1), (7, 2), (9, 1)]);
+
+        // The ledger isn't processed with an obsolete hard fork
+        let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let opts = ProcessOptions {
+            new_hard_forks: Some(vec![0]),
+            ..ProcessOptions::default()
+        };
+        assert_matches!(
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts.clone(), None),
+            Err(BlockstoreProcessorError::InvalidHardFork(0))
+        );
+        let opts = ProcessOptions {
+            allow_obsolete_hard_forks: true,
+            ..opts
+        };
+        assert!(process_blockstore(&genesis_config, &blockstore, Vec::new(), opts, None).is_ok());
+    }
+
     #[test]
     fn test_process_blockstore_from_root() {
         let GenesisConfigInfo {
@@ -2968,6 +5455,95 @@ pub mod tests {
         verify_fork_infos(&bank_forks);
     }
 
+    #[test]
+    fn test_process_blockstore_from_root_skip_capitalization_check() {
+        let GenesisConfigInfo {
+            mut genesis_config, ..
+        } = create_genesis_config(123);
+        let ticks_per_slot = 1;
+        genesis_config.ticks_per_slot = ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let mut last_hash = blockhash;
+        for i in 0..3 {
+            last_hash =
+                fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, i + 1, i, last_hash);
+        }
+        blockstore.set_roots(vec![2].iter()).unwrap();
+
+        let recyclers = VerifyRecyclers::default();
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        process_bank_0(
+            &bank0,
+            &blockstore,
+            &ProcessOptions::default(),
+            &recyclers,
+            None,
+        );
+
+        // Store an account behind the back of the capitalization, so that the
+        // capitalization of every bank built on top of bank1 mismatches its accounts
+        let new_bank1 = || {
+            let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+            confirm_full_slot(
+                &blockstore,
+                &bank1,
+                &ProcessOptions::default(),
+                &recyclers,
+                &mut ConfirmationProgress::new(bank0.last_blockhash()),
+                None,
+                None,
+                &mut ExecuteTimings::default(),
+            )
+            .unwrap();
+            bank1.store_account(
+                &solana_sdk::pubkey::new_rand(),
+                &AccountSharedData::new(42, 0, &Pubkey::default()),
+            );
+            bank1.squash();
+            bank1
+        };
+
+        let opts = ProcessOptions {
+            accounts_db_test_hash_calculation: true,
+            ..ProcessOptions::default()
+        };
+        // The error carries both capitalizations, off by the lamports stored above
+        assert_matches!(
+            do_process_blockstore_from_root(
+                &blockstore,
+                new_bank1(),
+                &opts,
+                &recyclers,
+                None,
+                None,
+                BankFromArchiveTimings::default(),
+            )
+            .err(),
+            Some(BlockstoreProcessorError::RootBankWithMismatchedCapitalization(2, expected, calculated))
+                if calculated == expected + 42
+        );
+
+        let opts = ProcessOptions {
+            skip_capitalization_check: true,
+            ..opts
+        };
+        let (bank_forks, _leader_schedule) = do_process_blockstore_from_root(
+            &blockstore,
+            new_bank1(),
+            &opts,
+            &recyclers,
+            None,
+            None,
+            BankFromArchiveTimings::default(),
+        )
+        .unwrap();
+        assert_eq!(bank_forks.root(), 2);
+        assert!(!bank_forks
+            .root_bank()
+            .calculate_and_verify_capitalization(true));
+    }
+
     #[test]
     #[ignore]
     fn test_process_entries_stress() {
@@ -3197,12 +5773,119 @@ pub mod tests {
             false,
             &mut ExecuteTimings::default(),
         );
-        let (err, signature) = get_first_error(&batch, fee_collection_results).unwrap();
+        let (err, signature) = get_first_error(
+            &batch,
+            fee_collection_results,
+            false,
+            &mut ExecuteTimings::default(),
+        )
+        .unwrap();
         // First error found should be for the 2nd transaction, due to iteration_order
         assert_eq!(err.unwrap_err(), TransactionError::AccountNotFound);
         assert_eq!(signature, account_not_found_sig);
     }
 
+    #[test]
+    fn test_process_entries_report_fee_payer_failures() {
+        let GenesisConfigInfo {
+            mut genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000_000);
+        genesis_config.fee_rate_governor = solana_sdk::fee_calculator::FeeRateGovernor::new(10, 0);
+
+        for &report_fee_payer_failures in &[false, true] {
+            let bank = Arc::new(Bank::new(&genesis_config));
+            let blockhash = bank.last_blockhash();
+
+            // Can't pay the fee of 10 lamports for its one signature
+            let underfunded_keypair = Keypair::new();
+            bank.transfer(5, &mint_keypair, &underfunded_keypair.pubkey())
+                .unwrap();
+            let underfunded_tx = system_transaction::transfer(
+                &underfunded_keypair,
+                &solana_sdk::pubkey::new_rand(),
+                1,
+                blockhash,
+            );
+            // Pays its fee, but fails for transferring more than it has
+            let overdrawn_keypair = Keypair::new();
+            bank.transfer(100, &mint_keypair, &overdrawn_keypair.pubkey())
+                .unwrap();
+            let overdrawn_tx = system_transaction::transfer(
+                &overdrawn_keypair,
+                &solana_sdk::pubkey::new_rand(),
+                1_000,
+                blockhash,
+            );
+            let entry = next_entry(&blockhash, 1, vec![underfunded_tx, overdrawn_tx]);
+            let mut entries = vec![EntryType::from(&entry)];
+
+            let mut timings = ExecuteTimings::default();
+            // The first error is propagated as before, only its reporting changes
+            assert_matches!(
+                process_entries_with_callback(
+                    &bank,
+                    &mut entries,
+                    false,
+                    None,
+                    None,
+                    None,
+                    None,
+                    report_fee_payer_failures,
+                    None,
+                    &mut timings,
+                ),
+                Err(BlockstoreProcessorError::InvalidTransaction(
+                    TransactionError::InsufficientFundsForFee
+                ))
+            );
+            assert_eq!(
+                timings.num_fee_payer_failures,
+                if report_fee_payer_failures { 1 } else { 0 }
+            );
+        }
+    }
+
+    #[test]
+    fn test_collect_batch_token_balances_without_token_program() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000_000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let transactions: Vec<_> = (0..3)
+            .map(|_| {
+                system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    42,
+                    bank.last_blockhash(),
+                )
+            })
+            .collect();
+        let batch = bank.prepare_batch(transactions.iter());
+        assert!(!batch_references_token_program(&batch));
+
+        // Skipping the collection yields the same empty balances, one set per transaction
+        let skipped = collect_batch_token_balances(&bank, &batch, false, &mut HashMap::new());
+        let collected = collect_token_balances(&bank, &batch, &mut HashMap::new());
+        assert_eq!(skipped, collected);
+        assert_eq!(skipped.len(), transactions.len());
+        assert!(skipped.iter().all(|balances| balances.is_empty()));
+        drop(batch);
+
+        // Any transaction referencing a token program disables the fast path
+        for token_program_id in solana_transaction_status::token_balances::token_program_ids() {
+            let mut token_transaction = transactions[0].clone();
+            token_transaction.message.account_keys[1] = *token_program_id;
+            let transactions = [transactions[1].clone(), token_transaction];
+            let batch = bank.prepare_batch(transactions.iter());
+            assert!(batch_references_token_program(&batch));
+        }
+    }
+
     #[test]
     fn test_replay_vote_sender() {
         let validator_keypairs: Vec<_> =
@@ -3513,23 +6196,36 @@ pub mod tests {
         let slot = 100;
 
         // Supermajority root should be None
-        assert!(
-            supermajority_root_from_vote_accounts(slot, total_stake, std::iter::empty()).is_none()
-        );
+        assert!(supermajority_root_from_vote_accounts(
+            slot,
+            total_stake,
+            std::iter::empty(),
+            VOTE_THRESHOLD_SIZE
+        )
+        .is_none());
 
         // Supermajority root should be None
         let roots_stakes = vec![(8, 1), (3, 1), (4, 1), (8, 1)];
         let accounts = convert_to_vote_accounts(roots_stakes);
-        assert!(
-            supermajority_root_from_vote_accounts(slot, total_stake, accounts.into_iter())
-                .is_none()
-        );
+        assert!(supermajority_root_from_vote_accounts(
+            slot,
+            total_stake,
+            accounts.into_iter(),
+            VOTE_THRESHOLD_SIZE
+        )
+        .is_none());
 
         // Supermajority root should be 4, has 7/10 of the stake
         let roots_stakes = vec![(8, 1), (3, 1), (4, 1), (8, 5)];
         let accounts = convert_to_vote_accounts(roots_stakes);
         assert_eq!(
-            supermajority_root_from_vote_accounts(slot, total_stake, accounts.into_iter()).unwrap(),
+            supermajority_root_from_vote_accounts(
+                slot,
+                total_stake,
+                accounts.into_iter(),
+                VOTE_THRESHOLD_SIZE
+            )
+            .unwrap(),
             4
         );
 
@@ -3537,8 +6233,228 @@ pub mod tests {
         let roots_stakes = vec![(8, 1), (3, 1), (4, 1), (8, 6)];
         let accounts = convert_to_vote_accounts(roots_stakes);
         assert_eq!(
-            supermajority_root_from_vote_accounts(slot, total_stake, accounts.into_iter()).unwrap(),
+            supermajority_root_from_vote_accounts(
+                slot,
+                total_stake,
+                accounts.into_iter(),
+                VOTE_THRESHOLD_SIZE
+            )
+            .unwrap(),
             8
         );
+
+        // Unstaked accounts are ignored, supermajority root should still be 4
+        let roots_stakes = vec![(8, 1), (3, 1), (4, 1), (8, 5), (9, 0)];
+        let accounts = convert_to_vote_accounts(roots_stakes);
+        assert_eq!(
+            supermajority_root_from_vote_accounts(
+                slot,
+                total_stake,
+                accounts.into_iter(),
+                VOTE_THRESHOLD_SIZE
+            )
+            .unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_supermajority_root_threshold() {
+        let total_stake = 10;
+        // Sorted from greatest to smallest slot, slot 3 is rooted by 7/10 of the stake
+        let roots_stakes = vec![(8, 5), (4, 1), (3, 1)];
+        assert_eq!(
+            supermajority_root(&roots_stakes, total_stake, VOTE_THRESHOLD_SIZE).unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            supermajority_root(&roots_stakes, total_stake, 0.9).unwrap(),
+            None
+        );
+
+        // With enough stake the higher threshold is met as well
+        let roots_stakes = vec![(8, 5), (4, 1), (3, 4)];
+        assert_eq!(
+            supermajority_root(&roots_stakes, total_stake, 0.9).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_supermajority_root_edge_cases() {
+        assert_eq!(
+            supermajority_root(&[], 10, VOTE_THRESHOLD_SIZE).unwrap(),
+            None
+        );
+
+        // A single validator holding all the stake
+        assert_eq!(
+            supermajority_root(&[(7, 10)], 10, VOTE_THRESHOLD_SIZE).unwrap(),
+            Some(7)
+        );
+
+        // Exactly 2/3 of the stake isn't a supermajority, the threshold must be exceeded.
+        // Only slot 3, rooted by both validators, is
+        assert_eq!(
+            supermajority_root(&[(5, 2)], 3, VOTE_THRESHOLD_SIZE).unwrap(),
+            None
+        );
+        assert_eq!(
+            supermajority_root(&[(5, 2), (3, 1)], 3, VOTE_THRESHOLD_SIZE).unwrap(),
+            Some(3)
+        );
+
+        // Just above and just below the threshold
+        assert_eq!(
+            supermajority_root(&[(5, 201), (3, 99)], 300, VOTE_THRESHOLD_SIZE).unwrap(),
+            Some(5)
+        );
+        assert_eq!(
+            supermajority_root(&[(5, 199), (3, 1)], 300, VOTE_THRESHOLD_SIZE).unwrap(),
+            None
+        );
+
+        // Unsorted roots are an error rather than a panic
+        assert_matches!(
+            supermajority_root(&[(3, 1), (5, 2)], 3, VOTE_THRESHOLD_SIZE),
+            Err(BlockstoreProcessorError::UnsortedRoots(5, 3))
+        );
+    }
+
+    #[test]
+    fn test_supermajority_root_from_roots() {
+        // Unsorted input is sorted first
+        assert_eq!(
+            supermajority_root_from_roots(vec![(3, 1), (5, 2)], 3, VOTE_THRESHOLD_SIZE),
+            Some(3)
+        );
+
+        // Several validators tied at the same root add up to exactly the threshold,
+        // which isn't enough
+        let roots_stakes = vec![(5, 1), (5, 1), (3, 1)];
+        assert_eq!(
+            supermajority_root_from_roots(roots_stakes.clone(), 3, VOTE_THRESHOLD_SIZE),
+            Some(3)
+        );
+        assert_eq!(supermajority_root_from_roots(roots_stakes, 3, 0.5), Some(5));
+
+        // Roots without stake never count towards the threshold
+        assert_eq!(
+            supermajority_root_from_roots(vec![(9, 0), (5, 3)], 3, VOTE_THRESHOLD_SIZE),
+            Some(5)
+        );
+        assert_eq!(
+            supermajority_root_from_roots(vec![(9, 0), (5, 0)], 3, VOTE_THRESHOLD_SIZE),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transaction_status_sender_try_send() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let transaction_status_sender = TransactionStatusSender {
+            sender,
+            enable_cpi_and_log_storage: false,
+        };
+
+        // The channel has room for a single message, any further ones are dropped
+        transaction_status_sender.try_send_transaction_status_freeze_message(&bank);
+        transaction_status_sender.try_send_transaction_status_freeze_message(&bank);
+        assert_eq!(receiver.len(), 1);
+        assert_matches!(
+            receiver.try_recv(),
+            Ok(TransactionStatusMessage::Freeze(slot)) if slot == bank.slot()
+        );
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_transaction_status_sender_frozen_message() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let (ledger_path, _) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let recyclers = VerifyRecyclers::default();
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+
+        let num_txs = 4;
+        let mut last_entry_hash = bank0.last_blockhash();
+        let mut entries: Vec<_> = (0..num_txs)
+            .map(|_| {
+                let tx = system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    1,
+                    bank0.last_blockhash(),
+                );
+                next_entry_mut(&mut last_entry_hash, 1, vec![tx])
+            })
+            .collect();
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        let num_entries = entries.len();
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                Some(0),
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let mut timing = ConfirmationTiming::default();
+        let mut progress = ConfirmationProgress::new(bank0.last_blockhash());
+        confirm_slot(
+            &blockstore,
+            &bank1,
+            &mut timing,
+            &mut progress,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            &recyclers,
+            false,
+            None,
+        )
+        .unwrap();
+        assert!(bank1.is_complete());
+
+        let (sender, receiver) = unbounded();
+        let transaction_status_sender = TransactionStatusSender {
+            sender,
+            enable_cpi_and_log_storage: false,
+        };
+        transaction_status_sender
+            .send_transaction_status_frozen_message(&bank1, &progress, &timing);
+        match receiver.try_recv().unwrap() {
+            TransactionStatusMessage::Frozen(stats) => {
+                assert_eq!(stats.slot, 1);
+                assert_eq!(stats.num_txs, num_txs);
+                assert_eq!(stats.num_entries, num_entries);
+                assert_eq!(stats.num_shreds, progress.num_shreds);
+            }
+            _ => panic!("expected a frozen message"),
+        }
     }
 }
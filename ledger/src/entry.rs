@@ -7,6 +7,7 @@ use dlopen::symbor::{Container, SymBorApi, Symbol};
 use dlopen_derive::SymBorApi;
 use log::*;
 use rand::{thread_rng, Rng};
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
 use rayon::prelude::*;
 use rayon::ThreadPool;
 use serde::{Deserialize, Serialize};
@@ -28,7 +29,7 @@ use std::ffi::OsStr;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Once;
 use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
+use std::thread::{JoinHandle, LocalKey};
 use std::time::Instant;
 use std::{cmp, thread};
 
@@ -38,6 +39,24 @@ thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::
                     .build()
                     .unwrap()));
 
+// Runs `op` in the given thread local pool. When already running on a rayon thread, e.g.
+// while replaying independent forks in parallel, `op` runs in the current pool instead,
+// otherwise every worker thread would build its own thread pool
+pub(crate) fn install_in_par_thread_pool<OP, R>(
+    thread_pool: &'static LocalKey<RefCell<ThreadPool>>,
+    op: OP,
+) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    if rayon::current_thread_index().is_some() {
+        op()
+    } else {
+        thread_pool.with(|thread_pool| thread_pool.borrow().install(op))
+    }
+}
+
 pub type EntrySender = Sender<Vec<Entry>>;
 pub type EntryReceiver = Receiver<Vec<Entry>>;
 
@@ -254,6 +273,8 @@ pub enum DeviceVerificationData {
 
 pub struct EntryVerificationState {
     verification_status: EntryVerificationStatus,
+    // Index of the first entry that failed verification
+    first_failed_entry: Option<usize>,
     poh_duration_us: u64,
     device_verification_data: DeviceVerificationData,
 }
@@ -280,6 +301,12 @@ impl EntryVerificationState {
         self.poh_duration_us
     }
 
+    /// Index of the first entry that doesn't chain correctly in proof of history, only
+    /// set once verification has failed
+    pub fn first_failed_entry(&self) -> Option<usize> {
+        self.first_failed_entry
+    }
+
     pub fn finish_verify(&mut self) -> bool {
         match &mut self.device_verification_data {
             DeviceVerificationData::Gpu(verification_state) => {
@@ -291,24 +318,23 @@ impl EntryVerificationState {
                     .expect("unwrap Arc")
                     .into_inner()
                     .expect("into_inner");
-                let res = PAR_THREAD_POOL.with(|thread_pool| {
-                    thread_pool.borrow().install(|| {
-                        hashes
-                            .into_par_iter()
-                            .cloned()
-                            .zip(verification_state.verifications.take().unwrap())
-                            .all(|(hash, (action, expected))| {
-                                let actual = match action {
-                                    VerifyAction::Mixin(mixin) => {
-                                        Poh::new(hash, None).record(mixin).unwrap().hash
-                                    }
-                                    VerifyAction::Tick => Poh::new(hash, None).tick().unwrap().hash,
-                                    VerifyAction::None => hash,
-                                };
-                                actual == expected
-                            })
-                    })
+                self.first_failed_entry = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+                    hashes
+                        .into_par_iter()
+                        .cloned()
+                        .zip(verification_state.verifications.take().unwrap())
+                        .position_first(|(hash, (action, expected))| {
+                            let actual = match action {
+                                VerifyAction::Mixin(mixin) => {
+                                    Poh::new(hash, None).record(mixin).unwrap().hash
+                                }
+                                VerifyAction::Tick => Poh::new(hash, None).tick().unwrap().hash,
+                                VerifyAction::None => hash,
+                            };
+                            actual != expected
+                        })
                 });
+                let res = self.first_failed_entry.is_none();
 
                 verify_check_time.stop();
                 self.poh_duration_us += gpu_time_us + verify_check_time.as_us();
@@ -341,6 +367,37 @@ fn compare_hashes(computed_hash: Hash, ref_entry: &Entry) -> bool {
     actual == ref_entry.hash
 }
 
+// Picks the indexes of the entries to verify out of `num_entries`, always including the
+// first and last ones
+fn sample_entries_to_verify(num_entries: usize, sample_rate: f64, seed: &Hash) -> Vec<usize> {
+    let sample_rate = sample_rate.max(0.0).min(1.0);
+    let mut rng = ChaChaRng::from_seed(seed.to_bytes());
+    (0..num_entries)
+        .filter(|i| *i == 0 || *i + 1 == num_entries || rng.gen_bool(sample_rate))
+        .collect()
+}
+
+/// Verifies the size, precompiles and signatures of `tx`, returning the hash of its
+/// message if they are all valid
+pub fn verify_transaction(
+    tx: &Transaction,
+    secp256k1_program_enabled: bool,
+    verify_tx_signatures_len: bool,
+) -> Option<Hash> {
+    let size = bincode::serialized_size(tx).ok()?;
+    if size > PACKET_DATA_SIZE as u64 {
+        return None;
+    }
+    if secp256k1_program_enabled {
+        // Verify tx precompiles if secp256k1 program is enabled.
+        tx.verify_precompiles().ok()?;
+    }
+    if verify_tx_signatures_len && !tx.verify_signatures_len() {
+        return None;
+    }
+    tx.verify_and_hash_message().ok()
+}
+
 // an EntrySlice is a slice of Entries
 pub trait EntrySlice {
     /// Verifies the hashes and counts of a slice of transactions are all consistent.
@@ -349,6 +406,16 @@ pub trait EntrySlice {
     fn verify_cpu_x86_simd(&self, start_hash: &Hash, simd_len: usize) -> EntryVerificationState;
     fn start_verify(&self, start_hash: &Hash, recyclers: VerifyRecyclers)
         -> EntryVerificationState;
+    /// Verifies the proof of history of only a `sample_rate` fraction of the entries, picked
+    /// deterministically from `seed`. The first and last entries are always verified, so
+    /// the slice still chains from `start_hash`. Tampering with entries that aren't
+    /// sampled, nor followed by a sampled entry, isn't detected
+    fn verify_sampled(
+        &self,
+        start_hash: &Hash,
+        sample_rate: f64,
+        seed: &Hash,
+    ) -> EntryVerificationState;
     fn verify(&self, start_hash: &Hash) -> bool;
     /// Checks that each entry tick has the correct number of hashes. Entry slices do not
     /// necessarily end in a tick, so `tick_hash_count` is used to carry over the hash count
@@ -378,30 +445,29 @@ impl EntrySlice for [Entry] {
             transactions: vec![],
         }];
         let entry_pairs = genesis.par_iter().chain(self).zip(self);
-        let res = PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                entry_pairs.all(|(x0, x1)| {
-                    let r = x1.verify(&x0.hash);
-                    if !r {
-                        warn!(
-                            "entry invalid!: x0: {:?}, x1: {:?} num txs: {}",
-                            x0.hash,
-                            x1.hash,
-                            x1.transactions.len()
-                        );
-                    }
-                    r
-                })
+        let first_failed_entry = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            entry_pairs.position_first(|(x0, x1)| {
+                let r = x1.verify(&x0.hash);
+                if !r {
+                    warn!(
+                        "entry invalid!: x0: {:?}, x1: {:?} num txs: {}",
+                        x0.hash,
+                        x1.hash,
+                        x1.transactions.len()
+                    );
+                }
+                !r
             })
         });
 
         let poh_duration_us = timing::duration_as_us(&now.elapsed());
         EntryVerificationState {
-            verification_status: if res {
+            verification_status: if first_failed_entry.is_none() {
                 EntryVerificationStatus::Success
             } else {
                 EntryVerificationStatus::Failure
             },
+            first_failed_entry,
             poh_duration_us,
             device_verification_data: DeviceVerificationData::Cpu(),
         }
@@ -438,53 +504,53 @@ impl EntrySlice for [Entry] {
         num_hashes.resize(aligned_len, 0);
         let num_hashes: Vec<_> = num_hashes.chunks(simd_len).collect();
 
-        let res = PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                hashes_chunked
-                    .par_iter_mut()
-                    .zip(num_hashes)
-                    .enumerate()
-                    .all(|(i, (chunk, num_hashes))| {
-                        match simd_len {
-                            8 => unsafe {
-                                (api().unwrap().poh_verify_many_simd_avx2)(
-                                    chunk.as_mut_ptr(),
-                                    num_hashes.as_ptr(),
-                                );
-                            },
-                            16 => unsafe {
-                                (api().unwrap().poh_verify_many_simd_avx512skx)(
-                                    chunk.as_mut_ptr(),
-                                    num_hashes.as_ptr(),
-                                );
-                            },
-                            _ => {
-                                panic!("unsupported simd len: {}", simd_len);
-                            }
+        let first_failed_entry = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            hashes_chunked
+                .par_iter_mut()
+                .zip(num_hashes)
+                .enumerate()
+                .find_map_first(|(i, (chunk, num_hashes))| {
+                    match simd_len {
+                        8 => unsafe {
+                            (api().unwrap().poh_verify_many_simd_avx2)(
+                                chunk.as_mut_ptr(),
+                                num_hashes.as_ptr(),
+                            );
+                        },
+                        16 => unsafe {
+                            (api().unwrap().poh_verify_many_simd_avx512skx)(
+                                chunk.as_mut_ptr(),
+                                num_hashes.as_ptr(),
+                            );
+                        },
+                        _ => {
+                            panic!("unsupported simd len: {}", simd_len);
                         }
-                        let entry_start = i * simd_len;
-                        // The last chunk may produce indexes larger than what we have in the reference entries
-                        // because it is aligned to simd_len.
-                        let entry_end = std::cmp::min(entry_start + simd_len, self.len());
-                        self[entry_start..entry_end]
-                            .iter()
-                            .enumerate()
-                            .all(|(j, ref_entry)| {
-                                let start = j * HASH_BYTES;
-                                let end = start + HASH_BYTES;
-                                let hash = Hash::new(&chunk[start..end]);
-                                compare_hashes(hash, ref_entry)
-                            })
-                    })
-            })
+                    }
+                    let entry_start = i * simd_len;
+                    // The last chunk may produce indexes larger than what we have in the reference entries
+                    // because it is aligned to simd_len.
+                    let entry_end = std::cmp::min(entry_start + simd_len, self.len());
+                    self[entry_start..entry_end]
+                        .iter()
+                        .enumerate()
+                        .position(|(j, ref_entry)| {
+                            let start = j * HASH_BYTES;
+                            let end = start + HASH_BYTES;
+                            let hash = Hash::new(&chunk[start..end]);
+                            !compare_hashes(hash, ref_entry)
+                        })
+                        .map(|j| entry_start + j)
+                })
         });
         let poh_duration_us = timing::duration_as_us(&now.elapsed());
         EntryVerificationState {
-            verification_status: if res {
+            verification_status: if first_failed_entry.is_none() {
                 EntryVerificationStatus::Success
             } else {
                 EntryVerificationStatus::Failure
             },
+            first_failed_entry,
             poh_duration_us,
             device_verification_data: DeviceVerificationData::Cpu(),
         }
@@ -512,6 +578,39 @@ impl EntrySlice for [Entry] {
         }
     }
 
+    fn verify_sampled(
+        &self,
+        start_hash: &Hash,
+        sample_rate: f64,
+        seed: &Hash,
+    ) -> EntryVerificationState {
+        let now = Instant::now();
+        let sampled_entries = sample_entries_to_verify(self.len(), sample_rate, seed);
+        inc_new_counter_info!("entry_verify-num_sampled_entries", sampled_entries.len());
+        let first_failed_entry = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            sampled_entries.into_par_iter().find_first(|i| {
+                let prev_hash = if *i == 0 {
+                    start_hash
+                } else {
+                    &self[*i - 1].hash
+                };
+                !self[*i].verify(prev_hash)
+            })
+        });
+
+        let poh_duration_us = timing::duration_as_us(&now.elapsed());
+        EntryVerificationState {
+            verification_status: if first_failed_entry.is_none() {
+                EntryVerificationStatus::Success
+            } else {
+                EntryVerificationStatus::Failure
+            },
+            first_failed_entry,
+            poh_duration_us,
+            device_verification_data: DeviceVerificationData::Cpu(),
+        }
+    }
+
     fn verify_and_hash_transactions<'a>(
         &'a self,
         skip_verification: bool,
@@ -520,18 +619,7 @@ impl EntrySlice for [Entry] {
     ) -> Option<Vec<EntryType<'a>>> {
         let verify_and_hash = |tx: &'a Transaction| -> Option<HashedTransaction<'a>> {
             let message_hash = if !skip_verification {
-                let size = bincode::serialized_size(tx).ok()?;
-                if size > PACKET_DATA_SIZE as u64 {
-                    return None;
-                }
-                if secp256k1_program_enabled {
-                    // Verify tx precompiles if secp256k1 program is enabled.
-                    tx.verify_precompiles().ok()?;
-                }
-                if verify_tx_signatures_len && !tx.verify_signatures_len() {
-                    return None;
-                }
-                tx.verify_and_hash_message().ok()?
+                verify_transaction(tx, secp256k1_program_enabled, verify_tx_signatures_len)?
             } else {
                 tx.message().hash()
             };
@@ -539,24 +627,22 @@ impl EntrySlice for [Entry] {
             Some(HashedTransaction::new(Cow::Borrowed(tx), message_hash))
         };
 
-        PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                self.par_iter()
-                    .map(|entry| {
-                        if entry.transactions.is_empty() {
-                            Some(EntryType::Tick(entry.hash))
-                        } else {
-                            Some(EntryType::Transactions(
-                                entry
-                                    .transactions
-                                    .par_iter()
-                                    .map(verify_and_hash)
-                                    .collect::<Option<Vec<HashedTransaction>>>()?,
-                            ))
-                        }
-                    })
-                    .collect()
-            })
+        install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            self.par_iter()
+                .map(|entry| {
+                    if entry.transactions.is_empty() {
+                        Some(EntryType::Tick(entry.hash))
+                    } else {
+                        Some(EntryType::Transactions(
+                            entry
+                                .transactions
+                                .par_iter()
+                                .map(verify_and_hash)
+                                .collect::<Option<Vec<HashedTransaction>>>()?,
+                        ))
+                    }
+                })
+                .collect()
         })
     }
 
@@ -625,24 +711,22 @@ impl EntrySlice for [Entry] {
             timing::duration_as_us(&gpu_wait.elapsed())
         });
 
-        let verifications = PAR_THREAD_POOL.with(|thread_pool| {
-            thread_pool.borrow().install(|| {
-                self.into_par_iter()
-                    .map(|entry| {
-                        let answer = entry.hash;
-                        let action = if entry.transactions.is_empty() {
-                            if entry.num_hashes == 0 {
-                                VerifyAction::None
-                            } else {
-                                VerifyAction::Tick
-                            }
+        let verifications = install_in_par_thread_pool(&PAR_THREAD_POOL, || {
+            self.into_par_iter()
+                .map(|entry| {
+                    let answer = entry.hash;
+                    let action = if entry.transactions.is_empty() {
+                        if entry.num_hashes == 0 {
+                            VerifyAction::None
                         } else {
-                            VerifyAction::Mixin(hash_transactions(&entry.transactions))
-                        };
-                        (action, answer)
-                    })
-                    .collect()
-            })
+                            VerifyAction::Tick
+                        }
+                    } else {
+                        VerifyAction::Mixin(hash_transactions(&entry.transactions))
+                    };
+                    (action, answer)
+                })
+                .collect()
         });
 
         let device_verification_data = DeviceVerificationData::Gpu(GpuVerificationData {
@@ -652,6 +736,7 @@ impl EntrySlice for [Entry] {
         });
         EntryVerificationState {
             verification_status: EntryVerificationStatus::Pending,
+            first_failed_entry: None,
             poh_duration_us: timing::duration_as_us(&start.elapsed()),
             device_verification_data,
         }
@@ -842,6 +927,71 @@ mod tests {
         assert!(!bad_ticks.verify(&zero)); // inductive step, bad
     }
 
+    #[test]
+    fn test_verify_first_failed_entry() {
+        solana_logger::setup();
+        let zero = Hash::default();
+        let mut entries = create_ticks(4, 1, zero);
+        let mut state = entries.start_verify(&zero, VerifyRecyclers::default());
+        assert!(state.finish_verify());
+        assert_eq!(state.first_failed_entry(), None);
+
+        entries[2].hash = Hash::new_unique();
+        let mut state = entries.start_verify(&zero, VerifyRecyclers::default());
+        assert!(!state.finish_verify());
+        assert_eq!(state.first_failed_entry(), Some(2));
+    }
+
+    #[test]
+    fn test_verify_sampled() {
+        solana_logger::setup();
+        let zero = Hash::default();
+        let seed = Hash::new_unique();
+        let entries = create_ticks(64, 1, zero);
+
+        // Sampling is reproducible from the seed
+        let sampled_entries = sample_entries_to_verify(entries.len(), 0.25, &seed);
+        assert_eq!(
+            sampled_entries,
+            sample_entries_to_verify(entries.len(), 0.25, &seed)
+        );
+        assert_eq!(sampled_entries[0], 0);
+        assert_eq!(*sampled_entries.last().unwrap(), entries.len() - 1);
+        assert_eq!(
+            sample_entries_to_verify(entries.len(), 1.0, &seed),
+            (0..entries.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            sample_entries_to_verify(entries.len(), 0.0, &seed),
+            vec![0, entries.len() - 1]
+        );
+        assert!(entries.verify_sampled(&zero, 0.25, &seed).finish_verify());
+
+        // Tampering with a sampled entry is caught
+        for i in &sampled_entries {
+            let mut bad_entries = entries.clone();
+            bad_entries[*i].hash = Hash::new_unique();
+            let mut state = bad_entries.verify_sampled(&zero, 0.25, &seed);
+            assert!(!state.finish_verify());
+            assert_eq!(state.first_failed_entry(), Some(*i));
+        }
+
+        // The first entry must still chain from the start hash
+        assert!(!entries
+            .verify_sampled(&Hash::new_unique(), 0.0, &seed)
+            .finish_verify());
+
+        // Tampering with entries that aren't sampled, nor followed by a sampled entry, passes
+        let mut bad_entries = entries;
+        bad_entries[10].hash = Hash::new_unique();
+        assert!(bad_entries
+            .verify_sampled(&zero, 0.0, &seed)
+            .finish_verify());
+        assert!(!bad_entries
+            .verify_sampled(&zero, 1.0, &seed)
+            .finish_verify());
+    }
+
     #[test]
     fn test_verify_slice_with_hashes1() {
         solana_logger::setup();
@@ -1,6 +1,9 @@
 use solana_core::validator::ValidatorConfig;
 use solana_sdk::exit::Exit;
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
 
 pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
     ValidatorConfig {
@@ -22,6 +25,7 @@ pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
         fixed_leader_schedule: config.fixed_leader_schedule.clone(),
         wait_for_supermajority: config.wait_for_supermajority,
         new_hard_forks: config.new_hard_forks.clone(),
+        allow_obsolete_hard_forks: config.allow_obsolete_hard_forks,
         trusted_validators: config.trusted_validators.clone(),
         repair_validators: config.repair_validators.clone(),
         gossip_validators: config.gossip_validators.clone(),
@@ -57,6 +61,20 @@ pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
         poh_hashes_per_batch: config.poh_hashes_per_batch,
         no_wait_for_vote_to_start_leader: config.no_wait_for_vote_to_start_leader,
         accounts_shrink_ratio: config.accounts_shrink_ratio,
+        max_entry_recv_per_iter: config.max_entry_recv_per_iter,
+        report_fee_payer_failures: config.report_fee_payer_failures,
+        vote_refresh_interval: config.vote_refresh_interval,
+        paused_slots: Arc::new(RwLock::new(HashSet::new())),
+        superminority_threshold: config.superminority_threshold,
+        max_concurrent_forks: config.max_concurrent_forks,
+        vote_fee_payer_keypair: config.vote_fee_payer_keypair.clone(),
+        duplicate_slot_recovery_mode: config.duplicate_slot_recovery_mode,
+        recover_dead_duplicate_confirmed_slots: config.recover_dead_duplicate_confirmed_slots,
+        max_vote_signatures: config.max_vote_signatures,
+        partition_detection_threshold: config.partition_detection_threshold,
+        defer_voting_until_within_slots: config.defer_voting_until_within_slots,
+        min_online_stake_to_lead: config.min_online_stake_to_lead,
+        max_replay_wait: config.max_replay_wait,
     }
 }
 
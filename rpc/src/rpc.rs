@@ -133,6 +133,9 @@ pub struct JsonRpcConfig {
     pub enable_cpi_and_log_storage: bool,
     pub faucet_addr: Option<SocketAddr>,
     pub health_check_slot_distance: u64,
+    // Report the validator unhealthy if the replay loop hasn't completed an iteration
+    // for this long, `None` doesn't check the replay loop
+    pub health_check_max_replay_stall_ms: Option<u64>,
     pub enable_bigtable_ledger_storage: bool,
     pub enable_bigtable_ledger_upload: bool,
     pub max_multiple_accounts: Option<usize>,
@@ -300,7 +303,14 @@ impl JsonRpcRequestProcessor {
             ))),
             blockstore,
             validator_exit: create_validator_exit(&exit),
-            health: Arc::new(RpcHealth::new(cluster_info.clone(), None, 0, exit.clone())),
+            health: Arc::new(RpcHealth::new(
+                cluster_info.clone(),
+                None,
+                0,
+                exit.clone(),
+                Arc::new(AtomicU64::default()),
+                None,
+            )),
             cluster_info,
             genesis_hash,
             transaction_sender: Arc::new(Mutex::new(sender)),
@@ -871,6 +881,7 @@ impl JsonRpcRequestProcessor {
                 self.blockstore.max_root(),
                 err
             );
+            self.check_slot_dead(slot)?;
             if slot >= self.blockstore.max_root() {
                 return Err(RpcCustomError::BlockNotAvailable { slot }.into());
             }
@@ -881,6 +892,18 @@ impl JsonRpcRequestProcessor {
         Ok(())
     }
 
+    // A dead block is never going to be available, report why it died instead
+    fn check_slot_dead(&self, slot: Slot) -> Result<()> {
+        if self.blockstore.is_dead(slot) {
+            return Err(RpcCustomError::BlockDead {
+                slot,
+                reason: self.blockstore.get_dead_slot_reason(slot),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     fn check_slot_cleaned_up<T>(
         &self,
         result: &std::result::Result<T, BlockstoreError>,
@@ -992,6 +1015,7 @@ impl JsonRpcRequestProcessor {
         } else {
             return Err(RpcCustomError::TransactionHistoryNotAvailable.into());
         }
+        self.check_slot_dead(slot)?;
         Err(RpcCustomError::BlockNotAvailable { slot }.into())
     }
 
@@ -2226,6 +2250,10 @@ pub mod rpc_minimal {
                     num_slots_behind: Some(num_slots),
                 }
                 .into()),
+                RpcHealthStatus::ReplayStalled { .. } => Err(RpcCustomError::NodeUnhealthy {
+                    num_slots_behind: None,
+                }
+                .into()),
             }
         }
 
@@ -3012,6 +3040,13 @@ pub mod rpc_full {
                         }
                         .into());
                     }
+                    RpcHealthStatus::ReplayStalled { .. } => {
+                        inc_new_counter_info!("rpc-send-tx_health-replay-stalled", 1);
+                        return Err(RpcCustomError::NodeUnhealthy {
+                            num_slots_behind: None,
+                        }
+                        .into());
+                    }
                 }
 
                 if let (Err(err), logs, _) = preflight_bank.simulate_transaction(&transaction) {
@@ -3910,7 +3945,9 @@ pub mod tests {
         solana_gossip::{contact_info::ContactInfo, socketaddr},
         solana_ledger::{
             blockstore_meta::PerfSample,
-            blockstore_processor::fill_blockstore_slot_with_ticks,
+            blockstore_processor::{
+                fill_blockstore_slot_with_ticks, DeadSlotInfo, TransactionStatusMessage,
+            },
             genesis_utils::{create_genesis_config, GenesisConfigInfo},
         },
         solana_runtime::{
@@ -6125,6 +6162,46 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_get_block_dead_slot() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let dead_slot = 12345;
+        meta.blockstore.set_dead_slot(dead_slot).unwrap();
+
+        let (transaction_status_sender, transaction_status_receiver) =
+            crossbeam_channel::unbounded();
+        let transaction_status_service =
+            crate::transaction_status_service::TransactionStatusService::new(
+                transaction_status_receiver,
+                Arc::new(AtomicU64::default()),
+                meta.blockstore.clone(),
+                &Arc::new(AtomicBool::new(false)),
+            );
+        transaction_status_sender
+            .send(TransactionStatusMessage::DeadSlot(DeadSlotInfo {
+                slot: dead_slot,
+                error: "invalid entry".to_string(),
+                last_entry: Hash::default(),
+            }))
+            .unwrap();
+        drop(transaction_status_sender);
+        transaction_status_service.join().unwrap();
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getBlock","params":[{}]}}"#,
+            dead_slot
+        );
+        let res = io.handle_request_sync(&req, meta);
+        assert_eq!(
+            res,
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32014,"message":"Block 12345 failed to replay: invalid entry"},"id":1}"#.to_string(),
+            )
+        );
+    }
+
     #[test]
     fn test_get_block_config() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
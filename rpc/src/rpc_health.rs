@@ -1,17 +1,37 @@
 use {
     solana_gossip::cluster_info::ClusterInfo,
-    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    solana_sdk::{clock::Slot, pubkey::Pubkey, timing::timestamp},
     std::{
         collections::HashSet,
-        sync::atomic::{AtomicBool, Ordering},
+        sync::atomic::{AtomicBool, AtomicU64, Ordering},
         sync::Arc,
     },
 };
 
+/// Returns how long the replay loop has been stalled for if that exceeds
+/// `max_replay_stall_ms`, given the timestamp of its last completed iteration. A zero
+/// timestamp means replay hasn't started yet
+pub fn replay_stall_ms(
+    replay_last_iteration_timestamp: &AtomicU64,
+    max_replay_stall_ms: u64,
+) -> Option<u64> {
+    let last_iteration_timestamp = replay_last_iteration_timestamp.load(Ordering::Relaxed);
+    if last_iteration_timestamp == 0 {
+        return None;
+    }
+    let stall_ms = timestamp().saturating_sub(last_iteration_timestamp);
+    if stall_ms > max_replay_stall_ms {
+        Some(stall_ms)
+    } else {
+        None
+    }
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum RpcHealthStatus {
     Ok,
     Behind { num_slots: Slot }, // Validator is behind its trusted validators
+    ReplayStalled { stall_ms: u64 }, // Replay loop hasn't completed an iteration recently
     Unknown,
 }
 
@@ -20,6 +40,8 @@ pub struct RpcHealth {
     trusted_validators: Option<HashSet<Pubkey>>,
     health_check_slot_distance: u64,
     override_health_check: Arc<AtomicBool>,
+    replay_last_iteration_timestamp: Arc<AtomicU64>,
+    max_replay_stall_ms: Option<u64>,
     #[cfg(test)]
     stub_health_status: std::sync::RwLock<Option<RpcHealthStatus>>,
 }
@@ -30,12 +52,16 @@ impl RpcHealth {
         trusted_validators: Option<HashSet<Pubkey>>,
         health_check_slot_distance: u64,
         override_health_check: Arc<AtomicBool>,
+        replay_last_iteration_timestamp: Arc<AtomicU64>,
+        max_replay_stall_ms: Option<u64>,
     ) -> Self {
         Self {
             cluster_info,
             trusted_validators,
             health_check_slot_distance,
             override_health_check,
+            replay_last_iteration_timestamp,
+            max_replay_stall_ms,
             #[cfg(test)]
             stub_health_status: std::sync::RwLock::new(None),
         }
@@ -51,6 +77,9 @@ impl RpcHealth {
 
         if self.override_health_check.load(Ordering::Relaxed) {
             RpcHealthStatus::Ok
+        } else if let Some(stall_ms) = self.replay_stall_ms() {
+            warn!("health check: replay loop stalled for {}ms", stall_ms);
+            RpcHealthStatus::ReplayStalled { stall_ms }
         } else if let Some(trusted_validators) = &self.trusted_validators {
             match (
                 self.cluster_info
@@ -115,6 +144,13 @@ impl RpcHealth {
         }
     }
 
+    fn replay_stall_ms(&self) -> Option<u64> {
+        replay_stall_ms(
+            &self.replay_last_iteration_timestamp,
+            self.max_replay_stall_ms?,
+        )
+    }
+
     #[cfg(test)]
     pub(crate) fn stub() -> Arc<Self> {
         Arc::new(Self::new(
@@ -122,6 +158,8 @@ impl RpcHealth {
             None,
             42,
             Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicU64::default()),
+            None,
         ))
     }
 
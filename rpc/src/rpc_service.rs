@@ -184,6 +184,7 @@ impl RpcRequestMiddleware {
         let response = match self.health.check() {
             RpcHealthStatus::Ok => "ok",
             RpcHealthStatus::Behind { .. } => "behind",
+            RpcHealthStatus::ReplayStalled { .. } => "stalled",
             RpcHealthStatus::Unknown => "unknown",
         };
         info!("health check: {}", response);
@@ -285,6 +286,7 @@ impl JsonRpcService {
         max_slots: Arc<MaxSlots>,
         leader_schedule_cache: Arc<LeaderScheduleCache>,
         current_transaction_status_slot: Arc<AtomicU64>,
+        replay_last_iteration_timestamp: Arc<AtomicU64>,
     ) -> Self {
         info!("rpc bound to {:?}", rpc_addr);
         info!("rpc configuration: {:?}", config);
@@ -295,6 +297,8 @@ impl JsonRpcService {
             trusted_validators,
             config.health_check_slot_distance,
             override_health_check,
+            replay_last_iteration_timestamp,
+            config.health_check_max_replay_stall_ms,
         ));
 
         let largest_accounts_cache = Arc::new(RwLock::new(LargestAccountsCache::new(
@@ -496,6 +500,7 @@ mod tests {
         solana_sdk::{
             genesis_config::{ClusterType, DEFAULT_GENESIS_ARCHIVE},
             signature::Signer,
+            timing::timestamp,
         },
         std::{
             io::Write,
@@ -545,6 +550,7 @@ mod tests {
             Arc::new(MaxSlots::default()),
             Arc::new(LeaderScheduleCache::default()),
             Arc::new(AtomicU64::default()),
+            Arc::new(AtomicU64::default()),
         );
         let thread = rpc_service.thread_hdl.thread();
         assert_eq!(thread.name().unwrap(), "solana-jsonrpc");
@@ -705,6 +711,36 @@ mod tests {
         assert_eq!(rm.health_check(), "ok");
     }
 
+    #[test]
+    fn test_health_check_replay_stalled() {
+        let override_health_check = Arc::new(AtomicBool::new(false));
+        let replay_last_iteration_timestamp = Arc::new(AtomicU64::default());
+        let health = Arc::new(RpcHealth::new(
+            Arc::new(ClusterInfo::default()),
+            None,
+            42,
+            override_health_check.clone(),
+            replay_last_iteration_timestamp.clone(),
+            Some(1_000),
+        ));
+        let rm = RpcRequestMiddleware::new(PathBuf::from("/"), None, create_bank_forks(), health);
+
+        // Replay hasn't started yet
+        assert_eq!(rm.health_check(), "ok");
+
+        // Replay loop iterated recently
+        replay_last_iteration_timestamp.store(timestamp(), Ordering::Relaxed);
+        assert_eq!(rm.health_check(), "ok");
+
+        // Replay loop hasn't iterated for longer than the allowed stall
+        replay_last_iteration_timestamp.store(timestamp() - 10_000, Ordering::Relaxed);
+        assert_eq!(rm.health_check(), "stalled");
+
+        // Override health check
+        override_health_check.store(true, Ordering::Relaxed);
+        assert_eq!(rm.health_check(), "ok");
+    }
+
     #[test]
     fn test_health_check_with_trusted_validators() {
         let cluster_info = Arc::new(ClusterInfo::default());
@@ -721,6 +757,8 @@ mod tests {
             Some(trusted_validators.clone().into_iter().collect()),
             health_check_slot_distance,
             override_health_check.clone(),
+            Arc::new(AtomicU64::default()),
+            None,
         ));
 
         let rm = RpcRequestMiddleware::new(PathBuf::from("/"), None, create_bank_forks(), health);
@@ -16,8 +16,9 @@ use {
         rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig},
         rpc_filter::RpcFilterType,
         rpc_response::{
-            ProcessedSignatureResult, ReceivedSignatureResult, Response, RpcKeyedAccount,
-            RpcLogsResponse, RpcResponseContext, RpcSignatureResult, SlotInfo, SlotUpdate,
+            ForkAbandonedReason, ProcessedSignatureResult, ReceivedSignatureResult, Response,
+            RpcKeyedAccount, RpcLogsResponse, RpcResponseContext, RpcSignatureResult, SlotInfo,
+            SlotUpdate,
         },
     },
     solana_measure::measure::Measure,
@@ -956,6 +957,20 @@ impl RpcSubscriptions {
         }));
     }
 
+    pub fn notify_fork_abandoned(
+        &self,
+        abandoned_slot: Slot,
+        reset_slot: Slot,
+        reason: ForkAbandonedReason,
+    ) {
+        self.enqueue_notification(NotificationEntry::SlotUpdate(SlotUpdate::ForkAbandoned {
+            slot: abandoned_slot,
+            reset_slot,
+            reason,
+            timestamp: timestamp(),
+        }));
+    }
+
     pub fn notify_signatures_received(&self, slot_signatures: (Slot, Vec<Signature>)) {
         self.enqueue_notification(NotificationEntry::SignaturesReceived(slot_signatures));
     }
@@ -3,7 +3,9 @@ use {
     itertools::izip,
     solana_ledger::{
         blockstore::Blockstore,
-        blockstore_processor::{TransactionStatusBatch, TransactionStatusMessage},
+        blockstore_processor::{
+            DeadSlotInfo, FrozenSlotStats, TransactionStatusBatch, TransactionStatusMessage,
+        },
     },
     solana_runtime::bank::{
         Bank, InnerInstructionsList, NonceRollbackInfo, TransactionLogMessages,
@@ -163,9 +165,29 @@ impl TransactionStatusService {
                     }
                 }
             }
-            TransactionStatusMessage::Freeze(slot) => {
+            TransactionStatusMessage::Freeze(slot)
+            | TransactionStatusMessage::Frozen(FrozenSlotStats { slot, .. }) => {
                 max_complete_transaction_status_slot.fetch_max(slot, Ordering::SeqCst);
             }
+            // The statuses already written for a dead slot are never served, as the slot
+            // is never confirmed, so it mustn't count as complete either. Its reason is
+            // recorded instead, for RPC to report why the block isn't available
+            TransactionStatusMessage::DeadSlot(DeadSlotInfo {
+                slot,
+                error,
+                last_entry,
+            }) => {
+                warn!(
+                    "slot {} died after entry {}, its transaction statuses are incomplete: {}",
+                    slot, last_entry, error
+                );
+                // Replay may have cleared the dead flag to retry the slot since
+                if blockstore.is_dead(slot) {
+                    blockstore
+                        .set_dead_slot_reason(slot, error)
+                        .expect("Expect database write to succeed");
+                }
+            }
         }
         Ok(())
     }
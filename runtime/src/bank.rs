@@ -159,6 +159,9 @@ pub struct ExecuteTimings {
     pub store_us: u64,
     pub total_batches_len: usize,
     pub num_execute_batches: u64,
+    // Transactions whose fee payer couldn't pay for them, only counted when replay is
+    // asked to report them
+    pub num_fee_payer_failures: u64,
     pub details: ExecuteDetailsTimings,
 }
 
@@ -170,6 +173,7 @@ impl ExecuteTimings {
         self.store_us += other.store_us;
         self.total_batches_len += other.total_batches_len;
         self.num_execute_batches += other.num_execute_batches;
+        self.num_fee_payer_failures += other.num_fee_payer_failures;
         self.details.accumulate(&other.details);
     }
 }
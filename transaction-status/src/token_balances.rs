@@ -30,8 +30,30 @@ impl TransactionTokenBalancesSet {
     }
 }
 
+lazy_static! {
+    static ref TOKEN_PROGRAM_IDS: [Pubkey; 1] = [spl_token_id_v2_0()];
+}
+
+/// Ids of the token programs whose balances `collect_token_balances()` decodes, a batch
+/// that references none of them has no token balances
+pub fn token_program_ids() -> &'static [Pubkey] {
+    TOKEN_PROGRAM_IDS.as_ref()
+}
+
 fn is_token_program(program_id: &Pubkey) -> bool {
-    program_id == &spl_token_id_v2_0()
+    token_program_ids().contains(program_id)
+}
+
+/// Whether any transaction of the batch references a token program, if not then all of
+/// its token balances are empty and there is no need to collect them
+pub fn batch_references_token_program(batch: &TransactionBatch) -> bool {
+    batch.transactions_iter().any(|transaction| {
+        transaction
+            .message
+            .account_keys
+            .iter()
+            .any(is_token_program)
+    })
 }
 
 fn get_mint_decimals(bank: &Bank, mint: &Pubkey) -> Option<u8> {
@@ -5,16 +5,22 @@ use {
     jsonrpc_ipc_server::{RequestContext, ServerBuilder},
     jsonrpc_server_utils::tokio,
     log::*,
-    solana_core::validator::ValidatorStartProgress,
+    serde_derive::{Deserialize, Serialize},
+    solana_core::{
+        replay_stage::{self, ReplayControl, VoteSimulationHandle},
+        validator::ValidatorStartProgress,
+    },
     solana_gossip::cluster_info::ClusterInfo,
+    solana_runtime::bank_forks::BankForks,
     solana_sdk::{
+        clock::Slot,
         exit::Exit,
         signature::{read_keypair_file, Keypair, Signer},
     },
     std::{
         net::SocketAddr,
         path::Path,
-        sync::{Arc, RwLock},
+        sync::{mpsc::Sender, Arc, Mutex, RwLock},
         thread::{self, Builder},
         time::{Duration, SystemTime},
     },
@@ -28,9 +34,22 @@ pub struct AdminRpcRequestMetadata {
     pub validator_exit: Arc<RwLock<Exit>>,
     pub authorized_voter_keypairs: Arc<RwLock<Vec<Arc<Keypair>>>>,
     pub cluster_info: Arc<RwLock<Option<Arc<ClusterInfo>>>>,
+    pub bank_forks: Arc<RwLock<Option<Arc<RwLock<BankForks>>>>>,
+    pub vote_simulation_handle: Arc<RwLock<Option<VoteSimulationHandle>>>,
+    pub replay_control_sender: Arc<Mutex<Option<Sender<ReplayControl>>>>,
 }
 impl Metadata for AdminRpcRequestMetadata {}
 
+/// What the validator would vote on if a given slot were the heaviest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedVote {
+    pub vote_slot: Option<Slot>,
+    pub switch_fork_decision: Option<String>,
+    pub reset_slot: Option<Slot>,
+    pub heaviest_fork_failures: Vec<String>,
+}
+
 #[rpc]
 pub trait AdminRpc {
     type Metadata;
@@ -58,6 +77,24 @@ pub trait AdminRpc {
 
     #[rpc(meta, name = "setIdentity")]
     fn set_identity(&self, meta: Self::Metadata, keypair_file: String) -> Result<()>;
+
+    #[rpc(meta, name = "forkTreeSnapshot")]
+    fn fork_tree_snapshot(&self, meta: Self::Metadata) -> Result<Vec<(Slot, Slot, String)>>;
+
+    #[rpc(meta, name = "simulateVote")]
+    fn simulate_vote(&self, meta: Self::Metadata, slot: Slot) -> Result<SimulatedVote>;
+
+    #[rpc(meta, name = "explainForkChoice")]
+    fn explain_fork_choice(&self, meta: Self::Metadata, slot: Slot) -> Result<String>;
+
+    #[rpc(meta, name = "pauseReplay")]
+    fn pause_replay(&self, meta: Self::Metadata) -> Result<()>;
+
+    #[rpc(meta, name = "resumeReplay")]
+    fn resume_replay(&self, meta: Self::Metadata) -> Result<()>;
+
+    #[rpc(meta, name = "stepReplay")]
+    fn step_replay(&self, meta: Self::Metadata) -> Result<()>;
 }
 
 pub struct AdminRpcImpl;
@@ -151,6 +188,94 @@ impl AdminRpc for AdminRpcImpl {
             ))
         }
     }
+
+    fn fork_tree_snapshot(&self, meta: Self::Metadata) -> Result<Vec<(Slot, Slot, String)>> {
+        debug!("fork_tree_snapshot request received");
+
+        if let Some(bank_forks) = meta.bank_forks.read().unwrap().as_ref() {
+            Ok(replay_stage::fork_tree_snapshot(bank_forks)
+                .into_iter()
+                .map(|(slot, parent_slot, bank_hash)| (slot, parent_slot, bank_hash.to_string()))
+                .collect())
+        } else {
+            Err(jsonrpc_core::error::Error::invalid_params(
+                "Retry once validator start up is complete",
+            ))
+        }
+    }
+
+    fn simulate_vote(&self, meta: Self::Metadata, slot: Slot) -> Result<SimulatedVote> {
+        debug!("simulate_vote request received for slot {}", slot);
+
+        let vote_simulation_handle = meta.vote_simulation_handle.read().unwrap().clone();
+        let vote_simulation_handle = vote_simulation_handle.ok_or_else(|| {
+            jsonrpc_core::error::Error::invalid_params("Retry once validator start up is complete")
+        })?;
+        let result = vote_simulation_handle.simulate_vote(slot).ok_or_else(|| {
+            jsonrpc_core::error::Error::invalid_params(format!(
+                "Slot {} has not been replayed, or replay did not respond in time",
+                slot
+            ))
+        })?;
+        Ok(SimulatedVote {
+            vote_slot: result.vote_bank.as_ref().map(|(bank, _)| bank.slot()),
+            switch_fork_decision: result
+                .vote_bank
+                .as_ref()
+                .map(|(_, switch_fork_decision)| format!("{:?}", switch_fork_decision)),
+            reset_slot: result.reset_bank.as_ref().map(|bank| bank.slot()),
+            heaviest_fork_failures: result
+                .heaviest_fork_failures
+                .iter()
+                .map(|failure| format!("{:?}", failure))
+                .collect(),
+        })
+    }
+
+    fn explain_fork_choice(&self, meta: Self::Metadata, slot: Slot) -> Result<String> {
+        debug!("explain_fork_choice request received for slot {}", slot);
+
+        let vote_simulation_handle = meta.vote_simulation_handle.read().unwrap().clone();
+        let vote_simulation_handle = vote_simulation_handle.ok_or_else(|| {
+            jsonrpc_core::error::Error::invalid_params("Retry once validator start up is complete")
+        })?;
+        vote_simulation_handle
+            .explain_fork_choice(slot)
+            .ok_or_else(|| {
+                jsonrpc_core::error::Error::invalid_params(format!(
+                    "Slot {} has no frozen bank, or replay did not respond in time",
+                    slot
+                ))
+            })
+    }
+
+    fn pause_replay(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("pause_replay request received");
+        send_replay_control(&meta, ReplayControl::Pause)
+    }
+
+    fn resume_replay(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("resume_replay request received");
+        send_replay_control(&meta, ReplayControl::Resume)
+    }
+
+    fn step_replay(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("step_replay request received");
+        send_replay_control(&meta, ReplayControl::StepOneIteration)
+    }
+}
+
+fn send_replay_control(
+    meta: &AdminRpcRequestMetadata,
+    replay_control: ReplayControl,
+) -> Result<()> {
+    let replay_control_sender = meta.replay_control_sender.lock().unwrap();
+    let replay_control_sender = replay_control_sender.as_ref().ok_or_else(|| {
+        jsonrpc_core::error::Error::invalid_params("Retry once validator start up is complete")
+    })?;
+    replay_control_sender
+        .send(replay_control)
+        .map_err(|_| jsonrpc_core::error::Error::invalid_params("Replay stage has exited"))
 }
 
 // Start the Admin RPC interface
@@ -32,7 +32,7 @@ use {
         net::{IpAddr, Ipv4Addr, SocketAddr},
         path::{Path, PathBuf},
         process::exit,
-        sync::{mpsc::channel, Arc, RwLock},
+        sync::{mpsc::channel, Arc, Mutex, RwLock},
         time::{Duration, SystemTime, UNIX_EPOCH},
     },
 };
@@ -502,6 +502,9 @@ fn main() {
     genesis.max_ledger_shreds = value_of(&matches, "limit_ledger_size");
 
     let admin_service_cluster_info = Arc::new(RwLock::new(None));
+    let admin_service_bank_forks = Arc::new(RwLock::new(None));
+    let admin_service_vote_simulation_handle = Arc::new(RwLock::new(None));
+    let admin_service_replay_control_sender = Arc::new(Mutex::new(None));
     admin_rpc_service::run(
         &ledger_path,
         admin_rpc_service::AdminRpcRequestMetadata {
@@ -514,6 +517,9 @@ fn main() {
             validator_exit: genesis.validator_exit.clone(),
             authorized_voter_keypairs: genesis.authorized_voter_keypairs.clone(),
             cluster_info: admin_service_cluster_info.clone(),
+            bank_forks: admin_service_bank_forks.clone(),
+            vote_simulation_handle: admin_service_vote_simulation_handle.clone(),
+            replay_control_sender: admin_service_replay_control_sender.clone(),
         },
     );
     let dashboard = if output == Output::Dashboard {
@@ -587,6 +593,11 @@ fn main() {
     match genesis.start_with_mint_address(mint_address) {
         Ok(test_validator) => {
             *admin_service_cluster_info.write().unwrap() = Some(test_validator.cluster_info());
+            *admin_service_bank_forks.write().unwrap() = Some(test_validator.bank_forks());
+            *admin_service_vote_simulation_handle.write().unwrap() =
+                Some(test_validator.vote_simulation_handle());
+            *admin_service_replay_control_sender.lock().unwrap() =
+                Some(test_validator.replay_control_sender());
             if let Some(dashboard) = dashboard {
                 dashboard.run(Duration::from_millis(250));
             }
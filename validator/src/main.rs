@@ -22,6 +22,10 @@ use {
     },
     solana_core::{
         ledger_cleanup_service::{DEFAULT_MAX_LEDGER_SHREDS, DEFAULT_MIN_MAX_LEDGER_SHREDS},
+        replay_stage::{
+            DuplicateSlotRecoveryMode, MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS,
+            MIN_REPLAY_WAIT_MS, MIN_VOTE_REFRESH_INTERVAL_MILLIS,
+        },
         tpu::DEFAULT_TPU_COALESCE_MS,
         validator::{
             is_snapshot_config_invalid, Validator, ValidatorConfig, ValidatorStartProgress,
@@ -71,7 +75,7 @@ use {
         str::FromStr,
         sync::{
             atomic::{AtomicBool, Ordering},
-            Arc, RwLock,
+            Arc, Mutex, RwLock,
         },
         thread::sleep,
         time::{Duration, Instant, SystemTime},
@@ -1239,6 +1243,15 @@ pub fn main() {
                        If no --trusted-validators are specified, the validator will always \
                        report itself to be healthy")
         )
+        .arg(
+            Arg::with_name("health_check_max_replay_stall_ms")
+                .long("health-check-max-replay-stall-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Report this validator unhealthy if the replay loop has not \
+                       completed an iteration within this many milliseconds")
+        )
         .arg(
             Arg::with_name("rpc_faucet_addr")
                 .long("rpc-faucet-address")
@@ -1469,6 +1482,186 @@ pub fn main() {
                       production until it sees a vote land in a rooted slot. This prevents
                       double signing. Turn off to risk double signing a block."),
         )
+        .arg(
+            Arg::with_name("max_entry_recv_per_iter")
+                .hidden(true)
+                .long("max-entry-recv-per-iter")
+                .value_name("NUM")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of entries replayed into a single bank per iteration \
+                       of the replay loop. By default all available entries are replayed"),
+        )
+        .arg(
+            Arg::with_name("report_fee_payer_failures")
+                .hidden(true)
+                .long("report-fee-payer-failures")
+                .takes_value(false)
+                .help("Report transactions whose fee payer can't pay for them separately \
+                       from other transaction errors"),
+        )
+        .arg(
+            Arg::with_name("vote_refresh_interval_ms")
+                .hidden(true)
+                .long("vote-refresh-interval-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(|s| {
+                    let interval = u64::from_str(&s).map_err(|e| e.to_string())?;
+                    if interval != 0
+                        && !(MIN_VOTE_REFRESH_INTERVAL_MILLIS
+                            ..=MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS)
+                            .contains(&interval)
+                    {
+                        return Err(format!(
+                            "vote refresh interval must be 0 or within [{}, {}]ms",
+                            MIN_VOTE_REFRESH_INTERVAL_MILLIS,
+                            MAX_CONFIGURABLE_VOTE_REFRESH_INTERVAL_MILLIS
+                        ));
+                    }
+                    Ok(())
+                })
+                .help(
+                    "Minimum time between attempts to refresh a vote that has not landed, \
+                     from 500 to 30000 milliseconds. 0 disables vote refreshing",
+                ),
+        )
+        .arg(
+            Arg::with_name("superminority_threshold")
+                .hidden(true)
+                .long("superminority-threshold")
+                .value_name("FRACTION")
+                .takes_value(true)
+                .validator(|s| {
+                    let threshold = f64::from_str(&s).map_err(|e| e.to_string())?;
+                    if threshold <= 0.0 || threshold > 0.5 {
+                        return Err("superminority threshold must be in (0.0, 0.5]".to_string());
+                    }
+                    Ok(())
+                })
+                .help(
+                    "Fraction of the epoch stake that must have received a leader block \
+                     for it to be considered propagated",
+                ),
+        )
+        .arg(
+            Arg::with_name("max_concurrent_forks")
+                .hidden(true)
+                .long("max-concurrent-forks")
+                .value_name("NUM")
+                .takes_value(true)
+                .validator(|s| match usize::from_str(&s) {
+                    Ok(0) => Err("max concurrent forks must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .help("Maximum number of new forks created per iteration of the replay loop, \
+                       children of the heaviest fork are always created. By default there is \
+                       no limit"),
+        )
+        .arg(
+            Arg::with_name("vote_fee_payer")
+                .long("vote-fee-payer")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .validator(is_keypair_or_ask_keyword)
+                .requires("vote_account")
+                .help("Keypair that pays the fees of vote transactions \
+                       [default: the --identity keypair]"),
+        )
+        .arg(
+            Arg::with_name("duplicate_slot_recovery_mode")
+                .long("duplicate-slot-recovery-mode")
+                .value_name("MODE")
+                .takes_value(true)
+                .possible_values(&DuplicateSlotRecoveryMode::STRINGS)
+                .default_value("conservative")
+                .help("How to recover from duplicate slots. \
+                       aggressive: purge a duplicate slot as soon as it is detected. \
+                       conservative: wait for the cluster to confirm a version of \
+                       the slot before purging it"),
+        )
+        .arg(
+            Arg::with_name("recover_dead_duplicate_confirmed_slots")
+                .long("recover-dead-duplicate-confirmed-slots")
+                .takes_value(false)
+                .help("Purge dead slots once the cluster confirms a version of them, \
+                       so that the confirmed version can be repaired and replayed"),
+        )
+        .arg(
+            Arg::with_name("max_vote_signatures")
+                .hidden(true)
+                .long("max-vote-signatures")
+                .value_name("NUM")
+                .takes_value(true)
+                .validator(|s| match usize::from_str(&s) {
+                    Ok(0) => Err("max vote signatures must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .help("Number of our most recent vote signatures to keep for detecting \
+                       that one of our votes has been rooted [default: 200]"),
+        )
+        .arg(
+            Arg::with_name("partition_detection_threshold")
+                .hidden(true)
+                .long("partition-detection-threshold")
+                .value_name("NUM")
+                .takes_value(true)
+                .validator(|s| match usize::from_str(&s) {
+                    Ok(0) => Err("partition detection threshold must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .help("Number of consecutive fork choice resets a partition must persist \
+                       for before it's reported [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("defer_voting_until_within_slots")
+                .hidden(true)
+                .long("defer-voting-until-within-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("After startup, don't vote until the heaviest fork is within SLOTS \
+                       slots of the highest slot the cluster is known to have reached"),
+        )
+        .arg(
+            Arg::with_name("min_online_stake_to_lead")
+                .hidden(true)
+                .long("min-online-stake-to-lead")
+                .value_name("FRACTION")
+                .takes_value(true)
+                .validator(|s| {
+                    let fraction = f64::from_str(&s).map_err(|e| e.to_string())?;
+                    if !(0.0..=1.0).contains(&fraction) {
+                        return Err("min online stake to lead must be in [0.0, 1.0]".to_string());
+                    }
+                    Ok(())
+                })
+                .help("Skip our leader slots unless at least this fraction of the stake \
+                       has voted recently. Never set this when starting a new cluster, \
+                       as nobody has voted yet"),
+        )
+        .arg(
+            Arg::with_name("max_replay_wait_ms")
+                .hidden(true)
+                .long("max-replay-wait-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(|s| {
+                    let wait = u64::from_str(&s).map_err(|e| e.to_string())?;
+                    if wait < MIN_REPLAY_WAIT_MS {
+                        return Err(format!(
+                            "max replay wait must be at least {}ms",
+                            MIN_REPLAY_WAIT_MS
+                        ));
+                    }
+                    Ok(())
+                })
+                .help("Longest time replay waits for new shreds when the node is idle. \
+                       Shorter waits lower replay latency at the cost of CPU [default: 200]"),
+        )
         .arg(
             Arg::with_name("hard_forks")
                 .long("hard-fork")
@@ -1478,6 +1671,14 @@ pub fn main() {
                 .takes_value(true)
                 .help("Add a hard fork at this slot"),
         )
+        .arg(
+            Arg::with_name("allow_obsolete_hard_forks")
+                .long("allow-obsolete-hard-forks")
+                .takes_value(false)
+                .requires("hard_forks")
+                .help("Ignore --hard-fork slots at or before the starting root instead of \
+                       refusing to start"),
+        )
         .arg(
             Arg::with_name("trusted_validators")
                 .long("trusted-validator")
@@ -2227,6 +2428,7 @@ pub fn main() {
             .map(|s| Hash::from_str(s).unwrap()),
         expected_shred_version,
         new_hard_forks: hardforks_of(&matches, "hard_forks"),
+        allow_obsolete_hard_forks: matches.is_present("allow_obsolete_hard_forks"),
         rpc_config: JsonRpcConfig {
             enable_rpc_transaction_history: matches.is_present("enable_rpc_transaction_history"),
             enable_cpi_and_log_storage: matches.is_present("enable_cpi_and_log_storage"),
@@ -2248,6 +2450,12 @@ pub fn main() {
                 "health_check_slot_distance",
                 u64
             ),
+            health_check_max_replay_stall_ms: value_t!(
+                matches,
+                "health_check_max_replay_stall_ms",
+                u64
+            )
+            .ok(),
             rpc_threads: value_t_or_exit!(matches, "rpc_threads", usize),
             rpc_bigtable_timeout: value_t!(matches, "rpc_bigtable_timeout", u64)
                 .ok()
@@ -2316,6 +2524,31 @@ pub fn main() {
         tpu_coalesce_ms,
         no_wait_for_vote_to_start_leader: matches.is_present("no_wait_for_vote_to_start_leader"),
         accounts_shrink_ratio,
+        max_entry_recv_per_iter: value_t!(matches, "max_entry_recv_per_iter", usize).ok(),
+        report_fee_payer_failures: matches.is_present("report_fee_payer_failures"),
+        vote_refresh_interval: value_t!(matches, "vote_refresh_interval_ms", u64)
+            .ok()
+            .map(Duration::from_millis),
+        superminority_threshold: value_t!(matches, "superminority_threshold", f64).ok(),
+        max_concurrent_forks: value_t!(matches, "max_concurrent_forks", usize).ok(),
+        vote_fee_payer_keypair: keypair_of(&matches, "vote_fee_payer").map(Arc::new),
+        duplicate_slot_recovery_mode: value_t!(
+            matches,
+            "duplicate_slot_recovery_mode",
+            DuplicateSlotRecoveryMode
+        )
+        .unwrap_or_default(),
+        recover_dead_duplicate_confirmed_slots: matches
+            .is_present("recover_dead_duplicate_confirmed_slots"),
+        max_vote_signatures: value_t!(matches, "max_vote_signatures", usize).ok(),
+        partition_detection_threshold: value_t!(matches, "partition_detection_threshold", usize)
+            .ok(),
+        defer_voting_until_within_slots: value_t!(matches, "defer_voting_until_within_slots", u64)
+            .ok(),
+        min_online_stake_to_lead: value_t!(matches, "min_online_stake_to_lead", f64).ok(),
+        max_replay_wait: value_t!(matches, "max_replay_wait_ms", u64)
+            .ok()
+            .map(Duration::from_millis),
         ..ValidatorConfig::default()
     };
 
@@ -2512,6 +2745,9 @@ pub fn main() {
 
     let start_progress = Arc::new(RwLock::new(ValidatorStartProgress::default()));
     let admin_service_cluster_info = Arc::new(RwLock::new(None));
+    let admin_service_bank_forks = Arc::new(RwLock::new(None));
+    let admin_service_vote_simulation_handle = Arc::new(RwLock::new(None));
+    let admin_service_replay_control_sender = Arc::new(Mutex::new(None));
     admin_rpc_service::run(
         &ledger_path,
         admin_rpc_service::AdminRpcRequestMetadata {
@@ -2521,6 +2757,9 @@ pub fn main() {
             start_progress: start_progress.clone(),
             authorized_voter_keypairs: authorized_voter_keypairs.clone(),
             cluster_info: admin_service_cluster_info.clone(),
+            bank_forks: admin_service_bank_forks.clone(),
+            vote_simulation_handle: admin_service_vote_simulation_handle.clone(),
+            replay_control_sender: admin_service_replay_control_sender.clone(),
         },
     );
 
@@ -2665,6 +2904,10 @@ pub fn main() {
         start_progress,
     );
     *admin_service_cluster_info.write().unwrap() = Some(validator.cluster_info.clone());
+    *admin_service_bank_forks.write().unwrap() = Some(validator.bank_forks.clone());
+    *admin_service_vote_simulation_handle.write().unwrap() =
+        Some(validator.vote_simulation_handle());
+    *admin_service_replay_control_sender.lock().unwrap() = Some(validator.replay_control_sender());
 
     if let Some(filename) = init_complete_file {
         File::create(filename).unwrap_or_else(|_| {